@@ -0,0 +1,196 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Cumulus author inherent pallet.
+//!
+//! This pallet records the collator that authored the current block. The collator puts its id into
+//! the inherent data (`AUTHOR_IDENTIFIER`) and every block is required to contain the resulting
+//! inherent, so other pallets can rely on [`Module::author`] to attribute the block, e.g. to reward
+//! the collator.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use cumulus_primitives::inherents::AUTHOR_IDENTIFIER;
+use frame_support::{
+	decl_module, decl_storage, ensure,
+	traits::Get,
+	weights::{DispatchClass, Weight},
+	Parameter,
+};
+use frame_system::ensure_none;
+use sp_inherents::{InherentData, InherentIdentifier, MakeFatalError, ProvideInherent};
+use sp_runtime::traits::Member;
+
+/// Configuration trait of this pallet.
+pub trait Trait: frame_system::Trait {
+	/// The id the collators author the blocks with, e.g. their account id.
+	///
+	/// Needs to decode from the id the collator puts into the inherent data.
+	type AuthorId: Parameter + Member;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as AuthorInherent {
+		/// The author of the current block.
+		Author get(fn author): Option<T::AuthorId>;
+	}
+}
+
+decl_module! {
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		/// Set the author of the current block.
+		///
+		/// The origin of this call needs to be `None` as this is an inherent.
+		#[weight = (10, DispatchClass::Mandatory)]
+		fn set_author(origin, author: T::AuthorId) {
+			ensure_none(origin)?;
+			ensure!(!<Author<T>>::exists(), "Author must be set only once per block");
+
+			<Author<T>>::put(author);
+		}
+
+		fn on_initialize() -> Weight {
+			<Author<T>>::kill();
+
+			T::DbWeight::get().writes(1)
+		}
+
+		fn on_finalize() {
+			assert!(<Author<T>>::exists(), "Author inherent must be included in every block");
+		}
+	}
+}
+
+impl<T: Trait> ProvideInherent for Module<T> {
+	type Call = Call<T>;
+	type Error = MakeFatalError<()>;
+	const INHERENT_IDENTIFIER: InherentIdentifier = AUTHOR_IDENTIFIER;
+
+	fn create_inherent(data: &InherentData) -> Option<Self::Call> {
+		data.get_data::<T::AuthorId>(&AUTHOR_IDENTIFIER)
+			.expect("Author inherent data failed to decode")
+			.map(|author| Call::set_author(author))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use frame_support::{
+		assert_noop, assert_ok, impl_outer_origin, parameter_types,
+		traits::{OnFinalize, OnInitialize, UnfilteredDispatchable},
+	};
+	use sp_core::H256;
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for Test where system = frame_system {}
+	}
+
+	#[derive(Clone, Eq, PartialEq)]
+	pub struct Test;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: Weight = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+	}
+	impl frame_system::Trait for Test {
+		type Origin = Origin;
+		type Call = ();
+		type Index = u64;
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = ();
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumExtrinsicWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+		type AccountData = ();
+		type OnNewAccount = ();
+		type OnKilledAccount = ();
+		type DbWeight = ();
+		type BlockExecutionWeight = ();
+		type ExtrinsicBaseWeight = ();
+		type BaseCallFilter = ();
+		type SystemWeightInfo = ();
+	}
+	impl Trait for Test {
+		type AuthorId = u64;
+	}
+
+	type AuthorInherent = Module<Test>;
+
+	fn new_test_ext() -> sp_io::TestExternalities {
+		frame_system::GenesisConfig::default()
+			.build_storage::<Test>()
+			.unwrap()
+			.into()
+	}
+
+	/// Run the given block with the inherent that is created from the given author.
+	fn run_block(n: u64, author: u64) {
+		let mut inherent_data = InherentData::new();
+		inherent_data.put_data(AUTHOR_IDENTIFIER, &author).unwrap();
+		let call = AuthorInherent::create_inherent(&inherent_data).expect("Creates the inherent");
+
+		AuthorInherent::on_initialize(n);
+		assert_ok!(call.dispatch_bypass_filter(Origin::none()));
+		AuthorInherent::on_finalize(n);
+	}
+
+	#[test]
+	fn block_records_the_author_of_the_inherent_data() {
+		new_test_ext().execute_with(|| {
+			run_block(1, 42);
+			assert_eq!(Some(42), AuthorInherent::author());
+
+			run_block(2, 7);
+			assert_eq!(Some(7), AuthorInherent::author());
+
+			assert_noop!(
+				AuthorInherent::set_author(Origin::none(), 42),
+				"Author must be set only once per block",
+			);
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "Author inherent must be included in every block")]
+	fn block_without_author_inherent_is_rejected() {
+		new_test_ext().execute_with(|| {
+			AuthorInherent::on_initialize(1);
+			AuthorInherent::on_finalize(1);
+		});
+	}
+
+	#[test]
+	fn no_inherent_without_author_data() {
+		assert!(AuthorInherent::create_inherent(&InherentData::new()).is_none());
+	}
+}
@@ -0,0 +1,166 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Backoff of the collations while the relay chain does not include any of them.
+//!
+//! Every collation is built on the head of the parachain that is included in the relay parent.
+//! While the relay chain does not include the collations, e.g. because backing fails, every
+//! collation is built on the same head and none of them has a chance to be included either. Once
+//! the number of collations on the same head reaches the threshold, the collator skips an
+//! exponentially growing number of relay chain blocks between two collations. The backoff is
+//! reset as soon as the included head advances.
+
+use polkadot_primitives::v0::HeadData;
+
+use log::{debug, info, warn};
+use parking_lot::Mutex;
+
+use std::num::NonZeroU32;
+
+/// The backoff doubles up to at most `2^MAX_EXPONENT` relay chain blocks.
+const MAX_EXPONENT: u32 = 6;
+
+#[derive(Default)]
+struct State {
+	/// The included head the collations are built on.
+	parent_head: Option<HeadData>,
+	/// The number of collations that were built on `parent_head`.
+	unincluded: u32,
+	/// The backoff is `2^exponent` relay chain blocks, `0` if not backing off.
+	exponent: u32,
+	/// The number of relay chain blocks that are still skipped.
+	skip: u32,
+}
+
+/// Decides whether a collation is built, backing off while they are not included.
+pub(crate) struct AuthoringBackoff {
+	threshold: u32,
+	state: Mutex<State>,
+}
+
+impl AuthoringBackoff {
+	/// Create a new instance that backs off after `threshold` collations on the same head.
+	pub(crate) fn new(threshold: NonZeroU32) -> Self {
+		Self {
+			threshold: threshold.get(),
+			state: Default::default(),
+		}
+	}
+
+	/// Returns whether a collation should be built on the included head `parent_head`.
+	///
+	/// Called once per relay chain block.
+	pub(crate) fn should_author(&self, parent_head: &HeadData) -> bool {
+		let mut state = self.state.lock();
+
+		if state.parent_head.as_ref() != Some(parent_head) {
+			if state.exponent > 0 {
+				info!(
+					target: "cumulus-collator",
+					"The included parachain head advanced, stopping the backoff",
+				);
+			}
+
+			*state = State {
+				parent_head: Some(parent_head.clone()),
+				..Default::default()
+			};
+			return true;
+		}
+
+		if state.skip > 0 {
+			state.skip -= 1;
+			debug!(
+				target: "cumulus-collator",
+				"Backing off, skipping collation ({} more relay chain blocks)",
+				state.skip,
+			);
+			return false;
+		}
+
+		if state.unincluded >= self.threshold {
+			state.exponent = (state.exponent + 1).min(MAX_EXPONENT);
+			let backoff = 1 << state.exponent;
+
+			warn!(
+				target: "cumulus-collator",
+				"{} collations on the same parachain head were not included, backing off for {} \
+				relay chain blocks",
+				state.unincluded,
+				backoff,
+			);
+
+			// This block is skipped as well. One collation is built after the backoff, if it is
+			// not included either the backoff doubles.
+			state.skip = backoff - 1;
+			state.unincluded = self.threshold - 1;
+			return false;
+		}
+
+		true
+	}
+
+	/// Note that a collation was built on the included head `parent_head`.
+	pub(crate) fn note_collation(&self, parent_head: &HeadData) {
+		let mut state = self.state.lock();
+
+		if state.parent_head.as_ref() == Some(parent_head) {
+			state.unincluded += 1;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Returns for every relay chain block whether a collation was built on `head`.
+	fn author(backoff: &AuthoringBackoff, head: &HeadData, blocks: usize) -> Vec<bool> {
+		(0..blocks)
+			.map(|_| {
+				let author = backoff.should_author(head);
+				if author {
+					backoff.note_collation(head);
+				}
+				author
+			})
+			.collect()
+	}
+
+	#[test]
+	fn backoff_grows_exponentially_and_resets_on_inclusion() {
+		let backoff = AuthoringBackoff::new(NonZeroU32::new(2).unwrap());
+		let head = HeadData(vec![1]);
+
+		assert_eq!(
+			vec![true, true, false, false, true, false, false, false, false, true],
+			author(&backoff, &head, 10),
+		);
+
+		let included = HeadData(vec![2]);
+		assert_eq!(vec![true, true, false], author(&backoff, &included, 3));
+	}
+
+	#[test]
+	fn backoff_is_capped() {
+		let backoff = AuthoringBackoff::new(NonZeroU32::new(1).unwrap());
+		let head = HeadData(vec![1]);
+
+		author(&backoff, &head, 1000);
+
+		assert_eq!(MAX_EXPONENT, backoff.state.lock().exponent);
+	}
+}
@@ -0,0 +1,165 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Check of the core assignment of the parachain before a collation is built.
+//!
+//! A collation built on a relay parent at which the parachain is not scheduled on any core can
+//! not be backed, so building it only wastes time.
+
+use polkadot_primitives::v0::Hash as PHash;
+
+use log::{debug, trace, warn};
+use parking_lot::Mutex;
+
+use std::{
+	fmt,
+	str::FromStr,
+	time::{Duration, Instant},
+};
+
+/// The minimum time between two warnings that the parachain has no core assigned.
+const WARN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Which relay chain blocks trigger a collation attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollateOn {
+	/// Build a collation on every relay chain block.
+	EveryBlock,
+	/// Only build a collation on relay chain blocks at which the parachain is assigned to a core.
+	WhenScheduled,
+}
+
+impl CollateOn {
+	/// The names of the variants, as parsed by `from_str`.
+	pub const VARIANTS: [&'static str; 2] = ["every-block", "when-scheduled"];
+}
+
+impl Default for CollateOn {
+	fn default() -> Self {
+		Self::WhenScheduled
+	}
+}
+
+impl FromStr for CollateOn {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, String> {
+		match s {
+			"every-block" => Ok(Self::EveryBlock),
+			"when-scheduled" => Ok(Self::WhenScheduled),
+			_ => Err(format!(
+				"unknown value `{}`, expected one of: {}",
+				s,
+				Self::VARIANTS.join(", "),
+			)),
+		}
+	}
+}
+
+impl fmt::Display for CollateOn {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::EveryBlock => write!(f, "every-block"),
+			Self::WhenScheduled => write!(f, "when-scheduled"),
+		}
+	}
+}
+
+/// Checks whether the parachain is assigned to a core at a relay parent.
+pub(crate) struct CoreAssignment {
+	has_core: Box<dyn Fn(PHash) -> Result<bool, String> + Send + Sync>,
+	last_warning: Mutex<Option<Instant>>,
+}
+
+impl CoreAssignment {
+	/// Create a new instance.
+	///
+	/// `has_core` returns whether the parachain is assigned to a core at the given relay chain
+	/// block.
+	pub(crate) fn new(has_core: Box<dyn Fn(PHash) -> Result<bool, String> + Send + Sync>) -> Self {
+		Self {
+			has_core,
+			last_warning: Mutex::new(None),
+		}
+	}
+
+	/// Returns `false` if the parachain has no core assigned at `relay_parent`.
+	///
+	/// If the assignment can not be fetched, the collation is built anyway.
+	pub(crate) fn has_core(&self, relay_parent: PHash) -> bool {
+		match (self.has_core)(relay_parent) {
+			Ok(true) => true,
+			Ok(false) => {
+				let mut last_warning = self.last_warning.lock();
+
+				if last_warning.map_or(true, |at| at.elapsed() >= WARN_INTERVAL) {
+					warn!(
+						target: "cumulus-collator",
+						"No core assigned at relay parent {:?}, skipping collation",
+						relay_parent,
+					);
+					*last_warning = Some(Instant::now());
+				} else {
+					trace!(
+						target: "cumulus-collator",
+						"No core assigned at relay parent {:?}, skipping collation",
+						relay_parent,
+					);
+				}
+
+				false
+			}
+			Err(e) => {
+				debug!(
+					target: "cumulus-collator",
+					"Failed to fetch the core assignment at relay parent {:?}: {}",
+					relay_parent,
+					e,
+				);
+
+				true
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn collation_is_only_skipped_without_core() {
+		let assignment = CoreAssignment::new(Box::new(|relay_parent| match relay_parent {
+			p if p == PHash::repeat_byte(1) => Ok(true),
+			p if p == PHash::repeat_byte(2) => Ok(false),
+			_ => Err("unknown block".into()),
+		}));
+
+		assert!(assignment.has_core(PHash::repeat_byte(1)));
+		assert!(!assignment.has_core(PHash::repeat_byte(2)));
+		assert!(assignment.last_warning.lock().is_some());
+		assert!(assignment.has_core(PHash::repeat_byte(3)));
+	}
+
+	#[test]
+	fn collate_on_round_trips() {
+		for name in &CollateOn::VARIANTS {
+			assert_eq!(*name, name.parse::<CollateOn>().unwrap().to_string());
+		}
+
+		assert!("sometimes".parse::<CollateOn>().is_err());
+	}
+}
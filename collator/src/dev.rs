@@ -0,0 +1,462 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Block authoring for development.
+//!
+//! Authors parachain blocks without a relay chain. The validation data that is normally provided
+//! by the relay chain is mocked, so the blocks produced by this module will never be valid on a
+//! real relay chain.
+//!
+//! Blocks can be authored on a fixed interval ([`run_with_block_time`]), for every transaction
+//! that is imported into the transaction pool ([`run_instant`]), on demand ([`run_manual`]) or
+//! for every recorded relay chain head ([`run_replay`]).
+
+use crate::{ParachainInherentExtension, RecordedRelayHead};
+
+use cumulus_primitives::HeadData;
+
+use sc_client_api::{Backend as BackendT, Finalizer};
+use sp_blockchain::HeaderBackend;
+use sp_consensus::{
+	BlockImport, BlockImportParams, BlockOrigin, Environment, Error as ConsensusError,
+	ForkChoiceStrategy, Proposal, Proposer, RecordProof,
+};
+use sp_inherents::InherentDataProviders;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, Header as HeaderT},
+};
+use sp_transaction_pool::TransactionPool;
+
+use polkadot_primitives::v0::{
+	self as parachain, BlockNumber as PBlockNumber, DownwardMessage, GlobalValidationData,
+	LocalValidationData,
+};
+
+use codec::{Decode, Encode};
+use futures::{
+	channel::{mpsc, oneshot},
+	future, StreamExt,
+};
+use futures_timer::Delay;
+use log::{error, info};
+
+use std::{marker::PhantomData, sync::Arc, time::Duration};
+
+/// The maximum code size that is reported in the mocked validation data.
+pub(crate) const MAX_CODE_SIZE: u32 = 10 * 1024 * 1024;
+
+/// The maximum head data size that is reported in the mocked validation data.
+pub(crate) const MAX_HEAD_DATA_SIZE: u32 = 32 * 1024;
+
+/// A command that controls the authoring when running [`run_manual`].
+pub enum DevCommand<Hash> {
+	/// Author a new block on top of the current best block.
+	CreateBlock {
+		/// Author the block even if there are no transactions ready for inclusion.
+		create_empty: bool,
+		/// Finalize the block directly after it was imported.
+		finalize: bool,
+		/// Returns the hash of the new block.
+		sender: oneshot::Sender<Result<Hash, String>>,
+	},
+	/// Finalize the given block.
+	FinalizeBlock {
+		/// The hash of the block to finalize.
+		hash: Hash,
+		/// Returns whether finalizing the block succeeded.
+		sender: oneshot::Sender<Result<(), String>>,
+	},
+}
+
+/// Authors parachain blocks on top of the best block using mocked relay chain data.
+pub struct DevAuthoring<Block, PF, BI, Client> {
+	proposer_factory: PF,
+	inherent_data_providers: InherentDataProviders,
+	inherent_extension: Arc<dyn ParachainInherentExtension>,
+	block_import: BI,
+	client: Arc<Client>,
+	relay_chain_block_number: PBlockNumber,
+	_marker: PhantomData<Block>,
+}
+
+impl<Block, PF, BI, Client> DevAuthoring<Block, PF, BI, Client>
+where
+	Block: BlockT,
+	PF: Environment<Block> + Send + 'static,
+	PF::Proposer: Send,
+	BI: BlockImport<
+			Block,
+			Error = ConsensusError,
+			Transaction = <PF::Proposer as Proposer<Block>>::Transaction,
+		> + Send
+		+ Sync
+		+ 'static,
+	Client: HeaderBackend<Block>,
+{
+	/// Create a new instance.
+	pub fn new(
+		proposer_factory: PF,
+		inherent_data_providers: InherentDataProviders,
+		block_import: BI,
+		client: Arc<Client>,
+	) -> Self {
+		Self {
+			proposer_factory,
+			inherent_data_providers,
+			inherent_extension: Arc::new(()),
+			block_import,
+			client,
+			relay_chain_block_number: 0,
+			_marker: PhantomData,
+		}
+	}
+
+	/// Use the given extension to supply additional inherent data for every block.
+	pub fn with_inherent_extension(
+		mut self,
+		inherent_extension: Arc<dyn ParachainInherentExtension>,
+	) -> Self {
+		self.inherent_extension = inherent_extension;
+		self
+	}
+
+	/// Author a new block on top of the current best block and import it as the new best block.
+	///
+	/// Returns the hash of the new block or `None` if authoring failed.
+	pub async fn author_block(&mut self) -> Option<Block::Hash> {
+		let best_hash = self.client.info().best_hash;
+		let parent = match self.client.header(BlockId::Hash(best_hash)) {
+			Ok(Some(parent)) => parent,
+			Ok(None) => {
+				error!(target: "cumulus-collator", "Best block `{:?}` not found.", best_hash);
+				return None;
+			}
+			Err(e) => {
+				error!(
+					target: "cumulus-collator",
+					"Failed to fetch best block `{:?}`: {:?}",
+					best_hash,
+					e,
+				);
+				return None;
+			}
+		};
+
+		self.relay_chain_block_number += 1;
+
+		let global_validation = GlobalValidationData {
+			max_code_size: MAX_CODE_SIZE,
+			max_head_data_size: MAX_HEAD_DATA_SIZE,
+			block_number: self.relay_chain_block_number,
+		};
+		let local_validation = LocalValidationData {
+			parent_head: parachain::HeadData(HeadData::<Block> { header: parent.clone() }.encode()),
+			balance: 0,
+			code_upgrade_allowed: None,
+		};
+
+		self.author_block_on(parent, global_validation, local_validation, Vec::new())
+			.await
+	}
+
+	/// Author a new block on top of the parent block of the recorded relay chain `head`, using the
+	/// recorded validation data, and import it as the new best block.
+	///
+	/// Returns the hash of the new block or `None` if authoring failed.
+	pub async fn replay_relay_head(&mut self, head: RecordedRelayHead) -> Option<Block::Hash> {
+		let parent_head = &head.local_validation.parent_head.0;
+		let parent = match HeadData::<Block>::decode(&mut &parent_head[..]) {
+			Ok(head_data) => head_data.header,
+			Err(e) => {
+				error!(
+					target: "cumulus-collator",
+					"Could not decode the head data of relay chain head `{:?}`: {:?}",
+					head.relay_parent,
+					e,
+				);
+				return None;
+			}
+		};
+
+		match self.client.header(BlockId::Hash(parent.hash())) {
+			Ok(Some(_)) => {}
+			Ok(None) => {
+				error!(
+					target: "cumulus-collator",
+					"Block `{:?}` of relay chain head `{:?}` is unknown. Replay the heads on the \
+					parachain state they were recorded on.",
+					parent.hash(),
+					head.relay_parent,
+				);
+				return None;
+			}
+			Err(e) => {
+				error!(
+					target: "cumulus-collator",
+					"Failed to fetch block `{:?}`: {:?}",
+					parent.hash(),
+					e,
+				);
+				return None;
+			}
+		}
+
+		self.relay_chain_block_number = head.global_validation.block_number;
+
+		self.author_block_on(
+			parent,
+			head.global_validation,
+			head.local_validation,
+			head.downward_messages,
+		)
+		.await
+	}
+
+	/// Author a new block on top of `parent` with the given validation data and import it as the
+	/// new best block.
+	async fn author_block_on(
+		&mut self,
+		parent: Block::Header,
+		global_validation: GlobalValidationData,
+		local_validation: LocalValidationData,
+		downward_messages: Vec<DownwardMessage>,
+	) -> Option<Block::Hash> {
+		let proposer = self
+			.proposer_factory
+			.init(&parent)
+			.await
+			.map_err(|e| {
+				error!(
+					target: "cumulus-collator",
+					"Could not create proposer: {:?}",
+					e,
+				)
+			})
+			.ok()?;
+
+		let inherent_data = crate::inherent_data(
+			self.inherent_data_providers.clone(),
+			&*self.inherent_extension,
+			global_validation,
+			local_validation,
+			downward_messages,
+		)
+		.map_err(|e| error!(target: "cumulus-collator", "{}", e))
+		.ok()?;
+
+		let Proposal {
+			block,
+			storage_changes,
+			..
+		} = proposer
+			.propose(
+				inherent_data,
+				Default::default(),
+				Duration::from_millis(500),
+				RecordProof::No,
+			)
+			.await
+			.map_err(|e| {
+				error!(
+					target: "cumulus-collator",
+					"Proposing failed: {:?}",
+					e,
+				)
+			})
+			.ok()?;
+
+		let (header, extrinsics) = block.deconstruct();
+		let hash = header.hash();
+		let number = *header.number();
+
+		let mut block_import_params = BlockImportParams::new(BlockOrigin::Own, header);
+		block_import_params.body = Some(extrinsics);
+		// There is no relay chain that could determine the best block.
+		block_import_params.fork_choice = Some(ForkChoiceStrategy::Custom(true));
+		block_import_params.storage_changes = Some(storage_changes);
+
+		if let Err(err) = self
+			.block_import
+			.import_block(block_import_params, Default::default())
+		{
+			error!(
+				target: "cumulus-collator",
+				"Error importing dev block `{:?}`: {:?}",
+				hash,
+				err,
+			);
+
+			return None;
+		}
+
+		info!(
+			target: "cumulus-collator",
+			"Authored dev block #{} ({:?})",
+			number,
+			hash,
+		);
+
+		Some(hash)
+	}
+
+	/// Finalize the block with the given `hash`.
+	pub fn finalize_block<B>(&self, hash: Block::Hash) -> Result<(), String>
+	where
+		Client: Finalizer<Block, B>,
+		B: BackendT<Block>,
+	{
+		self.client
+			.finalize_block(BlockId::Hash(hash), None, true)
+			.map_err(|e| {
+				error!(
+					target: "cumulus-collator",
+					"Failed to finalize dev block `{:?}`: {:?}",
+					hash,
+					e,
+				);
+
+				format!("Failed to finalize block: {:?}", e)
+			})
+	}
+}
+
+/// Author a new block every `block_time`.
+pub async fn run_with_block_time<Block, PF, BI, Client>(
+	mut authoring: DevAuthoring<Block, PF, BI, Client>,
+	block_time: Duration,
+) where
+	Block: BlockT,
+	PF: Environment<Block> + Send + 'static,
+	PF::Proposer: Send,
+	BI: BlockImport<
+			Block,
+			Error = ConsensusError,
+			Transaction = <PF::Proposer as Proposer<Block>>::Transaction,
+		> + Send
+		+ Sync
+		+ 'static,
+	Client: HeaderBackend<Block>,
+{
+	loop {
+		Delay::new(block_time).await;
+
+		authoring.author_block().await;
+	}
+}
+
+/// Author a block for every recorded relay chain head in `heads`, one after another.
+pub async fn run_replay<Block, PF, BI, Client>(
+	mut authoring: DevAuthoring<Block, PF, BI, Client>,
+	heads: Vec<RecordedRelayHead>,
+) where
+	Block: BlockT,
+	PF: Environment<Block> + Send + 'static,
+	PF::Proposer: Send,
+	BI: BlockImport<
+			Block,
+			Error = ConsensusError,
+			Transaction = <PF::Proposer as Proposer<Block>>::Transaction,
+		> + Send
+		+ Sync
+		+ 'static,
+	Client: HeaderBackend<Block>,
+{
+	let count = heads.len();
+
+	for head in heads {
+		authoring.replay_relay_head(head).await;
+	}
+
+	info!(
+		target: "cumulus-collator",
+		"Replayed {} relay chain heads",
+		count,
+	);
+
+	future::pending::<()>().await;
+}
+
+/// Author a new block for every transaction that is imported into the given `pool`.
+pub async fn run_instant<Block, PF, BI, Client, TP>(
+	mut authoring: DevAuthoring<Block, PF, BI, Client>,
+	pool: Arc<TP>,
+) where
+	Block: BlockT,
+	PF: Environment<Block> + Send + 'static,
+	PF::Proposer: Send,
+	BI: BlockImport<
+			Block,
+			Error = ConsensusError,
+			Transaction = <PF::Proposer as Proposer<Block>>::Transaction,
+		> + Send
+		+ Sync
+		+ 'static,
+	Client: HeaderBackend<Block>,
+	TP: TransactionPool<Block = Block>,
+{
+	let mut imported_transactions = pool.import_notification_stream();
+
+	while imported_transactions.next().await.is_some() {
+		authoring.author_block().await;
+	}
+}
+
+/// Author and finalize blocks as requested by the received `commands`.
+pub async fn run_manual<Block, PF, BI, Client, B, TP>(
+	mut authoring: DevAuthoring<Block, PF, BI, Client>,
+	pool: Arc<TP>,
+	mut commands: mpsc::Receiver<DevCommand<Block::Hash>>,
+) where
+	Block: BlockT,
+	PF: Environment<Block> + Send + 'static,
+	PF::Proposer: Send,
+	BI: BlockImport<
+			Block,
+			Error = ConsensusError,
+			Transaction = <PF::Proposer as Proposer<Block>>::Transaction,
+		> + Send
+		+ Sync
+		+ 'static,
+	Client: HeaderBackend<Block> + Finalizer<Block, B>,
+	B: BackendT<Block>,
+	TP: TransactionPool<Block = Block>,
+{
+	while let Some(command) = commands.next().await {
+		match command {
+			DevCommand::CreateBlock {
+				create_empty,
+				finalize,
+				sender,
+			} => {
+				let res = if !create_empty && pool.status().ready == 0 {
+					Err("No transactions are ready and `create_empty` is `false`".into())
+				} else {
+					match authoring.author_block().await {
+						Some(hash) if finalize => authoring.finalize_block(hash).map(|_| hash),
+						Some(hash) => Ok(hash),
+						None => Err("Failed to author a block, see the logs for details".into()),
+					}
+				};
+
+				let _ = sender.send(res);
+			}
+			DevCommand::FinalizeBlock { hash, sender } => {
+				let _ = sender.send(authoring.finalize_block(hash));
+			}
+		}
+	}
+}
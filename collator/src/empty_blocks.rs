@@ -0,0 +1,192 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Decide whether a block without transactions is built.
+//!
+//! A parachain with little traffic may not want a block on every relay chain block, while others
+//! rely on the empty blocks to advance the finality and the timestamp.
+
+use log::trace;
+use parking_lot::Mutex;
+
+use std::{
+	fmt,
+	str::FromStr,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+/// The default of the interval of [`ProduceEmptyBlocks::Timeout`].
+pub const DEFAULT_EMPTY_BLOCK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// When a block without transactions is built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProduceEmptyBlocks {
+	/// Build a block on every collation attempt.
+	Always,
+	/// Only build a block if there are transactions or downward messages to process.
+	Never,
+	/// Build an empty block if no block was built within the interval.
+	Timeout,
+}
+
+impl ProduceEmptyBlocks {
+	/// The names of the variants, as parsed by `from_str`.
+	pub const VARIANTS: [&'static str; 3] = ["always", "never", "timeout"];
+}
+
+impl Default for ProduceEmptyBlocks {
+	fn default() -> Self {
+		Self::Always
+	}
+}
+
+impl FromStr for ProduceEmptyBlocks {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, String> {
+		match s {
+			"always" => Ok(Self::Always),
+			"never" => Ok(Self::Never),
+			"timeout" => Ok(Self::Timeout),
+			_ => Err(format!(
+				"unknown value `{}`, expected one of: {}",
+				s,
+				Self::VARIANTS.join(", "),
+			)),
+		}
+	}
+}
+
+impl fmt::Display for ProduceEmptyBlocks {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Always => write!(f, "always"),
+			Self::Never => write!(f, "never"),
+			Self::Timeout => write!(f, "timeout"),
+		}
+	}
+}
+
+/// Decides whether a block is built, given whether there are transactions to include.
+pub struct EmptyBlocks {
+	mode: ProduceEmptyBlocks,
+	interval: Duration,
+	has_pending_transactions: Arc<dyn Fn() -> bool + Send + Sync>,
+	last_block: Mutex<Instant>,
+}
+
+impl EmptyBlocks {
+	/// Create a new instance.
+	///
+	/// `interval` is only used by [`ProduceEmptyBlocks::Timeout`]. `has_pending_transactions`
+	/// returns whether the transaction pool has transactions that can be included.
+	pub fn new(
+		mode: ProduceEmptyBlocks,
+		interval: Duration,
+		has_pending_transactions: Arc<dyn Fn() -> bool + Send + Sync>,
+	) -> Self {
+		Self {
+			mode,
+			interval,
+			has_pending_transactions,
+			last_block: Mutex::new(Instant::now()),
+		}
+	}
+
+	/// Returns whether a block should be built, given whether there are `downward_messages` to
+	/// process.
+	pub(crate) fn should_author(&self, downward_messages: bool) -> bool {
+		if self.mode == ProduceEmptyBlocks::Always
+			|| downward_messages
+			|| (self.has_pending_transactions)()
+		{
+			return true;
+		}
+
+		let author = match self.mode {
+			ProduceEmptyBlocks::Timeout => self.last_block.lock().elapsed() >= self.interval,
+			_ => false,
+		};
+
+		if !author {
+			trace!(
+				target: "cumulus-collator",
+				"Skipping candidate production, because there is nothing to include.",
+			);
+		}
+
+		author
+	}
+
+	/// Note that a block was built.
+	pub(crate) fn note_block(&self) {
+		*self.last_block.lock() = Instant::now();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::atomic::{AtomicBool, Ordering};
+
+	/// Create an instance whose pending transactions are controlled by the returned flag.
+	fn with_pending(
+		mode: ProduceEmptyBlocks,
+		interval: Duration,
+	) -> (EmptyBlocks, Arc<AtomicBool>) {
+		let pending = Arc::new(AtomicBool::new(false));
+		let has_pending = pending.clone();
+		let empty_blocks = EmptyBlocks::new(
+			mode,
+			interval,
+			Arc::new(move || has_pending.load(Ordering::SeqCst)),
+		);
+
+		(empty_blocks, pending)
+	}
+
+	#[test]
+	fn never_only_builds_blocks_with_content() {
+		let (empty_blocks, pending) =
+			with_pending(ProduceEmptyBlocks::Never, Duration::from_secs(0));
+		assert!(!empty_blocks.should_author(false));
+		assert!(empty_blocks.should_author(true));
+
+		pending.store(true, Ordering::SeqCst);
+		assert!(empty_blocks.should_author(false));
+
+		let (empty_blocks, _) = with_pending(ProduceEmptyBlocks::Always, Duration::from_secs(60));
+		assert!(empty_blocks.should_author(false));
+	}
+
+	#[test]
+	fn timeout_builds_empty_blocks_after_the_interval() {
+		let (empty_blocks, _) = with_pending(ProduceEmptyBlocks::Timeout, Duration::from_secs(60));
+		assert!(!empty_blocks.should_author(false));
+
+		let (empty_blocks, _) = with_pending(ProduceEmptyBlocks::Timeout, Duration::from_secs(0));
+		assert!(empty_blocks.should_author(false));
+	}
+
+	#[test]
+	fn mode_round_trips() {
+		for name in ProduceEmptyBlocks::VARIANTS.iter() {
+			assert_eq!(*name, name.parse::<ProduceEmptyBlocks>().unwrap().to_string());
+		}
+		assert!("sometimes".parse::<ProduceEmptyBlocks>().is_err());
+	}
+}
@@ -0,0 +1,103 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Rate limited logging of recurring collation errors.
+//!
+//! When collating fails persistently, every relay chain block logs the same error. Only the first
+//! error of a kind is logged per interval, the repetitions are summarized with the next error of
+//! that kind that is logged.
+
+use log::error;
+use parking_lot::Mutex;
+
+use std::{
+	collections::HashMap,
+	fmt,
+	time::{Duration, Instant},
+};
+
+/// The interval in which an error of the same kind is logged at most once.
+const LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+/// When an error of a kind was last logged and how often it was suppressed since then.
+struct Logged {
+	at: Instant,
+	suppressed: usize,
+}
+
+/// Logs collation errors, at most one per kind and [`LOG_INTERVAL`].
+#[derive(Default)]
+pub(crate) struct ErrorLog {
+	logged: Mutex<HashMap<&'static str, Logged>>,
+}
+
+impl ErrorLog {
+	/// Log the error `message` of the given `kind`, unless an error of this kind was already
+	/// logged in the current interval.
+	pub(crate) fn error(&self, kind: &'static str, message: fmt::Arguments) {
+		if let Some(suppressed) = self.should_log(kind, Instant::now()) {
+			if suppressed > 0 {
+				error!(
+					target: "cumulus-collator",
+					"The same error repeated {} times in the last {} seconds",
+					suppressed,
+					LOG_INTERVAL.as_secs(),
+				);
+			}
+
+			error!(target: "cumulus-collator", "{}", message);
+		}
+	}
+
+	/// Returns the number of suppressed errors of `kind` if the error should be logged at `now`.
+	fn should_log(&self, kind: &'static str, now: Instant) -> Option<usize> {
+		let mut logged = self.logged.lock();
+
+		match logged.get_mut(kind) {
+			Some(last) if now.duration_since(last.at) < LOG_INTERVAL => {
+				last.suppressed += 1;
+				None
+			}
+			Some(last) => {
+				let suppressed = last.suppressed;
+				*last = Logged { at: now, suppressed: 0 };
+				Some(suppressed)
+			}
+			None => {
+				logged.insert(kind, Logged { at: now, suppressed: 0 });
+				Some(0)
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn repeated_errors_are_logged_once_per_interval() {
+		let log = ErrorLog::default();
+		let start = Instant::now();
+
+		assert_eq!(Some(0), log.should_log("propose", start));
+		assert_eq!(None, log.should_log("propose", start + Duration::from_secs(1)));
+		assert_eq!(None, log.should_log("propose", start + Duration::from_secs(2)));
+		assert_eq!(Some(0), log.should_log("import", start + Duration::from_secs(2)));
+		assert_eq!(Some(2), log.should_log("propose", start + LOG_INTERVAL));
+		assert_eq!(None, log.should_log("propose", start + LOG_INTERVAL));
+	}
+}
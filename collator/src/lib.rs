@@ -16,12 +16,55 @@
 
 //! Cumulus Collator implementation for Substrate.
 
+mod backoff;
+mod core_assignment;
+pub mod dev;
+mod empty_blocks;
+mod error_log;
+pub mod mock_relay;
+mod pov_archive;
+mod pov_cache;
+mod relay_cache;
+mod relay_heads;
+mod relay_runtime;
+mod self_validation;
+mod start_delay;
+mod status;
+
+pub use core_assignment::CollateOn;
+pub use empty_blocks::{EmptyBlocks, ProduceEmptyBlocks, DEFAULT_EMPTY_BLOCK_INTERVAL};
+pub use pov_archive::{read_archived_pov, ArchivedPov, PovArchive};
+pub use pov_cache::DEFAULT_POV_CACHE_SIZE;
+pub use relay_cache::RelayCallCacheStats;
+pub use relay_heads::{read_relay_heads, RecordedRelayHead, RelayHeadRecorder};
+pub use relay_runtime::watch_relay_runtime;
+pub use self_validation::{
+	validate_collation, DEFAULT_SELF_VALIDATION_TIMEOUT, VALIDATION_HEAP_PAGES,
+	VALIDATION_WASM_EXECUTION,
+};
+pub use status::{
+	follow_relay_chain, monitor_finality, record_inclusions, AssignedCore, CollationEvent,
+	CollationResult, CollatorStatus, Inclusion, RecentCollation, Rejection, RelayChainHeights,
+	RelayParent, UnincludedSegment, DEFAULT_KEEP_RECENT_COLLATIONS, DEFAULT_KEEP_RECENT_REJECTIONS,
+	MAX_KEEP_RECENT_COLLATIONS,
+};
+
+use backoff::AuthoringBackoff;
+use core_assignment::CoreAssignment;
+use error_log::ErrorLog;
+use pov_cache::PovCache;
+use self_validation::SelfValidation;
+use start_delay::StartDelay;
+use status::{track_assigned_core, track_inclusion, wait_for_registration};
+
+use cumulus_consensus::{BackedHead, FetchRetry, RetryingPolkadotClient};
 use cumulus_network::{
-	DelayedBlockAnnounceValidator, JustifiedBlockAnnounceValidator, WaitToAnnounce,
+	AnnouncementExpiry, DelayedBlockAnnounceValidator, JustifiedBlockAnnounceValidator,
+	WaitToAnnounce,
 };
 use cumulus_primitives::{
 	inherents::{
-		DownwardMessagesType, DOWNWARD_MESSAGES_IDENTIFIER,
+		DownwardMessagesType, AUTHOR_IDENTIFIER, DOWNWARD_MESSAGES_IDENTIFIER,
 		VALIDATION_FUNCTION_PARAMS_IDENTIFIER as VFP_IDENT,
 	},
 	validation_function_params::ValidationFunctionParams,
@@ -29,25 +72,33 @@ use cumulus_primitives::{
 };
 use cumulus_runtime::ParachainBlockData;
 
-use sc_client_api::{Backend as BackendT, BlockBackend, Finalizer, StateBackend, UsageProvider};
+use sc_client_api::{
+	Backend as BackendT, BlockBackend, Finalizer, StateBackend, StorageProvider, UsageProvider,
+};
+use sc_executor::WasmExecutionMethod;
+use sp_api::ProvideRuntimeApi;
 use sp_blockchain::HeaderBackend;
 use sp_consensus::{
 	BlockImport, BlockImportParams, BlockOrigin, BlockStatus, Environment, Error as ConsensusError,
 	ForkChoiceStrategy, Proposal, Proposer, RecordProof, SyncOracle,
 };
-use sp_core::traits::SpawnNamed;
+use sp_core::{
+	storage::{well_known_keys, StorageKey},
+	traits::SpawnNamed,
+};
 use sp_inherents::{InherentData, InherentDataProviders};
 use sp_runtime::{
 	generic::BlockId,
 	traits::{BlakeTwo256, Block as BlockT, Header as HeaderT},
 };
+use substrate_prometheus_endpoint::Registry;
 
 use polkadot_collator::{
 	BuildParachainContext, Network as CollatorNetwork, ParachainContext, RuntimeApiCollection,
 };
 use polkadot_primitives::v0::{
-	self as parachain, Block as PBlock, BlockData, DownwardMessage, GlobalValidationData,
-	Hash as PHash, Id as ParaId, LocalValidationData,
+	self as parachain, Block as PBlock, BlockData, BlockNumber as PBlockNumber, DownwardMessage,
+	GlobalValidationData, Hash as PHash, Id as ParaId, LocalValidationData, ParachainHost,
 };
 
 use codec::{Decode, Encode};
@@ -56,19 +107,87 @@ use log::{debug, error, trace};
 
 use futures::prelude::*;
 
-use std::{marker::PhantomData, pin::Pin, sync::Arc, time::Duration};
+use std::{marker::PhantomData, num::NonZeroU32, pin::Pin, sync::Arc, time::Duration};
 
 use parking_lot::Mutex;
 
+/// Supplies additional inherent data for every parachain block.
+///
+/// The data is put into the [`InherentData`] after the mandatory parachain inherents. The
+/// mandatory inherents can not be replaced, putting data under one of their identifiers lets the
+/// block production fail. As the inherent data is keyed by the identifier, the order in which the
+/// data is put is not relevant for the resulting block.
+///
+/// The runtime needs to provide the matching inherents, e.g. through `ProvideInherent`, so that
+/// they are part of the block that is validated by the relay chain.
+pub trait ParachainInherentExtension: Send + Sync {
+	/// Put the additional inherent data for a block that is built on the given validation data.
+	fn provide_inherent_data(
+		&self,
+		inherent_data: &mut InherentData,
+		global_validation: &GlobalValidationData,
+		local_validation: &LocalValidationData,
+	) -> Result<(), sp_inherents::Error>;
+}
+
+impl ParachainInherentExtension for () {
+	fn provide_inherent_data(
+		&self,
+		_: &mut InherentData,
+		_: &GlobalValidationData,
+		_: &LocalValidationData,
+	) -> Result<(), sp_inherents::Error> {
+		Ok(())
+	}
+}
+
+/// Puts the author of every block into the inherent data (`AUTHOR_IDENTIFIER`).
+///
+/// The runtime records the author with the `cumulus-author-inherent` pallet, the encoded `author`
+/// needs to decode as the `AuthorId` of the runtime.
+pub struct AuthorInherent<AuthorId> {
+	author: AuthorId,
+}
+
+impl<AuthorId> AuthorInherent<AuthorId> {
+	/// Create a new instance that puts the given `author` into every block.
+	pub fn new(author: AuthorId) -> Self {
+		Self { author }
+	}
+}
+
+impl<AuthorId: Encode + Send + Sync> ParachainInherentExtension for AuthorInherent<AuthorId> {
+	fn provide_inherent_data(
+		&self,
+		inherent_data: &mut InherentData,
+		_: &GlobalValidationData,
+		_: &LocalValidationData,
+	) -> Result<(), sp_inherents::Error> {
+		inherent_data.put_data(AUTHOR_IDENTIFIER, &self.author)
+	}
+}
+
 /// The implementation of the Cumulus `Collator`.
 pub struct Collator<Block: BlockT, PF, BI, BS> {
 	proposer_factory: Arc<Mutex<PF>>,
 	_phantom: PhantomData<Block>,
 	inherent_data_providers: InherentDataProviders,
+	inherent_extension: Arc<dyn ParachainInherentExtension>,
 	collator_network: Arc<dyn CollatorNetwork>,
 	block_import: Arc<Mutex<BI>>,
 	block_status: Arc<BS>,
 	wait_to_announce: Arc<Mutex<WaitToAnnounce<Block>>>,
+	status: Arc<CollatorStatus>,
+	self_validation: Option<Arc<SelfValidation<Block::Hash>>>,
+	relay_head_recorder: Option<Arc<RelayHeadRecorder>>,
+	pov_archive: Option<Arc<PovArchive>>,
+	max_downward_messages: Option<usize>,
+	core_assignment: Option<Arc<CoreAssignment>>,
+	authoring_backoff: Option<Arc<AuthoringBackoff>>,
+	forced_parent: Option<Block::Hash>,
+	start_delay: Option<Arc<StartDelay>>,
+	empty_blocks: Option<Arc<EmptyBlocks>>,
+	error_log: Arc<ErrorLog>,
 }
 
 impl<Block: BlockT, PF, BI, BS> Collator<Block, PF, BI, BS> {
@@ -76,75 +195,140 @@ impl<Block: BlockT, PF, BI, BS> Collator<Block, PF, BI, BS> {
 	fn new(
 		proposer_factory: PF,
 		inherent_data_providers: InherentDataProviders,
+		inherent_extension: Arc<dyn ParachainInherentExtension>,
 		collator_network: impl CollatorNetwork + Clone + 'static,
 		block_import: BI,
 		block_status: Arc<BS>,
 		spawner: Arc<dyn SpawnNamed + Send + Sync>,
 		announce_block: Arc<dyn Fn(Block::Hash, Vec<u8>) + Send + Sync>,
+		status: Arc<CollatorStatus>,
+		self_validation: Option<SelfValidation<Block::Hash>>,
+		relay_head_recorder: Option<Arc<RelayHeadRecorder>>,
+		pov_archive: Option<Arc<PovArchive>>,
+		announcement_expiry_blocks: Option<PBlockNumber>,
+		max_downward_messages: Option<usize>,
+		core_assignment: Option<CoreAssignment>,
+		authoring_backoff: Option<AuthoringBackoff>,
+		forced_parent: Option<Block::Hash>,
+		start_delay: Option<Duration>,
+		empty_blocks: Option<EmptyBlocks>,
 	) -> Self {
 		let collator_network = Arc::new(collator_network);
-		let wait_to_announce = Arc::new(Mutex::new(WaitToAnnounce::new(
-			spawner,
-			announce_block,
-			collator_network.clone(),
-		)));
+		let announcement_expiry = announcement_expiry_blocks.map(|blocks| {
+			let status = status.clone();
+
+			AnnouncementExpiry {
+				blocks,
+				relay_best: Arc::new(move || status.relay_chain_heights().map(|h| h.best)),
+			}
+		});
+		let wait_to_announce = Arc::new(Mutex::new(
+			WaitToAnnounce::new(spawner, announce_block, collator_network.clone())
+				.with_expiry(announcement_expiry),
+		));
 
 		Self {
 			proposer_factory: Arc::new(Mutex::new(proposer_factory)),
 			inherent_data_providers,
+			inherent_extension,
 			_phantom: PhantomData,
 			collator_network,
 			block_import: Arc::new(Mutex::new(block_import)),
 			block_status,
 			wait_to_announce,
+			status,
+			self_validation: self_validation.map(Arc::new),
+			relay_head_recorder,
+			pov_archive,
+			max_downward_messages,
+			core_assignment: core_assignment.map(Arc::new),
+			authoring_backoff: authoring_backoff.map(Arc::new),
+			forced_parent,
+			start_delay: start_delay.map(|delay| Arc::new(StartDelay::new(delay))),
+			empty_blocks: empty_blocks.map(Arc::new),
+			error_log: Default::default(),
 		}
 	}
+}
 
-	/// Get the inherent data with validation function parameters injected
-	fn inherent_data(
-		inherent_providers: InherentDataProviders,
-		global_validation: GlobalValidationData,
-		local_validation: LocalValidationData,
-		downward_messages: DownwardMessagesType,
-	) -> Option<InherentData> {
-		let mut inherent_data = inherent_providers
-			.create_inherent_data()
-			.map_err(|e| {
-				error!(
-					target: "cumulus-collator",
-					"Failed to create inherent data: {:?}",
-					e,
-				)
-			})
-			.ok()?;
+/// Limit the downward messages that are processed in one block to the first `max` messages.
+///
+/// The relay chain only removes the processed messages from the queue of the parachain, so the
+/// remaining messages are passed again, in the same order, when the next block is built.
+fn limit_downward_messages(
+	mut downward_messages: Vec<DownwardMessage>,
+	max: Option<usize>,
+) -> Vec<DownwardMessage> {
+	if let Some(max) = max {
+		if downward_messages.len() > max {
+			debug!(
+				target: "cumulus-collator",
+				"Processing {} of {} downward messages, the remaining are processed in the \
+				following blocks.",
+				max,
+				downward_messages.len(),
+			);
+			downward_messages.truncate(max);
+		}
+	}
 
-		inherent_data
-			.put_data(
-				VFP_IDENT,
-				&ValidationFunctionParams::from((global_validation, local_validation)),
+	downward_messages
+}
+
+/// Get the inherent data with validation function parameters injected
+///
+/// The order of the resulting inherents is determined by the runtime, see
+/// [`cumulus_primitives::inherents`].
+pub(crate) fn inherent_data(
+	inherent_providers: InherentDataProviders,
+	inherent_extension: &dyn ParachainInherentExtension,
+	global_validation: GlobalValidationData,
+	local_validation: LocalValidationData,
+	downward_messages: DownwardMessagesType,
+) -> Result<InherentData, String> {
+	let mut inherent_data = inherent_providers
+		.create_inherent_data()
+		.map_err(|e| format!("Failed to create inherent data: {:?}", e))?;
+
+	inherent_data
+		.put_data(
+			VFP_IDENT,
+			&ValidationFunctionParams::from((global_validation.clone(), local_validation.clone())),
+		)
+		.map_err(|e| {
+			format!(
+				"Failed to put validation function params into inherent data: {:?}",
+				e
 			)
-			.map_err(|e| {
-				error!(
-					target: "cumulus-collator",
-					"Failed to put validation function params into inherent data: {:?}",
-					e,
-				)
-			})
-			.ok()?;
+		})?;
+
+	inherent_data
+		.put_data(DOWNWARD_MESSAGES_IDENTIFIER, &downward_messages)
+		.map_err(|e| {
+			format!(
+				"Failed to put downward messages into inherent data: {:?}",
+				e
+			)
+		})?;
+
+	inherent_extension
+		.provide_inherent_data(&mut inherent_data, &global_validation, &local_validation)
+		.map_err(|e| {
+			format!(
+				"Failed to provide the inherent data of the extension: {:?}",
+				e
+			)
+		})?;
 
-		inherent_data
-			.put_data(DOWNWARD_MESSAGES_IDENTIFIER, &downward_messages)
-			.map_err(|e| {
-				error!(
-					target: "cumulus-collator",
-					"Failed to put downward messages into inherent data: {:?}",
-					e,
-				)
-			})
-			.ok()?;
+	Ok(inherent_data)
+}
 
-		Some(inherent_data)
-	}
+/// Why building a collation failed.
+struct Rejected {
+	/// The head data of the parachain block or `None` if no block was built.
+	head_data: Option<parachain::HeadData>,
+	/// The error that rejected the collation.
+	reason: String,
 }
 
 impl<Block: BlockT, PF, BI, BS> Clone for Collator<Block, PF, BI, BS> {
@@ -152,11 +336,23 @@ impl<Block: BlockT, PF, BI, BS> Clone for Collator<Block, PF, BI, BS> {
 		Self {
 			proposer_factory: self.proposer_factory.clone(),
 			inherent_data_providers: self.inherent_data_providers.clone(),
+			inherent_extension: self.inherent_extension.clone(),
 			_phantom: PhantomData,
 			collator_network: self.collator_network.clone(),
 			block_import: self.block_import.clone(),
 			block_status: self.block_status.clone(),
 			wait_to_announce: self.wait_to_announce.clone(),
+			status: self.status.clone(),
+			self_validation: self.self_validation.clone(),
+			relay_head_recorder: self.relay_head_recorder.clone(),
+			pov_archive: self.pov_archive.clone(),
+			max_downward_messages: self.max_downward_messages,
+			core_assignment: self.core_assignment.clone(),
+			authoring_backoff: self.authoring_backoff.clone(),
+			forced_parent: self.forced_parent,
+			start_delay: self.start_delay.clone(),
+			empty_blocks: self.empty_blocks.clone(),
+			error_log: self.error_log.clone(),
 		}
 	}
 }
@@ -175,6 +371,37 @@ where
 		+ 'static,
 	BS: BlockBackend<Block>,
 {
+	/// The head data of the parachain block `parent` that is built on instead of the head that is
+	/// included in the relay parent.
+	fn forced_parent_head(&self, parent: Block::Hash) -> Option<parachain::HeadData> {
+		match self.block_status.block(&BlockId::Hash(parent)) {
+			Ok(Some(block)) => {
+				debug!(
+					target: "cumulus-collator",
+					"Building the collation on the forced parent `{:?}`",
+					parent,
+				);
+
+				let header = block.block.header().clone();
+				Some(parachain::HeadData(HeadData::<Block> { header }.encode()))
+			}
+			Ok(None) => {
+				self.error_log.error(
+					"forced-parent",
+					format_args!("Forced parent `{:?}` not found, skipping collation", parent),
+				);
+				None
+			}
+			Err(e) => {
+				self.error_log.error(
+					"forced-parent",
+					format_args!("Failed to get the forced parent `{:?}`: {:?}", parent, e),
+				);
+				None
+			}
+		}
+	}
+
 	/// Checks the status of the given block hash in the Parachain.
 	///
 	/// Returns `true` if the block could be found and is good to be build on.
@@ -189,16 +416,23 @@ where
 			}
 			Ok(BlockStatus::InChainWithState) => true,
 			Ok(BlockStatus::InChainPruned) => {
-				error!(
-					target: "cumulus-collator",
-					"Skipping candidate production, because block `{:?}` is already pruned!", hash,
+				self.error_log.error(
+					"block-pruned",
+					format_args!(
+						"Skipping candidate production, because block `{:?}` is already pruned!",
+						hash,
+					),
 				);
 				false
 			}
 			Ok(BlockStatus::KnownBad) => {
-				error!(
-					target: "cumulus-collator",
-					"Block `{}` is tagged as known bad and is included in the relay chain! Skipping candidate production!", hash,
+				self.error_log.error(
+					"block-known-bad",
+					format_args!(
+						"Block `{}` is tagged as known bad and is included in the relay chain! \
+						Skipping candidate production!",
+						hash,
+					),
 				);
 				false
 			}
@@ -210,7 +444,10 @@ where
 				false
 			}
 			Err(e) => {
-				error!(target: "cumulus-collator", "Failed to get block status of `{:?}`: {:?}", hash, e);
+				self.error_log.error(
+					"block-status",
+					format_args!("Failed to get block status of `{:?}`: {:?}", hash, e),
+				);
 				false
 			}
 		}
@@ -238,19 +475,101 @@ where
 		&mut self,
 		relay_chain_parent: PHash,
 		global_validation: GlobalValidationData,
-		local_validation: LocalValidationData,
+		mut local_validation: LocalValidationData,
 		downward_messages: Vec<DownwardMessage>,
 	) -> Self::ProduceCandidate {
 		let factory = self.proposer_factory.clone();
 		let inherent_providers = self.inherent_data_providers.clone();
+		let inherent_extension = self.inherent_extension.clone();
 		let block_import = self.block_import.clone();
+		let status = self.status.clone();
+		let self_validation = self.self_validation.clone();
+		let relay_parent = RelayParent {
+			hash: relay_chain_parent,
+			number: global_validation.block_number,
+		};
+
+		if let Some(recorder) = &self.relay_head_recorder {
+			recorder.record(&RecordedRelayHead {
+				relay_parent: relay_chain_parent,
+				global_validation: global_validation.clone(),
+				local_validation: local_validation.clone(),
+				downward_messages: downward_messages.clone(),
+			});
+		}
+
+		// The head included in the relay parent, also if the collation is built on a forced parent.
+		let included_head = local_validation.parent_head.clone();
+
+		if let Some(parent) = self.forced_parent {
+			match self.forced_parent_head(parent) {
+				Some(parent_head) => local_validation.parent_head = parent_head,
+				None => return future::ready(None).boxed(),
+			}
+		}
+
+		let downward_messages =
+			limit_downward_messages(downward_messages, self.max_downward_messages);
+
+		let pov_archive = self
+			.pov_archive
+			.clone()
+			.map(|archive| (archive, global_validation.clone(), local_validation.clone()));
+
+		if status.is_paused() {
+			debug!(
+				target: "cumulus-collator",
+				"Skipping candidate production, because the collator is paused.",
+			);
+			return future::ready(None).boxed();
+		}
+
+		if !status.para_registered() {
+			debug!(
+				target: "cumulus-collator",
+				"Skipping candidate production, because the registration of the parachain is not \
+				visible on the relay chain yet.",
+			);
+			return future::ready(None).boxed();
+		}
+
+		if let Some(start_delay) = &self.start_delay {
+			if !start_delay.may_collate(!status.relay_major_syncing()) {
+				return future::ready(None).boxed();
+			}
+		}
+
+		if let Some(core_assignment) = &self.core_assignment {
+			if !core_assignment.has_core(relay_chain_parent) {
+				return future::ready(None).boxed();
+			}
+		}
+
+		if let Some(empty_blocks) = &self.empty_blocks {
+			if !empty_blocks.should_author(!downward_messages.is_empty()) {
+				return future::ready(None).boxed();
+			}
+		}
+
+		let empty_blocks = self.empty_blocks.clone();
+		let authoring_backoff = self.authoring_backoff.clone();
+		status.note_included_head(&included_head);
+
+		if let Some(backoff) = &authoring_backoff {
+			if !backoff.should_author(&included_head) {
+				return future::ready(None).boxed();
+			}
+		}
 
 		trace!(target: "cumulus-collator", "Producing candidate");
 
 		let last_head = match HeadData::<Block>::decode(&mut &local_validation.parent_head.0[..]) {
 			Ok(x) => x,
 			Err(e) => {
-				error!(target: "cumulus-collator", "Could not decode the head data: {:?}", e);
+				self.error_log.error(
+					"decode-head-data",
+					format_args!("Could not decode the head data: {:?}", e),
+				);
 				return Box::pin(future::ready(None));
 			}
 		};
@@ -262,25 +581,33 @@ where
 		let proposer_future = factory.lock().init(&last_head.header);
 
 		let wait_to_announce = self.wait_to_announce.clone();
+		let error_log = self.error_log.clone();
+
+		let build_candidate = async move {
+			let reject = |kind, reason: String| {
+				error_log.error(kind, format_args!("{}", reason));
+				Rejected {
+					head_data: None,
+					reason,
+				}
+			};
 
-		Box::pin(async move {
-			let proposer = proposer_future
-				.await
-				.map_err(|e| {
-					error!(
-						target: "cumulus-collator",
-						"Could not create proposer: {:?}",
-						e,
-					)
-				})
-				.ok()?;
+			let proposer = proposer_future.await.map_err(|e| {
+				reject(
+					"create-proposer",
+					format!("Could not create proposer: {:?}", e),
+				)
+			})?;
 
-			let inherent_data = Self::inherent_data(
+			let validation_data = (global_validation.clone(), local_validation.clone());
+			let inherent_data = inherent_data(
 				inherent_providers,
+				&*inherent_extension,
 				global_validation,
 				local_validation,
 				downward_messages,
-			)?;
+			)
+			.map_err(|e| reject("inherent-data", e))?;
 
 			let Proposal {
 				block,
@@ -295,32 +622,56 @@ where
 					RecordProof::Yes,
 				)
 				.await
-				.map_err(|e| {
-					error!(
-						target: "cumulus-collator",
-						"Proposing failed: {:?}",
-						e,
-					)
-				})
-				.ok()?;
-
-			let proof = match proof {
-				Some(proof) => proof,
-				None => {
-					error!(
-						target: "cumulus-collator",
-						"Proposer did not return the requested proof.",
-					);
+				.map_err(|e| reject("propose", format!("Proposing failed: {:?}", e)))?;
 
-					return None;
-				}
-			};
+			let proof = proof.ok_or_else(|| {
+				reject(
+					"missing-proof",
+					"Proposer did not return the requested proof.".into(),
+				)
+			})?;
 
 			let (header, extrinsics) = block.deconstruct();
+			let parent = *header.parent_hash();
 
 			// Create the parachain block data for the validators.
 			let b = ParachainBlockData::<Block>::new(header.clone(), extrinsics, proof);
 
+			let block_data = BlockData(b.encode());
+			let encoded_header = header.encode();
+			let hash = header.hash();
+			let head_data = HeadData::<Block> {
+				header: header.clone(),
+			};
+
+			let candidate = (block_data, parachain::HeadData(head_data.encode()));
+
+			// Validate before the import, so a rejected collation is not imported.
+			if let Some(self_validation) = self_validation {
+				let (global_validation, local_validation) = validation_data;
+
+				if let Err(e) = self_validation
+					.validate(
+						parent,
+						&candidate.0,
+						&candidate.1,
+						&global_validation,
+						&local_validation,
+					)
+					.await
+				{
+					let reason = format!(
+						"Self validation of the collation on top of `{:?}` failed: {}",
+						parent, e,
+					);
+
+					return Err(Rejected {
+						head_data: Some(candidate.1),
+						..reject("self-validation", reason)
+					});
+				}
+			}
+
 			let mut block_import_params = BlockImportParams::new(BlockOrigin::Own, header);
 			block_import_params.body = Some(b.extrinsics().to_vec());
 			// Best block is determined by the relay chain.
@@ -331,31 +682,75 @@ where
 				.lock()
 				.import_block(block_import_params, Default::default())
 			{
-				error!(
-					target: "cumulus-collator",
-					"Error importing build block (at {:?}): {:?}",
-					b.header().parent_hash(),
-					err,
-				);
-
-				return None;
+				return Err(reject(
+					"import-block",
+					format!("Error importing build block (at {:?}): {:?}", parent, err),
+				));
 			}
 
-			let block_data = BlockData(b.encode());
-			let header = b.into_header();
-			let encoded_header = header.encode();
-			let hash = header.hash();
-			let head_data = HeadData::<Block> { header };
-
-			let candidate = (block_data, parachain::HeadData(head_data.encode()));
-
-			wait_to_announce
-				.lock()
-				.wait_to_announce(hash, relay_chain_parent, encoded_header);
+			wait_to_announce.lock().wait_to_announce(
+				hash,
+				relay_chain_parent,
+				relay_parent.number,
+				encoded_header,
+			);
 
 			trace!(target: "cumulus-collator", "Produced candidate: {:?}", candidate);
 
-			Some(candidate)
+			Ok(candidate)
+		};
+
+		Box::pin(async move {
+			match build_candidate.await {
+				Ok(candidate) => {
+					let (block_data, head_data) = &candidate;
+
+					status.set_relay_parent(relay_parent);
+					status.add_collation(RecentCollation {
+						relay_parent,
+						head_data: head_data.clone(),
+						block_data: block_data.clone(),
+					});
+					status.notify_collation(CollationEvent {
+						relay_parent,
+						head_data: Some(head_data.clone()),
+						pov_size: Some(block_data.0.len()),
+						result: CollationResult::Submitted,
+					});
+
+					if let Some((archive, global_validation, local_validation)) = pov_archive {
+						archive.archive(ArchivedPov {
+							relay_parent: relay_parent.hash,
+							relay_number: relay_parent.number,
+							head_data: head_data.clone(),
+							block_data: block_data.clone(),
+							global_validation,
+							local_validation,
+						});
+					}
+
+					status.note_unincluded_collation(&included_head, relay_parent);
+					if let Some(empty_blocks) = empty_blocks {
+						empty_blocks.note_block();
+					}
+					if let Some(backoff) = authoring_backoff {
+						backoff.note_collation(&included_head);
+					}
+
+					Some(candidate)
+				}
+				Err(rejected) => {
+					status.notify_collation(CollationEvent {
+						relay_parent,
+						head_data: rejected.head_data.clone(),
+						pov_size: None,
+						result: CollationResult::Rejected,
+					});
+					status.add_rejection(relay_parent, rejected.head_data, rejected.reason);
+
+					None
+				}
+			}
 		})
 	}
 }
@@ -364,12 +759,30 @@ where
 pub struct CollatorBuilder<Block: BlockT, PF, BI, Backend, Client, BS> {
 	proposer_factory: PF,
 	inherent_data_providers: InherentDataProviders,
+	inherent_extension: Arc<dyn ParachainInherentExtension>,
 	block_import: BI,
 	block_status: Arc<BS>,
 	para_id: ParaId,
 	client: Arc<Client>,
 	announce_block: Arc<dyn Fn(Block::Hash, Vec<u8>) + Send + Sync>,
 	delayed_block_announce_validator: DelayedBlockAnnounceValidator<Block>,
+	status: Arc<CollatorStatus>,
+	fetch_retry: FetchRetry,
+	prometheus_registry: Option<Registry>,
+	backed_head: BackedHead<Block>,
+	self_validation: bool,
+	self_validation_timeout: Duration,
+	wasm_execution: WasmExecutionMethod,
+	pov_cache_size: usize,
+	relay_head_recorder: Option<Arc<RelayHeadRecorder>>,
+	pov_archive: Option<Arc<PovArchive>>,
+	announcement_expiry_blocks: Option<PBlockNumber>,
+	max_downward_messages: Option<usize>,
+	authoring_backoff_threshold: Option<NonZeroU32>,
+	collate_on: CollateOn,
+	forced_parent: Option<Block::Hash>,
+	collation_start_delay: Option<Duration>,
+	empty_blocks: Option<EmptyBlocks>,
 	_marker: PhantomData<(Block, Backend)>,
 }
 
@@ -386,19 +799,242 @@ impl<Block: BlockT, PF, BI, Backend, Client, BS>
 		client: Arc<Client>,
 		announce_block: Arc<dyn Fn(Block::Hash, Vec<u8>) + Send + Sync>,
 		delayed_block_announce_validator: DelayedBlockAnnounceValidator<Block>,
+		status: Arc<CollatorStatus>,
+		fetch_retry: FetchRetry,
+		prometheus_registry: Option<Registry>,
+		backed_head: BackedHead<Block>,
 	) -> Self {
 		Self {
 			proposer_factory,
 			inherent_data_providers,
+			inherent_extension: Arc::new(()),
 			block_import,
 			block_status,
 			para_id,
 			client,
 			announce_block,
 			delayed_block_announce_validator,
+			status,
+			fetch_retry,
+			prometheus_registry,
+			backed_head,
+			self_validation: false,
+			self_validation_timeout: DEFAULT_SELF_VALIDATION_TIMEOUT,
+			wasm_execution: VALIDATION_WASM_EXECUTION,
+			pov_cache_size: DEFAULT_POV_CACHE_SIZE,
+			relay_head_recorder: None,
+			pov_archive: None,
+			announcement_expiry_blocks: None,
+			max_downward_messages: None,
+			authoring_backoff_threshold: None,
+			collate_on: CollateOn::default(),
+			forced_parent: None,
+			collation_start_delay: None,
+			empty_blocks: None,
 			_marker: PhantomData,
 		}
 	}
+
+	/// Use the given extension to supply additional inherent data for every block.
+	pub fn with_inherent_extension(
+		mut self,
+		inherent_extension: Arc<dyn ParachainInherentExtension>,
+	) -> Self {
+		self.inherent_extension = inherent_extension;
+		self
+	}
+
+	/// Validate every collation with the local validation function before it is handed over to
+	/// the relay chain.
+	///
+	/// Collations that fail the validation are dropped.
+	pub fn with_self_validation(mut self, self_validation: bool) -> Self {
+		self.self_validation = self_validation;
+		self
+	}
+
+	/// Drop a collation if its self validation takes longer than `timeout`.
+	///
+	/// Defaults to [`DEFAULT_SELF_VALIDATION_TIMEOUT`].
+	pub fn with_self_validation_timeout(mut self, timeout: Duration) -> Self {
+		self.self_validation_timeout = timeout;
+		self
+	}
+
+	/// Execute the validation function of the self validation with `method`.
+	///
+	/// This should be the method the parachain runtime is executed with, so building and
+	/// validating a collation give the same results. Defaults to [`VALIDATION_WASM_EXECUTION`].
+	pub fn with_wasm_execution(mut self, method: WasmExecutionMethod) -> Self {
+		self.wasm_execution = method;
+		self
+	}
+
+	/// Remember the last `size` PoVs that passed the self validation, so they are not validated
+	/// again.
+	///
+	/// Defaults to [`DEFAULT_POV_CACHE_SIZE`], `0` disables the cache.
+	pub fn with_pov_cache_size(mut self, size: usize) -> Self {
+		self.pov_cache_size = size;
+		self
+	}
+
+	/// Record the relay chain heads and validation data the collator receives with the given
+	/// `relay_head_recorder`, if any.
+	pub fn with_relay_head_recorder(
+		mut self,
+		relay_head_recorder: Option<Arc<RelayHeadRecorder>>,
+	) -> Self {
+		self.relay_head_recorder = relay_head_recorder;
+		self
+	}
+
+	/// Write every collation to the given `pov_archive`, if any.
+	pub fn with_pov_archive(mut self, pov_archive: Option<Arc<PovArchive>>) -> Self {
+		self.pov_archive = pov_archive;
+		self
+	}
+
+	/// Withdraw the announcement of a collated block that still waits for its candidate message
+	/// when the relay parent is more than `blocks` behind the best relay chain block.
+	pub fn with_announcement_expiry(mut self, blocks: Option<PBlockNumber>) -> Self {
+		self.announcement_expiry_blocks = blocks;
+		self
+	}
+
+	/// Process at most `max` downward messages per block, the remaining messages are processed
+	/// in the following blocks.
+	pub fn with_max_downward_messages(mut self, max: Option<usize>) -> Self {
+		self.max_downward_messages = max;
+		self
+	}
+
+	/// Back off exponentially once `threshold` collations on the same parachain head were not
+	/// included, if any.
+	///
+	/// The backoff is reset when the relay chain includes a new head of the parachain.
+	pub fn with_authoring_backoff(mut self, threshold: Option<NonZeroU32>) -> Self {
+		self.authoring_backoff_threshold = threshold;
+		self
+	}
+
+	/// Choose the relay chain blocks that trigger a collation attempt.
+	///
+	/// Defaults to [`CollateOn::WhenScheduled`].
+	pub fn with_collate_on(mut self, collate_on: CollateOn) -> Self {
+		self.collate_on = collate_on;
+		self
+	}
+
+	/// Build every collation on the parachain block `parent`, if any, instead of the head that
+	/// is included in the relay parent.
+	///
+	/// For creating forks in tests, the relay chain rejects these collations unless `parent` is
+	/// the included head.
+	pub fn with_forced_parent(mut self, parent: Option<Block::Hash>) -> Self {
+		self.forced_parent = parent;
+		self
+	}
+
+	/// Hold off the first collation for `delay` after the collator was built and until the relay
+	/// chain node is synced, if any.
+	///
+	/// By default the collator starts collating immediately.
+	pub fn with_collation_start_delay(mut self, delay: Option<Duration>) -> Self {
+		self.collation_start_delay = delay;
+		self
+	}
+
+	/// Decide with `empty_blocks` whether a block without transactions is built, if any.
+	///
+	/// By default a block is built on every collation attempt.
+	pub fn with_empty_blocks(mut self, empty_blocks: Option<EmptyBlocks>) -> Self {
+		self.empty_blocks = empty_blocks;
+		self
+	}
+}
+
+impl<Block: BlockT, PF, BI, Backend, Client, BS> CollatorBuilder<Block, PF, BI, Backend, Client, BS>
+where
+	Backend: sc_client_api::Backend<Block> + 'static,
+	Client: StorageProvider<Block, Backend> + Send + Sync + 'static,
+{
+	/// The self validation of the collations, if enabled.
+	fn build_self_validation(&self) -> Option<SelfValidation<Block::Hash>> {
+		if !self.self_validation {
+			return None;
+		}
+
+		let client = self.client.clone();
+
+		Some(SelfValidation::new(
+			Box::new(move |hash| {
+				let code = StorageKey(well_known_keys::CODE.to_vec());
+
+				client
+					.storage(&BlockId::Hash(hash), &code)
+					.ok()
+					.flatten()
+					.map(|code| code.0)
+			}),
+			self.wasm_execution,
+			self.self_validation_timeout,
+			PovCache::new(self.pov_cache_size, self.prometheus_registry.as_ref()),
+		))
+	}
+
+	/// Build a collator that is driven by a [`MockRelayChain`](mock_relay::MockRelayChain)
+	/// instead of a relay chain node, for tests.
+	///
+	/// The collator does not follow any relay chain, so the parachain is registered and
+	/// scheduled at every relay chain block and the relay chain related options are ignored.
+	pub fn build_with_mock_relay_chain(
+		self,
+		spawner: impl SpawnNamed + Send + Sync + 'static,
+	) -> Collator<Block, PF, BI, BS> {
+		let self_validation = self.build_self_validation();
+		let CollatorBuilder {
+			proposer_factory,
+			inherent_data_providers,
+			inherent_extension,
+			block_import,
+			block_status,
+			announce_block,
+			status,
+			relay_head_recorder,
+			pov_archive,
+			max_downward_messages,
+			authoring_backoff_threshold,
+			forced_parent,
+			empty_blocks,
+			..
+		} = self;
+
+		status.set_para_registered();
+		status.set_authoring_backoff_threshold(authoring_backoff_threshold.map(NonZeroU32::get));
+
+		Collator::new(
+			proposer_factory,
+			inherent_data_providers,
+			inherent_extension,
+			mock_relay::MockCollatorNetwork,
+			block_import,
+			block_status,
+			Arc::new(spawner),
+			announce_block,
+			status,
+			self_validation,
+			relay_head_recorder,
+			pov_archive,
+			None,
+			max_downward_messages,
+			None,
+			authoring_backoff_threshold.map(AuthoringBackoff::new),
+			forced_parent,
+			None,
+			empty_blocks,
+		)
+	}
 }
 
 type TransactionFor<E, Block> =
@@ -416,6 +1052,7 @@ where
 	Client: Finalizer<Block, Backend>
 		+ UsageProvider<Block>
 		+ HeaderBackend<Block>
+		+ StorageProvider<Block, Backend>
 		+ Send
 		+ Sync
 		+ BlockBackend<Block>
@@ -439,15 +1076,34 @@ where
 		PClient::Api: RuntimeApiCollection<StateBackend = PBackend::State>,
 		PNetwork: CollatorNetwork + SyncOracle + Clone + 'static,
 	{
+		let self_validation = self.build_self_validation();
 		let CollatorBuilder {
 			proposer_factory,
 			inherent_data_providers,
+			inherent_extension,
 			block_import,
 			block_status,
 			para_id,
 			client,
 			announce_block,
 			delayed_block_announce_validator,
+			status,
+			fetch_retry,
+			prometheus_registry,
+			backed_head,
+			self_validation: _,
+			self_validation_timeout: _,
+			wasm_execution: _,
+			pov_cache_size: _,
+			relay_head_recorder,
+			pov_archive,
+			announcement_expiry_blocks,
+			max_downward_messages,
+			authoring_backoff_threshold,
+			collate_on,
+			forced_parent,
+			collation_start_delay,
+			empty_blocks,
 			_marker,
 		} = self;
 		delayed_block_announce_validator.set(Box::new(JustifiedBlockAnnounceValidator::new(
@@ -456,11 +1112,75 @@ where
 			Box::new(polkadot_network.clone()),
 		)));
 
+		status.set_relay_sync_oracle(Box::new(polkadot_network.clone()));
+		status.set_authoring_backoff_threshold(authoring_backoff_threshold.map(NonZeroU32::get));
+		if let Some(registry) = &prometheus_registry {
+			status.register_metrics(registry);
+		}
+		spawner.spawn(
+			"cumulus-follow-relay-chain",
+			follow_relay_chain(polkadot_client.clone(), status.clone()).boxed(),
+		);
+
+		spawner.spawn(
+			"cumulus-track-assigned-core",
+			track_assigned_core(polkadot_client.clone(), para_id, status.clone()).boxed(),
+		);
+
+		spawner.spawn(
+			"cumulus-record-inclusions",
+			record_inclusions(polkadot_client.clone(), para_id, status.clone()).boxed(),
+		);
+
+		let core_assignment = match collate_on {
+			CollateOn::WhenScheduled => {
+				let polkadot_client = polkadot_client.clone();
+				let status = status.clone();
+
+				Some(CoreAssignment::new(Box::new(move |relay_parent| {
+					status
+						.relay_call_cache()
+						.get_or_fetch(relay_parent, "active_parachains", || {
+							polkadot_client
+								.runtime_api()
+								.active_parachains(&BlockId::Hash(relay_parent))
+						})
+						.map(|active| active.iter().any(|(id, _)| *id == para_id))
+						.map_err(|e| format!("{:?}", e))
+				})))
+			}
+			CollateOn::EveryBlock => None,
+		};
+
+		let relay_client = polkadot_client.clone();
+		let polkadot_client =
+			RetryingPolkadotClient::new(polkadot_client, fetch_retry, prometheus_registry.as_ref());
+
+		let cache_client = polkadot_client.clone();
+		spawner.spawn(
+			"cumulus-watch-relay-runtime",
+			watch_relay_runtime(relay_client, status.clone(), move || {
+				cache_client.clear_cache()
+			})
+			.boxed(),
+		);
+
+		spawner.spawn(
+			"cumulus-wait-for-registration",
+			wait_for_registration(polkadot_client.clone(), para_id, status.clone()).boxed(),
+		);
+
+		spawner.spawn(
+			"cumulus-track-inclusion",
+			track_inclusion(polkadot_client.clone(), para_id, status.clone()).boxed(),
+		);
+
 		let follow = match cumulus_consensus::follow_polkadot(
 			para_id,
 			client,
 			polkadot_client,
 			announce_block.clone(),
+			backed_head,
 		) {
 			Ok(follow) => follow,
 			Err(e) => {
@@ -473,11 +1193,23 @@ where
 		Ok(Collator::new(
 			proposer_factory,
 			inherent_data_providers,
+			inherent_extension,
 			polkadot_network,
 			block_import,
 			block_status,
 			Arc::new(spawner),
 			announce_block,
+			status,
+			self_validation,
+			relay_head_recorder,
+			pov_archive,
+			announcement_expiry_blocks,
+			max_downward_messages,
+			core_assignment,
+			authoring_backoff_threshold.map(AuthoringBackoff::new),
+			forced_parent,
+			collation_start_delay,
+			empty_blocks,
 		))
 	}
 }
@@ -610,6 +1342,8 @@ mod tests {
 		let announce_block = |_, _| ();
 		let block_announce_validator = DelayedBlockAnnounceValidator::new();
 		let client = Arc::new(TestClientBuilder::new().build());
+		let status = Arc::new(CollatorStatus::new());
+		status.set_para_registered();
 
 		let builder = CollatorBuilder::new(
 			DummyFactory,
@@ -620,6 +1354,10 @@ mod tests {
 			client.clone(),
 			Arc::new(announce_block),
 			block_announce_validator,
+			status,
+			FetchRetry::default(),
+			None,
+			BackedHead::default(),
 		);
 		let context = builder
 			.build::<_, _, polkadot_service::FullBackend, _>(
@@ -667,4 +1405,168 @@ mod tests {
 
 		assert_eq!(1337, *block.header().number());
 	}
+
+	#[test]
+	fn mock_relay_chain_includes_collations() {
+		let _ = env_logger::try_init();
+		let client = Arc::new(TestClientBuilder::new().build());
+		let header = client.header(&BlockId::Number(0)).unwrap().unwrap();
+		let genesis_head = parachain::HeadData(HeadData::<Block> { header }.encode());
+
+		let mut collator = CollatorBuilder::<_, _, _, test_client::Backend, _, _>::new(
+			DummyFactory,
+			InherentDataProviders::default(),
+			client.clone(),
+			client.clone(),
+			ParaId::from(100),
+			client.clone(),
+			Arc::new(|_, _| ()),
+			DelayedBlockAnnounceValidator::new(),
+			Arc::new(CollatorStatus::new()),
+			FetchRetry::default(),
+			None,
+			BackedHead::default(),
+		)
+		.build_with_mock_relay_chain(TaskExecutor::new());
+		let mut relay_chain = mock_relay::MockRelayChain::new(genesis_head.clone());
+
+		relay_chain.set_include_collations(false);
+		let (_, head_data) = futures::executor::block_on(relay_chain.new_block(&mut collator))
+			.expect("Produces a collation");
+		assert_eq!(&genesis_head, relay_chain.included_head());
+
+		relay_chain.set_include_collations(true);
+		let (block_data, included) =
+			futures::executor::block_on(relay_chain.new_block(&mut collator))
+				.expect("Produces a collation");
+		assert_eq!(head_data, included);
+		assert_eq!(&included, relay_chain.included_head());
+		assert_eq!(2, relay_chain.best_number());
+
+		let block = Block::decode(&mut &block_data.0[..]).expect("Is a valid block");
+		assert_eq!(1337, *block.header().number());
+	}
+
+	const CUSTOM_IDENTIFIER: sp_inherents::InherentIdentifier = *b"custom00";
+
+	struct CustomInherent(sp_inherents::InherentIdentifier);
+
+	impl ParachainInherentExtension for CustomInherent {
+		fn provide_inherent_data(
+			&self,
+			inherent_data: &mut InherentData,
+			global_validation: &GlobalValidationData,
+			_: &LocalValidationData,
+		) -> Result<(), sp_inherents::Error> {
+			inherent_data.put_data(self.0, &global_validation.block_number)
+		}
+	}
+
+	fn validation_data() -> (GlobalValidationData, LocalValidationData) {
+		(
+			GlobalValidationData {
+				block_number: 42,
+				max_code_size: 0,
+				max_head_data_size: 0,
+			},
+			LocalValidationData {
+				parent_head: Default::default(),
+				balance: 10,
+				code_upgrade_allowed: None,
+			},
+		)
+	}
+
+	#[test]
+	fn inherent_extension_data_is_merged() {
+		let (global_validation, local_validation) = validation_data();
+
+		let inherent_data = inherent_data(
+			InherentDataProviders::default(),
+			&CustomInherent(CUSTOM_IDENTIFIER),
+			global_validation,
+			local_validation,
+			Vec::new(),
+		)
+		.expect("Creates inherent data");
+
+		assert_eq!(
+			Some(42u32),
+			inherent_data.get_data(&CUSTOM_IDENTIFIER).unwrap()
+		);
+		assert!(inherent_data
+			.get_data::<ValidationFunctionParams>(&VFP_IDENT)
+			.unwrap()
+			.is_some());
+	}
+
+	#[test]
+	fn author_is_put_into_the_inherent_data() {
+		let (global_validation, local_validation) = validation_data();
+		let author = Sr25519Keyring::Alice.public();
+
+		let inherent_data = inherent_data(
+			InherentDataProviders::default(),
+			&AuthorInherent::new(author),
+			global_validation,
+			local_validation,
+			Vec::new(),
+		)
+		.expect("Creates inherent data");
+
+		assert_eq!(
+			Some(author),
+			inherent_data.get_data(&AUTHOR_IDENTIFIER).unwrap()
+		);
+	}
+
+	#[test]
+	fn downward_message_backlog_is_drained_incrementally() {
+		let backlog = (0..25u8)
+			.map(|i| DownwardMessage::Opaque(vec![i]))
+			.collect::<Vec<_>>();
+		let mut queue = backlog.clone();
+		let mut processed = Vec::new();
+		let mut blocks = 0;
+
+		while !queue.is_empty() {
+			let (global_validation, local_validation) = validation_data();
+			let inherent_data = inherent_data(
+				InherentDataProviders::default(),
+				&(),
+				global_validation,
+				local_validation,
+				limit_downward_messages(queue.clone(), Some(10)),
+			)
+			.expect("Creates inherent data");
+			let messages = inherent_data
+				.get_data::<DownwardMessagesType>(&DOWNWARD_MESSAGES_IDENTIFIER)
+				.unwrap()
+				.unwrap();
+			assert!(messages.len() <= 10);
+
+			// The relay chain only removes the processed messages from the queue.
+			queue.drain(..messages.len());
+			processed.extend(messages);
+			blocks += 1;
+		}
+
+		assert_eq!(3, blocks);
+		assert_eq!(backlog, processed);
+		assert_eq!(backlog, limit_downward_messages(backlog.clone(), None));
+	}
+
+	#[test]
+	fn inherent_extension_can_not_replace_mandatory_inherents() {
+		let (global_validation, local_validation) = validation_data();
+
+		assert!(inherent_data(
+			InherentDataProviders::default(),
+			&CustomInherent(VFP_IDENT),
+			global_validation,
+			local_validation,
+			Vec::new(),
+		)
+		.is_err());
+	}
 }
@@ -0,0 +1,132 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An in-process relay chain for testing the collation pipeline.
+//!
+//! [`MockRelayChain`] replaces the relay chain node of a collator that is built with
+//! [`CollatorBuilder::build_with_mock_relay_chain`](crate::CollatorBuilder). Every relay chain
+//! block is produced on demand with [`MockRelayChain::new_block`], which lets the collator build
+//! a collation on top of the included parachain head and includes it. There are no validators,
+//! no network and no timing involved, so the same calls always lead to the same collations.
+
+use crate::dev::{MAX_CODE_SIZE, MAX_HEAD_DATA_SIZE};
+
+use polkadot_collator::{Network as CollatorNetwork, ParachainContext, SignedStatement};
+use polkadot_primitives::v0::{
+	self as parachain, BlockData, BlockNumber as PBlockNumber, GlobalValidationData,
+	Hash as PHash, LocalValidationData,
+};
+
+use futures::{stream, Stream, StreamExt};
+
+use std::pin::Pin;
+
+/// A relay chain that includes the collations of one parachain.
+pub struct MockRelayChain {
+	best_number: PBlockNumber,
+	included_head: parachain::HeadData,
+	include_collations: bool,
+}
+
+impl MockRelayChain {
+	/// Create a new instance on which `genesis_head` is the included head of the parachain.
+	pub fn new(genesis_head: parachain::HeadData) -> Self {
+		Self {
+			best_number: 0,
+			included_head: genesis_head,
+			include_collations: true,
+		}
+	}
+
+	/// Set whether the collations are included, `true` by default.
+	///
+	/// Not including them simulates a relay chain on which the backing of the collations fails.
+	pub fn set_include_collations(&mut self, include: bool) {
+		self.include_collations = include;
+	}
+
+	/// The number of the best relay chain block.
+	pub fn best_number(&self) -> PBlockNumber {
+		self.best_number
+	}
+
+	/// The head of the parachain that is included at the best relay chain block.
+	pub fn included_head(&self) -> &parachain::HeadData {
+		&self.included_head
+	}
+
+	/// The hash of the relay chain block with the given `number`.
+	pub fn block_hash(number: PBlockNumber) -> PHash {
+		PHash::from_low_u64_be(number.into())
+	}
+
+	/// The validation data of the parachain at the best relay chain block.
+	pub fn validation_data(&self) -> (GlobalValidationData, LocalValidationData) {
+		(
+			GlobalValidationData {
+				max_code_size: MAX_CODE_SIZE,
+				max_head_data_size: MAX_HEAD_DATA_SIZE,
+				block_number: self.best_number,
+			},
+			LocalValidationData {
+				parent_head: self.included_head.clone(),
+				balance: 0,
+				code_upgrade_allowed: None,
+			},
+		)
+	}
+
+	/// Produce a new relay chain block and let `collator` build a collation on top of it.
+	///
+	/// Returns the collation, if any, which is included by the next relay chain block.
+	pub async fn new_block<PC: ParachainContext>(
+		&mut self,
+		collator: &mut PC,
+	) -> Option<(BlockData, parachain::HeadData)> {
+		self.best_number += 1;
+
+		let (global_validation, local_validation) = self.validation_data();
+		let collation = collator
+			.produce_candidate(
+				Self::block_hash(self.best_number),
+				global_validation,
+				local_validation,
+				Vec::new(),
+			)
+			.await;
+
+		if let Some((_, head_data)) = &collation {
+			if self.include_collations {
+				self.included_head = head_data.clone();
+			}
+		}
+
+		collation
+	}
+}
+
+/// The collator network of a collator that is connected to a [`MockRelayChain`].
+///
+/// There are no validators, so no statement about a collation is ever received and no block is
+/// announced.
+#[derive(Clone)]
+pub(crate) struct MockCollatorNetwork;
+
+impl CollatorNetwork for MockCollatorNetwork {
+	fn checked_statements(&self, _: PHash) -> Pin<Box<dyn Stream<Item = SignedStatement> + Send>> {
+		stream::empty().boxed()
+	}
+}
@@ -0,0 +1,221 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! An on-disk archive of the PoVs the collator produces, for the analysis of a stalled parachain.
+//!
+//! Every PoV is stored SCALE encoded in its own file, together with the head data the collator
+//! committed to and the validation data it was built on. The candidate receipt is created by the
+//! relay chain node from these, so it is not part of the archive.
+
+use polkadot_primitives::v0::{
+	BlockData, BlockNumber as PBlockNumber, GlobalValidationData, HeadData, Hash as PHash,
+	LocalValidationData,
+};
+
+use codec::{Decode, Encode};
+use log::{debug, error};
+use parking_lot::Mutex;
+
+use std::{
+	collections::VecDeque,
+	fs, io,
+	path::{Path, PathBuf},
+	sync::mpsc,
+	thread,
+};
+
+/// The extension of the files of the archived PoVs.
+const POV_FILE_EXTENSION: &str = "pov";
+
+/// A PoV the collator produced.
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+pub struct ArchivedPov {
+	/// The hash of the relay chain block the PoV was built on.
+	pub relay_parent: PHash,
+	/// The number of the relay chain block the PoV was built on.
+	pub relay_number: PBlockNumber,
+	/// The head data the collator committed to.
+	pub head_data: HeadData,
+	/// The PoV.
+	pub block_data: BlockData,
+	/// The global validation data at the relay parent.
+	pub global_validation: GlobalValidationData,
+	/// The validation data of the parachain at the relay parent.
+	pub local_validation: LocalValidationData,
+}
+
+impl ArchivedPov {
+	/// The name of the file the PoV is stored in.
+	///
+	/// The names sort in the order of the relay parents.
+	fn file_name(&self) -> String {
+		format!(
+			"{:010}-{:?}.{}",
+			self.relay_number, self.relay_parent, POV_FILE_EXTENSION,
+		)
+	}
+}
+
+/// Writes the PoVs the collator produces to a directory, keeping only the most recent ones.
+///
+/// The files are written by a dedicated thread, so archiving a PoV never blocks the collator.
+pub struct PovArchive {
+	sender: Mutex<mpsc::Sender<ArchivedPov>>,
+}
+
+impl PovArchive {
+	/// Create a new instance that archives to the directory `dir` and keeps at most `keep` PoVs.
+	///
+	/// The directory is created if it does not exist. PoVs that are already archived in it count
+	/// towards `keep`.
+	pub fn open(dir: &Path, keep: usize) -> io::Result<Self> {
+		fs::create_dir_all(dir)?;
+
+		let mut archived = fs::read_dir(dir)?
+			.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+			.filter(|path| path.extension().map_or(false, |e| e == POV_FILE_EXTENSION))
+			.collect::<Vec<_>>();
+		archived.sort();
+
+		let (sender, receiver) = mpsc::channel();
+		let dir = dir.to_path_buf();
+
+		thread::Builder::new()
+			.name("cumulus-pov-archive".into())
+			.spawn(move || write_povs(dir, keep, archived.into(), receiver))?;
+
+		Ok(Self {
+			sender: Mutex::new(sender),
+		})
+	}
+
+	/// Archive the given `pov`.
+	pub(crate) fn archive(&self, pov: ArchivedPov) {
+		if self.sender.lock().send(pov).is_err() {
+			error!(
+				target: "cumulus-collator",
+				"Failed to archive a PoV: the archive thread stopped",
+			);
+		}
+	}
+}
+
+/// Write the PoVs received from `receiver` to `dir`, removing the oldest beyond `keep`.
+fn write_povs(
+	dir: PathBuf,
+	keep: usize,
+	mut archived: VecDeque<PathBuf>,
+	receiver: mpsc::Receiver<ArchivedPov>,
+) {
+	while let Ok(pov) = receiver.recv() {
+		let path = dir.join(pov.file_name());
+
+		match fs::write(&path, pov.encode()) {
+			Ok(()) => archived.push_back(path),
+			Err(e) => error!(
+				target: "cumulus-collator",
+				"Failed to archive the PoV built on `{:?}` to `{}`: {}",
+				pov.relay_parent,
+				path.display(),
+				e,
+			),
+		}
+
+		while archived.len() > keep {
+			if let Some(oldest) = archived.pop_front() {
+				debug!(
+					target: "cumulus-collator",
+					"Removing `{}` from the PoV archive",
+					oldest.display(),
+				);
+
+				if let Err(e) = fs::remove_file(&oldest) {
+					error!(
+						target: "cumulus-collator",
+						"Failed to remove `{}` from the PoV archive: {}",
+						oldest.display(),
+						e,
+					);
+				}
+			}
+		}
+	}
+}
+
+/// Read a PoV that was archived by a [`PovArchive`] to `path`.
+pub fn read_archived_pov(path: &Path) -> Result<ArchivedPov, String> {
+	let data =
+		fs::read(path).map_err(|e| format!("Failed to read `{}`: {}", path.display(), e))?;
+
+	ArchivedPov::decode(&mut &data[..])
+		.map_err(|e| format!("Invalid archived PoV in `{}`: {:?}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::time::{Duration, Instant};
+
+	fn pov(relay_number: PBlockNumber) -> ArchivedPov {
+		ArchivedPov {
+			relay_parent: PHash::repeat_byte(relay_number as u8),
+			relay_number,
+			head_data: HeadData(vec![1, 2, 3]),
+			block_data: BlockData(vec![relay_number as u8; 64]),
+			global_validation: GlobalValidationData {
+				block_number: relay_number,
+				max_code_size: 1024,
+				max_head_data_size: 32,
+			},
+			local_validation: LocalValidationData {
+				parent_head: Default::default(),
+				balance: 10,
+				code_upgrade_allowed: None,
+			},
+		}
+	}
+
+	fn archived_files(dir: &Path) -> Vec<PathBuf> {
+		let mut files = fs::read_dir(dir)
+			.unwrap()
+			.map(|entry| entry.unwrap().path())
+			.collect::<Vec<_>>();
+		files.sort();
+		files
+	}
+
+	#[test]
+	fn only_the_most_recent_povs_are_kept() {
+		let dir = std::env::temp_dir().join(format!("cumulus-pov-archive-{}", std::process::id()));
+		let archive = PovArchive::open(&dir, 2).unwrap();
+
+		(1..=3).for_each(|n| archive.archive(pov(n)));
+
+		let expected = vec![dir.join(pov(2).file_name()), dir.join(pov(3).file_name())];
+		let started = Instant::now();
+		while archived_files(&dir) != expected && started.elapsed() < Duration::from_secs(10) {
+			thread::sleep(Duration::from_millis(10));
+		}
+
+		let files = archived_files(&dir);
+		let archived = files.iter().map(|f| read_archived_pov(f)).collect::<Vec<_>>();
+		let _ = fs::remove_dir_all(&dir);
+
+		assert_eq!(expected, files);
+		assert_eq!(vec![Ok(pov(2)), Ok(pov(3))], archived);
+	}
+}
@@ -0,0 +1,155 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A content addressed cache of the PoVs that passed the self validation.
+//!
+//! A PoV is identified by the hash of everything the validation function is executed with, so a
+//! PoV that is validated again with the same parameters and validation code is not executed
+//! twice. Only PoVs that passed are cached, failed validations are always repeated.
+
+use sp_core::hashing::blake2_256;
+use substrate_prometheus_endpoint::{register, Counter, PrometheusError, Registry, U64};
+
+use log::warn;
+use parking_lot::Mutex;
+
+use std::collections::VecDeque;
+
+/// The default number of PoVs that are remembered.
+pub const DEFAULT_POV_CACHE_SIZE: usize = 16;
+
+/// Prometheus metrics of the [`PovCache`].
+///
+/// The hit rate is `hits / (hits + misses)`.
+struct Metrics {
+	hits: Counter<U64>,
+	misses: Counter<U64>,
+}
+
+impl Metrics {
+	fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			hits: register(
+				Counter::new(
+					"cumulus_pov_cache_hits",
+					"Number of PoVs whose validation was answered by the PoV cache.",
+				)?,
+				registry,
+			)?,
+			misses: register(
+				Counter::new(
+					"cumulus_pov_cache_misses",
+					"Number of PoVs that were not found in the PoV cache.",
+				)?,
+				registry,
+			)?,
+		})
+	}
+}
+
+/// Remembers the hashes of the most recent PoVs that passed the validation.
+pub(crate) struct PovCache {
+	size: usize,
+	valid: Mutex<VecDeque<[u8; 32]>>,
+	metrics: Option<Metrics>,
+}
+
+impl PovCache {
+	/// Create a new instance that remembers at most `size` PoVs, `0` disables the cache.
+	pub(crate) fn new(size: usize, registry: Option<&Registry>) -> Self {
+		let metrics = registry.and_then(|registry| {
+			Metrics::register(registry)
+				.map_err(|err| {
+					warn!(target: "cumulus-collator", "Failed to register metrics: {:?}", err)
+				})
+				.ok()
+		});
+
+		Self {
+			size,
+			valid: Mutex::new(VecDeque::with_capacity(size)),
+			metrics,
+		}
+	}
+
+	/// The key of the PoV that is validated with the encoded `params` by `code`.
+	pub(crate) fn key(params: &[u8], code: &[u8]) -> [u8; 32] {
+		blake2_256(&[blake2_256(params), blake2_256(code)].concat())
+	}
+
+	/// Returns whether the PoV with the given `key` is known to be valid.
+	pub(crate) fn is_valid(&self, key: &[u8; 32]) -> bool {
+		if self.size == 0 {
+			return false;
+		}
+
+		let hit = self.valid.lock().contains(key);
+
+		if let Some(metrics) = &self.metrics {
+			if hit {
+				metrics.hits.inc();
+			} else {
+				metrics.misses.inc();
+			}
+		}
+
+		hit
+	}
+
+	/// Remember that the PoV with the given `key` is valid, evicting the oldest if the cache is
+	/// full.
+	pub(crate) fn insert_valid(&self, key: [u8; 32]) {
+		if self.size == 0 {
+			return;
+		}
+
+		let mut valid = self.valid.lock();
+
+		if valid.contains(&key) {
+			return;
+		}
+
+		while valid.len() >= self.size {
+			valid.pop_front();
+		}
+
+		valid.push_back(key);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn most_recent_valid_povs_are_cached() {
+		let cache = PovCache::new(2, None);
+		let keys = (0..3u8)
+			.map(|i| PovCache::key(&[i], b"code"))
+			.collect::<Vec<_>>();
+
+		keys.iter().for_each(|key| cache.insert_valid(*key));
+
+		assert!(!cache.is_valid(&keys[0]));
+		assert!(cache.is_valid(&keys[1]));
+		assert!(cache.is_valid(&keys[2]));
+		assert!(!cache.is_valid(&PovCache::key(&[2], b"other code")));
+
+		let disabled = PovCache::new(0, None);
+		disabled.insert_valid(keys[0]);
+		assert!(!disabled.is_valid(&keys[0]));
+	}
+}
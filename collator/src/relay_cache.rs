@@ -0,0 +1,167 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Cache of the results of relay chain runtime api calls.
+//!
+//! The results only change from one relay chain block to the next, but are requested by several
+//! parts of the collator for the same block. Every result is cached under the hash of the relay
+//! chain block it was fetched at, so it is never served for another relay parent.
+
+use codec::{Decode, Encode};
+use parking_lot::Mutex;
+use polkadot_primitives::v0::Hash as PHash;
+
+use std::{
+	collections::HashMap,
+	sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Statistics of the [`RelayCallCache`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RelayCallCacheStats {
+	/// The number of calls that were served from the cache.
+	pub hits: u64,
+	/// The number of calls that were made to the relay chain runtime.
+	pub misses: u64,
+	/// The number of results that were dropped because a new best relay chain block was imported.
+	pub invalidated: u64,
+	/// The number of results that are currently cached.
+	pub entries: u64,
+}
+
+/// Caches the encoded results of relay chain runtime api calls per relay chain block.
+#[derive(Default)]
+pub(crate) struct RelayCallCache {
+	results: Mutex<HashMap<(PHash, &'static str), Vec<u8>>>,
+	hits: AtomicU64,
+	misses: AtomicU64,
+	invalidated: AtomicU64,
+}
+
+impl RelayCallCache {
+	/// Returns the result of the runtime api `call` at the relay chain block `at`.
+	///
+	/// The result is served from the cache or fetched with `fetch`. Errors are not cached.
+	pub(crate) fn get_or_fetch<T: Encode + Decode, E>(
+		&self,
+		at: PHash,
+		call: &'static str,
+		fetch: impl FnOnce() -> Result<T, E>,
+	) -> Result<T, E> {
+		let cached = self
+			.results
+			.lock()
+			.get(&(at, call))
+			.and_then(|result| T::decode(&mut &result[..]).ok());
+
+		if let Some(result) = cached {
+			self.hits.fetch_add(1, Ordering::Relaxed);
+			return Ok(result);
+		}
+
+		self.misses.fetch_add(1, Ordering::Relaxed);
+		let result = fetch()?;
+		self.results.lock().insert((at, call), result.encode());
+
+		Ok(result)
+	}
+
+	/// Drop the results of all relay chain blocks but the new best block `best`.
+	pub(crate) fn note_new_best(&self, best: PHash) {
+		let mut results = self.results.lock();
+		let before = results.len();
+		results.retain(|(at, _), _| *at == best);
+
+		self.invalidated.fetch_add((before - results.len()) as u64, Ordering::Relaxed);
+	}
+
+	/// Drop all results, e.g. because the relay chain runtime was upgraded.
+	pub(crate) fn clear(&self) {
+		let mut results = self.results.lock();
+		self.invalidated.fetch_add(results.len() as u64, Ordering::Relaxed);
+		results.clear();
+	}
+
+	/// Returns the statistics of the cache.
+	pub(crate) fn stats(&self) -> RelayCallCacheStats {
+		RelayCallCacheStats {
+			hits: self.hits.load(Ordering::Relaxed),
+			misses: self.misses.load(Ordering::Relaxed),
+			invalidated: self.invalidated.load(Ordering::Relaxed),
+			entries: self.results.lock().len() as u64,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn fetch(cache: &RelayCallCache, at: PHash, result: u32) -> u32 {
+		cache.get_or_fetch(at, "test", || Ok::<_, ()>(result)).unwrap()
+	}
+
+	#[test]
+	fn results_are_cached_per_relay_block() {
+		let cache = RelayCallCache::default();
+		let first = PHash::repeat_byte(1);
+		let second = PHash::repeat_byte(2);
+
+		assert_eq!(1, fetch(&cache, first, 1));
+		assert_eq!(1, fetch(&cache, first, 10));
+		// Another relay parent is never served the result of the first.
+		assert_eq!(2, fetch(&cache, second, 2));
+
+		assert_eq!(
+			RelayCallCacheStats {
+				hits: 1,
+				misses: 2,
+				invalidated: 0,
+				entries: 2,
+			},
+			cache.stats(),
+		);
+	}
+
+	#[test]
+	fn new_relay_head_invalidates_the_other_blocks() {
+		let cache = RelayCallCache::default();
+		let first = PHash::repeat_byte(1);
+		let second = PHash::repeat_byte(2);
+
+		fetch(&cache, first, 1);
+		fetch(&cache, second, 2);
+		cache.note_new_best(second);
+
+		assert_eq!(1, cache.stats().invalidated);
+		assert_eq!(1, cache.stats().entries);
+		assert_eq!(10, fetch(&cache, first, 10));
+		assert_eq!(2, fetch(&cache, second, 20));
+
+		cache.clear();
+		assert_eq!(0, cache.stats().entries);
+		assert_eq!(20, fetch(&cache, second, 20));
+	}
+
+	#[test]
+	fn errors_are_not_cached() {
+		let cache = RelayCallCache::default();
+		let at = PHash::repeat_byte(1);
+
+		assert!(cache.get_or_fetch(at, "test", || Err::<u32, _>(())).is_err());
+		assert_eq!(1, fetch(&cache, at, 1));
+	}
+}
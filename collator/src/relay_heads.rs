@@ -0,0 +1,135 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Recording of the relay chain heads the collator builds collations on.
+//!
+//! The recorded heads can be replayed with [`dev::run_replay`](crate::dev::run_replay), to build
+//! the same parachain blocks on the same validation data without a relay chain.
+
+use polkadot_primitives::v0::{
+	DownwardMessage, GlobalValidationData, Hash as PHash, LocalValidationData,
+};
+
+use codec::{Decode, Encode};
+use log::error;
+use parking_lot::Mutex;
+
+use std::{
+	fs::File,
+	io::{self, BufWriter, Write},
+	path::Path,
+};
+
+/// A relay chain head and the validation data the collator received for it.
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+pub struct RecordedRelayHead {
+	/// The hash of the relay chain block.
+	pub relay_parent: PHash,
+	/// The global validation data at the relay chain block.
+	pub global_validation: GlobalValidationData,
+	/// The validation data of the parachain at the relay chain block.
+	pub local_validation: LocalValidationData,
+	/// The downward messages of the parachain at the relay chain block.
+	pub downward_messages: Vec<DownwardMessage>,
+}
+
+/// Appends the relay chain heads the collator receives to a file.
+///
+/// The heads are stored SCALE encoded, one after another.
+pub struct RelayHeadRecorder {
+	file: Mutex<BufWriter<File>>,
+}
+
+impl RelayHeadRecorder {
+	/// Create a new instance that records to the file at `path`, replacing an existing file.
+	pub fn create(path: &Path) -> io::Result<Self> {
+		Ok(Self {
+			file: Mutex::new(BufWriter::new(File::create(path)?)),
+		})
+	}
+
+	/// Record the given relay chain `head`.
+	pub(crate) fn record(&self, head: &RecordedRelayHead) {
+		let mut file = self.file.lock();
+
+		if let Err(e) = file.write_all(&head.encode()).and_then(|_| file.flush()) {
+			error!(
+				target: "cumulus-collator",
+				"Failed to record the relay chain head `{:?}`: {:?}",
+				head.relay_parent,
+				e,
+			);
+		}
+	}
+}
+
+/// Read the relay chain heads that were recorded by a [`RelayHeadRecorder`] to `path`.
+pub fn read_relay_heads(path: &Path) -> Result<Vec<RecordedRelayHead>, String> {
+	let data = std::fs::read(path)
+		.map_err(|e| format!("Failed to read `{}`: {}", path.display(), e))?;
+	let mut input = &data[..];
+	let mut heads = Vec::new();
+
+	while !input.is_empty() {
+		let head = RecordedRelayHead::decode(&mut input).map_err(|e| {
+			format!(
+				"Invalid relay chain head #{} in `{}`: {:?}",
+				heads.len(),
+				path.display(),
+				e,
+			)
+		})?;
+		heads.push(head);
+	}
+
+	Ok(heads)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn head(block_number: u32) -> RecordedRelayHead {
+		RecordedRelayHead {
+			relay_parent: PHash::repeat_byte(block_number as u8),
+			global_validation: GlobalValidationData {
+				block_number,
+				max_code_size: 1024,
+				max_head_data_size: 32,
+			},
+			local_validation: LocalValidationData {
+				parent_head: Default::default(),
+				balance: 10,
+				code_upgrade_allowed: None,
+			},
+			downward_messages: Vec::new(),
+		}
+	}
+
+	#[test]
+	fn recorded_heads_are_read_back() {
+		let path = std::env::temp_dir().join(format!("cumulus-relay-heads-{}", std::process::id()));
+		let recorder = RelayHeadRecorder::create(&path).unwrap();
+
+		(1..=3).for_each(|n| recorder.record(&head(n)));
+		drop(recorder);
+
+		let heads = read_relay_heads(&path);
+		let _ = std::fs::remove_file(&path);
+
+		assert_eq!(Ok(vec![head(1), head(2), head(3)]), heads);
+	}
+}
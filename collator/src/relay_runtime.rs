@@ -0,0 +1,165 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Detection of relay chain runtime upgrades.
+//!
+//! A runtime upgrade of the relay chain can change the runtime api and the validation data the
+//! collations are built with. Collation failures around an upgrade are otherwise hard to
+//! attribute, so the upgrade is logged prominently and the cached relay chain data is dropped.
+
+use sc_client_api::BlockchainEvents;
+use sp_api::{CallApiAt, RuntimeVersion};
+use sp_runtime::{generic::BlockId, traits::Header as HeaderT};
+
+use polkadot_primitives::v0::Block as PBlock;
+
+use crate::CollatorStatus;
+
+use futures::StreamExt;
+use log::{debug, warn};
+
+use std::sync::Arc;
+
+/// Remembers the runtime version of the relay chain to detect upgrades.
+#[derive(Default)]
+pub(crate) struct RelayRuntimeTracker {
+	version: Option<RuntimeVersion>,
+}
+
+impl RelayRuntimeTracker {
+	/// Note the runtime `version` of the latest best relay chain block.
+	///
+	/// Returns the previous version if the runtime was upgraded. The first version that is noted
+	/// is not an upgrade.
+	pub(crate) fn note(&mut self, version: RuntimeVersion) -> Option<RuntimeVersion> {
+		match self.version.take() {
+			Some(previous)
+				if previous.spec_name != version.spec_name
+					|| previous.spec_version != version.spec_version =>
+			{
+				self.version = Some(version);
+				Some(previous)
+			}
+			_ => {
+				self.version = Some(version);
+				None
+			}
+		}
+	}
+}
+
+/// The number of runtime apis that were added, removed or changed their version from `previous`
+/// to `new`.
+fn changed_apis(previous: &RuntimeVersion, new: &RuntimeVersion) -> usize {
+	let removed = previous
+		.apis
+		.iter()
+		.filter(|(id, _)| !new.apis.iter().any(|(new_id, _)| new_id == id))
+		.count();
+
+	removed + new.apis.iter().filter(|api| !previous.apis.contains(api)).count()
+}
+
+/// Watch the best blocks of the relay chain `client` for runtime upgrades.
+///
+/// The runtime version of the best block is kept up to date in the given `status`. Every upgrade
+/// is logged, the cached relay chain runtime api results of `status` are dropped and `on_upgrade`
+/// is called, to drop other data that was cached with the previous runtime.
+pub async fn watch_relay_runtime<PClient>(
+	client: Arc<PClient>,
+	status: Arc<CollatorStatus>,
+	on_upgrade: impl Fn() + Send,
+) where
+	PClient: BlockchainEvents<PBlock> + CallApiAt<PBlock>,
+{
+	let mut tracker = RelayRuntimeTracker::default();
+	let mut imports = client.import_notification_stream();
+
+	while let Some(notification) = imports.next().await {
+		if !notification.is_new_best {
+			continue;
+		}
+
+		let version = match client.runtime_version_at(&BlockId::Hash(notification.hash)) {
+			Ok(version) => version,
+			Err(e) => {
+				debug!(
+					target: "cumulus-collator",
+					"Failed to fetch the relay chain runtime version at `{:?}`: {:?}",
+					notification.hash,
+					e,
+				);
+				continue;
+			}
+		};
+
+		status.set_relay_runtime_version(version.clone());
+
+		if let Some(previous) = tracker.note(version.clone()) {
+			warn!(
+				target: "cumulus-collator",
+				"Relay chain runtime upgraded from `{}` v{} to `{}` v{} at #{} (`{:?}`), {} \
+				runtime apis changed. Collation failures around this block may be caused by the \
+				upgrade.",
+				previous.spec_name,
+				previous.spec_version,
+				version.spec_name,
+				version.spec_version,
+				notification.header.number(),
+				notification.hash,
+				changed_apis(&previous, &version),
+			);
+
+			status.relay_call_cache().clear();
+			on_upgrade();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn version(spec_version: u32, apis: Vec<([u8; 8], u32)>) -> RuntimeVersion {
+		RuntimeVersion {
+			spec_name: "rococo".into(),
+			spec_version,
+			apis: apis.into(),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn spec_version_changes_are_upgrades() {
+		let mut tracker = RelayRuntimeTracker::default();
+
+		assert!(tracker.note(version(1, Vec::new())).is_none());
+		assert!(tracker.note(version(1, Vec::new())).is_none());
+
+		let previous = tracker.note(version(2, vec![([1; 8], 1)])).expect("Is an upgrade");
+		assert_eq!(1, previous.spec_version);
+		assert!(tracker.note(version(2, vec![([1; 8], 1)])).is_none());
+	}
+
+	#[test]
+	fn changed_apis_are_counted() {
+		let previous = version(1, vec![([1; 8], 1), ([2; 8], 1), ([3; 8], 1)]);
+		let new = version(2, vec![([1; 8], 1), ([2; 8], 2), ([4; 8], 1)]);
+
+		// `[2; 8]` changed its version, `[3; 8]` was removed and `[4; 8]` was added.
+		assert_eq!(3, changed_apis(&previous, &new));
+	}
+}
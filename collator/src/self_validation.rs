@@ -0,0 +1,319 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Validation of produced collations with the local validation function.
+//!
+//! Runs `validate_block` of the parachain runtime the same way the relay chain validators do, so
+//! a collation that would be rejected is detected before it is handed over to the relay chain.
+//!
+//! The validation function is executed on a separate thread, which is awaited without blocking
+//! the executor. If it does not finish within the timeout, the collation is dropped. The
+//! execution can not be interrupted, so the thread keeps running until the validation function
+//! returns, but the collator does not wait for it. At most [`MAX_VALIDATION_THREADS`] threads run
+//! at once, collations are dropped while the limit is reached.
+
+use crate::pov_cache::PovCache;
+
+use sc_executor::{sp_wasm_interface::HostFunctions, WasmExecutionMethod, WasmExecutor};
+use sp_core::traits::{CallInWasm, MissingHostFunctions};
+use sp_state_machine::BasicExternalities;
+
+use polkadot_parachain::primitives::{ValidationParams, ValidationResult};
+use polkadot_primitives::v0::{BlockData, GlobalValidationData, HeadData, LocalValidationData};
+
+use codec::{Decode, Encode};
+use futures::{
+	channel::oneshot,
+	future::{self, Either},
+};
+use futures_timer::Delay;
+use log::debug;
+
+use std::{
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	},
+	thread,
+	time::Duration,
+};
+
+/// The number of heap pages the relay chain validators execute the validation function with.
+pub const VALIDATION_HEAP_PAGES: u64 = 1024;
+
+/// The wasm execution method the relay chain validators execute the validation function with.
+pub const VALIDATION_WASM_EXECUTION: WasmExecutionMethod = WasmExecutionMethod::Interpreted;
+
+/// The default time the validation function may run before the collation is dropped.
+pub const DEFAULT_SELF_VALIDATION_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The maximum number of threads executing the validation function at once.
+///
+/// Includes the threads of validations that timed out, but did not return yet.
+const MAX_VALIDATION_THREADS: usize = 2;
+
+/// Validates collations by executing the validation function of the parachain.
+pub(crate) struct SelfValidation<Hash> {
+	executor: Arc<WasmExecutor>,
+	validation_code: Box<dyn Fn(Hash) -> Option<Vec<u8>> + Send + Sync>,
+	timeout: Duration,
+	cache: PovCache,
+	threads: Arc<AtomicUsize>,
+}
+
+impl<Hash: std::fmt::Debug + Copy> SelfValidation<Hash> {
+	/// Create a new instance.
+	///
+	/// `validation_code` returns the validation function at the given parachain block, which is
+	/// executed with `wasm_execution`. A collation is dropped if its validation takes longer than
+	/// `timeout`. PoVs that are found in `cache` are not validated again.
+	pub(crate) fn new(
+		validation_code: Box<dyn Fn(Hash) -> Option<Vec<u8>> + Send + Sync>,
+		wasm_execution: WasmExecutionMethod,
+		timeout: Duration,
+		cache: PovCache,
+	) -> Self {
+		Self {
+			executor: Arc::new(WasmExecutor::new(
+				wasm_execution,
+				Some(VALIDATION_HEAP_PAGES),
+				sp_io::SubstrateHostFunctions::host_functions(),
+				1,
+			)),
+			validation_code,
+			timeout,
+			cache,
+			threads: Default::default(),
+		}
+	}
+
+	/// Validate the collation that was built on top of the parachain block `parent`.
+	///
+	/// Returns an error if the validation function rejected the collation or committed to
+	/// different head data.
+	pub(crate) async fn validate(
+		&self,
+		parent: Hash,
+		block_data: &BlockData,
+		head_data: &HeadData,
+		global_validation: &GlobalValidationData,
+		local_validation: &LocalValidationData,
+	) -> Result<(), String> {
+		let code = (self.validation_code)(parent)
+			.ok_or_else(|| format!("no validation code found at `{:?}`", parent))?;
+
+		let params = validation_params(block_data, global_validation, local_validation);
+
+		let key = PovCache::key(&params, &code);
+		if self.cache.is_valid(&key) {
+			debug!(
+				target: "cumulus-collator",
+				"Collation on top of `{:?}` was already validated.",
+				parent,
+			);
+			return Ok(());
+		}
+
+		let executor = self.executor.clone();
+		let result = run_with_timeout(&self.threads, self.timeout, move || {
+			execute(&executor, &code, &params)
+		})
+		.await?;
+		check_head_data(&result, head_data)?;
+
+		debug!(
+			target: "cumulus-collator",
+			"Self validation of the collation on top of `{:?}` succeeded.",
+			parent,
+		);
+		self.cache.insert_valid(key);
+
+		Ok(())
+	}
+}
+
+/// Validate the collation `block_data` with the validation function `code`, executed with
+/// `wasm_execution`, the same way [`SelfValidation`] does, but without a timeout.
+///
+/// Returns an error if the validation function rejected the collation or committed to different
+/// head data than `head_data`.
+pub fn validate_collation(
+	code: &[u8],
+	wasm_execution: WasmExecutionMethod,
+	block_data: &BlockData,
+	head_data: &HeadData,
+	global_validation: &GlobalValidationData,
+	local_validation: &LocalValidationData,
+) -> Result<(), String> {
+	let executor = WasmExecutor::new(
+		wasm_execution,
+		Some(VALIDATION_HEAP_PAGES),
+		sp_io::SubstrateHostFunctions::host_functions(),
+		1,
+	);
+	let params = validation_params(block_data, global_validation, local_validation);
+
+	check_head_data(&execute(&executor, code, &params)?, head_data)
+}
+
+/// The encoded parameters of the validation function for the collation `block_data`.
+fn validation_params(
+	block_data: &BlockData,
+	global_validation: &GlobalValidationData,
+	local_validation: &LocalValidationData,
+) -> Vec<u8> {
+	ValidationParams {
+		block_data: block_data.clone(),
+		parent_head: local_validation.parent_head.clone(),
+		code_upgrade_allowed: local_validation.code_upgrade_allowed,
+		max_code_size: global_validation.max_code_size,
+		max_head_data_size: global_validation.max_head_data_size,
+		relay_chain_height: global_validation.block_number,
+	}
+	.encode()
+}
+
+/// Execute `validate_block` of the validation function `code` with the encoded `params`.
+fn execute(
+	executor: &WasmExecutor,
+	code: &[u8],
+	params: &[u8],
+) -> Result<ValidationResult, String> {
+	let mut ext = BasicExternalities::new(Default::default());
+
+	executor
+		.call_in_wasm(
+			code,
+			None,
+			"validate_block",
+			params,
+			&mut ext,
+			MissingHostFunctions::Disallow,
+		)
+		.map_err(|e| format!("{:?}", e))
+		.and_then(|r| ValidationResult::decode(&mut &r[..]).map_err(|e| format!("{:?}", e)))
+}
+
+/// Check that the validation function committed to the `expected` head data.
+fn check_head_data(result: &ValidationResult, expected: &HeadData) -> Result<(), String> {
+	if result.head_data == *expected {
+		return Ok(());
+	}
+
+	Err(format!(
+		"expected head data {:?}, validation function returned {:?} (upward messages: {}, \
+		processed downward messages: {}, new validation code: {})",
+		expected,
+		result.head_data,
+		result.upward_messages.len(),
+		result.processed_downward_messages,
+		result.new_validation_code.is_some(),
+	))
+}
+
+/// A running validation thread, counted in `threads` until dropped.
+struct RunningThread(Arc<AtomicUsize>);
+
+impl RunningThread {
+	/// Count a new thread, unless [`MAX_VALIDATION_THREADS`] threads are running already.
+	fn start(threads: &Arc<AtomicUsize>) -> Option<Self> {
+		if threads.fetch_add(1, Ordering::SeqCst) >= MAX_VALIDATION_THREADS {
+			threads.fetch_sub(1, Ordering::SeqCst);
+			return None;
+		}
+
+		Some(Self(threads.clone()))
+	}
+}
+
+impl Drop for RunningThread {
+	fn drop(&mut self) {
+		self.0.fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
+/// Run `f` on a separate thread and return its result, unless it takes longer than `timeout`.
+///
+/// `threads` counts the running threads, `f` is not run if the limit is reached.
+async fn run_with_timeout<T: Send + 'static>(
+	threads: &Arc<AtomicUsize>,
+	timeout: Duration,
+	f: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+	let running = RunningThread::start(threads).ok_or_else(|| {
+		format!("{} self-validations are still running", MAX_VALIDATION_THREADS)
+	})?;
+	let (sender, receiver) = oneshot::channel();
+
+	thread::Builder::new()
+		.name("cumulus-self-validation".into())
+		.spawn(move || {
+			let _ = sender.send(f());
+			drop(running);
+		})
+		.map_err(|e| format!("failed to spawn the validation thread: {}", e))?;
+
+	match future::select(receiver, Delay::new(timeout)).await {
+		Either::Left((Ok(result), _)) => result,
+		Either::Left((Err(_), _)) => Err("the validation thread panicked".into()),
+		Either::Right(_) => {
+			Err(format!("self-validation timed out after {}ms", timeout.as_millis()))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::block_on;
+	use std::sync::mpsc;
+
+	#[test]
+	fn validation_that_does_not_finish_in_time_fails() {
+		let threads = Arc::new(AtomicUsize::new(0));
+		let run = |timeout, f: Box<dyn FnOnce() -> Result<u32, String> + Send>| {
+			block_on(run_with_timeout(&threads, timeout, f))
+		};
+		assert_eq!(Ok(42), run(Duration::from_secs(10), Box::new(|| Ok(42))));
+
+		let mut finish = Vec::new();
+		for _ in 0..MAX_VALIDATION_THREADS {
+			let (sender, finished) = mpsc::channel::<()>();
+			finish.push(sender);
+
+			let result = run(
+				Duration::from_millis(10),
+				Box::new(move || {
+					let _ = finished.recv();
+					Ok(0)
+				}),
+			);
+			assert_eq!(Err("self-validation timed out after 10ms".into()), result);
+		}
+
+		// The threads of the validations that timed out are still running.
+		assert_eq!(
+			Err(format!("{} self-validations are still running", MAX_VALIDATION_THREADS)),
+			run(Duration::from_secs(10), Box::new(|| Ok(42))),
+		);
+
+		drop(finish);
+		while threads.load(Ordering::SeqCst) > 0 {
+			thread::sleep(Duration::from_millis(1));
+		}
+		assert_eq!(Ok(42), run(Duration::from_secs(10), Box::new(|| Ok(42))));
+	}
+}
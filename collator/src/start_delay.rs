@@ -0,0 +1,92 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Delay of the first collation after the collator started.
+//!
+//! Right after startup the relay chain node may still be syncing, so a collation would be built on
+//! a stale relay parent.
+
+use log::{info, trace};
+use parking_lot::Mutex;
+
+use std::time::{Duration, Instant};
+
+/// Holds off the first collation for a given time and until the relay chain is synced.
+pub(crate) struct StartDelay {
+	/// When the delay ends or `None` once the first collation was allowed.
+	until: Mutex<Option<Instant>>,
+	logged: Mutex<bool>,
+}
+
+impl StartDelay {
+	/// Create a new instance that delays the first collation for `delay` from now.
+	pub(crate) fn new(delay: Duration) -> Self {
+		Self::until(Instant::now() + delay)
+	}
+
+	fn until(until: Instant) -> Self {
+		Self {
+			until: Mutex::new(Some(until)),
+			logged: Mutex::new(false),
+		}
+	}
+
+	/// Returns whether a collation may be built, given whether the relay chain is `synced`.
+	///
+	/// Once this returned `true`, it always does.
+	pub(crate) fn may_collate(&self, synced: bool) -> bool {
+		let mut until = self.until.lock();
+
+		match *until {
+			None => true,
+			Some(at) if synced && Instant::now() >= at => {
+				info!(target: "cumulus-collator", "Starting to collate");
+				*until = None;
+				true
+			}
+			Some(_) => {
+				let mut logged = self.logged.lock();
+
+				if *logged {
+					trace!(target: "cumulus-collator", "Delaying first collation until synced");
+				} else {
+					info!(target: "cumulus-collator", "Delaying first collation until synced");
+					*logged = true;
+				}
+
+				false
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn first_collation_waits_for_the_delay_and_the_sync() {
+		let delay = StartDelay::new(Duration::from_secs(60));
+		assert!(!delay.may_collate(true));
+
+		let delay = StartDelay::until(Instant::now());
+		assert!(!delay.may_collate(false));
+		assert!(delay.may_collate(true));
+
+		// Once collating started, it is not delayed again.
+		assert!(delay.may_collate(false));
+	}
+}
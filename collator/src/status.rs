@@ -0,0 +1,1134 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Shared view on the state of the collator.
+
+use crate::relay_cache::{RelayCallCache, RelayCallCacheStats};
+use cumulus_consensus::PolkadotClient;
+
+use codec::Encode;
+
+use sc_client_api::BlockchainEvents;
+use sp_api::{ProvideRuntimeApi, RuntimeVersion};
+use sp_blockchain::{Error as ClientError, HeaderBackend};
+use sp_consensus::SyncOracle;
+use substrate_prometheus_endpoint::{register, Gauge, Registry, U64};
+
+use sp_runtime::{
+	generic::BlockId,
+	traits::{BlakeTwo256, Block as BlockT, Hash as HashT, Header as HeaderT},
+};
+
+use polkadot_primitives::v0::{
+	Block as PBlock, BlockData, BlockNumber as PBlockNumber, Chain, DutyRoster, HeadData,
+	Hash as PHash, Id as ParaId, ParachainHost, ValidatorId,
+};
+
+use futures::{channel::mpsc, stream, FutureExt, StreamExt};
+use futures_timer::Delay;
+use log::{debug, error, info, warn};
+use parking_lot::Mutex;
+
+use std::{
+	collections::VecDeque,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// The default number of recent collations that are kept.
+pub const DEFAULT_KEEP_RECENT_COLLATIONS: usize = 32;
+
+/// The maximum number of recent collations that can be kept.
+pub const MAX_KEEP_RECENT_COLLATIONS: usize = 4096;
+
+/// The default number of recently rejected collations that are kept.
+pub const DEFAULT_KEEP_RECENT_REJECTIONS: usize = 32;
+
+/// The number of parachain blocks whose inclusion in the relay chain is remembered.
+const KEEP_INCLUSIONS: usize = 256;
+
+/// How often the status is logged while waiting for the registration of the parachain.
+const REGISTRATION_LOG_INTERVAL: Duration = Duration::from_secs(12);
+
+/// How often [`monitor_finality`] checks whether the finalized head is stuck.
+const FINALITY_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A relay chain block a collation was built on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RelayParent {
+	/// The hash of the relay chain block.
+	pub hash: PHash,
+	/// The number of the relay chain block.
+	pub number: PBlockNumber,
+}
+
+/// A collation that was recently built by the collator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecentCollation {
+	/// The relay chain block the collation was built on.
+	pub relay_parent: RelayParent,
+	/// The head data of the parachain block.
+	pub head_data: HeadData,
+	/// The PoV of the parachain block.
+	pub block_data: BlockData,
+}
+
+/// What happened to a collation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollationResult {
+	/// The collation was built and handed over to the relay chain node.
+	Submitted,
+	/// Building the collation failed, e.g. because proposing or importing the block failed.
+	Rejected,
+	/// The parachain block of the collation was included in a finalized relay chain block.
+	Included,
+}
+
+/// A collation that was recently rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rejection {
+	/// The relay chain block the collation was built on.
+	pub relay_parent: RelayParent,
+	/// The head data of the parachain block or `None` if no block was built.
+	pub head_data: Option<HeadData>,
+	/// Why the collation was rejected.
+	pub reason: String,
+	/// When the collation was rejected, in milliseconds since the unix epoch.
+	pub timestamp: u64,
+}
+
+/// An event in the lifecycle of a collation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CollationEvent {
+	/// The relay chain block the collation was built on.
+	pub relay_parent: RelayParent,
+	/// The head data of the parachain block or `None` if no block was built.
+	pub head_data: Option<HeadData>,
+	/// The size of the PoV in bytes or `None` if no block was built.
+	pub pov_size: Option<usize>,
+	/// What happened to the collation.
+	pub result: CollationResult,
+}
+
+/// The relay chain core the parachain is assigned to.
+///
+/// The relay chain schedules every active parachain on its own core. The index of the core is the
+/// position of the parachain in the active parachains.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AssignedCore {
+	/// The index of the core.
+	pub core_index: u32,
+	/// The number of the relay chain block the assignment was observed at.
+	pub relay_block: PBlockNumber,
+}
+
+/// The relay chain block that included a parachain block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Inclusion {
+	/// The hash of the relay chain block.
+	pub relay_block: PHash,
+	/// The number of the relay chain block.
+	pub relay_number: PBlockNumber,
+}
+
+/// The collations that were built on the included parachain head and are not included yet.
+///
+/// Once its length reaches the authoring backoff threshold, the collator backs off.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UnincludedSegment {
+	/// The number of collations that were built on the included head.
+	pub length: u32,
+	/// The authoring backoff threshold, the length at which the collator starts backing off, or
+	/// `None` if it never does.
+	pub backoff_threshold: Option<u32>,
+	/// The relay parent of the oldest of these collations.
+	pub oldest_relay_parent: Option<RelayParent>,
+}
+
+/// The [`UnincludedSegment`] and the included head it is built on.
+#[derive(Default)]
+struct SegmentState {
+	included_head: Option<HeadData>,
+	segment: UnincludedSegment,
+	gauge: Option<Gauge<U64>>,
+}
+
+impl SegmentState {
+	fn update_gauge(&self) {
+		if let Some(gauge) = &self.gauge {
+			gauge.set(self.segment.length.into());
+		}
+	}
+}
+
+/// The best and finalized block numbers of the relay chain.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RelayChainHeights {
+	/// The number of the best relay chain block.
+	pub best: PBlockNumber,
+	/// The number of the last finalized relay chain block.
+	pub finalized: PBlockNumber,
+}
+
+/// The status of the collator.
+///
+/// This is updated by the [`Collator`](crate::Collator) while it is producing candidates and can
+/// be queried by other parts of the node, e.g. the RPC.
+pub struct CollatorStatus {
+	relay_parent: Mutex<Option<RelayParent>>,
+	recent_collations: Mutex<VecDeque<RecentCollation>>,
+	keep_recent_collations: usize,
+	recent_rejections: Mutex<VecDeque<Rejection>>,
+	keep_recent_rejections: usize,
+	relay_chain_heights: Mutex<Option<RelayChainHeights>>,
+	relay_runtime_version: Mutex<Option<RuntimeVersion>>,
+	relay_sync_oracle: Mutex<Option<Box<dyn SyncOracle + Send>>>,
+	collation_event_sinks: Mutex<Vec<mpsc::UnboundedSender<CollationEvent>>>,
+	final_head_sinks: Mutex<Vec<mpsc::UnboundedSender<HeadData>>>,
+	finality_confirmations: Option<u32>,
+	paused: AtomicBool,
+	para_registered: AtomicBool,
+	assigned_core: Mutex<Option<AssignedCore>>,
+	backing_group: Mutex<Vec<ValidatorId>>,
+	relay_call_cache: RelayCallCache,
+	finalized_head_advanced_at: Mutex<Option<Instant>>,
+	inclusions: Mutex<VecDeque<(PHash, Inclusion)>>,
+	unincluded_segment: Mutex<SegmentState>,
+}
+
+impl Default for CollatorStatus {
+	fn default() -> Self {
+		Self::with_keep_recent_collations(DEFAULT_KEEP_RECENT_COLLATIONS)
+	}
+}
+
+impl CollatorStatus {
+	/// Create a new instance.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Create a new instance that keeps at most `keep` recent collations.
+	pub fn with_keep_recent_collations(keep: usize) -> Self {
+		Self {
+			relay_parent: Default::default(),
+			recent_collations: Mutex::new(VecDeque::with_capacity(
+				keep.min(DEFAULT_KEEP_RECENT_COLLATIONS),
+			)),
+			keep_recent_collations: keep,
+			recent_rejections: Default::default(),
+			keep_recent_rejections: DEFAULT_KEEP_RECENT_REJECTIONS,
+			relay_chain_heights: Default::default(),
+			relay_runtime_version: Default::default(),
+			relay_sync_oracle: Default::default(),
+			collation_event_sinks: Default::default(),
+			final_head_sinks: Default::default(),
+			finality_confirmations: None,
+			paused: AtomicBool::new(false),
+			para_registered: AtomicBool::new(false),
+			assigned_core: Default::default(),
+			backing_group: Default::default(),
+			relay_call_cache: Default::default(),
+			finalized_head_advanced_at: Default::default(),
+			inclusions: Default::default(),
+			unincluded_segment: Default::default(),
+		}
+	}
+
+	/// Keep at most `keep` recently rejected collations instead of
+	/// [`DEFAULT_KEEP_RECENT_REJECTIONS`].
+	pub fn with_keep_recent_rejections(mut self, keep: usize) -> Self {
+		self.keep_recent_rejections = keep;
+		self
+	}
+
+	/// Report a parachain block as final once the relay chain block that included it has
+	/// `confirmations` descendants on the best relay chain, instead of once it is finalized.
+	pub fn with_finality_confirmations(mut self, confirmations: Option<u32>) -> Self {
+		self.finality_confirmations = confirmations;
+		self
+	}
+
+	/// Returns the relay parent the collator last built a collation on.
+	///
+	/// Returns `None` if no collation was built yet.
+	pub fn relay_parent(&self) -> Option<RelayParent> {
+		*self.relay_parent.lock()
+	}
+
+	/// Set the relay parent the last collation was built on.
+	pub(crate) fn set_relay_parent(&self, relay_parent: RelayParent) {
+		*self.relay_parent.lock() = Some(relay_parent);
+	}
+
+	/// Returns the recently built collations, oldest first.
+	pub fn recent_collations(&self) -> Vec<RecentCollation> {
+		self.recent_collations.lock().iter().cloned().collect()
+	}
+
+	/// Remember the given collation, evicting the oldest one if too many are kept.
+	pub(crate) fn add_collation(&self, collation: RecentCollation) {
+		if self.keep_recent_collations == 0 {
+			return;
+		}
+
+		let mut recent_collations = self.recent_collations.lock();
+
+		while recent_collations.len() >= self.keep_recent_collations {
+			if let Some(evicted) = recent_collations.pop_front() {
+				debug!(
+					target: "cumulus-collator",
+					"Evicting collation at relay parent `{:?}` from the recent collations.",
+					evicted.relay_parent.hash,
+				);
+			}
+		}
+
+		recent_collations.push_back(collation);
+	}
+
+	/// Returns the at most `count` most recently rejected collations, oldest first.
+	pub fn recent_rejections(&self, count: usize) -> Vec<Rejection> {
+		let recent_rejections = self.recent_rejections.lock();
+		let skip = recent_rejections.len().saturating_sub(count);
+
+		recent_rejections.iter().skip(skip).cloned().collect()
+	}
+
+	/// Remember that the collation built on `relay_parent` was rejected for `reason`, evicting
+	/// the oldest rejection if too many are kept.
+	pub(crate) fn add_rejection(
+		&self,
+		relay_parent: RelayParent,
+		head_data: Option<HeadData>,
+		reason: String,
+	) {
+		if self.keep_recent_rejections == 0 {
+			return;
+		}
+
+		let timestamp = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|d| d.as_millis() as u64)
+			.unwrap_or_default();
+		let mut recent_rejections = self.recent_rejections.lock();
+
+		while recent_rejections.len() >= self.keep_recent_rejections {
+			recent_rejections.pop_front();
+		}
+
+		recent_rejections.push_back(Rejection {
+			relay_parent,
+			head_data,
+			reason,
+			timestamp,
+		});
+	}
+
+	/// Returns a stream of all collation events from now on.
+	pub fn collation_events(&self) -> mpsc::UnboundedReceiver<CollationEvent> {
+		let (sink, stream) = mpsc::unbounded();
+		self.collation_event_sinks.lock().push(sink);
+		stream
+	}
+
+	/// Send the given event to all collation event streams.
+	pub(crate) fn notify_collation(&self, event: CollationEvent) {
+		self.collation_event_sinks
+			.lock()
+			.retain(|sink| sink.unbounded_send(event.clone()).is_ok());
+	}
+
+	/// Returns a stream of the heads of the parachain blocks that become final from now on.
+	///
+	/// By default these are the finalized parachain blocks, see [`monitor_finality`]. With
+	/// [`with_finality_confirmations`](Self::with_finality_confirmations) a block is final once
+	/// enough best relay chain blocks were built on the one that included it, see
+	/// [`record_inclusions`].
+	pub fn final_heads(&self) -> mpsc::UnboundedReceiver<HeadData> {
+		let (sink, stream) = mpsc::unbounded();
+		self.final_head_sinks.lock().push(sink);
+		stream
+	}
+
+	/// Send the given head to all final head streams.
+	fn notify_final_head(&self, head: HeadData) {
+		self.final_head_sinks
+			.lock()
+			.retain(|sink| sink.unbounded_send(head.clone()).is_ok());
+	}
+
+	/// Returns the best and finalized block numbers of the relay chain.
+	///
+	/// Returns `None` if the node is not following a relay chain.
+	pub fn relay_chain_heights(&self) -> Option<RelayChainHeights> {
+		*self.relay_chain_heights.lock()
+	}
+
+	/// Set the best and finalized block numbers of the relay chain.
+	pub(crate) fn set_relay_chain_heights(&self, heights: RelayChainHeights) {
+		*self.relay_chain_heights.lock() = Some(heights);
+	}
+
+	/// Returns the runtime version of the best relay chain block.
+	///
+	/// Returns `None` if the node is not following a relay chain.
+	pub fn relay_runtime_version(&self) -> Option<RuntimeVersion> {
+		self.relay_runtime_version.lock().clone()
+	}
+
+	/// Set the runtime version of the best relay chain block.
+	pub(crate) fn set_relay_runtime_version(&self, version: RuntimeVersion) {
+		*self.relay_runtime_version.lock() = Some(version);
+	}
+
+	/// Returns whether the relay chain node is connected to any peers.
+	pub fn relay_connected(&self) -> bool {
+		self.relay_sync_oracle
+			.lock()
+			.as_mut()
+			.map_or(false, |oracle| !oracle.is_offline())
+	}
+
+	/// Returns whether the relay chain node is catching up with the relay chain.
+	///
+	/// Returns `false` if the node is not following a relay chain.
+	pub fn relay_major_syncing(&self) -> bool {
+		self.relay_sync_oracle
+			.lock()
+			.as_mut()
+			.map_or(false, |oracle| oracle.is_major_syncing())
+	}
+
+	/// Returns whether the collator is paused and does not build any collations.
+	pub fn is_paused(&self) -> bool {
+		self.paused.load(Ordering::SeqCst)
+	}
+
+	/// Pause or resume building collations.
+	///
+	/// Blocks that are built by other collators are still imported while paused.
+	pub fn set_paused(&self, paused: bool) {
+		self.paused.store(paused, Ordering::SeqCst);
+	}
+
+	/// Returns whether the registration of the parachain is visible on the relay chain.
+	pub fn para_registered(&self) -> bool {
+		self.para_registered.load(Ordering::SeqCst)
+	}
+
+	/// Remember that the registration of the parachain is visible on the relay chain.
+	pub(crate) fn set_para_registered(&self) {
+		self.para_registered.store(true, Ordering::SeqCst);
+	}
+
+	/// Returns the core the parachain is assigned to at the best relay chain block.
+	///
+	/// Returns `None` if the parachain is not scheduled on any core or the assignment is unknown.
+	pub fn assigned_core(&self) -> Option<AssignedCore> {
+		*self.assigned_core.lock()
+	}
+
+	/// Set the core the parachain is assigned to at the best relay chain block.
+	fn set_assigned_core(&self, assigned_core: Option<AssignedCore>) {
+		*self.assigned_core.lock() = assigned_core;
+	}
+
+	/// Returns the relay chain validators that back the collations of the parachain at the best
+	/// relay chain block.
+	///
+	/// The list is empty if no validator is assigned to the parachain or the assignment is
+	/// unknown.
+	pub fn backing_group(&self) -> Vec<ValidatorId> {
+		self.backing_group.lock().clone()
+	}
+
+	/// Set the relay chain validators that back the collations of the parachain.
+	fn set_backing_group(&self, backing_group: Vec<ValidatorId>) {
+		*self.backing_group.lock() = backing_group;
+	}
+
+	/// Returns the statistics of the cache of the relay chain runtime api results.
+	pub fn relay_call_cache_stats(&self) -> RelayCallCacheStats {
+		self.relay_call_cache.stats()
+	}
+
+	/// The cache of the relay chain runtime api results, shared by all parts of the collator.
+	pub(crate) fn relay_call_cache(&self) -> &RelayCallCache {
+		&self.relay_call_cache
+	}
+
+	/// Returns how long ago the finalized head of the parachain last advanced.
+	///
+	/// Returns `None` if the finality is not monitored by [`monitor_finality`].
+	pub fn finalized_head_age(&self) -> Option<Duration> {
+		self.finalized_head_advanced_at.lock().map(|at| at.elapsed())
+	}
+
+	/// Remember that the finalized head of the parachain advanced just now.
+	fn note_finalized_head_advanced(&self) {
+		*self.finalized_head_advanced_at.lock() = Some(Instant::now());
+	}
+
+	/// Returns the relay chain block that included the parachain block `para_block`.
+	///
+	/// `para_block` is the hash of the parachain block, in the hash type of the parachain. Returns
+	/// `None` if the block is not included yet or was included too long ago to be remembered.
+	pub fn inclusion_of(&self, para_block: &impl AsRef<[u8]>) -> Option<Inclusion> {
+		self.inclusions
+			.lock()
+			.iter()
+			.rev()
+			.find(|(hash, _)| hash.as_ref() == para_block.as_ref())
+			.map(|(_, inclusion)| *inclusion)
+	}
+
+	/// Remember that `para_block` was included by the relay chain block of `inclusion`.
+	fn add_inclusion(&self, para_block: PHash, inclusion: Inclusion) {
+		let mut inclusions = self.inclusions.lock();
+
+		if inclusions.len() >= KEEP_INCLUSIONS {
+			inclusions.pop_front();
+		}
+
+		inclusions.push_back((para_block, inclusion));
+	}
+
+	/// Returns the collations that were built on the included parachain head.
+	pub fn unincluded_segment(&self) -> UnincludedSegment {
+		self.unincluded_segment.lock().segment
+	}
+
+	/// Set the authoring backoff threshold that is reported with the unincluded segment.
+	pub(crate) fn set_authoring_backoff_threshold(&self, threshold: Option<u32>) {
+		self.unincluded_segment.lock().segment.backoff_threshold = threshold;
+	}
+
+	/// Register the `cumulus_unincluded_segment_length` gauge in `registry`.
+	pub(crate) fn register_metrics(&self, registry: &Registry) {
+		let gauge = Gauge::new(
+			"cumulus_unincluded_segment_length",
+			"Number of collations that were built on the included parachain head.",
+		)
+		.and_then(|gauge| register(gauge, registry));
+
+		match gauge {
+			Ok(gauge) => self.unincluded_segment.lock().gauge = Some(gauge),
+			Err(err) => warn!(target: "cumulus-collator", "Failed to register metrics: {:?}", err),
+		}
+	}
+
+	/// Note the parachain head that is included in the relay parent of the next collation.
+	///
+	/// The unincluded segment is emptied when the included head advanced.
+	pub(crate) fn note_included_head(&self, included_head: &HeadData) {
+		let mut state = self.unincluded_segment.lock();
+
+		if state.included_head.as_ref() != Some(included_head) {
+			state.included_head = Some(included_head.clone());
+			state.segment.length = 0;
+			state.segment.oldest_relay_parent = None;
+			state.update_gauge();
+		}
+	}
+
+	/// Note that a collation was built on `relay_parent` on top of `included_head`.
+	pub(crate) fn note_unincluded_collation(
+		&self,
+		included_head: &HeadData,
+		relay_parent: RelayParent,
+	) {
+		let mut state = self.unincluded_segment.lock();
+
+		if state.included_head.as_ref() == Some(included_head) {
+			state.segment.length += 1;
+			state.segment.oldest_relay_parent.get_or_insert(relay_parent);
+			state.update_gauge();
+		}
+	}
+
+	/// Set the sync oracle of the relay chain network that is used to determine whether the relay
+	/// chain node is connected.
+	pub fn set_relay_sync_oracle(&self, oracle: Box<dyn SyncOracle + Send>) {
+		*self.relay_sync_oracle.lock() = Some(oracle);
+	}
+}
+
+/// Record what the collator would, for tests of crates that read the status, e.g. the RPC
+/// methods.
+#[cfg(feature = "test-helpers")]
+impl CollatorStatus {
+	/// Remember that the collation built on `relay_parent` was rejected for `reason`.
+	pub fn record_rejection(
+		&self,
+		relay_parent: RelayParent,
+		head_data: Option<HeadData>,
+		reason: String,
+	) {
+		self.add_rejection(relay_parent, head_data, reason);
+	}
+
+	/// Remember that `para_block` was included by the relay chain block of `inclusion`.
+	pub fn record_inclusion(&self, para_block: PHash, inclusion: Inclusion) {
+		self.add_inclusion(para_block, inclusion);
+	}
+}
+
+/// Keep the relay chain heights of the given `status` up to date with the relay chain `client`.
+pub async fn follow_relay_chain<PClient>(client: Arc<PClient>, status: Arc<CollatorStatus>)
+where
+	PClient: BlockchainEvents<PBlock> + HeaderBackend<PBlock>,
+{
+	let imports = client.import_notification_stream().map(|_| ());
+	let finality = client.finality_notification_stream().map(|_| ());
+	let mut notifications = stream::select(imports, finality);
+
+	loop {
+		let info = client.info();
+		status.set_relay_chain_heights(RelayChainHeights {
+			best: info.best_number,
+			finalized: info.finalized_number,
+		});
+
+		if notifications.next().await.is_none() {
+			return;
+		}
+	}
+}
+
+enum FinalityEvent<Header> {
+	Finalized(Header),
+	Tick,
+}
+
+/// Warn if the finalized head of the parachain `client` does not advance for `warn_after`, while
+/// the best block does.
+///
+/// The parachain blocks are finalized when the relay chain finalizes the blocks that include them,
+/// so this usually means that the relay chain does not finalize or does not include the parachain.
+///
+/// Unless the `status` counts finality confirmations, the finalized blocks are reported as final.
+pub async fn monitor_finality<Block, C>(
+	client: Arc<C>,
+	status: Arc<CollatorStatus>,
+	warn_after: Duration,
+) where
+	Block: BlockT,
+	C: BlockchainEvents<Block> + HeaderBackend<Block>,
+{
+	let finalized = client
+		.finality_notification_stream()
+		.map(|notification| FinalityEvent::Finalized(notification.header));
+	let ticks = stream::unfold((), |_| {
+		Delay::new(FINALITY_CHECK_INTERVAL).map(|_| Some((FinalityEvent::Tick, ())))
+	});
+	let mut events = stream::select(finalized, ticks);
+
+	status.note_finalized_head_advanced();
+	let mut last_warning: Option<Instant> = None;
+
+	while let Some(event) = events.next().await {
+		match event {
+			FinalityEvent::Finalized(header) => {
+				status.note_finalized_head_advanced();
+				last_warning = None;
+
+				if status.finality_confirmations.is_none() {
+					status.notify_final_head(HeadData(header.encode()));
+				}
+			}
+			FinalityEvent::Tick => {
+				let info = client.info();
+				let stalled_for = status.finalized_head_age().unwrap_or_default();
+
+				if info.best_number > info.finalized_number
+					&& stalled_for >= warn_after
+					&& last_warning.map_or(true, |at| at.elapsed() >= warn_after)
+				{
+					warn!(
+						target: "cumulus-collator",
+						"The finalized block #{} (`{:?}`) did not advance for {} seconds, while \
+						the best block is #{}. Is the relay chain finalizing the blocks that \
+						include the parachain?",
+						info.finalized_number,
+						info.finalized_hash,
+						stalled_for.as_secs(),
+						info.best_number,
+					);
+					last_warning = Some(Instant::now());
+				}
+			}
+		}
+	}
+}
+
+/// Keep the core `para_id` is assigned to at the best block of the relay chain `client` up to
+/// date in the given `status`.
+pub(crate) async fn track_assigned_core<PClient>(
+	client: Arc<PClient>,
+	para_id: ParaId,
+	status: Arc<CollatorStatus>,
+) where
+	PClient: BlockchainEvents<PBlock> + ProvideRuntimeApi<PBlock>,
+	PClient::Api: ParachainHost<PBlock, Error = ClientError>,
+{
+	let mut imports = client.import_notification_stream();
+
+	while let Some(notification) = imports.next().await {
+		if !notification.is_new_best {
+			continue;
+		}
+
+		let cache = status.relay_call_cache();
+		cache.note_new_best(notification.hash);
+
+		let at = BlockId::Hash(notification.hash);
+		let api = client.runtime_api();
+
+		match cache.get_or_fetch(notification.hash, "active_parachains", || {
+			api.active_parachains(&at)
+		}) {
+			Ok(active_parachains) => status.set_assigned_core(
+				active_parachains
+					.iter()
+					.position(|(id, _)| *id == para_id)
+					.map(|core_index| AssignedCore {
+						core_index: core_index as u32,
+						relay_block: *notification.header.number(),
+					}),
+			),
+			Err(e) => debug!(
+				target: "cumulus-collator",
+				"Failed to fetch the active parachains at `{:?}`: {:?}",
+				notification.hash,
+				e,
+			),
+		}
+
+		let duties = cache
+			.get_or_fetch(notification.hash, "validators", || api.validators(&at))
+			.and_then(|validators| {
+				cache
+					.get_or_fetch(notification.hash, "duty_roster", || api.duty_roster(&at))
+					.map(|roster| (validators, roster))
+			});
+
+		match duties {
+			Ok((validators, roster)) => {
+				status.set_backing_group(backing_group(validators, &roster, para_id))
+			}
+			Err(e) => debug!(
+				target: "cumulus-collator",
+				"Failed to fetch the validator duties at `{:?}`: {:?}",
+				notification.hash,
+				e,
+			),
+		}
+	}
+}
+
+/// The `validators` whose duty in the `roster` is to validate `para_id`.
+fn backing_group(
+	validators: Vec<ValidatorId>,
+	roster: &DutyRoster,
+	para_id: ParaId,
+) -> Vec<ValidatorId> {
+	validators
+		.into_iter()
+		.zip(roster.validator_duty.iter())
+		.filter(|(_, duty)| **duty == Chain::Parachain(para_id))
+		.map(|(validator, _)| validator)
+		.collect()
+}
+
+/// The included parachain heads that wait for enough finality confirmations.
+struct ConfirmationQueue {
+	confirmations: u32,
+	pending: VecDeque<(PBlockNumber, HeadData)>,
+}
+
+impl ConfirmationQueue {
+	fn new(confirmations: u32) -> Self {
+		Self {
+			confirmations,
+			pending: Default::default(),
+		}
+	}
+
+	/// Note that `head` was included by the relay chain block `relay_number`.
+	fn note_inclusion(&mut self, relay_number: PBlockNumber, head: HeadData) {
+		self.pending.push_back((relay_number, head));
+	}
+
+	/// Returns the heads that have enough confirmations at the best relay chain block `best`.
+	fn confirmed(&mut self, best: PBlockNumber) -> Vec<HeadData> {
+		let confirmations = self.confirmations;
+		let mut confirmed = Vec::new();
+
+		while self
+			.pending
+			.front()
+			.map_or(false, |(number, _)| number.saturating_add(confirmations) <= best)
+		{
+			confirmed.extend(self.pending.pop_front().map(|(_, head)| head));
+		}
+
+		confirmed
+	}
+}
+
+/// Remember the relay chain blocks that include new heads of `para_id`.
+///
+/// Every new best relay chain block whose head of the parachain differs from the one of its
+/// parent included that head. The head is compared with the parent and not with the previous
+/// best block, so a reorg of the relay chain does not report an inclusion twice or miss one. The hash of the parachain block is the hash of its head, which
+/// holds for parachains that hash with [`BlakeTwo256`].
+///
+/// If the `status` counts finality confirmations, the included heads are reported as final once
+/// they have enough of them.
+pub async fn record_inclusions<PClient>(
+	client: Arc<PClient>,
+	para_id: ParaId,
+	status: Arc<CollatorStatus>,
+) where
+	PClient: BlockchainEvents<PBlock> + ProvideRuntimeApi<PBlock>,
+	PClient::Api: ParachainHost<PBlock, Error = ClientError>,
+{
+	let mut imports = client.import_notification_stream();
+	let mut confirmation_queue = status.finality_confirmations.map(ConfirmationQueue::new);
+
+	while let Some(notification) = imports.next().await {
+		if !notification.is_new_best {
+			continue;
+		}
+
+		let head = match parachain_head_at(&*client, notification.hash, para_id) {
+			Some(head) => head,
+			None => continue,
+		};
+
+		let relay_number = *notification.header.number();
+		// Without the head of the parent, there is nothing to tell whether this block included it.
+		let parent = *notification.header.parent_hash();
+		let included = parachain_head_at(&*client, parent, para_id)
+			.map_or(false, |parent_head| parent_head != head);
+
+		if included {
+			status.add_inclusion(
+				BlakeTwo256::hash(&head.0),
+				Inclusion {
+					relay_block: notification.hash,
+					relay_number,
+				},
+			);
+
+			if let Some(queue) = confirmation_queue.as_mut() {
+				queue.note_inclusion(relay_number, head.clone());
+			}
+		}
+
+		if let Some(queue) = confirmation_queue.as_mut() {
+			for head in queue.confirmed(relay_number) {
+				status.notify_final_head(head);
+			}
+		}
+	}
+}
+
+/// The head of `para_id` at the relay chain block `at`.
+fn parachain_head_at<PClient>(client: &PClient, at: PHash, para_id: ParaId) -> Option<HeadData>
+where
+	PClient: ProvideRuntimeApi<PBlock>,
+	PClient::Api: ParachainHost<PBlock, Error = ClientError>,
+{
+	match client
+		.runtime_api()
+		.local_validation_data(&BlockId::Hash(at), para_id)
+	{
+		Ok(local_validation) => local_validation.map(|validation| validation.parent_head),
+		Err(e) => {
+			debug!(
+				target: "cumulus-collator",
+				"Failed to fetch the head of the parachain at `{:?}`: {:?}",
+				at,
+				e,
+			);
+			None
+		}
+	}
+}
+
+enum RegistrationEvent {
+	Registered,
+	Tick,
+}
+
+/// Wait until the relay chain `polkadot` reports `para_id` as registered and remember it in the
+/// given `status`.
+pub(crate) async fn wait_for_registration<P: PolkadotClient>(
+	polkadot: P,
+	para_id: ParaId,
+	status: Arc<CollatorStatus>,
+) {
+	let heads = match polkadot.new_best_heads(para_id) {
+		Ok(heads) => heads.map(|_| RegistrationEvent::Registered),
+		Err(e) => {
+			error!(
+				target: "cumulus-collator",
+				"Could not wait for the registration of para-id {:?}: {:?}",
+				para_id,
+				e,
+			);
+			return;
+		}
+	};
+	let ticks = stream::unfold((), |_| {
+		Delay::new(REGISTRATION_LOG_INTERVAL).map(|_| Some((RegistrationEvent::Tick, ())))
+	});
+	let mut events = stream::select(heads, ticks);
+
+	info!(
+		target: "cumulus-collator",
+		"Waiting for para-id {:?} registration to be visible on the relay chain",
+		para_id,
+	);
+
+	while let Some(event) = events.next().await {
+		match event {
+			RegistrationEvent::Registered => {
+				info!(
+					target: "cumulus-collator",
+					"Para-id {:?} is registered on the relay chain",
+					para_id,
+				);
+				status.set_para_registered();
+				return;
+			}
+			RegistrationEvent::Tick => info!(
+				target: "cumulus-collator",
+				"Waiting for para-id {:?} registration to be visible on the relay chain (best \
+				relay chain block: {})",
+				para_id,
+				status
+					.relay_chain_heights()
+					.map_or_else(|| "unknown".into(), |h| format!("#{}", h.best)),
+			),
+		}
+	}
+}
+
+/// Notify about recent collations of `para_id` that get included in finalized relay chain blocks.
+pub(crate) async fn track_inclusion<P: PolkadotClient>(
+	polkadot: P,
+	para_id: ParaId,
+	status: Arc<CollatorStatus>,
+) {
+	let mut finalized_heads = match polkadot.finalized_heads(para_id) {
+		Ok(finalized_heads) => finalized_heads,
+		Err(e) => {
+			error!(
+				target: "cumulus-collator",
+				"Could not track the inclusion of collations: {:?}",
+				e,
+			);
+			return;
+		}
+	};
+
+	let mut last_included = None;
+	while let Some(head) = finalized_heads.next().await {
+		if last_included.as_ref() == Some(&head) {
+			continue;
+		}
+
+		let collation = status
+			.recent_collations()
+			.into_iter()
+			.find(|c| c.head_data.0 == head);
+
+		if let Some(collation) = collation {
+			status.notify_collation(CollationEvent {
+				relay_parent: collation.relay_parent,
+				head_data: Some(collation.head_data),
+				pov_size: Some(collation.block_data.0.len()),
+				result: CollationResult::Included,
+			});
+		}
+
+		last_included = Some(head);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn collation(number: PBlockNumber) -> RecentCollation {
+		RecentCollation {
+			relay_parent: RelayParent {
+				hash: Default::default(),
+				number,
+			},
+			head_data: Default::default(),
+			block_data: BlockData(Vec::new()),
+		}
+	}
+
+	#[test]
+	fn included_heads_are_confirmed_after_the_given_relay_blocks() {
+		let mut queue = ConfirmationQueue::new(2);
+		queue.note_inclusion(10, HeadData(vec![1]));
+		queue.note_inclusion(11, HeadData(vec![2]));
+
+		assert!(queue.confirmed(11).is_empty());
+		assert_eq!(vec![HeadData(vec![1])], queue.confirmed(12));
+		assert_eq!(vec![HeadData(vec![2])], queue.confirmed(14));
+		assert!(queue.confirmed(15).is_empty());
+
+		let mut queue = ConfirmationQueue::new(0);
+		queue.note_inclusion(10, HeadData(vec![1]));
+		assert_eq!(vec![HeadData(vec![1])], queue.confirmed(10));
+	}
+
+	#[test]
+	fn backing_group_are_the_validators_of_the_parachain() {
+		use sp_keyring::Sr25519Keyring;
+
+		let validators = [Sr25519Keyring::Alice, Sr25519Keyring::Bob, Sr25519Keyring::Charlie]
+			.iter()
+			.map(|k| k.public().into())
+			.collect::<Vec<ValidatorId>>();
+		let roster = DutyRoster {
+			validator_duty: vec![
+				Chain::Parachain(100.into()),
+				Chain::Relay,
+				Chain::Parachain(100.into()),
+			],
+		};
+
+		assert_eq!(
+			vec![validators[0].clone(), validators[2].clone()],
+			backing_group(validators.clone(), &roster, 100.into()),
+		);
+		assert!(backing_group(validators, &roster, 200.into()).is_empty());
+	}
+
+	#[test]
+	fn unincluded_segment_is_emptied_when_the_included_head_advances() {
+		let status = CollatorStatus::new();
+		let head = HeadData(vec![1]);
+		status.set_authoring_backoff_threshold(Some(8));
+
+		for number in 1..=3 {
+			status.note_included_head(&head);
+			status.note_unincluded_collation(&head, collation(number).relay_parent);
+		}
+
+		let segment = status.unincluded_segment();
+		assert_eq!(3, segment.length);
+		assert_eq!(Some(8), segment.backoff_threshold);
+		assert_eq!(Some(1), segment.oldest_relay_parent.map(|r| r.number));
+
+		status.note_included_head(&HeadData(vec![2]));
+		assert_eq!(
+			UnincludedSegment {
+				length: 0,
+				backoff_threshold: Some(8),
+				oldest_relay_parent: None,
+			},
+			status.unincluded_segment(),
+		);
+	}
+
+	#[test]
+	fn oldest_collations_are_evicted() {
+		let status = CollatorStatus::with_keep_recent_collations(2);
+
+		(1..=3).for_each(|n| status.add_collation(collation(n)));
+
+		assert_eq!(vec![collation(2), collation(3)], status.recent_collations());
+	}
+
+	#[test]
+	fn no_collations_are_kept_with_zero() {
+		let status = CollatorStatus::with_keep_recent_collations(0);
+
+		status.add_collation(collation(1));
+
+		assert!(status.recent_collations().is_empty());
+	}
+
+	#[test]
+	fn most_recent_rejections_are_returned() {
+		let status = CollatorStatus::new().with_keep_recent_rejections(2);
+
+		(1..=3).for_each(|n| {
+			status.add_rejection(collation(n).relay_parent, None, format!("reason {}", n))
+		});
+
+		let reasons = |count| {
+			status
+				.recent_rejections(count)
+				.into_iter()
+				.map(|r| r.reason)
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(vec!["reason 2", "reason 3"], reasons(10));
+		assert_eq!(vec!["reason 3"], reasons(1));
+		assert!(reasons(0).is_empty());
+	}
+
+	#[test]
+	fn collation_events_are_sent_to_all_streams() {
+		let status = CollatorStatus::new();
+		let mut first = status.collation_events();
+		let second = status.collation_events();
+		drop(second);
+
+		let event = CollationEvent {
+			relay_parent: collation(1).relay_parent,
+			head_data: None,
+			pov_size: None,
+			result: CollationResult::Rejected,
+		};
+		status.notify_collation(event.clone());
+
+		assert_eq!(Ok(Some(event)), first.try_next().map_err(drop));
+		assert_eq!(1, status.collation_event_sinks.lock().len());
+	}
+
+	#[test]
+	fn latest_inclusion_is_returned() {
+		let status = CollatorStatus::new();
+		let inclusion = |number| Inclusion {
+			relay_block: PHash::repeat_byte(number as u8),
+			relay_number: number,
+		};
+
+		status.add_inclusion(PHash::repeat_byte(1), inclusion(1));
+		status.add_inclusion(PHash::repeat_byte(1), inclusion(2));
+
+		assert_eq!(Some(inclusion(2)), status.inclusion_of(&PHash::repeat_byte(1)));
+		assert_eq!(None, status.inclusion_of(&PHash::repeat_byte(2)));
+
+		(0..KEEP_INCLUSIONS).for_each(|_| status.add_inclusion(PHash::zero(), inclusion(3)));
+
+		assert_eq!(None, status.inclusion_of(&PHash::repeat_byte(1)));
+	}
+}
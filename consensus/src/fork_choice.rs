@@ -0,0 +1,223 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Fork choice that prefers the parachain branch that is backed by the relay chain.
+
+use sp_blockchain::HeaderBackend;
+use sp_consensus::{
+	import_queue::CacheKeyId, BlockCheckParams, BlockImport, BlockImportParams,
+	Error as ConsensusError, ForkChoiceStrategy, ImportResult,
+};
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, Header as HeaderT, NumberFor},
+};
+
+use log::debug;
+use parking_lot::RwLock;
+
+use std::{collections::HashMap, sync::Arc};
+
+/// The parachain head that is backed by the best relay chain block.
+///
+/// This is updated while following the relay chain and shared with the [`ParachainBlockImport`].
+pub struct BackedHead<Block: BlockT>(Arc<RwLock<Option<(Block::Hash, NumberFor<Block>)>>>);
+
+impl<Block: BlockT> BackedHead<Block> {
+	/// Returns the hash and number of the backed head or `None` if it is not known yet.
+	pub fn get(&self) -> Option<(Block::Hash, NumberFor<Block>)> {
+		*self.0.read()
+	}
+
+	/// Set the given `header` as the backed head.
+	pub fn set(&self, header: &Block::Header) {
+		*self.0.write() = Some((header.hash(), *header.number()));
+	}
+}
+
+impl<Block: BlockT> Clone for BackedHead<Block> {
+	fn clone(&self) -> Self {
+		Self(self.0.clone())
+	}
+}
+
+impl<Block: BlockT> Default for BackedHead<Block> {
+	fn default() -> Self {
+		Self(Default::default())
+	}
+}
+
+/// A [`BlockImport`] that only makes blocks the new best block that are on the backed branch.
+///
+/// A block is imported as new best block if it is the [`BackedHead`], or if the wrapped fork choice
+/// would make it the new best block and it is a descendant of the backed head. This prevents a
+/// longer branch that is not backed by the relay chain from becoming the best chain.
+pub struct ParachainBlockImport<Client, I, Block: BlockT> {
+	client: Arc<Client>,
+	inner: I,
+	backed_head: BackedHead<Block>,
+}
+
+impl<Client, I, Block: BlockT> ParachainBlockImport<Client, I, Block> {
+	/// Create a new instance.
+	///
+	/// - `client`: The client of the parachain, used to look up the ancestry of blocks.
+	/// - `inner`: The block import that is wrapped.
+	/// - `backed_head`: The head that is backed by the relay chain.
+	pub fn new(client: Arc<Client>, inner: I, backed_head: BackedHead<Block>) -> Self {
+		Self {
+			client,
+			inner,
+			backed_head,
+		}
+	}
+}
+
+impl<Client, I, Block> ParachainBlockImport<Client, I, Block>
+where
+	Block: BlockT,
+	Client: HeaderBackend<Block>,
+{
+	/// Returns whether the block with the given `parent` descends from the backed head.
+	fn is_backed_descendant(
+		&self,
+		parent: Block::Hash,
+		(backed_hash, backed_number): (Block::Hash, NumberFor<Block>),
+	) -> Result<bool, ConsensusError> {
+		let mut current = parent;
+
+		loop {
+			if current == backed_hash {
+				return Ok(true);
+			}
+
+			match self
+				.client
+				.header(BlockId::Hash(current))
+				.map_err(|e| ConsensusError::ClientImport(e.to_string()))?
+			{
+				Some(header) if *header.number() > backed_number => {
+					current = *header.parent_hash();
+				}
+				_ => return Ok(false),
+			}
+		}
+	}
+}
+
+impl<Client, I, Block> BlockImport<Block> for ParachainBlockImport<Client, I, Block>
+where
+	Block: BlockT,
+	Client: HeaderBackend<Block>,
+	I: BlockImport<Block, Error = ConsensusError>,
+{
+	type Error = ConsensusError;
+	type Transaction = I::Transaction;
+
+	fn check_block(&mut self, block: BlockCheckParams<Block>) -> Result<ImportResult, Self::Error> {
+		self.inner.check_block(block)
+	}
+
+	fn import_block(
+		&mut self,
+		mut block: BlockImportParams<Block, Self::Transaction>,
+		cache: HashMap<CacheKeyId, Vec<u8>>,
+	) -> Result<ImportResult, Self::Error> {
+		if let Some(backed_head) = self.backed_head.get() {
+			let hash = block.post_hash();
+
+			if hash == backed_head.0 {
+				block.fork_choice = Some(ForkChoiceStrategy::Custom(true));
+			} else if !matches!(block.fork_choice, Some(ForkChoiceStrategy::Custom(false)))
+				&& !self.is_backed_descendant(*block.header.parent_hash(), backed_head)?
+			{
+				debug!(
+					target: "cumulus-consensus",
+					"Not importing `{:?}` as new best block, because it is not on the backed branch.",
+					hash,
+				);
+
+				block.fork_choice = Some(ForkChoiceStrategy::Custom(false));
+			}
+		}
+
+		self.inner.import_block(block, cache)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sc_block_builder::BlockBuilderProvider;
+	use sp_consensus::BlockOrigin;
+	use sp_runtime::{generic::Digest, DigestItem};
+	use test_client::{
+		runtime::Block, Client, DefaultTestClientBuilderExt, TestClientBuilder,
+		TestClientBuilderExt,
+	};
+
+	fn build_block(client: &Client, parent: <Block as BlockT>::Hash, fork: u8) -> Block {
+		let digest = Digest {
+			logs: vec![DigestItem::Other(vec![fork])],
+		};
+
+		client
+			.new_block_at(&BlockId::Hash(parent), digest, false)
+			.unwrap()
+			.build()
+			.unwrap()
+			.block
+	}
+
+	fn import(
+		block_import: &mut ParachainBlockImport<Client, Arc<Client>, Block>,
+		block: Block,
+	) {
+		let (header, body) = block.deconstruct();
+		let mut params = BlockImportParams::new(BlockOrigin::NetworkInitialSync, header);
+		params.body = Some(body);
+		// Initial sync imports all blocks as new best.
+		params.fork_choice = Some(ForkChoiceStrategy::Custom(true));
+
+		block_import.import_block(params, Default::default()).unwrap();
+	}
+
+	#[test]
+	fn backed_branch_is_preferred_over_longer_branch() {
+		let client = Arc::new(TestClientBuilder::new().build());
+		let genesis = client.info().genesis_hash;
+		let backed_head = BackedHead::default();
+		let mut block_import =
+			ParachainBlockImport::new(client.clone(), client.clone(), backed_head.clone());
+
+		let backed = build_block(&client, genesis, 0);
+		backed_head.set(backed.header());
+		import(&mut block_import, backed.clone());
+
+		let unbacked_1 = build_block(&client, genesis, 1);
+		import(&mut block_import, unbacked_1.clone());
+		let unbacked_2 = build_block(&client, unbacked_1.hash(), 1);
+		import(&mut block_import, unbacked_2);
+
+		assert_eq!(client.info().best_hash, backed.hash());
+
+		let descendant = build_block(&client, backed.hash(), 0);
+		import(&mut block_import, descendant.clone());
+
+		assert_eq!(client.info().best_hash, descendant.hash());
+	}
+}
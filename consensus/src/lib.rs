@@ -34,7 +34,12 @@ use log::{error, trace, warn};
 
 use std::{marker::PhantomData, sync::Arc};
 
+mod fork_choice;
 pub mod import_queue;
+mod retry;
+
+pub use fork_choice::{BackedHead, ParachainBlockImport};
+pub use retry::{FetchRetry, RetryingPolkadotClient};
 
 /// Errors that can occur while following the polkadot relay-chain.
 #[derive(Debug)]
@@ -103,6 +108,7 @@ pub fn follow_polkadot<L, P, Block, B>(
 	local: Arc<L>,
 	polkadot: P,
 	announce_block: Arc<dyn Fn(Block::Hash, Vec<u8>) + Send + Sync>,
+	backed_head: BackedHead<Block>,
 ) -> ClientResult<impl Future<Output = ()> + Send + Unpin>
 where
 	Block: BlockT,
@@ -146,7 +152,7 @@ where
 
 	Ok(future::select(
 		follow_finalized,
-		follow_new_best(para_id, local, polkadot, announce_block)?,
+		follow_new_best(para_id, local, polkadot, announce_block, backed_head)?,
 	)
 	.map(|_| ()))
 }
@@ -157,6 +163,7 @@ fn follow_new_best<L, P, Block, B>(
 	local: Arc<L>,
 	polkadot: P,
 	announce_block: Arc<dyn Fn(Block::Hash, Vec<u8>) + Send + Sync>,
+	backed_head: BackedHead<Block>,
 ) -> ClientResult<impl Future<Output = ()> + Send + Unpin>
 where
 	Block: BlockT,
@@ -182,6 +189,7 @@ where
 		})
 		.for_each(move |h| {
 			let hash = h.hash();
+			backed_head.set(&h);
 
 			if local.usage_info().chain.best_hash == hash {
 				trace!(
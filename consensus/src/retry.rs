@@ -0,0 +1,391 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A [`PolkadotClient`] that retries fetching the validation data from the relay chain.
+//!
+//! The parachain heads at the best and at the finalized relay chain block are fetched once by the
+//! head streams and then served from a cache to the [`PolkadotClient::parachain_head_at`] callers
+//! of this client, until a new best respectively finalized relay chain block is seen.
+
+use crate::PolkadotClient;
+
+use sc_client_api::BlockchainEvents;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::{Error as ClientError, Result as ClientResult};
+use sp_runtime::generic::BlockId;
+use substrate_prometheus_endpoint::{register, Counter, PrometheusError, Registry, U64};
+
+use polkadot_primitives::v0::{Block as PBlock, Hash as PHash, Id as ParaId, ParachainHost};
+
+use futures::{future, Stream, StreamExt};
+use futures_timer::Delay;
+use log::{error, warn};
+use parking_lot::Mutex;
+
+use std::{pin::Pin, sync::Arc, time::Duration};
+
+/// How fetching the validation data from the relay chain is retried.
+///
+/// This only covers the fetches of the [`RetryingPolkadotClient`]. The validation data a
+/// collation is built on is fetched by the Polkadot collator before
+/// `ParachainContext::produce_candidate` is called, a failure there skips the collation with an
+/// error logged by the Polkadot collator and is not retried.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FetchRetry {
+	/// The maximum number of attempts, including the first one.
+	pub attempts: u32,
+	/// The delay between two attempts.
+	pub delay: Duration,
+}
+
+impl Default for FetchRetry {
+	fn default() -> Self {
+		Self {
+			attempts: 3,
+			delay: Duration::from_millis(200),
+		}
+	}
+}
+
+/// Prometheus metrics of the [`RetryingPolkadotClient`].
+#[derive(Clone)]
+struct Metrics {
+	validation_data_fetch_failures: Counter<U64>,
+	validation_data_cache_hits: Counter<U64>,
+	validation_data_cache_misses: Counter<U64>,
+}
+
+impl Metrics {
+	fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			validation_data_fetch_failures: register(
+				Counter::new(
+					"cumulus_validation_data_fetch_failures",
+					"Number of times fetching the validation data from the relay chain failed \
+					after all retries.",
+				)?,
+				registry,
+			)?,
+			validation_data_cache_hits: register(
+				Counter::new(
+					"cumulus_validation_data_cache_hits",
+					"Number of times the validation data was served from the cache.",
+				)?,
+				registry,
+			)?,
+			validation_data_cache_misses: register(
+				Counter::new(
+					"cumulus_validation_data_cache_misses",
+					"Number of times the validation data was fetched from the relay chain.",
+				)?,
+				registry,
+			)?,
+		})
+	}
+}
+
+/// Which relay chain block a fetched parachain head belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CachedAt {
+	/// The new best relay chain block.
+	Best,
+	/// The new finalized relay chain block.
+	Finalized,
+}
+
+/// A cached parachain head, the relay chain block it was fetched at and its parachain.
+type Entry = Mutex<Option<(PHash, ParaId, Option<Vec<u8>>)>>;
+
+/// The parachain heads at the best and at the finalized relay chain block.
+#[derive(Default)]
+struct HeadCache {
+	best: Entry,
+	finalized: Entry,
+}
+
+impl HeadCache {
+	/// Returns the cached head of `para_id` at `relay_parent`, if it is the best or the finalized
+	/// relay chain block.
+	fn get(&self, relay_parent: &PHash, para_id: ParaId) -> Option<Option<Vec<u8>>> {
+		let get = |entry: &Entry| {
+			entry
+				.lock()
+				.as_ref()
+				.filter(|(hash, id, _)| hash == relay_parent && *id == para_id)
+				.map(|(_, _, head)| head.clone())
+		};
+
+		get(&self.best).or_else(|| get(&self.finalized))
+	}
+
+	/// Replace the cached head of `at` by the `head` of `para_id` at the relay chain block
+	/// `relay_parent`.
+	fn set(&self, at: CachedAt, relay_parent: PHash, para_id: ParaId, head: Option<Vec<u8>>) {
+		let entry = match at {
+			CachedAt::Best => &self.best,
+			CachedAt::Finalized => &self.finalized,
+		};
+
+		*entry.lock() = Some((relay_parent, para_id, head));
+	}
+
+	/// Drop the cached heads.
+	fn clear(&self) {
+		*self.best.lock() = None;
+		*self.finalized.lock() = None;
+	}
+}
+
+/// A [`PolkadotClient`] that retries fetching the validation data of the parachain.
+///
+/// Transient errors of the relay chain runtime api would otherwise lead to silently skipped head
+/// updates.
+pub struct RetryingPolkadotClient<T> {
+	client: Arc<T>,
+	retry: FetchRetry,
+	metrics: Option<Metrics>,
+	cache: Arc<HeadCache>,
+}
+
+impl<T> RetryingPolkadotClient<T> {
+	/// Create a new instance.
+	///
+	/// - `client`: The client of the Polkadot node.
+	/// - `retry`: How failed fetches should be retried.
+	/// - `registry`: The prometheus registry to register the metrics in.
+	pub fn new(client: Arc<T>, retry: FetchRetry, registry: Option<&Registry>) -> Self {
+		let metrics = registry.and_then(|registry| {
+			Metrics::register(registry)
+				.map_err(|err| {
+					warn!(target: "cumulus-consensus", "Failed to register metrics: {:?}", err)
+				})
+				.ok()
+		});
+
+		Self {
+			client,
+			retry,
+			metrics,
+			cache: Default::default(),
+		}
+	}
+
+	/// Drop the cached parachain heads.
+	///
+	/// Used when the relay chain runtime was upgraded, the heads are fetched again with the new
+	/// runtime.
+	pub fn clear_cache(&self) {
+		self.cache.clear();
+	}
+
+	/// Returns the cached head of `para_id` at `relay_parent` and counts the cache hit or miss.
+	fn cached_head(&self, relay_parent: &PHash, para_id: ParaId) -> Option<Option<Vec<u8>>> {
+		let head = self.cache.get(relay_parent, para_id);
+
+		if let Some(metrics) = &self.metrics {
+			if head.is_some() {
+				metrics.validation_data_cache_hits.inc();
+			} else {
+				metrics.validation_data_cache_misses.inc();
+			}
+		}
+
+		head
+	}
+}
+
+impl<T> Clone for RetryingPolkadotClient<T> {
+	fn clone(&self) -> Self {
+		Self {
+			client: self.client.clone(),
+			retry: self.retry,
+			metrics: self.metrics.clone(),
+			cache: self.cache.clone(),
+		}
+	}
+}
+
+/// Fetch the parachain head at the given relay parent, retrying as described by the `retry` of
+/// `client`.
+///
+/// The head is cached as the head at the new best or finalized relay chain block, as given by
+/// `at`.
+async fn parachain_head_with_retry<T>(
+	client: RetryingPolkadotClient<T>,
+	relay_parent: PHash,
+	para_id: ParaId,
+	at: CachedAt,
+) -> Option<Vec<u8>>
+where
+	T: BlockchainEvents<PBlock> + ProvideRuntimeApi<PBlock> + 'static + Send + Sync,
+	<T as ProvideRuntimeApi<PBlock>>::Api: ParachainHost<PBlock, Error = ClientError>,
+{
+	if let Some(head) = client.cached_head(&relay_parent, para_id) {
+		return head;
+	}
+
+	let RetryingPolkadotClient {
+		client,
+		retry,
+		metrics,
+		cache,
+	} = client;
+	let mut attempt = 1;
+
+	loop {
+		match client.parachain_head_at(&BlockId::hash(relay_parent), para_id) {
+			Ok(head) => {
+				cache.set(at, relay_parent, para_id, head.clone());
+
+				return head;
+			}
+			Err(e) if attempt < retry.attempts => {
+				warn!(
+					target: "cumulus-consensus",
+					"Failed to fetch validation data at relay parent `{:?}` (attempt {}/{}), retrying: {:?}",
+					relay_parent,
+					attempt,
+					retry.attempts,
+					e,
+				);
+
+				Delay::new(retry.delay).await;
+				attempt += 1;
+			}
+			Err(e) => {
+				error!(
+					target: "cumulus-consensus",
+					"Failed to fetch validation data at relay parent `{:?}` after {} attempts: {:?}",
+					relay_parent,
+					attempt,
+					e,
+				);
+
+				if let Some(metrics) = metrics {
+					metrics.validation_data_fetch_failures.inc();
+				}
+
+				return None;
+			}
+		}
+	}
+}
+
+impl<T> PolkadotClient for RetryingPolkadotClient<T>
+where
+	T: BlockchainEvents<PBlock> + ProvideRuntimeApi<PBlock> + 'static + Send + Sync,
+	<T as ProvideRuntimeApi<PBlock>>::Api: ParachainHost<PBlock, Error = ClientError>,
+{
+	type Error = ClientError;
+
+	type HeadStream = Pin<Box<dyn Stream<Item = Vec<u8>> + Send>>;
+
+	fn new_best_heads(&self, para_id: ParaId) -> ClientResult<Self::HeadStream> {
+		let this = self.clone();
+
+		let s = self
+			.client
+			.import_notification_stream()
+			.filter(|n| future::ready(n.is_new_best))
+			.then(move |n| {
+				parachain_head_with_retry(this.clone(), n.hash, para_id, CachedAt::Best)
+			})
+			.filter_map(future::ready);
+
+		Ok(Box::pin(s))
+	}
+
+	fn finalized_heads(&self, para_id: ParaId) -> ClientResult<Self::HeadStream> {
+		let this = self.clone();
+
+		let s = self
+			.client
+			.finality_notification_stream()
+			.then(move |n| {
+				parachain_head_with_retry(this.clone(), n.hash, para_id, CachedAt::Finalized)
+			})
+			.filter_map(future::ready);
+
+		Ok(Box::pin(s))
+	}
+
+	fn parachain_head_at(
+		&self,
+		at: &BlockId<PBlock>,
+		para_id: ParaId,
+	) -> ClientResult<Option<Vec<u8>>> {
+		if let BlockId::Hash(relay_parent) = at {
+			if let Some(head) = self.cached_head(relay_parent, para_id) {
+				return Ok(head);
+			}
+		}
+
+		self.client.parachain_head_at(at, para_id)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const PARA_ID: ParaId = ParaId::new(100);
+
+	#[test]
+	fn only_the_head_at_the_best_relay_parent_is_cached() {
+		let cache = HeadCache::default();
+		let (first, second) = (PHash::repeat_byte(1), PHash::repeat_byte(2));
+
+		assert_eq!(None, cache.get(&first, PARA_ID));
+
+		cache.set(CachedAt::Best, first, PARA_ID, Some(vec![1]));
+		assert_eq!(Some(Some(vec![1])), cache.get(&first, PARA_ID));
+
+		cache.set(CachedAt::Best, second, PARA_ID, None);
+		assert_eq!(None, cache.get(&first, PARA_ID));
+		assert_eq!(Some(None), cache.get(&second, PARA_ID));
+
+		cache.clear();
+		assert_eq!(None, cache.get(&second, PARA_ID));
+	}
+
+	#[test]
+	fn the_finalized_head_is_cached_next_to_the_best_head() {
+		let cache = HeadCache::default();
+		let (finalized, best) = (PHash::repeat_byte(1), PHash::repeat_byte(2));
+
+		cache.set(CachedAt::Finalized, finalized, PARA_ID, Some(vec![1]));
+		cache.set(CachedAt::Best, best, PARA_ID, Some(vec![2]));
+		assert_eq!(Some(Some(vec![1])), cache.get(&finalized, PARA_ID));
+		assert_eq!(Some(Some(vec![2])), cache.get(&best, PARA_ID));
+
+		cache.set(CachedAt::Finalized, best, PARA_ID, Some(vec![2]));
+		assert_eq!(None, cache.get(&finalized, PARA_ID));
+		assert_eq!(Some(Some(vec![2])), cache.get(&best, PARA_ID));
+
+		cache.clear();
+		assert_eq!(None, cache.get(&best, PARA_ID));
+	}
+
+	#[test]
+	fn the_head_of_another_parachain_is_not_served_from_the_cache() {
+		let cache = HeadCache::default();
+		let relay_parent = PHash::repeat_byte(1);
+
+		cache.set(CachedAt::Best, relay_parent, PARA_ID, Some(vec![1]));
+		assert_eq!(Some(Some(vec![1])), cache.get(&relay_parent, PARA_ID));
+		assert_eq!(None, cache.get(&relay_parent, ParaId::new(200)));
+	}
+}
@@ -31,7 +31,7 @@ use cumulus_primitives::{
 };
 use cumulus_upward_message::XCMPMessage;
 use frame_support::{
-	decl_event, decl_module, storage,
+	debug, decl_event, decl_module, decl_storage, storage,
 	traits::Get,
 	weights::{DispatchClass, Weight},
 };
@@ -40,6 +40,16 @@ use sp_inherents::{InherentData, InherentIdentifier, MakeFatalError, ProvideInhe
 use sp_runtime::traits::Hash;
 use sp_std::vec::Vec;
 
+/// For how many blocks upward messages can be queued.
+///
+/// [`UpwardMessageSender::send_upward_message`] fails when `PendingUpwardMessages` already holds
+/// `MaxUpwardMessagesPerBlock` times this many messages.
+pub const PENDING_UPWARD_MESSAGES_BLOCKS: u32 = 4;
+
+/// The weight of decoding, measuring and encoding one pending upward message in `on_finalize`,
+/// without the database accesses.
+pub const PENDING_UPWARD_MESSAGE_WEIGHT: Weight = 1_000_000;
+
 /// Configuration trait of this pallet.
 pub trait Trait: frame_system::Trait {
 	/// Event type used by the runtime.
@@ -59,6 +69,23 @@ pub trait Trait: frame_system::Trait {
 
 	/// The Id of the parachain.
 	type ParachainId: Get<ParaId>;
+
+	/// The maximum number of upward messages that are sent to the relay chain per block.
+	///
+	/// Also bounds the number of pending upward messages, see [`PENDING_UPWARD_MESSAGES_BLOCKS`].
+	type MaxUpwardMessagesPerBlock: Get<u32>;
+
+	/// The maximum total size in bytes of the upward messages that are sent per block.
+	///
+	/// Upward messages that are larger than this on their own are rejected.
+	type MaxUpwardMessagesSizePerBlock: Get<u32>;
+}
+
+decl_storage! {
+	trait Store for Module<T: Trait> as MessageBroker {
+		/// Upward messages that were not sent yet, because of the per block limits.
+		PendingUpwardMessages: Vec<GenericUpwardMessage>;
+	}
 }
 
 decl_event! {
@@ -102,21 +129,80 @@ decl_module! {
 		fn on_initialize() -> Weight {
 			storage::unhashed::kill(well_known_keys::UPWARD_MESSAGES);
 
-			T::DbWeight::get().writes(1)
+			// `on_finalize` takes the `PendingUpwardMessages`, puts back the deferred ones and
+			// puts the `UPWARD_MESSAGES`. The work on the messages is bounded by the size of the
+			// queue.
+			let max_pending = Self::max_pending_upward_messages() as Weight;
+			T::DbWeight::get()
+				.reads_writes(1, 4)
+				.saturating_add(max_pending.saturating_mul(PENDING_UPWARD_MESSAGE_WEIGHT))
+		}
+
+		fn on_finalize() {
+			Self::send_pending_upward_messages();
 		}
 
 		fn deposit_event() = default;
 	}
 }
 
+impl<T: Trait> Module<T> {
+	/// The maximum number of messages in `PendingUpwardMessages`.
+	fn max_pending_upward_messages() -> u32 {
+		T::MaxUpwardMessagesPerBlock::get().saturating_mul(PENDING_UPWARD_MESSAGES_BLOCKS)
+	}
+
+	/// Move as many pending upward messages to the relay chain as the per block limits allow.
+	///
+	/// The remaining messages are sent in the following blocks.
+	fn send_pending_upward_messages() {
+		let mut pending = PendingUpwardMessages::take();
+		let max_count = T::MaxUpwardMessagesPerBlock::get() as usize;
+		let max_size = T::MaxUpwardMessagesSizePerBlock::get() as usize;
+
+		let mut size = 0;
+		let count = pending
+			.iter()
+			.take(max_count)
+			.take_while(|msg| {
+				size += msg.data.len();
+				size <= max_size
+			})
+			.count();
+
+		let deferred = pending.split_off(count);
+		if !deferred.is_empty() {
+			debug::info!(
+				target: "cumulus-message-broker",
+				"Deferring {} upward messages to the next block, the per block limits are reached",
+				deferred.len(),
+			);
+			PendingUpwardMessages::put(deferred);
+		}
+
+		if !pending.is_empty() {
+			storage::unhashed::put(well_known_keys::UPWARD_MESSAGES, &pending);
+		}
+	}
+}
+
 impl<T: Trait> UpwardMessageSender<T::UpwardMessage> for Module<T> {
 	fn send_upward_message(msg: &T::UpwardMessage, origin: UpwardMessageOrigin) -> Result<(), ()> {
 		//TODO: check fee schedule
 		let data = msg.encode();
+		if data.len() > T::MaxUpwardMessagesSizePerBlock::get() as usize {
+			return Err(());
+		}
+
+		let pending = PendingUpwardMessages::decode_len().unwrap_or(0);
+		if pending >= Self::max_pending_upward_messages() as usize {
+			return Err(());
+		}
+
 		let data_hash = T::Hashing::hash(&data);
 
 		let msg = GenericUpwardMessage { origin, data };
-		sp_io::storage::append(well_known_keys::UPWARD_MESSAGES, msg.encode());
+		PendingUpwardMessages::append(msg);
 
 		Self::deposit_event(RawEvent::UpwardMessageSent(data_hash));
 
@@ -149,3 +235,179 @@ impl<T: Trait> ProvideInherent for Module<T> {
 			.map(|msgs| Call::execute_downward_messages(msgs))
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use frame_support::{
+		impl_outer_event, impl_outer_origin, parameter_types,
+		traits::{OnFinalize, OnInitialize},
+		weights::Weight,
+	};
+	use sp_core::H256;
+	use sp_runtime::{
+		testing::Header,
+		traits::{BlakeTwo256, IdentityLookup},
+		Perbill,
+	};
+
+	impl_outer_origin! {
+		pub enum Origin for Test where system = frame_system {}
+	}
+
+	mod message_broker {
+		pub use crate::Event;
+	}
+
+	impl_outer_event! {
+		pub enum TestEvent for Test {
+			frame_system<T>,
+			message_broker<T>,
+		}
+	}
+
+	#[derive(Encode, Decode)]
+	pub struct TestMessage(Vec<u8>);
+
+	impl XCMPMessage for TestMessage {
+		fn send_message(_: ParaId, msg: Vec<u8>) -> Self {
+			TestMessage(msg)
+		}
+	}
+
+	pub struct Handlers;
+
+	impl DownwardMessageHandler for Handlers {
+		fn handle_downward_message(_: &DownwardMessage) {}
+	}
+
+	impl XCMPMessageHandler<TestMessage> for Handlers {
+		fn handle_xcmp_message(_: ParaId, _: &TestMessage) {}
+	}
+
+	#[derive(Clone, Eq, PartialEq)]
+	pub struct Test;
+	parameter_types! {
+		pub const BlockHashCount: u64 = 250;
+		pub const MaximumBlockWeight: Weight = 1024;
+		pub const MaximumBlockLength: u32 = 2 * 1024;
+		pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+		pub ParachainId: ParaId = ParaId::from(100);
+		pub const MaxUpwardMessagesPerBlock: u32 = 2;
+		pub const MaxUpwardMessagesSizePerBlock: u32 = 10;
+	}
+	impl frame_system::Trait for Test {
+		type Origin = Origin;
+		type Call = ();
+		type Index = u64;
+		type BlockNumber = u64;
+		type Hash = H256;
+		type Hashing = BlakeTwo256;
+		type AccountId = u64;
+		type Lookup = IdentityLookup<Self::AccountId>;
+		type Header = Header;
+		type Event = TestEvent;
+		type BlockHashCount = BlockHashCount;
+		type MaximumBlockWeight = MaximumBlockWeight;
+		type MaximumExtrinsicWeight = MaximumBlockWeight;
+		type MaximumBlockLength = MaximumBlockLength;
+		type AvailableBlockRatio = AvailableBlockRatio;
+		type Version = ();
+		type ModuleToIndex = ();
+		type AccountData = ();
+		type OnNewAccount = ();
+		type OnKilledAccount = ();
+		type DbWeight = ();
+		type BlockExecutionWeight = ();
+		type ExtrinsicBaseWeight = ();
+		type BaseCallFilter = ();
+		type SystemWeightInfo = ();
+	}
+	impl Trait for Test {
+		type Event = TestEvent;
+		type DownwardMessageHandlers = Handlers;
+		type UpwardMessage = TestMessage;
+		type XCMPMessageHandlers = Handlers;
+		type XCMPMessage = TestMessage;
+		type ParachainId = ParachainId;
+		type MaxUpwardMessagesPerBlock = MaxUpwardMessagesPerBlock;
+		type MaxUpwardMessagesSizePerBlock = MaxUpwardMessagesSizePerBlock;
+	}
+
+	type MessageBroker = Module<Test>;
+
+	fn new_test_ext() -> sp_io::TestExternalities {
+		frame_system::GenesisConfig::default()
+			.build_storage::<Test>()
+			.unwrap()
+			.into()
+	}
+
+	fn send(len: usize) -> Result<(), ()> {
+		MessageBroker::send_upward_message(
+			&TestMessage(vec![0; len]),
+			UpwardMessageOrigin::Signed,
+		)
+	}
+
+	/// Run the hooks of the given block and return the upward messages it sends.
+	fn run_block(n: u64) -> Vec<GenericUpwardMessage> {
+		MessageBroker::on_initialize(n);
+		MessageBroker::on_finalize(n);
+
+		storage::unhashed::get(well_known_keys::UPWARD_MESSAGES).unwrap_or_default()
+	}
+
+	#[test]
+	fn excess_upward_messages_are_deferred() {
+		new_test_ext().execute_with(|| {
+			// Encoded with a one byte length prefix, every message is 4 bytes long.
+			(0..3).for_each(|_| send(3).unwrap());
+			assert_eq!(2, run_block(1).len());
+			assert_eq!(1, run_block(2).len());
+			assert!(run_block(3).is_empty());
+
+			// Together the messages exceed the size limit of a block.
+			send(3).unwrap();
+			send(6).unwrap();
+			assert_eq!(1, run_block(4).len());
+			assert_eq!(1, run_block(5).len());
+		});
+	}
+
+	#[test]
+	fn upward_messages_are_rejected_when_the_queue_is_full() {
+		new_test_ext().execute_with(|| {
+			let max_pending = MaxUpwardMessagesPerBlock::get() * PENDING_UPWARD_MESSAGES_BLOCKS;
+			(0..max_pending).for_each(|_| send(1).unwrap());
+			assert_eq!(Err(()), send(1));
+
+			// Sending the messages of a block makes room for new ones.
+			assert_eq!(2, run_block(1).len());
+			send(1).unwrap();
+			send(1).unwrap();
+			assert_eq!(Err(()), send(1));
+		});
+	}
+
+	#[test]
+	fn weight_accounts_for_a_full_queue() {
+		new_test_ext().execute_with(|| {
+			let max_pending = MaxUpwardMessagesPerBlock::get() * PENDING_UPWARD_MESSAGES_BLOCKS;
+
+			assert_eq!(
+				max_pending as Weight * PENDING_UPWARD_MESSAGE_WEIGHT,
+				MessageBroker::on_initialize(1),
+			);
+		});
+	}
+
+	#[test]
+	fn too_large_upward_message_is_rejected() {
+		new_test_ext().execute_with(|| {
+			assert_eq!(Err(()), send(10));
+			assert!(run_block(1).is_empty());
+		});
+	}
+}
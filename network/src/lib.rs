@@ -35,23 +35,89 @@ use sp_runtime::{
 
 use polkadot_collator::Network as CollatorNetwork;
 use polkadot_network::legacy::gossip::{GossipMessage, GossipStatement};
-use polkadot_primitives::v0::{Block as PBlock, Hash as PHash, Id as ParaId, ParachainHost};
+use polkadot_primitives::v0::{
+	Block as PBlock, BlockNumber as PBlockNumber, Hash as PHash, Id as ParaId, ParachainHost,
+};
 use polkadot_statement_table::v0::{SignedStatement, Statement};
 use polkadot_validation::check_statement;
 
 use cumulus_primitives::HeadData;
 
 use codec::{Decode, Encode};
-use futures::{channel::oneshot, future::FutureExt, pin_mut, select, StreamExt};
-use log::trace;
+use futures::{
+	channel::oneshot,
+	future::{self, FutureExt},
+	pin_mut, select, StreamExt,
+};
+use futures_timer::Delay;
+use log::{debug, error, info, trace, warn};
 
 use parking_lot::Mutex;
-use std::{marker::PhantomData, sync::Arc};
+use std::{
+	marker::PhantomData,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+/// The version of the signature scheme of the block announcement justifications this node sends.
+///
+/// The justification is a candidate statement signed by a relay chain validator as `GossipMessage`.
+/// Version `0` is the justification without a version prefix. Nodes that do not check the version
+/// decode the justification directly as `GossipMessage`, so version `0` is sent until all peers
+/// accept [`LATEST_SIGNATURE_VERSION`].
+pub const ANNOUNCEMENT_SIGNATURE_VERSION: u8 = 0;
+
+/// The latest signature version of block announcement justifications this node can check.
+pub const LATEST_SIGNATURE_VERSION: u8 = 1;
+
+/// The signature versions of block announcement justifications this node can check.
+const SUPPORTED_SIGNATURE_VERSIONS: &[u8] = &[0, LATEST_SIGNATURE_VERSION];
+
+/// The first byte of a versioned justification, no `GossipMessage` variant uses this index.
+const VERSIONED_JUSTIFICATION_TAG: u8 = 0xff;
+
+/// The interval in which a justification with an unsupported signature version is warned about at
+/// most once.
+const UNSUPPORTED_VERSION_WARN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Encode the given gossip message as justification of a block announcement with the signature
+/// version [`ANNOUNCEMENT_SIGNATURE_VERSION`].
+pub fn encode_justification(gossip_message: &GossipMessage) -> Vec<u8> {
+	encode_versioned_justification(gossip_message, ANNOUNCEMENT_SIGNATURE_VERSION)
+}
+
+/// Encode the given gossip message as justification of a block announcement with the given
+/// signature `version`.
+fn encode_versioned_justification(gossip_message: &GossipMessage, version: u8) -> Vec<u8> {
+	if version == 0 {
+		return gossip_message.encode();
+	}
+
+	let mut data = vec![VERSIONED_JUSTIFICATION_TAG, version];
+	gossip_message.encode_to(&mut data);
+	data
+}
+
+/// Split the signature version from the justification `data` of a block announcement.
+///
+/// Returns the version as error if it is not supported by this node.
+fn decode_justification_version(data: &[u8]) -> Result<(u8, &[u8]), u8> {
+	let (version, data) = match data {
+		[VERSIONED_JUSTIFICATION_TAG, version, data @ ..] => (*version, data),
+		data => (0, data),
+	};
+
+	if SUPPORTED_SIGNATURE_VERSIONS.contains(&version) {
+		Ok((version, data))
+	} else {
+		Err(version)
+	}
+}
 
 /// Validate that data is a valid justification from a relay-chain validator that the block is a
 /// valid parachain-block candidate.
-/// Data encoding is just `GossipMessage`, the relay-chain validator candidate statement message is
-/// the justification.
+/// Data encoding is `GossipMessage`, the relay-chain validator candidate statement message is
+/// the justification, optionally prefixed with its signature version, see [`encode_justification`].
 ///
 /// Note: if no justification is provided the annouce is considered valid.
 pub struct JustifiedBlockAnnounceValidator<B, P> {
@@ -59,6 +125,7 @@ pub struct JustifiedBlockAnnounceValidator<B, P> {
 	polkadot_client: Arc<P>,
 	para_id: ParaId,
 	polkadot_sync_oracle: Box<dyn SyncOracle + Send>,
+	unsupported_version_warned_at: Option<Instant>,
 }
 
 impl<B, P> JustifiedBlockAnnounceValidator<B, P> {
@@ -72,6 +139,36 @@ impl<B, P> JustifiedBlockAnnounceValidator<B, P> {
 			polkadot_client,
 			para_id,
 			polkadot_sync_oracle,
+			unsupported_version_warned_at: None,
+		}
+	}
+
+	/// Report a justification with the unsupported signature `version`.
+	///
+	/// The justifications are sent by remote peers, so this warns at most once per
+	/// [`UNSUPPORTED_VERSION_WARN_INTERVAL`].
+	fn report_unsupported_version(&mut self, version: u8) {
+		let now = Instant::now();
+		let recently_warned = self
+			.unsupported_version_warned_at
+			.map_or(false, |at| now.duration_since(at) < UNSUPPORTED_VERSION_WARN_INTERVAL);
+
+		if recently_warned {
+			debug!(
+				target: "cumulus-network",
+				"Rejecting a block announcement with unsupported signature version {}",
+				version,
+			);
+		} else {
+			self.unsupported_version_warned_at = Some(now);
+			warn!(
+				target: "cumulus-network",
+				"Rejecting a block announcement with unsupported signature version {}, this node \
+				supports the versions {:?}. The announcing peer runs an incompatible version of \
+				Cumulus.",
+				version,
+				SUPPORTED_SIGNATURE_VERSIONS,
+			);
 		}
 	}
 }
@@ -84,7 +181,7 @@ where
 	fn validate(
 		&mut self,
 		header: &B::Header,
-		mut data: &[u8],
+		data: &[u8],
 	) -> Result<Validation, Box<dyn std::error::Error + Send>> {
 		if self.polkadot_sync_oracle.is_major_syncing() {
 			return Ok(Validation::Success { is_new_best: false });
@@ -127,6 +224,23 @@ where
 			});
 		}
 
+		let (version, mut data) = match decode_justification_version(data) {
+			Ok(decoded) => decoded,
+			Err(version) => {
+				self.report_unsupported_version(version);
+
+				return Err(Box::new(ClientError::BadJustification(format!(
+					"block announced justification has the unsupported signature version {}",
+					version,
+				))));
+			}
+		};
+		trace!(
+			target: "cumulus-network",
+			"validating block announcement justification with signature version {}",
+			version,
+		);
+
 		// Check data is a gossip message.
 		let gossip_message = GossipMessage::decode(&mut data).map_err(|_| {
 			Box::new(ClientError::BadJustification(
@@ -265,6 +379,37 @@ impl<B: BlockT> BlockAnnounceValidator<B> for DelayedBlockAnnounceValidator<B> {
 	}
 }
 
+/// How often the relay chain is checked for the expiry of a waiting announcement.
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// When a block announcement that waits for its candidate message is withdrawn.
+#[derive(Clone)]
+pub struct AnnouncementExpiry {
+	/// The announcement is withdrawn when its relay parent is more than this many blocks behind
+	/// the best relay chain block.
+	pub blocks: PBlockNumber,
+	/// Returns the number of the best relay chain block, if known.
+	pub relay_best: Arc<dyn Fn() -> Option<PBlockNumber> + Send + Sync>,
+}
+
+/// Resolves once the relay parent with the given number is expired.
+async fn wait_for_expiry(expiry: Option<AnnouncementExpiry>, relay_chain_number: PBlockNumber) {
+	let expiry = match expiry {
+		Some(expiry) => expiry,
+		None => return future::pending().await,
+	};
+
+	loop {
+		Delay::new(EXPIRY_CHECK_INTERVAL).await;
+
+		let expired = (expiry.relay_best)()
+			.map_or(false, |best| best.saturating_sub(relay_chain_number) > expiry.blocks);
+		if expired {
+			return;
+		}
+	}
+}
+
 /// Wait before announcing a block that a candidate message has been received for this block, then
 /// add this message as justification for the block announcement.
 ///
@@ -275,6 +420,7 @@ pub struct WaitToAnnounce<Block: BlockT> {
 	announce_block: Arc<dyn Fn(Block::Hash, Vec<u8>) + Send + Sync>,
 	collator_network: Arc<dyn CollatorNetwork>,
 	current_trigger: oneshot::Sender<()>,
+	expiry: Option<AnnouncementExpiry>,
 }
 
 impl<Block: BlockT> WaitToAnnounce<Block> {
@@ -291,20 +437,29 @@ impl<Block: BlockT> WaitToAnnounce<Block> {
 			announce_block,
 			collator_network,
 			current_trigger: tx,
+			expiry: None,
 		}
 	}
 
+	/// Withdraw announcements that wait for their candidate message as configured by `expiry`.
+	pub fn with_expiry(mut self, expiry: Option<AnnouncementExpiry>) -> Self {
+		self.expiry = expiry;
+		self
+	}
+
 	/// Wait for a candidate message for the block, then announce the block. The candidate
 	/// message will be added as justification to the block announcement.
 	pub fn wait_to_announce(
 		&mut self,
 		hash: <Block as BlockT>::Hash,
 		relay_chain_leaf: PHash,
+		relay_chain_number: PBlockNumber,
 		head_data: Vec<u8>,
 	) {
 		let (tx, rx) = oneshot::channel();
 		let announce_block = self.announce_block.clone();
 		let collator_network = self.collator_network.clone();
+		let expiry = self.expiry.clone();
 
 		self.current_trigger = tx;
 
@@ -320,8 +475,9 @@ impl<Block: BlockT> WaitToAnnounce<Block> {
 				)
 				.fuse();
 				let t2 = rx.fuse();
+				let t3 = wait_for_expiry(expiry, relay_chain_number).fuse();
 
-				pin_mut!(t1, t2);
+				pin_mut!(t1, t2, t3);
 
 				trace!(
 					target: "cumulus-network",
@@ -340,6 +496,16 @@ impl<Block: BlockT> WaitToAnnounce<Block> {
 							target: "cumulus-network",
 							"previous task that waits for announce block has been canceled",
 						);
+					},
+					_ = t3 => {
+						info!(
+							target: "cumulus-network",
+							"Withdrawing the announcement of block `{:?}`, relay parent #{} ({:?}) \
+							is expired.",
+							hash,
+							relay_chain_number,
+							relay_chain_leaf,
+						);
 					}
 				}
 			}
@@ -358,19 +524,41 @@ async fn wait_to_announce<Block: BlockT>(
 	let mut checked_statements = collator_network.checked_statements(relay_chain_leaf);
 
 	while let Some(statement) = checked_statements.next().await {
-		match &statement.statement {
-			Statement::Candidate(c) if &c.head_data.0 == head_data => {
-				let gossip_message: GossipMessage = GossipStatement {
-					relay_chain_leaf,
-					signed_statement: statement,
-				}
-				.into();
+		if is_candidate_of_collation(&statement.statement, relay_chain_leaf, head_data) {
+			let gossip_message: GossipMessage = GossipStatement {
+				relay_chain_leaf,
+				signed_statement: statement,
+			}
+			.into();
 
-				announce_block(hash, gossip_message.encode());
+			announce_block(hash, encode_justification(&gossip_message));
 
-				break;
-			}
-			_ => {}
+			break;
+		}
+	}
+}
+
+/// Returns `true` if `statement` is about the candidate of the collation with the given
+/// `head_data`, that was built on `relay_parent`.
+///
+/// A candidate with the same head data but another relay parent is rejected with an error. Its
+/// descriptor does not match the validation data the block was built with, so the relay chain
+/// would reject it and it can not justify the announcement of the block.
+fn is_candidate_of_collation(statement: &Statement, relay_parent: PHash, head_data: &[u8]) -> bool {
+	match statement {
+		Statement::Candidate(c) if c.head_data.0 == head_data && c.relay_parent == relay_parent => {
+			true
+		}
+		Statement::Candidate(c) if c.head_data.0 == head_data => {
+			error!(
+				target: "cumulus-network",
+				"The candidate of the collation built on relay parent `{:?}` has the relay parent \
+				`{:?}` in its descriptor, the block is not announced.",
+				relay_parent,
+				c.relay_parent,
+			);
+			false
 		}
+		_ => false,
 	}
 }
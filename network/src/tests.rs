@@ -170,6 +170,48 @@ fn check_relay_parent_is_head() {
 	);
 }
 
+#[test]
+fn check_sent_justification_is_decoded_by_older_nodes() {
+	let (_, client) = make_validator_and_client();
+	let (gossip_message, _) = make_gossip_message_and_header(client, H256::zero());
+
+	assert_eq!(gossip_message.encode(), encode_justification(&gossip_message));
+}
+
+#[test]
+fn check_versioned_justification_is_decoded() {
+	let (mut validator, client) = make_validator_and_client();
+	let relay_chain_leaf = H256::zero();
+	let (gossip_message, header) = make_gossip_message_and_header(client, relay_chain_leaf);
+	let data = encode_versioned_justification(&gossip_message, LATEST_SIGNATURE_VERSION);
+	let res = validator.validate(&header, data.as_slice());
+
+	assert_eq!(
+		res.unwrap(),
+		Validation::Failure,
+		"versioned justifications are validated like unversioned ones",
+	);
+}
+
+#[test]
+fn check_signature_version_is_supported() {
+	let (mut validator, client) = make_validator_and_client();
+	let relay_chain_leaf = H256::zero();
+	let (gossip_message, header) = make_gossip_message_and_header(client, relay_chain_leaf);
+	let mut data = vec![VERSIONED_JUSTIFICATION_TAG, LATEST_SIGNATURE_VERSION + 1];
+	gossip_message.encode_to(&mut data);
+	let res = validator.validate(&header, data.as_slice()).err();
+
+	assert!(
+		res.is_some(),
+		"validation should fail if the signature version is not supported"
+	);
+	assert!(matches!(
+		*res.unwrap().downcast::<ClientError>().unwrap(),
+		ClientError::BadJustification(x) if x.contains("unsupported signature version")
+	));
+}
+
 #[test]
 fn check_relay_parent_actually_exists() {
 	let (mut validator, client) = make_validator_and_client();
@@ -511,3 +553,35 @@ impl HeaderBackend<PBlock> for TestApi {
 		Ok(None)
 	}
 }
+
+#[test]
+fn candidate_must_have_the_relay_parent_of_the_collation() {
+	let header = default_header();
+	let relay_parent = H256::from_low_u64_be(1);
+	let candidate = |relay_parent| {
+		Statement::Candidate(AbridgedCandidateReceipt {
+			relay_parent,
+			head_data: header.encode().into(),
+			..AbridgedCandidateReceipt::default()
+		})
+	};
+
+	assert!(is_candidate_of_collation(
+		&candidate(relay_parent),
+		relay_parent,
+		&header.encode(),
+	));
+	assert!(
+		!is_candidate_of_collation(
+			&candidate(H256::from_low_u64_be(2)),
+			relay_parent,
+			&header.encode(),
+		),
+		"a candidate with a different relay parent must not justify the announcement",
+	);
+	assert!(!is_candidate_of_collation(
+		&candidate(relay_parent),
+		relay_parent,
+		&header.encode()[1..],
+	));
+}
@@ -36,6 +36,17 @@ use codec::{Decode, Encode};
 use sp_runtime::traits::Block as BlockT;
 
 /// Identifiers and types related to Cumulus Inherents
+///
+/// The collator only puts the inherent data together, the order of the inherent extrinsics in a
+/// block is the order of the modules in `construct_runtime!`. The runtimes of this repository
+/// declare the modules such that the inherents are always created in this order:
+///
+/// 1. the timestamp,
+/// 2. the validation function params (`VALIDATION_FUNCTION_PARAMS_IDENTIFIER`),
+/// 3. the downward messages (`DOWNWARD_MESSAGES_IDENTIFIER`).
+/// 4. the author of the block (`AUTHOR_IDENTIFIER`), if the runtime requires it.
+///
+/// The validation function executes the inherents in the order they appear in the block.
 pub mod inherents {
 	use sp_inherents::InherentIdentifier;
 
@@ -50,6 +61,11 @@ pub mod inherents {
 	/// The type of the inherent.
 	pub type ValidationFunctionParamsType =
 		crate::validation_function_params::ValidationFunctionParams;
+
+	/// Inherent identifier for the author of a block.
+	///
+	/// The data is the encoded id of the collator, its type is defined by the runtime.
+	pub const AUTHOR_IDENTIFIER: InherentIdentifier = *b"cumauthr";
 }
 
 /// Well known keys for values in the storage.
@@ -61,7 +61,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("cumulus-contracts-parachain"),
 	impl_name: create_runtime_str!("cumulus-contracts-parachain"),
 	authoring_version: 1,
-	spec_version: 4,
+	spec_version: 5,
 	impl_version: 1,
 	apis: RUNTIME_API_VERSIONS,
 	transaction_version: 1,
@@ -200,6 +200,12 @@ impl cumulus_parachain_upgrade::Trait for Runtime {
 	type OnValidationFunctionParams = ();
 }
 
+parameter_types! {
+	// Stay within the upward message queue limits of the relay chain.
+	pub const MaxUpwardMessagesPerBlock: u32 = 8;
+	pub const MaxUpwardMessagesSizePerBlock: u32 = 8 * 1024;
+}
+
 impl cumulus_message_broker::Trait for Runtime {
 	type Event = Event;
 	type DownwardMessageHandlers = TokenDealer;
@@ -207,6 +213,8 @@ impl cumulus_message_broker::Trait for Runtime {
 	type ParachainId = ParachainInfo;
 	type XCMPMessage = cumulus_token_dealer::XCMPMessage<AccountId, Balance>;
 	type XCMPMessageHandlers = TokenDealer;
+	type MaxUpwardMessagesPerBlock = MaxUpwardMessagesPerBlock;
+	type MaxUpwardMessagesSizePerBlock = MaxUpwardMessagesSizePerBlock;
 }
 
 impl cumulus_token_dealer::Trait for Runtime {
@@ -247,6 +255,8 @@ impl cumulus_pallet_contracts::Trait for Runtime {
 	type WeightPrice = pallet_transaction_payment::Module<Self>;
 }
 
+// The inherents are created in the order of the modules. Keep `Timestamp` before
+// `ParachainUpgrade` before `MessageBroker`, see `cumulus_primitives::inherents`.
 construct_runtime! {
 	pub enum Runtime where
 		Block = Block,
@@ -260,7 +270,7 @@ construct_runtime! {
 		Sudo: pallet_sudo::{Module, Call, Storage, Config<T>, Event<T>},
 		RandomnessCollectiveFlip: pallet_randomness_collective_flip::{Module, Call, Storage},
 		ParachainUpgrade: cumulus_parachain_upgrade::{Module, Call, Storage, Inherent, Event},
-		MessageBroker: cumulus_message_broker::{Module, Call, Inherent, Event<T>},
+		MessageBroker: cumulus_message_broker::{Module, Call, Storage, Inherent, Event<T>},
 		TokenDealer: cumulus_token_dealer::{Module, Call, Event<T>},
 		TransactionPayment: pallet_transaction_payment::{Module, Storage},
 		ParachainInfo: parachain_info::{Module, Storage, Config},
@@ -409,3 +419,104 @@ impl_runtime_apis! {
 }
 
 cumulus_runtime::register_validate_block!(Block, Executive);
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Decode;
+	use cumulus_primitives::{
+		inherents::{
+			DownwardMessagesType, DOWNWARD_MESSAGES_IDENTIFIER,
+			VALIDATION_FUNCTION_PARAMS_IDENTIFIER,
+		},
+		validation_function_params::ValidationFunctionParams,
+		DownwardMessage,
+	};
+	use cumulus_runtime::test_helpers::{build_block_with_proof, validate_block};
+	use sp_inherents::InherentData;
+	use sp_keyring::Sr25519Keyring;
+	use sp_runtime::traits::Header as HeaderT;
+
+	fn inherent_data() -> InherentData {
+		let downward_messages: DownwardMessagesType = vec![DownwardMessage::TransferInto(
+			Sr25519Keyring::Bob.to_account_id(),
+			1_000,
+			[0; 32],
+		)];
+
+		let mut inherent_data = InherentData::new();
+		inherent_data
+			.put_data(sp_timestamp::INHERENT_IDENTIFIER, &SLOT_DURATION)
+			.unwrap();
+		inherent_data
+			.put_data(
+				VALIDATION_FUNCTION_PARAMS_IDENTIFIER,
+				&ValidationFunctionParams {
+					max_code_size: 1024,
+					relay_chain_height: 1,
+					code_upgrade_allowed: None,
+				},
+			)
+			.unwrap();
+		inherent_data
+			.put_data(DOWNWARD_MESSAGES_IDENTIFIER, &downward_messages)
+			.unwrap();
+		inherent_data
+	}
+
+	#[test]
+	fn inherents_are_created_in_a_fixed_order() {
+		let calls = inherent_data()
+			.create_extrinsics()
+			.into_iter()
+			.map(|xt| xt.function)
+			.collect::<Vec<_>>();
+
+		assert!(matches!(
+			&calls[..],
+			[
+				Call::Timestamp(_),
+				Call::ParachainUpgrade(_),
+				Call::MessageBroker(_),
+			]
+		));
+	}
+
+	/// Build a block with the inherents on top of the block `parent_hash`, like the collator does.
+	fn build_block(parent_hash: Hash) -> Block {
+		Executive::initialize_block(&Header::new(
+			1,
+			Default::default(),
+			Default::default(),
+			parent_hash,
+			Default::default(),
+		));
+
+		let extrinsics = inherent_data().create_extrinsics();
+		for xt in &extrinsics {
+			Executive::apply_extrinsic(xt.clone()).unwrap().unwrap();
+		}
+
+		Block::new(Executive::finalize_block(), extrinsics)
+	}
+
+	#[test]
+	fn block_with_inherents_is_executed_with_the_same_commitments() {
+		let storage = GenesisConfig::default().build_storage().unwrap();
+		let (parent_head, block, storage_proof) = build_block_with_proof(storage, build_block);
+		let header = block.header().clone();
+
+		// This panics if the inherents are executed differently by the validation function, as the
+		// extrinsics or the state root would not match the header.
+		let wasm = WASM_BINARY.expect("You need to build the WASM binaries to run the tests!");
+		let result = validate_block(wasm, &parent_head, block, storage_proof);
+
+		assert_eq!(
+			header,
+			Header::decode(&mut &result.head_data.0[..]).expect("Decode `Header`.")
+		);
+		assert_eq!(1, result.processed_downward_messages);
+		assert!(result.upward_messages.is_empty());
+		assert!(result.new_validation_code.is_none());
+	}
+}
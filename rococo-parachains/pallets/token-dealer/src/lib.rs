@@ -17,9 +17,10 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
 use frame_support::{
-	decl_event, decl_module,
+	decl_error, decl_event, decl_module,
 	dispatch::DispatchResult,
 	traits::{Currency, ExistenceRequirement, WithdrawReason},
+	transactional,
 };
 use frame_system::ensure_signed;
 
@@ -73,11 +74,22 @@ decl_event! {
 	}
 }
 
+decl_error! {
+	pub enum Error for Module<T: Trait> {
+		/// The message to the relay chain could not be sent, e.g. because too many upward
+		/// messages are pending.
+		FailedToSend,
+	}
+}
+
 decl_module! {
 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
 		/// Transfer `amount` of tokens on the relay chain from the Parachain account to
 		/// the given `dest` account.
 		#[weight = 10]
+		#[transactional]
 		fn transfer_tokens_to_relay_chain(origin, dest: T::AccountId, amount: BalanceOf<T>) {
 			let who = ensure_signed(origin)?;
 
@@ -90,13 +102,14 @@ decl_module! {
 
 			let msg = <T as Trait>::UpwardMessage::transfer(dest.clone(), amount.clone());
 			<T as Trait>::UpwardMessageSender::send_upward_message(&msg, UpwardMessageOrigin::Signed)
-				.expect("Should not fail; qed");
+				.map_err(|_| Error::<T>::FailedToSend)?;
 
 			Self::deposit_event(Event::<T>::TransferredTokensToRelayChain(dest, amount));
 		}
 
 		/// Transfer `amount` of tokens to another parachain.
 		#[weight = 10]
+		#[transactional]
 		fn transfer_tokens_to_parachain_chain(
 			origin,
 			para_id: u32,
@@ -116,7 +129,7 @@ decl_module! {
 			T::XCMPMessageSender::send_xcmp_message(
 				para_id.into(),
 				&XCMPMessage::TransferToken(dest, amount),
-			).expect("Should not fail; qed");
+			).map_err(|_| Error::<T>::FailedToSend)?;
 		}
 
 		fn deposit_event() = default;
@@ -63,7 +63,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	spec_name: create_runtime_str!("cumulus-test-parachain"),
 	impl_name: create_runtime_str!("cumulus-test-parachain"),
 	authoring_version: 1,
-	spec_version: 3,
+	spec_version: 5,
 	impl_version: 1,
 	apis: RUNTIME_API_VERSIONS,
 	transaction_version: 1,
@@ -202,6 +202,12 @@ impl cumulus_parachain_upgrade::Trait for Runtime {
 	type OnValidationFunctionParams = ();
 }
 
+parameter_types! {
+	// Stay within the upward message queue limits of the relay chain.
+	pub const MaxUpwardMessagesPerBlock: u32 = 8;
+	pub const MaxUpwardMessagesSizePerBlock: u32 = 8 * 1024;
+}
+
 impl cumulus_message_broker::Trait for Runtime {
 	type Event = Event;
 	type DownwardMessageHandlers = TokenDealer;
@@ -209,6 +215,8 @@ impl cumulus_message_broker::Trait for Runtime {
 	type ParachainId = ParachainInfo;
 	type XCMPMessage = cumulus_token_dealer::XCMPMessage<AccountId, Balance>;
 	type XCMPMessageHandlers = TokenDealer;
+	type MaxUpwardMessagesPerBlock = MaxUpwardMessagesPerBlock;
+	type MaxUpwardMessagesSizePerBlock = MaxUpwardMessagesSizePerBlock;
 }
 
 impl cumulus_token_dealer::Trait for Runtime {
@@ -221,6 +229,13 @@ impl cumulus_token_dealer::Trait for Runtime {
 
 impl parachain_info::Trait for Runtime {}
 
+impl cumulus_author_inherent::Trait for Runtime {
+	type AuthorId = AccountId;
+}
+
+// The inherents are created in the order of the modules. Keep `Timestamp` before
+// `ParachainUpgrade` before `MessageBroker` before `AuthorInherent`, see
+// `cumulus_primitives::inherents`.
 construct_runtime! {
 	pub enum Runtime where
 		Block = Block,
@@ -233,7 +248,8 @@ construct_runtime! {
 		Sudo: pallet_sudo::{Module, Call, Storage, Config<T>, Event<T>},
 		RandomnessCollectiveFlip: pallet_randomness_collective_flip::{Module, Call, Storage},
 		ParachainUpgrade: cumulus_parachain_upgrade::{Module, Call, Storage, Inherent, Event},
-		MessageBroker: cumulus_message_broker::{Module, Call, Inherent, Event<T>},
+		MessageBroker: cumulus_message_broker::{Module, Call, Storage, Inherent, Event<T>},
+		AuthorInherent: cumulus_author_inherent::{Module, Call, Storage, Inherent},
 		TokenDealer: cumulus_token_dealer::{Module, Call, Event<T>},
 		TransactionPayment: pallet_transaction_payment::{Module, Storage},
 		ParachainInfo: parachain_info::{Module, Storage, Config},
@@ -346,3 +362,239 @@ impl_runtime_apis! {
 }
 
 cumulus_runtime::register_validate_block!(Block, Executive);
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::{Decode, Encode};
+	use cumulus_primitives::{
+		inherents::{
+			DownwardMessagesType, AUTHOR_IDENTIFIER, DOWNWARD_MESSAGES_IDENTIFIER,
+			VALIDATION_FUNCTION_PARAMS_IDENTIFIER,
+		},
+		validation_function_params::ValidationFunctionParams,
+		DownwardMessage,
+	};
+	use cumulus_runtime::test_helpers::{build_block_with_proof, validate_block};
+	use sp_inherents::InherentData;
+	use sp_keyring::Sr25519Keyring;
+	use sp_runtime::{
+		generic::{Era, SignedPayload},
+		traits::Header as HeaderT,
+	};
+
+	fn inherent_data() -> InherentData {
+		let downward_messages: DownwardMessagesType = vec![DownwardMessage::TransferInto(
+			Sr25519Keyring::Bob.to_account_id(),
+			1_000,
+			[0; 32],
+		)];
+
+		let mut inherent_data = InherentData::new();
+		inherent_data
+			.put_data(sp_timestamp::INHERENT_IDENTIFIER, &SLOT_DURATION)
+			.unwrap();
+		inherent_data
+			.put_data(
+				VALIDATION_FUNCTION_PARAMS_IDENTIFIER,
+				&ValidationFunctionParams {
+					max_code_size: 1024,
+					relay_chain_height: 1,
+					code_upgrade_allowed: None,
+				},
+			)
+			.unwrap();
+		inherent_data
+			.put_data(DOWNWARD_MESSAGES_IDENTIFIER, &downward_messages)
+			.unwrap();
+		inherent_data
+			.put_data(AUTHOR_IDENTIFIER, &Sr25519Keyring::Alice.to_account_id())
+			.unwrap();
+		inherent_data
+	}
+
+	#[test]
+	fn inherents_are_created_in_a_fixed_order() {
+		let calls = inherent_data()
+			.create_extrinsics()
+			.into_iter()
+			.map(|xt| xt.function)
+			.collect::<Vec<_>>();
+
+		assert!(matches!(
+			&calls[..],
+			[
+				Call::Timestamp(_),
+				Call::ParachainUpgrade(_),
+				Call::MessageBroker(_),
+				Call::AuthorInherent(_),
+			]
+		));
+	}
+
+	/// Build a block with the inherents and the `transactions` on top of the block `parent_hash`,
+	/// like the collator does.
+	///
+	/// `transactions` is called once the block is initialized, as the signatures commit to the
+	/// genesis hash that `initialize_block` stores.
+	fn build_block(
+		parent_hash: Hash,
+		transactions: impl FnOnce() -> Vec<UncheckedExtrinsic>,
+	) -> Block {
+		Executive::initialize_block(&Header::new(
+			1,
+			Default::default(),
+			Default::default(),
+			parent_hash,
+			Default::default(),
+		));
+
+		let mut extrinsics = inherent_data().create_extrinsics();
+		extrinsics.extend(transactions());
+		for xt in &extrinsics {
+			Executive::apply_extrinsic(xt.clone()).unwrap().unwrap();
+		}
+
+		Block::new(Executive::finalize_block(), extrinsics)
+	}
+
+	#[test]
+	fn block_with_inherents_is_executed_with_the_same_commitments() {
+		let storage = GenesisConfig::default().build_storage().unwrap();
+		let (parent_head, block, storage_proof) =
+			build_block_with_proof(storage, |parent_hash| {
+				let block = build_block(parent_hash, Vec::new);
+				assert_eq!(Some(Sr25519Keyring::Alice.to_account_id()), AuthorInherent::author());
+
+				block
+			});
+		let header = block.header().clone();
+
+		// This panics if the inherents are executed differently by the validation function, as the
+		// extrinsics or the state root would not match the header.
+		let wasm = WASM_BINARY.expect("You need to build the WASM binaries to run the tests!");
+		let result = validate_block(wasm, &parent_head, block, storage_proof);
+
+		assert_eq!(
+			header,
+			Header::decode(&mut &result.head_data.0[..]).expect("Decode `Header`.")
+		);
+		assert_eq!(1, result.processed_downward_messages);
+		assert!(result.upward_messages.is_empty());
+		assert!(result.new_validation_code.is_none());
+	}
+
+	#[test]
+	fn block_with_excess_upward_messages_stays_valid() {
+		let storage = GenesisConfig {
+			pallet_balances: Some(BalancesConfig {
+				balances: vec![(Sr25519Keyring::Alice.to_account_id(), 1 << 60)],
+			}),
+			..Default::default()
+		}
+		.build_storage()
+		.unwrap();
+		let max_messages = MaxUpwardMessagesPerBlock::get();
+
+		// Every transfer sends an upward message, one more than a block may send.
+		let transfers = || {
+			(0..=max_messages)
+				.map(|nonce| {
+					let function = Call::TokenDealer(
+						cumulus_token_dealer::Call::transfer_tokens_to_relay_chain(
+							Sr25519Keyring::Bob.to_account_id(),
+							1_000,
+						),
+					);
+
+					sign(Sr25519Keyring::Alice, nonce, 0, function)
+				})
+				.collect()
+		};
+		let (parent_head, block, storage_proof) =
+			build_block_with_proof(storage, |parent_hash| build_block(parent_hash, transfers));
+		let header = block.header().clone();
+
+		let wasm = WASM_BINARY.expect("You need to build the WASM binaries to run the tests!");
+		let result = validate_block(wasm, &parent_head, block, storage_proof);
+
+		assert_eq!(
+			header,
+			Header::decode(&mut &result.head_data.0[..]).expect("Decode `Header`.")
+		);
+		assert_eq!(max_messages as usize, result.upward_messages.len());
+	}
+
+	/// The `function` signed by `from` with the given `nonce` and `tip`.
+	fn sign(
+		from: Sr25519Keyring,
+		nonce: Index,
+		tip: Balance,
+		function: Call,
+	) -> UncheckedExtrinsic {
+		let genesis_hash = System::block_hash(0);
+		let extra: SignedExtra = (
+			frame_system::CheckSpecVersion::new(),
+			frame_system::CheckGenesis::new(),
+			frame_system::CheckEra::from(Era::Immortal),
+			frame_system::CheckNonce::from(nonce),
+			frame_system::CheckWeight::new(),
+			pallet_transaction_payment::ChargeTransactionPayment::from(tip),
+		);
+		let payload = SignedPayload::from_raw(
+			function.clone(),
+			extra.clone(),
+			(VERSION.spec_version, genesis_hash, genesis_hash, (), (), ()),
+		);
+		let signature = payload.using_encoded(|payload| from.sign(payload));
+
+		UncheckedExtrinsic::new_signed(function, from.to_account_id(), signature.into(), extra)
+	}
+
+	/// A balance transfer signed by `from` that pays the given `tip`.
+	fn transfer_with_tip(from: Sr25519Keyring, tip: Balance) -> UncheckedExtrinsic {
+		let function = Call::Balances(BalancesCall::transfer(
+			Sr25519Keyring::Ferdie.to_account_id(),
+			1,
+		));
+
+		sign(from, 0, tip, function)
+	}
+
+	#[test]
+	fn higher_tips_give_a_higher_priority() {
+		let senders = [
+			(Sr25519Keyring::Alice, 0),
+			(Sr25519Keyring::Bob, 1_000),
+			(Sr25519Keyring::Charlie, 10),
+		];
+		let storage = GenesisConfig {
+			pallet_balances: Some(BalancesConfig {
+				balances: senders
+					.iter()
+					.map(|(sender, _)| (sender.to_account_id(), 1 << 60))
+					.collect(),
+			}),
+			..Default::default()
+		}
+		.build_storage()
+		.unwrap();
+
+		sp_io::TestExternalities::new(storage).execute_with(|| {
+			let priority = |(sender, tip): &(Sr25519Keyring, Balance)| {
+				Executive::validate_transaction(
+					TransactionSource::External,
+					transfer_with_tip(*sender, *tip),
+				)
+				.unwrap()
+				.priority
+			};
+			let [no_tip, high_tip, low_tip] = senders;
+
+			// The transaction pool returns the ready transactions by descending priority, so the
+			// transfers with the higher tips are the first to be put into a block.
+			assert!(priority(&high_tip) > priority(&low_tip));
+			assert!(priority(&low_tip) > priority(&no_tip));
+		});
+	}
+}
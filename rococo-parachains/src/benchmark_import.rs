@@ -0,0 +1,187 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Benchmark of the import of exported blocks.
+//!
+//! The blocks are imported through the import queue of the collator, the same way blocks are
+//! imported while syncing. The import time of a block is the time between its import notification
+//! and the notification of the previous block, so it includes decoding the block from the file.
+
+use crate::cli::BenchmarkImportCommand;
+
+use rococo_parachain_primitives::Block;
+use sc_cli::Result;
+use sc_client_api::{BlockchainEvents, UsageProvider};
+use sp_blockchain::HeaderBackend;
+use sp_consensus::import_queue::ImportQueue;
+
+use futures::{pin_mut, select, FutureExt, StreamExt};
+use log::info;
+
+use std::{
+	fmt,
+	fs::File,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+/// The blocks per second and the distribution of the import times of a benchmark.
+#[derive(Debug, PartialEq)]
+struct ImportReport {
+	/// The number of imported blocks.
+	blocks: usize,
+	/// The time it took to import all blocks.
+	total: Duration,
+	/// The import time of every block, sorted ascending.
+	import_times: Vec<Duration>,
+}
+
+impl ImportReport {
+	/// Create the report of an import that started at `started`, the blocks were imported at
+	/// `imported_at`.
+	fn new(started: Instant, imported_at: &[Instant]) -> Self {
+		let mut previous = started;
+		let mut import_times = imported_at
+			.iter()
+			.map(|at| {
+				let time = at.duration_since(previous);
+				previous = *at;
+				time
+			})
+			.collect::<Vec<_>>();
+		import_times.sort();
+
+		Self {
+			blocks: import_times.len(),
+			total: previous.duration_since(started),
+			import_times,
+		}
+	}
+
+	/// The import time that `percent` percent of the blocks did not exceed.
+	fn percentile(&self, percent: usize) -> Duration {
+		if self.import_times.is_empty() {
+			return Duration::default();
+		}
+
+		self.import_times[(self.import_times.len() - 1) * percent / 100]
+	}
+
+	fn blocks_per_second(&self) -> f64 {
+		if self.total == Duration::default() {
+			return 0.0;
+		}
+
+		self.blocks as f64 / self.total.as_secs_f64()
+	}
+}
+
+impl fmt::Display for ImportReport {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"Imported {} blocks in {:?} ({:.2} blocks/s), import time per block: min {:?}, \
+			median {:?}, 90% {:?}, 99% {:?}, max {:?}",
+			self.blocks,
+			self.total,
+			self.blocks_per_second(),
+			self.percentile(0),
+			self.percentile(50),
+			self.percentile(90),
+			self.percentile(99),
+			self.percentile(100),
+		)
+	}
+}
+
+/// Import the blocks given by `params` with the given `import_queue` into `client` and report
+/// the blocks per second and the distribution of the import times.
+pub async fn benchmark_import<C, IQ>(
+	client: Arc<C>,
+	import_queue: IQ,
+	params: &BenchmarkImportCommand,
+) -> Result<()>
+where
+	C: BlockchainEvents<Block>
+		+ HeaderBackend<Block>
+		+ UsageProvider<Block>
+		+ Send
+		+ Sync
+		+ 'static,
+	IQ: ImportQueue<Block> + 'static,
+{
+	let file = File::open(&params.blocks)
+		.map_err(|e| format!("Failed to open `{}`: {}", params.blocks.display(), e))?;
+
+	let mut imported = client.import_notification_stream().fuse();
+	let import =
+		sc_service::chain_ops::import_blocks(client, import_queue, file, false, params.binary)
+			.fuse();
+	pin_mut!(import);
+
+	let started = Instant::now();
+	let mut imported_at = Vec::new();
+
+	loop {
+		select! {
+			res = import => {
+				res?;
+				break;
+			},
+			notification = imported.next() => if notification.is_some() {
+				imported_at.push(Instant::now());
+			},
+		}
+	}
+
+	// Notifications of the last blocks may not have been received when the import finished.
+	while let Some(Some(_)) = imported.next().now_or_never() {
+		imported_at.push(Instant::now());
+	}
+
+	info!("{}", ImportReport::new(started, &imported_at));
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn import_times_are_the_time_between_imports() {
+		let started = Instant::now();
+		let imported_at = [10, 30, 35, 75]
+			.iter()
+			.map(|ms| started + Duration::from_millis(*ms))
+			.collect::<Vec<_>>();
+
+		let report = ImportReport::new(started, &imported_at);
+
+		assert_eq!(4, report.blocks);
+		assert_eq!(Duration::from_millis(75), report.total);
+		assert_eq!(
+			vec![5, 10, 20, 40]
+				.into_iter()
+				.map(Duration::from_millis)
+				.collect::<Vec<_>>(),
+			report.import_times,
+		);
+		assert_eq!(Duration::from_millis(5), report.percentile(0));
+		assert_eq!(Duration::from_millis(10), report.percentile(50));
+		assert_eq!(Duration::from_millis(40), report.percentile(100));
+	}
+}
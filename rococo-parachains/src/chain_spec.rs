@@ -20,8 +20,10 @@ use rococo_parachain_primitives::{AccountId, Signature};
 use sc_chain_spec::{ChainSpecExtension, ChainSpecGroup};
 use sc_service::ChainType;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use sp_core::{sr25519, Pair, Public};
 use sp_runtime::traits::{IdentifyAccount, Verify};
+use std::{path::Path, sync::Arc};
 
 /// Specialized `ChainSpec` for the normal parachain runtime.
 pub type ChainSpec = sc_service::GenericChainSpec<parachain_runtime::GenesisConfig, Extensions>;
@@ -64,30 +66,97 @@ where
 	AccountPublic::from(get_from_seed::<TPublic>(seed)).into_account()
 }
 
+/// Populates the genesis config of a [`ChainSpec`] programmatically.
+///
+/// The builder is called every time the genesis storage is built, e.g. by
+/// `export-genesis-state`, so it needs to return the same genesis every time.
+pub trait GenesisBuilder: Send + Sync {
+	/// Modify the given static `genesis` config.
+	fn build(&self, genesis: &mut parachain_runtime::GenesisConfig);
+}
+
+/// Keeps the static genesis config.
+impl GenesisBuilder for () {
+	fn build(&self, _: &mut parachain_runtime::GenesisConfig) {}
+}
+
+/// Overrides fields of the genesis config with the fields of a JSON object.
+///
+/// Objects are merged recursively, every other value replaces the value in the genesis config.
+pub struct JsonGenesisOverride {
+	overrides: Value,
+}
+
+impl JsonGenesisOverride {
+	/// Read the overrides from the JSON file at `path`.
+	///
+	/// Returns an error if the overrides contain unknown or invalid fields for the genesis config
+	/// of the local testnet of parachain `id`.
+	pub fn from_file(path: &Path, id: ParaId) -> Result<Self, String> {
+		let overrides = std::fs::read(path)
+			.map_err(|e| format!("Failed to read `{}`: {}", path.display(), e))
+			.and_then(|json| {
+				serde_json::from_slice(&json)
+					.map_err(|e| format!("Invalid JSON in `{}`: {}", path.display(), e))
+			})?;
+
+		let genesis_override = Self { overrides };
+		genesis_override
+			.apply(local_testnet_genesis(id))
+			.map_err(|e| format!("Invalid genesis config in `{}`: {}", path.display(), e))?;
+
+		Ok(genesis_override)
+	}
+
+	/// Apply the overrides to the given `genesis` config.
+	fn apply(
+		&self,
+		genesis: parachain_runtime::GenesisConfig,
+	) -> Result<parachain_runtime::GenesisConfig, String> {
+		let mut genesis = serde_json::to_value(genesis).map_err(|e| e.to_string())?;
+		merge_json(&mut genesis, &self.overrides);
+
+		serde_json::from_value(genesis).map_err(|e| e.to_string())
+	}
+}
+
+impl GenesisBuilder for JsonGenesisOverride {
+	fn build(&self, genesis: &mut parachain_runtime::GenesisConfig) {
+		*genesis = self
+			.apply(std::mem::take(genesis))
+			.expect("The overrides are checked against the genesis config when reading them; qed");
+	}
+}
+
+/// Merge `overrides` into `base`.
+fn merge_json(base: &mut Value, overrides: &Value) {
+	match (base, overrides) {
+		(Value::Object(base), Value::Object(overrides)) => {
+			for (key, value) in overrides {
+				merge_json(base.entry(key.clone()).or_insert(Value::Null), value);
+			}
+		}
+		(base, overrides) => *base = overrides.clone(),
+	}
+}
+
 pub fn get_chain_spec(id: ParaId) -> ChainSpec {
+	get_chain_spec_with_genesis_builder(id, Arc::new(()))
+}
+
+/// Same as [`get_chain_spec`], but the genesis config is passed through the given `builder`.
+pub fn get_chain_spec_with_genesis_builder(
+	id: ParaId,
+	builder: Arc<dyn GenesisBuilder>,
+) -> ChainSpec {
 	ChainSpec::from_genesis(
 		"Local Testnet",
 		"local_testnet",
 		ChainType::Local,
 		move || {
-			testnet_genesis(
-				get_account_id_from_seed::<sr25519::Public>("Alice"),
-				vec![
-					get_account_id_from_seed::<sr25519::Public>("Alice"),
-					get_account_id_from_seed::<sr25519::Public>("Bob"),
-					get_account_id_from_seed::<sr25519::Public>("Charlie"),
-					get_account_id_from_seed::<sr25519::Public>("Dave"),
-					get_account_id_from_seed::<sr25519::Public>("Eve"),
-					get_account_id_from_seed::<sr25519::Public>("Ferdie"),
-					get_account_id_from_seed::<sr25519::Public>("Alice//stash"),
-					get_account_id_from_seed::<sr25519::Public>("Bob//stash"),
-					get_account_id_from_seed::<sr25519::Public>("Charlie//stash"),
-					get_account_id_from_seed::<sr25519::Public>("Dave//stash"),
-					get_account_id_from_seed::<sr25519::Public>("Eve//stash"),
-					get_account_id_from_seed::<sr25519::Public>("Ferdie//stash"),
-				],
-				id,
-			)
+			let mut genesis = local_testnet_genesis(id);
+			builder.build(&mut genesis);
+			genesis
 		},
 		vec![],
 		None,
@@ -161,6 +230,28 @@ pub fn staging_test_net(id: ParaId) -> ChainSpec {
 	)
 }
 
+/// The genesis config of the local testnet of parachain `id`.
+fn local_testnet_genesis(id: ParaId) -> parachain_runtime::GenesisConfig {
+	testnet_genesis(
+		get_account_id_from_seed::<sr25519::Public>("Alice"),
+		vec![
+			get_account_id_from_seed::<sr25519::Public>("Alice"),
+			get_account_id_from_seed::<sr25519::Public>("Bob"),
+			get_account_id_from_seed::<sr25519::Public>("Charlie"),
+			get_account_id_from_seed::<sr25519::Public>("Dave"),
+			get_account_id_from_seed::<sr25519::Public>("Eve"),
+			get_account_id_from_seed::<sr25519::Public>("Ferdie"),
+			get_account_id_from_seed::<sr25519::Public>("Alice//stash"),
+			get_account_id_from_seed::<sr25519::Public>("Bob//stash"),
+			get_account_id_from_seed::<sr25519::Public>("Charlie//stash"),
+			get_account_id_from_seed::<sr25519::Public>("Dave//stash"),
+			get_account_id_from_seed::<sr25519::Public>("Eve//stash"),
+			get_account_id_from_seed::<sr25519::Public>("Ferdie//stash"),
+		],
+		id,
+	)
+}
+
 fn testnet_genesis(
 	root_key: AccountId,
 	endowed_accounts: Vec<AccountId>,
@@ -209,3 +300,108 @@ fn contracts_testnet_genesis(
 		cumulus_pallet_contracts: None,
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rococo_parachain_primitives::Balance;
+	use serde_json::json;
+	use sp_core::crypto::Ss58Codec;
+	use sp_runtime::{traits::Header as HeaderT, BuildStorage};
+
+	/// Funds the accounts of a CSV file with lines of `<ss58 address>,<balance>`.
+	struct CsvBalances(Vec<(AccountId, Balance)>);
+
+	impl CsvBalances {
+		fn from_file(path: &std::path::Path) -> Result<Self, String> {
+			std::fs::read_to_string(path)
+				.map_err(|e| format!("Failed to read `{}`: {}", path.display(), e))?
+				.lines()
+				.filter(|line| !line.trim().is_empty())
+				.map(|line| {
+					let mut fields = line.split(',').map(str::trim);
+					let account = fields
+						.next()
+						.and_then(|a| AccountId::from_ss58check(a).ok())
+						.ok_or_else(|| format!("Invalid account in `{}`", line))?;
+					let balance = fields
+						.next()
+						.and_then(|b| b.parse().ok())
+						.ok_or_else(|| format!("Invalid balance in `{}`", line))?;
+
+					Ok((account, balance))
+				})
+				.collect::<Result<_, String>>()
+				.map(Self)
+		}
+	}
+
+	impl GenesisBuilder for CsvBalances {
+		fn build(&self, genesis: &mut parachain_runtime::GenesisConfig) {
+			if let Some(balances) = genesis.pallet_balances.as_mut() {
+				balances.balances.extend(self.0.iter().cloned());
+			}
+		}
+	}
+
+	#[test]
+	fn genesis_builder_is_reflected_in_genesis_state() {
+		let account = get_account_id_from_seed::<sr25519::Public>("Genesis");
+		let csv = std::env::temp_dir().join("cumulus-genesis-builder-test.csv");
+		std::fs::write(&csv, format!("{},1000000\n", account.to_ss58check())).unwrap();
+
+		let builder = CsvBalances::from_file(&csv).unwrap();
+		assert_eq!(vec![(account, 1_000_000)], builder.0);
+
+		let static_spec: Box<dyn sc_service::ChainSpec> = Box::new(get_chain_spec(100.into()));
+		let built_spec: Box<dyn sc_service::ChainSpec> = Box::new(
+			get_chain_spec_with_genesis_builder(100.into(), Arc::new(builder)),
+		);
+
+		let static_state = crate::command::generate_genesis_state(&static_spec).unwrap();
+		let built_state = crate::command::generate_genesis_state(&built_spec).unwrap();
+
+		assert_ne!(
+			static_state.header.state_root(),
+			built_state.header.state_root(),
+		);
+		assert_eq!(
+			built_spec.build_storage().unwrap().top,
+			get_chain_spec_with_genesis_builder(
+				100.into(),
+				Arc::new(CsvBalances::from_file(&csv).unwrap()),
+			)
+			.build_storage()
+			.unwrap()
+			.top,
+		);
+	}
+
+	#[test]
+	fn genesis_config_fields_are_overridden() {
+		let genesis_override = JsonGenesisOverride {
+			overrides: json!({
+				"parachainInfo": { "parachainId": 2000 },
+			}),
+		};
+		let genesis = genesis_override.apply(local_testnet_genesis(100.into())).unwrap();
+
+		assert_eq!(ParaId::from(2000), genesis.parachain_info.unwrap().parachain_id);
+		assert_eq!(
+			local_testnet_genesis(100.into()).pallet_sudo.unwrap().key,
+			genesis.pallet_sudo.unwrap().key,
+		);
+	}
+
+	#[test]
+	fn unknown_genesis_config_fields_are_rejected() {
+		let genesis_override = JsonGenesisOverride {
+			overrides: json!({
+				"parachainInfo": { "paraId": 2000 },
+			}),
+		};
+		let err = genesis_override.apply(local_testnet_genesis(100.into())).unwrap_err();
+
+		assert!(err.contains("unknown field `paraId`"), "{}", err);
+	}
+}
@@ -0,0 +1,134 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Checks whether the relay chain and the para id of a parachain chain spec are consistent.
+
+use crate::{
+	chain_spec::Extensions,
+	command::generate_genesis_block,
+	registration::{print_report, Check},
+};
+use codec::Decode;
+use cumulus_primitives::ParaId;
+use polkadot_primitives::v0::Block as PBlock;
+use sc_cli::Result;
+use sc_service::ChainSpec;
+use sp_core::{storage::Storage, twox_128};
+use sp_runtime::traits::Block as BlockT;
+
+/// The storage key of the para id in the genesis of the parachain.
+fn para_id_key() -> Vec<u8> {
+	[twox_128(b"ParachainUpgrade"), twox_128(b"ParachainId")].concat()
+}
+
+/// Check that the para id in the genesis `storage` is the `declared` one.
+fn check_para_id(declared: ParaId, storage: &Storage) -> std::result::Result<String, String> {
+	let genesis = storage
+		.top
+		.get(&para_id_key())
+		.ok_or_else(|| "the genesis does not set a para id".to_string())?;
+	let genesis = ParaId::decode(&mut &genesis[..])
+		.map_err(|e| format!("the para id in the genesis does not decode: {:?}", e))?;
+
+	if genesis == declared {
+		Ok(format!("{:?} in the extensions and the genesis", declared))
+	} else {
+		Err(format!(
+			"the extensions declare {:?}, but the genesis sets {:?}",
+			declared, genesis,
+		))
+	}
+}
+
+/// Run all checks on the parachain `chain_spec` and print a report.
+///
+/// `load_relay_spec` loads the relay chain spec the parachain spec refers to. Returns an error if
+/// any of the checks failed.
+pub fn check_spec(
+	chain_spec: &Box<dyn ChainSpec>,
+	load_relay_spec: impl Fn(&str) -> std::result::Result<Box<dyn ChainSpec>, String>,
+) -> Result<()> {
+	let extensions = match Extensions::try_get(chain_spec) {
+		Some(extensions) => extensions,
+		None => {
+			let checks = [Check {
+				name: "extensions",
+				result: Err("the chain spec declares no `relay_chain` and `para_id`".into()),
+			}];
+			return print_report(&checks, "Chain spec check failed");
+		}
+	};
+	let para_id = ParaId::from(extensions.para_id);
+
+	let mut checks = vec![Check {
+		name: "extensions",
+		result: Ok(format!("relay chain `{}` and {:?}", extensions.relay_chain, para_id)),
+	}];
+
+	checks.push(Check {
+		name: "para id",
+		result: chain_spec
+			.build_storage()
+			.map_err(|e| format!("failed to build the genesis: {}", e))
+			.and_then(|storage| check_para_id(para_id, &storage)),
+	});
+
+	match load_relay_spec(&extensions.relay_chain) {
+		Ok(relay_spec) => {
+			checks.push(Check {
+				name: "relay chain",
+				result: Ok(format!("`{}` ({})", relay_spec.name(), relay_spec.id())),
+			});
+			checks.push(Check {
+				name: "relay chain genesis",
+				result: generate_genesis_block::<PBlock>(&relay_spec)
+					.map(|block| format!("{:?}", block.header().hash()))
+					.map_err(|e| format!("failed to build the genesis: {}", e)),
+			});
+		}
+		Err(e) => checks.push(Check {
+			name: "relay chain",
+			result: Err(format!("unknown relay chain `{}`: {}", extensions.relay_chain, e)),
+		}),
+	}
+
+	print_report(&checks, "Chain spec check failed")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Encode;
+	use sp_runtime::BuildStorage;
+
+	#[test]
+	fn para_id_of_the_genesis_is_checked() {
+		let mut storage = Storage::default();
+		assert!(check_para_id(100.into(), &storage).is_err());
+
+		storage.top.insert(para_id_key(), ParaId::from(100).encode());
+		assert!(check_para_id(100.into(), &storage).is_ok());
+		assert!(check_para_id(200.into(), &storage).is_err());
+	}
+
+	#[test]
+	fn para_id_of_the_local_chain_spec_is_consistent() {
+		let chain_spec = crate::chain_spec::get_chain_spec(2000.into());
+		let storage = chain_spec.build_storage().unwrap();
+
+		assert!(check_para_id(2000.into(), &storage).is_ok());
+	}
+}
@@ -14,9 +14,13 @@
 // You should have received a copy of the GNU General Public License
 // along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
 
+use cumulus_collator::{CollateOn, ProduceEmptyBlocks};
+use cumulus_service::RelayBootstrapOnFail;
 use std::path::PathBuf;
 
 use sc_cli;
+use sc_service::config::MultiaddrWithPeerId;
+use sp_core::H256;
 use structopt::StructOpt;
 
 /// Sub-commands supported by the collator.
@@ -32,6 +36,39 @@ pub enum Subcommand {
 	/// Export the genesis wasm of the parachain.
 	#[structopt(name = "export-genesis-wasm")]
 	ExportGenesisWasm(ExportGenesisWasmCommand),
+
+	/// Export the genesis state, the genesis wasm and a summary for the registration of the
+	/// parachain into a directory.
+	#[structopt(name = "export-genesis-bundle")]
+	ExportGenesisBundle(ExportGenesisBundleCommand),
+
+	/// Export the state of the parachain at a given block.
+	#[structopt(name = "export-state")]
+	ExportState(ExportStateCommand),
+
+	/// Export the head data of the parachain at a given block.
+	#[structopt(name = "export-head")]
+	ExportHead(ExportHeadCommand),
+
+	/// Check whether the parachain can be registered on a relay chain.
+	#[structopt(name = "check-registration")]
+	CheckRegistration(CheckRegistrationCommand),
+
+	/// Check whether the relay chain and the para id of a chain spec are consistent.
+	#[structopt(name = "check-spec")]
+	CheckSpec(CheckSpecCommand),
+
+	/// Check whether the parachain is ready to produce blocks on a relay chain.
+	#[structopt(name = "doctor")]
+	Doctor(DoctorCommand),
+
+	/// Rebuild a collation and run it through the validation function, for debugging.
+	#[structopt(name = "replay-collation")]
+	ReplayCollation(ReplayCollationCommand),
+
+	/// Benchmark the import of blocks that were exported with `export-blocks`.
+	#[structopt(name = "benchmark-import")]
+	BenchmarkImport(BenchmarkImportCommand),
 }
 
 /// Command for exporting the genesis state of the parachain
@@ -48,6 +85,10 @@ pub struct ExportGenesisStateCommand {
 	/// The name of the chain for that the genesis state should be exported.
 	#[structopt(long)]
 	pub chain: Option<String>,
+
+	/// Seconds after which building the genesis state is given up.
+	#[structopt(long, default_value = "60")]
+	pub timeout: u64,
 }
 
 /// Command for exporting the genesis wasm file.
@@ -62,6 +103,161 @@ pub struct ExportGenesisWasmCommand {
 	pub chain: Option<String>,
 }
 
+/// Command for exporting everything that is needed to register the parachain.
+#[derive(Debug, StructOpt)]
+pub struct ExportGenesisBundleCommand {
+	/// Directory the bundle is written to, created if it does not exist.
+	#[structopt(long, parse(from_os_str))]
+	pub output: PathBuf,
+
+	/// Id of the parachain the bundle is for.
+	#[structopt(long, default_value = "100")]
+	pub parachain_id: u32,
+
+	/// The name of the chain for that the bundle should be exported.
+	#[structopt(long)]
+	pub chain: Option<String>,
+
+	/// Seconds after which building the genesis state is given up.
+	#[structopt(long, default_value = "60")]
+	pub timeout: u64,
+}
+
+/// Command for exporting the state of the parachain at a given block.
+#[derive(Debug, StructOpt)]
+pub struct ExportStateCommand {
+	/// Output file name or stdout if unspecified.
+	#[structopt(parse(from_os_str))]
+	pub output: Option<PathBuf>,
+
+	/// Hash of the block to export the state at. Defaults to the best block.
+	#[structopt(long)]
+	pub at: Option<String>,
+
+	/// Export the state as the raw `genesis` section of a chain spec.
+	#[structopt(long)]
+	pub raw: bool,
+
+	#[structopt(flatten)]
+	pub shared_params: sc_cli::SharedParams,
+
+	#[structopt(flatten)]
+	pub pruning_params: sc_cli::PruningParams,
+
+	#[structopt(flatten)]
+	pub database_params: sc_cli::DatabaseParams,
+}
+
+/// Command for exporting the head data of the parachain at a given block.
+#[derive(Debug, StructOpt)]
+pub struct ExportHeadCommand {
+	/// Output file name or stdout if unspecified.
+	#[structopt(parse(from_os_str))]
+	pub output: Option<PathBuf>,
+
+	/// Hash of the block to export the head data of.
+	#[structopt(long)]
+	pub at: String,
+
+	#[structopt(flatten)]
+	pub shared_params: sc_cli::SharedParams,
+
+	#[structopt(flatten)]
+	pub pruning_params: sc_cli::PruningParams,
+
+	#[structopt(flatten)]
+	pub database_params: sc_cli::DatabaseParams,
+}
+
+/// Command for replaying a collation that failed the validation.
+#[derive(Debug, StructOpt)]
+pub struct ReplayCollationCommand {
+	/// Hash of the relay chain block the collation was built on.
+	#[structopt(long)]
+	pub relay_parent: String,
+
+	/// File with the collation and the validation data it was built on, as archived by
+	/// `--pov-archive-path`.
+	#[structopt(long, parse(from_os_str))]
+	pub validation_data: PathBuf,
+
+	/// File with the transactions of the pool, as returned by the `author_pendingExtrinsics` RPC.
+	#[structopt(long, parse(from_os_str))]
+	pub pool: PathBuf,
+
+	#[structopt(flatten)]
+	pub shared_params: sc_cli::SharedParams,
+
+	#[structopt(flatten)]
+	pub import_params: sc_cli::ImportParams,
+}
+
+/// Command for benchmarking the import of exported blocks.
+#[derive(Debug, StructOpt)]
+pub struct BenchmarkImportCommand {
+	/// File with the blocks to import, as written by `export-blocks`.
+	#[structopt(long, parse(from_os_str))]
+	pub blocks: PathBuf,
+
+	/// The blocks are exported in the binary format instead of JSON.
+	#[structopt(long)]
+	pub binary: bool,
+
+	#[structopt(flatten)]
+	pub shared_params: sc_cli::SharedParams,
+
+	#[structopt(flatten)]
+	pub import_params: sc_cli::ImportParams,
+}
+
+/// Command for checking the registration of a parachain without submitting it.
+#[derive(Debug, StructOpt)]
+pub struct CheckRegistrationCommand {
+	/// HTTP RPC endpoint of a relay chain node.
+	#[structopt(long)]
+	pub relay_rpc: String,
+
+	/// Id of the parachain that should be registered.
+	#[structopt(long)]
+	pub para_id: u32,
+
+	/// File with the genesis state, as written by `export-genesis-state`.
+	#[structopt(long, parse(from_os_str))]
+	pub genesis_state: PathBuf,
+
+	/// File with the genesis wasm, as written by `export-genesis-wasm`.
+	#[structopt(long, parse(from_os_str))]
+	pub genesis_wasm: PathBuf,
+}
+
+/// Command for checking the consistency of a chain spec.
+#[derive(Debug, StructOpt)]
+pub struct CheckSpecCommand {
+	/// The chain spec to check.
+	#[structopt(long)]
+	pub chain: Option<String>,
+}
+
+/// Command for diagnosing why a parachain does not produce blocks.
+#[derive(Debug, StructOpt)]
+pub struct DoctorCommand {
+	/// HTTP RPC endpoint of a relay chain node.
+	#[structopt(long)]
+	pub relay_rpc: String,
+
+	/// The chain spec of the parachain.
+	#[structopt(long)]
+	pub chain: Option<String>,
+
+	/// Id of the parachain, defaults to the para id of the chain spec.
+	#[structopt(long)]
+	pub parachain_id: Option<u32>,
+
+	/// The secret URI of the key the collator signs its collations with.
+	#[structopt(long)]
+	pub collator_key: Option<String>,
+}
+
 #[derive(Debug, StructOpt)]
 pub struct RunCmd {
 	#[structopt(flatten)]
@@ -70,6 +266,365 @@ pub struct RunCmd {
 	/// Id of the parachain this collator collates for.
 	#[structopt(long)]
 	pub parachain_id: Option<u32>,
+
+	/// Where the embedded relay chain node stores its data.
+	///
+	/// Defaults to the `polkadot` subdirectory of `--base-path`.
+	#[structopt(long, parse(from_os_str))]
+	pub relay_base_path: Option<PathBuf>,
+
+	/// The role of the embedded relay chain node.
+	///
+	/// The embedded relay chain node never runs as a validator, a collator only needs the relay
+	/// chain data to build collations.
+	#[structopt(
+		long,
+		default_value = "full",
+		possible_values = &RelayChainRole::variants(),
+		case_insensitive = true,
+	)]
+	pub relay_chain_role: RelayChainRole,
+
+	/// The expected sha256 checksum of the chain spec, if `--chain` is a `http(s)://` URL.
+	#[structopt(long)]
+	pub chain_sha256: Option<String>,
+
+	/// The expected sha256 checksum of the relay chain spec, if the `--chain` of the relay chain
+	/// is a `http(s)://` URL.
+	#[structopt(long)]
+	pub relay_chain_sha256: Option<String>,
+
+	/// The expected genesis hash of the relay chain spec.
+	///
+	/// The relay chain specs of Polkadot, Kusama and Westend are always verified against their
+	/// known genesis hash.
+	#[structopt(long, value_name = "HASH")]
+	pub relay_chain_genesis_hash: Option<String>,
+
+	/// How often fetching the validation data from the relay chain is attempted before giving up.
+	///
+	/// Applies to following the parachain heads on the relay chain, not to the validation data
+	/// fetched by the Polkadot collator for building a collation.
+	#[structopt(long, default_value = "3")]
+	pub validation_data_fetch_attempts: u32,
+
+	/// Delay in milliseconds between two attempts to fetch the validation data.
+	#[structopt(long, default_value = "200")]
+	pub validation_data_fetch_delay: u64,
+
+	/// Number of inbound peers of the parachain network. Overrides `--in-peers`.
+	///
+	/// The peers of the relay chain network are configured by the relay chain arguments.
+	#[structopt(long)]
+	pub para_in_peers: Option<u32>,
+
+	/// Number of outgoing connections of the parachain network. Overrides `--out-peers`.
+	#[structopt(long)]
+	pub para_out_peers: Option<u32>,
+
+	/// Reserved nodes of the parachain network, in addition to `--reserved-nodes`.
+	#[structopt(long, value_name = "ADDR")]
+	pub para_reserved_nodes: Vec<MultiaddrWithPeerId>,
+
+	/// Only connect the parachain network to reserved nodes, peers that are not reserved are
+	/// rejected.
+	///
+	/// Requires at least one reserved node, set by `--para-reserved-nodes` or `--reserved-nodes`.
+	/// The relay chain network is configured by the relay chain arguments.
+	#[structopt(long)]
+	pub para_reserved_only: bool,
+
+	/// How many recently built collations are kept in memory, at most 4096.
+	#[structopt(long, default_value = "32")]
+	pub keep_recent_collations: usize,
+
+	/// How many recently rejected collations are kept for `cumulus_recentRejections`.
+	#[structopt(long, default_value = "32")]
+	pub keep_recent_rejections: usize,
+
+	/// Purge transactions from the transaction pool that were not included within the given
+	/// number of seconds.
+	#[structopt(long)]
+	pub tx_ttl: Option<u64>,
+
+	/// How many seconds a transaction that was purged because of `--tx-ttl` is rejected.
+	///
+	/// The transaction pool bans removed transactions for at least 30 minutes on its own.
+	#[structopt(long, default_value = "1800", requires = "tx-ttl")]
+	pub tx_ban_seconds: u64,
+
+	/// Warn if the finalized parachain block does not advance for the given number of seconds,
+	/// while new blocks are imported.
+	#[structopt(long, default_value = "120")]
+	pub finality_stall_warn_secs: u64,
+
+	/// Report a parachain block as final via `cumulus_subscribeParachainFinality` once the given
+	/// number of relay chain blocks were built on the block that included it.
+	///
+	/// By default a parachain block is reported once the relay chain finalized its inclusion.
+	#[structopt(long, value_name = "K")]
+	pub finality_confirmations: Option<u32>,
+
+	/// The number of heap pages the parachain runtime is executed with, if the runtime does not
+	/// declare them itself.
+	///
+	/// Defaults to the heap pages the relay chain validators execute the validation function
+	/// with. With a different value, blocks may be built that fail the validation.
+	#[structopt(long)]
+	pub runtime_heap_pages: Option<u64>,
+
+	/// Refuse to start if the parachain runtime requires host functions this node does not
+	/// provide.
+	///
+	/// By default the missing host functions are only logged, and block execution fails once the
+	/// runtime calls one of them.
+	#[structopt(long)]
+	pub strict_host_functions: bool,
+
+	/// The maximum number of extrinsics from the transaction pool that are put into a block,
+	/// in addition to the limits of the block weight and size.
+	#[structopt(long)]
+	pub max_extrinsics_per_block: Option<usize>,
+
+	/// The share of the maximum block length that the transactions of a proposed block may fill,
+	/// greater than 0 and at most 1.
+	///
+	/// The default leaves room for the inherents and the proof of the block within the PoV.
+	#[structopt(long, value_name = "RATIO", default_value = "0.75")]
+	pub proposal_block_fill_ratio: f64,
+
+	/// The maximum number of downward messages that are processed per block. The remaining
+	/// messages stay queued on the relay chain and are processed in the following blocks.
+	///
+	/// The runtime may process fewer messages per block than this.
+	#[structopt(long = "max-dmp-messages-per-block")]
+	pub max_downward_messages_per_block: Option<usize>,
+
+	/// The maximum number of `cumulus_subscribe*` RPC subscriptions a single connection may have.
+	///
+	/// Extrinsics watched with `author_submitAndWatchExtrinsic` count as well. New subscriptions
+	/// over the limit are rejected with an error.
+	#[structopt(long, value_name = "COUNT")]
+	pub rpc_max_subscriptions_per_connection: Option<usize>,
+
+	/// The maximum number of `cumulus_subscribe*` RPC subscriptions of all connections.
+	///
+	/// Extrinsics watched with `author_submitAndWatchExtrinsic` count as well. New subscriptions
+	/// over the limit are rejected with an error.
+	#[structopt(long, value_name = "COUNT")]
+	pub rpc_max_subscriptions: Option<usize>,
+
+	/// Which nodes announce parachain blocks to their peers.
+	///
+	/// With `collators-only` a full node does not announce the blocks it imports, so blocks are
+	/// only gossiped by the collators. The network can not select peers by their role, so a
+	/// collator still announces its blocks to all of its peers. The advertisement of collations
+	/// to the relay chain validators is not affected.
+	#[structopt(long, default_value = "all", possible_values = &GossipTo::VARIANTS)]
+	pub gossip_to: GossipTo,
+
+	/// Withdraw the announcement of a collated block that is still waiting for its candidate
+	/// message when the relay parent is more than the given number of blocks behind the best
+	/// relay chain block.
+	#[structopt(long)]
+	pub advertisement_expiry_blocks: Option<u32>,
+
+	/// Keep the state of every parachain block, so the state can be queried at any historical
+	/// block, e.g. with `state_getStorage`.
+	///
+	/// This is the same as `--pruning archive` and can not be used with a numeric `--pruning`.
+	/// The node can not collate in this mode.
+	#[structopt(long)]
+	pub archive: bool,
+
+	/// Back off once the given number of collations on the same parachain head were not
+	/// included by the relay chain.
+	///
+	/// While backing off, the collator skips an exponentially growing number of relay chain
+	/// blocks, up to 64, between two collations. The backoff is reset when the relay chain
+	/// includes a new head of the parachain. By default the collator never backs off.
+	#[structopt(long, value_name = "COUNT")]
+	pub authoring_backoff_threshold: Option<u32>,
+
+	/// Which relay chain blocks trigger a collation attempt.
+	///
+	/// `when-scheduled` only builds a collation on relay chain blocks at which the parachain is
+	/// assigned to a core, `every-block` builds one on every relay chain block.
+	#[structopt(long, default_value = "when-scheduled", possible_values = &CollateOn::VARIANTS)]
+	pub collate_on: CollateOn,
+
+	/// Build every collation on the parachain block with the given hash, instead of the head
+	/// that is included in the relay parent.
+	///
+	/// For deliberately creating parachain forks when testing. The relay chain rejects these
+	/// collations unless the block is the included head.
+	#[structopt(long, value_name = "HASH")]
+	pub collate_on_para_parent: Option<String>,
+
+	/// Seconds to hold off the first collation after startup.
+	///
+	/// The first collation is also held off until the embedded relay chain node is synced, so it
+	/// is not built on a stale relay parent.
+	#[structopt(long, value_name = "SECS")]
+	pub collation_start_delay: Option<u64>,
+
+	/// When the collator builds a block without transactions.
+	///
+	/// `always` builds a block on every collation attempt, `never` only if there are transactions
+	/// or downward messages to include and `timeout` also if no block was built within
+	/// `--empty-block-interval`.
+	#[structopt(long, default_value = "always", possible_values = &ProduceEmptyBlocks::VARIANTS)]
+	pub produce_empty_blocks: ProduceEmptyBlocks,
+
+	/// Seconds after which `--produce-empty-blocks timeout` builds an empty block.
+	#[structopt(long, value_name = "SECS", default_value = "60")]
+	pub empty_block_interval: u64,
+
+	/// Seconds the embedded relay chain node may take to connect to a relay chain peer.
+	///
+	/// If no peer is found in time, `--relay-bootstrap-on-fail` decides whether the node exits or
+	/// keeps waiting. Without this option the node waits for peers forever.
+	#[structopt(long, value_name = "SECS")]
+	pub relay_bootstrap_timeout: Option<u64>,
+
+	/// What to do if no relay chain peer is found within `--relay-bootstrap-timeout`.
+	///
+	/// `exit` stops the node, `retry` logs the failure and keeps waiting, logging it again after
+	/// every timeout.
+	#[structopt(long, default_value = "exit", possible_values = &RelayBootstrapOnFail::VARIANTS)]
+	pub relay_bootstrap_on_fail: RelayBootstrapOnFail,
+
+	/// Do not benchmark the hardware at startup.
+	///
+	/// By default a collator benchmarks the cpu, memory and disk at startup and warns if the
+	/// machine may be too slow to collate reliably.
+	#[structopt(long)]
+	pub no_hardware_benchmarks: bool,
+
+	/// Run every produced collation through the local `validate_block` before submitting it.
+	///
+	/// Collations that the relay chain validators would reject are dropped and logged, at the cost
+	/// of executing the validation function once more per collation.
+	#[structopt(long)]
+	pub self_validate_collations: bool,
+
+	/// Milliseconds after which the self validation of a collation is given up and the collation
+	/// is dropped, e.g. if the validation function does not terminate.
+	#[structopt(long, default_value = "2000", requires = "self-validate-collations")]
+	pub self_validation_timeout_ms: u64,
+
+	/// How many PoVs that passed the self validation are remembered by their hash, so the same
+	/// PoV is not validated twice. `0` disables the cache.
+	#[structopt(long, default_value = "16", requires = "self-validate-collations")]
+	pub pov_cache_size: usize,
+
+	/// Author a parachain block every given number of milliseconds, without a relay chain.
+	///
+	/// The validation data of the relay chain is mocked. The block time should not be shorter
+	/// than the minimum block period of the runtime, or the timestamp inherent will be rejected.
+	#[structopt(long, requires = "dev")]
+	pub dev_block_time: Option<u64>,
+
+	/// How parachain blocks are authored without a relay chain.
+	///
+	/// `instant` authors a block for every imported transaction, `manual` authors blocks when
+	/// `cumulus_createBlock` is called and `slot` authors a block every `--dev-block-time`.
+	#[structopt(
+		long,
+		requires = "dev",
+		possible_values = &Sealing::variants(),
+		case_insensitive = true,
+	)]
+	pub sealing: Option<Sealing>,
+
+	/// Record the relay chain heads and validation data the collator receives to the given file.
+	#[structopt(long, parse(from_os_str), value_name = "FILE", conflicts_with = "dev")]
+	pub record_relay_heads: Option<PathBuf>,
+
+	/// Write every PoV the collator produces to the given directory, for later analysis.
+	#[structopt(long, parse(from_os_str), value_name = "DIR", conflicts_with = "dev")]
+	pub pov_archive_path: Option<PathBuf>,
+
+	/// Stream the collation events to a Unix domain socket at the given path.
+	///
+	/// Every connected client receives one JSON object per line for every submitted, rejected
+	/// and included collation, in the format of the `cumulus_subscribeCollations` RPC.
+	#[structopt(long, parse(from_os_str), value_name = "PATH")]
+	pub event_socket: Option<PathBuf>,
+
+	/// The number of PoVs that are kept in `--pov-archive-path`, the oldest are removed.
+	#[structopt(long, default_value = "1000")]
+	pub pov_archive_keep: usize,
+
+	/// Author a parachain block for every relay chain head recorded with `--record-relay-heads`,
+	/// without a relay chain.
+	///
+	/// The parachain database needs to contain the blocks the heads were recorded on.
+	#[structopt(
+		long,
+		parse(from_os_str),
+		value_name = "FILE",
+		requires = "dev",
+		conflicts_with_all = &["sealing", "dev-block-time"],
+	)]
+	pub replay_relay_heads: Option<PathBuf>,
+}
+
+structopt::clap::arg_enum! {
+	/// How parachain blocks are authored without a relay chain.
+	#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+	pub enum Sealing {
+		Instant,
+		Manual,
+		Slot,
+	}
+}
+
+structopt::clap::arg_enum! {
+	/// The role of the parachain node.
+	#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+	pub enum NodeRole {
+		Collator,
+		Full,
+	}
+}
+
+structopt::clap::arg_enum! {
+	/// The role of the embedded relay chain node.
+	#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+	pub enum RelayChainRole {
+		Full,
+	}
+}
+
+/// Which peers parachain blocks are announced to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GossipTo {
+	/// Every node announces new best blocks to its peers.
+	All,
+	/// Only collators announce blocks, full nodes do not pass them on.
+	CollatorsOnly,
+}
+
+impl GossipTo {
+	/// The names of the variants, as parsed by `from_str`.
+	pub const VARIANTS: [&'static str; 2] = ["all", "collators-only"];
+}
+
+impl std::str::FromStr for GossipTo {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, String> {
+		match s {
+			"all" => Ok(Self::All),
+			"collators-only" => Ok(Self::CollatorsOnly),
+			_ => Err(format!(
+				"unknown value `{}`, expected one of: {}",
+				s,
+				Self::VARIANTS.join(", "),
+			)),
+		}
+	}
 }
 
 impl std::ops::Deref for RunCmd {
@@ -99,6 +654,32 @@ pub struct Cli {
 	#[structopt(long, conflicts_with = "validator")]
 	pub collator: bool,
 
+	/// The role of the node.
+	///
+	/// `collator` requires `--collator-key` and a para id, given by `--parachain-id` or the chain
+	/// spec. Without `--role`, the node collates if `--collator` or `--validator` is given.
+	#[structopt(
+		long,
+		possible_values = &NodeRole::variants(),
+		case_insensitive = true,
+		conflicts_with_all = &["collator", "validator", "light"],
+	)]
+	pub role: Option<NodeRole>,
+
+	/// The secret URI of the key the collator signs its collations with.
+	///
+	/// Without `--role collator`, a random key is generated if this is not given.
+	#[structopt(long)]
+	pub collator_key: Option<String>,
+
+	/// JSON file with fields that override the genesis config of the local testnet chain spec,
+	/// e.g. for `build-spec` or `export-genesis-state`.
+	///
+	/// Objects are merged into the default genesis config, unknown or invalid fields are
+	/// rejected.
+	#[structopt(long, global = true, parse(from_os_str))]
+	pub genesis_config: Option<PathBuf>,
+
 	/// Relaychain arguments
 	#[structopt(raw = true)]
 	pub relaychain_args: Vec<String>,
@@ -114,6 +695,12 @@ pub struct RelayChainCli {
 
 	/// The base path that should be used by the relay chain.
 	pub base_path: Option<PathBuf>,
+
+	/// The expected sha256 checksum of the relay chain spec, if it is loaded from a URL.
+	pub chain_sha256: Option<String>,
+
+	/// The expected genesis hash of the relay chain spec.
+	pub genesis_hash: Option<H256>,
 }
 
 impl RelayChainCli {
@@ -121,11 +708,15 @@ impl RelayChainCli {
 	pub fn new<'a>(
 		base_path: Option<PathBuf>,
 		chain_id: Option<String>,
+		chain_sha256: Option<String>,
+		genesis_hash: Option<H256>,
 		relay_chain_args: impl Iterator<Item = &'a String>,
 	) -> Self {
 		Self {
 			base_path,
 			chain_id,
+			chain_sha256,
+			genesis_hash,
 			base: polkadot_cli::RunCmd::from_iter(relay_chain_args),
 		}
 	}
@@ -15,27 +15,153 @@
 // along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::{
-	chain_spec,
-	cli::{Cli, RelayChainCli, Subcommand},
+	chain_spec, relay_genesis, remote_chain_spec,
+	cli::{
+		BenchmarkImportCommand, Cli, ExportHeadCommand, ExportStateCommand, GossipTo, NodeRole,
+		RelayChainCli, RelayChainRole, ReplayCollationCommand, RunCmd, Sealing, Subcommand,
+	},
+	service::{DevSealing, NodeOptions},
 };
 use codec::Encode;
+use cumulus_collator::{MAX_KEEP_RECENT_COLLATIONS, VALIDATION_HEAP_PAGES};
+use cumulus_consensus::FetchRetry;
 use cumulus_primitives::ParaId;
-use log::info;
+use cumulus_service::TransactionTtl;
+use log::{info, warn};
 use parachain_runtime::Block;
 use polkadot_parachain::primitives::AccountIdConversion;
+use polkadot_primitives::v0::CollatorPair;
 use sc_cli::{
-	ChainSpec, CliConfiguration, DefaultConfigurationValues, ImportParams, KeystoreParams,
-	NetworkParams, Result, RuntimeVersion, SharedParams, SubstrateCli,
+	ChainSpec, CliConfiguration, DatabaseParams, DefaultConfigurationValues, ImportParams,
+	KeystoreParams, NetworkParams, PruningParams, Result, RuntimeVersion, SharedParams,
+	SubstrateCli,
 };
-use sc_service::config::{BasePath, PrometheusConfig};
-use sp_core::hexdisplay::HexDisplay;
+use sc_network::config::NonReservedPeerMode;
+use sc_service::{
+	config::{BasePath, NetworkConfiguration, PrometheusConfig, PruningMode},
+	Role,
+};
+use sp_core::{Pair, H256};
 use sp_runtime::traits::{Block as BlockT, Hash as HashT, Header as HeaderT, Zero};
-use std::{io::Write, net::SocketAddr, sync::Arc};
+use std::{
+	io::Write,
+	net::SocketAddr,
+	num::NonZeroU32,
+	path::{Path, PathBuf},
+	sync::{
+		mpsc::{self, RecvTimeoutError},
+		Arc,
+	},
+	thread,
+	time::Duration,
+};
+
+/// The role of the node, given by `--role` or otherwise by `--collator` and `--validator`.
+///
+/// The parachain node can not run as a light client, so this fails if `--light` is given.
+fn node_role(cli: &Cli) -> std::result::Result<NodeRole, String> {
+	match cli.role {
+		Some(role) => Ok(role),
+		None if cli.run.base.light => {
+			Err("The parachain node can not run as a light client, remove `--light`".into())
+		}
+		None if cli.run.base.validator || cli.collator => Ok(NodeRole::Collator),
+		None => Ok(NodeRole::Full),
+	}
+}
+
+/// The key the node signs its collations with.
+///
+/// `--role collator` requires `--collator-key` and a para id, given by `--parachain-id` or the
+/// `chain_spec_para_id`. Otherwise a random key is generated if no `--collator-key` is given.
+fn collator_key(
+	cli: &Cli,
+	chain_spec_para_id: Option<u32>,
+) -> std::result::Result<CollatorPair, String> {
+	if cli.role == Some(NodeRole::Collator) {
+		if cli.collator_key.is_none() {
+			return Err("`--role collator` requires the key to collate with, set `--collator-key`"
+				.into());
+		}
+
+		if cli.run.parachain_id.or(chain_spec_para_id).is_none() {
+			return Err("`--role collator` requires a para id, set `--parachain-id` or use a chain \
+				spec that contains the para id"
+				.into());
+		}
+	}
+
+	match &cli.collator_key {
+		Some(suri) => CollatorPair::from_string(suri, None)
+			.map_err(|e| format!("Invalid `--collator-key`: {:?}", e)),
+		None => Ok(CollatorPair::generate().0),
+	}
+}
+
+/// The role the embedded relay chain node runs with for the `requested` role.
+///
+/// `configured` is the role that results from the relay chain arguments. The embedded relay chain
+/// node must never run as a validator, so this fails instead of silently dropping `--validator`.
+fn relay_chain_role(
+	requested: RelayChainRole,
+	configured: &Role,
+) -> std::result::Result<Role, String> {
+	match configured {
+		Role::Authority { .. } | Role::Sentry { .. } => Err(
+			"The embedded relay chain node can not run as a validator or sentry, remove \
+			`--validator` and `--sentry` from the relay chain arguments"
+				.into(),
+		),
+		_ => match requested {
+			RelayChainRole::Full => Ok(Role::Full),
+		},
+	}
+}
+
+/// The base path of the embedded relay chain node.
+///
+/// This is the given `relay_base_path` or otherwise the `polkadot` subdirectory of the parachain's
+/// `base_path`.
+fn relay_chain_base_path(
+	base_path: Option<&Path>,
+	relay_base_path: Option<PathBuf>,
+) -> Option<PathBuf> {
+	relay_base_path.or_else(|| base_path.map(|p| p.join("polkadot")))
+}
+
+/// The `--relay-chain-genesis-hash` the relay chain spec is verified against.
+fn relay_chain_genesis_hash(run: &RunCmd) -> Result<Option<H256>> {
+	run.relay_chain_genesis_hash
+		.as_deref()
+		.map(crate::export_state::parse_hash)
+		.transpose()
+}
+
+/// The base path that is used by `C` if none is given on the command line.
+fn default_base_path<C: SubstrateCli>() -> PathBuf {
+	BasePath::from_project("", "", &C::executable_name())
+		.path()
+		.to_path_buf()
+}
 
 fn load_spec(
 	id: &str,
 	para_id: ParaId,
+	genesis_config: Option<&Path>,
 ) -> std::result::Result<Box<dyn sc_service::ChainSpec>, String> {
+	if let Some(genesis_config) = genesis_config {
+		if !id.is_empty() {
+			return Err(
+				"`--genesis-config` is only supported with the local testnet chain spec".into(),
+			);
+		}
+
+		return Ok(Box::new(chain_spec::get_chain_spec_with_genesis_builder(
+			para_id,
+			Arc::new(chain_spec::JsonGenesisOverride::from_file(genesis_config, para_id)?),
+		)));
+	}
+
 	match id {
 		"staging" => Ok(Box::new(chain_spec::staging_test_net(para_id))),
 		"tick" => Ok(Box::new(chain_spec::ChainSpec::from_json_bytes(
@@ -87,7 +213,23 @@ impl SubstrateCli for Cli {
 	}
 
 	fn load_spec(&self, id: &str) -> std::result::Result<Box<dyn sc_service::ChainSpec>, String> {
-		load_spec(id, self.run.parachain_id.unwrap_or(100).into())
+		let para_id = self.run.parachain_id.unwrap_or(100).into();
+
+		if remote_chain_spec::is_url(id) {
+			let base_path = self
+				.run
+				.base
+				.shared_params
+				.base_path
+				.clone()
+				.unwrap_or_else(|| default_base_path::<Self>());
+			let path =
+				remote_chain_spec::fetch(id, &base_path, self.run.chain_sha256.as_deref())?;
+
+			load_spec(&path.to_string_lossy(), para_id, self.genesis_config.as_deref())
+		} else {
+			load_spec(id, para_id, self.genesis_config.as_deref())
+		}
 	}
 
 	fn native_runtime_version(_: &Box<dyn ChainSpec>) -> &'static RuntimeVersion {
@@ -125,8 +267,24 @@ impl SubstrateCli for RelayChainCli {
 	}
 
 	fn load_spec(&self, id: &str) -> std::result::Result<Box<dyn sc_service::ChainSpec>, String> {
-		polkadot_cli::Cli::from_iter([RelayChainCli::executable_name().to_string()].iter())
-			.load_spec(id)
+		let polkadot_cli =
+			polkadot_cli::Cli::from_iter([RelayChainCli::executable_name().to_string()].iter());
+
+		let chain_spec = if remote_chain_spec::is_url(id) {
+			let base_path = self
+				.base_path
+				.clone()
+				.unwrap_or_else(|| default_base_path::<Self>());
+			let path = remote_chain_spec::fetch(id, &base_path, self.chain_sha256.as_deref())?;
+
+			polkadot_cli.load_spec(&path.to_string_lossy())
+		} else {
+			polkadot_cli.load_spec(id)
+		}?;
+
+		relay_genesis::verify_genesis_hash(&chain_spec, self.genesis_hash)?;
+
+		Ok(chain_spec)
 	}
 
 	fn native_runtime_version(chain_spec: &Box<dyn ChainSpec>) -> &'static RuntimeVersion {
@@ -135,23 +293,30 @@ impl SubstrateCli for RelayChainCli {
 }
 
 pub fn generate_genesis_state(chain_spec: &Box<dyn sc_service::ChainSpec>) -> Result<Block> {
+	generate_genesis_block(chain_spec)
+}
+
+/// Build the genesis block of `chain_spec`, e.g. to get the genesis hash of a relay chain spec.
+pub(crate) fn generate_genesis_block<B: BlockT>(
+	chain_spec: &Box<dyn sc_service::ChainSpec>,
+) -> Result<B> {
 	let storage = chain_spec.build_storage()?;
 
 	let child_roots = storage.children_default.iter().map(|(sk, child_content)| {
-		let state_root = <<<Block as BlockT>::Header as HeaderT>::Hashing as HashT>::trie_root(
+		let state_root = <<<B as BlockT>::Header as HeaderT>::Hashing as HashT>::trie_root(
 			child_content.data.clone().into_iter().collect(),
 		);
 		(sk.clone(), state_root.encode())
 	});
-	let state_root = <<<Block as BlockT>::Header as HeaderT>::Hashing as HashT>::trie_root(
+	let state_root = <<<B as BlockT>::Header as HeaderT>::Hashing as HashT>::trie_root(
 		storage.top.clone().into_iter().chain(child_roots).collect(),
 	);
 
 	let extrinsics_root =
-		<<<Block as BlockT>::Header as HeaderT>::Hashing as HashT>::trie_root(Vec::new());
+		<<<B as BlockT>::Header as HeaderT>::Hashing as HashT>::trie_root(Vec::new());
 
-	Ok(Block::new(
-		<<Block as BlockT>::Header as HeaderT>::new(
+	Ok(B::new(
+		<<B as BlockT>::Header as HeaderT>::new(
 			Zero::zero(),
 			extrinsics_root,
 			state_root,
@@ -162,7 +327,32 @@ pub fn generate_genesis_state(chain_spec: &Box<dyn sc_service::ChainSpec>) -> Re
 	))
 }
 
-fn extract_genesis_wasm(chain_spec: &Box<dyn sc_service::ChainSpec>) -> Result<Vec<u8>> {
+/// Generate the genesis state of `chain_spec` on a separate thread, giving up after `timeout`.
+pub(crate) fn generate_genesis_state_with_timeout(
+	chain_spec: Box<dyn sc_service::ChainSpec>,
+	timeout: Duration,
+) -> Result<Block> {
+	let (sender, receiver) = mpsc::channel();
+
+	thread::spawn(move || {
+		let _ = sender.send(generate_genesis_state(&chain_spec).map_err(|e| e.to_string()));
+	});
+
+	match receiver.recv_timeout(timeout) {
+		Ok(Ok(block)) => Ok(block),
+		Ok(Err(e)) => Err(format!("Failed to build the genesis state: {}", e).into()),
+		Err(RecvTimeoutError::Timeout) => Err(format!(
+			"Building the genesis state did not finish within {} seconds",
+			timeout.as_secs(),
+		)
+		.into()),
+		Err(RecvTimeoutError::Disconnected) => {
+			Err("Building the genesis state failed unexpectedly".into())
+		}
+	}
+}
+
+pub(crate) fn extract_genesis_wasm(chain_spec: &Box<dyn sc_service::ChainSpec>) -> Result<Vec<u8>> {
 	let mut storage = chain_spec.build_storage()?;
 
 	storage
@@ -171,6 +361,232 @@ fn extract_genesis_wasm(chain_spec: &Box<dyn sc_service::ChainSpec>) -> Result<V
 		.ok_or_else(|| "Could not find wasm file in genesis state!".into())
 }
 
+/// The maximum number of runtime instances the executor supports.
+const MAX_RUNTIME_INSTANCES: usize = 256;
+
+/// Apply the `--para-*` peer options of `run` to the configuration of the parachain network.
+///
+/// Fails if `--para-reserved-only` is given without any reserved node, the node would never
+/// connect to a peer.
+fn apply_para_network_options(
+	run: &RunCmd,
+	network: &mut NetworkConfiguration,
+) -> std::result::Result<(), String> {
+	if let Some(in_peers) = run.para_in_peers {
+		network.in_peers = in_peers;
+	}
+	if let Some(out_peers) = run.para_out_peers {
+		network.out_peers = out_peers;
+	}
+	network.reserved_nodes.extend(run.para_reserved_nodes.iter().cloned());
+
+	if run.para_reserved_only {
+		if network.reserved_nodes.is_empty() {
+			return Err("`--para-reserved-only` requires at least one reserved node, set \
+				`--para-reserved-nodes`"
+				.into());
+		}
+
+		network.non_reserved_mode = NonReservedPeerMode::Deny;
+	}
+
+	Ok(())
+}
+
+/// Disable the Prometheus metrics of the `chain` if their port can not be bound.
+///
+/// The metrics server binds its port only after the node started and fails silently, so the port
+/// is checked upfront. Losing the metrics must not stop a collator, so this only logs a warning.
+fn disable_unavailable_metrics(prometheus: &mut Option<PrometheusConfig>, chain: &str) {
+	let port = match prometheus {
+		Some(config) => config.port,
+		None => return,
+	};
+
+	if let Err(err) = std::net::TcpListener::bind(port) {
+		warn!("{} metrics disabled: port {} in use ({})", chain, port.port(), err);
+		*prometheus = None;
+	}
+}
+
+/// The pruning of the parachain database if `--archive` is given, otherwise the pruning
+/// configured by `--pruning` is used.
+///
+/// An archive node only serves the historical state, so it can not run with the `role` of a
+/// collator.
+fn archive_pruning(
+	run: &RunCmd,
+	role: NodeRole,
+) -> std::result::Result<Option<PruningMode>, String> {
+	if !run.archive {
+		return Ok(None);
+	}
+
+	if role == NodeRole::Collator {
+		return Err("`--archive` can not be used by a collator".into());
+	}
+
+	match run.base.import_params.pruning_params.pruning.as_deref() {
+		None | Some("archive") => Ok(Some(PruningMode::ArchiveAll)),
+		Some(pruning) => Err(format!(
+			"`--archive` keeps the state of all blocks and can not be used with `--pruning {}`",
+			pruning,
+		)),
+	}
+}
+
+/// Collect the Cumulus specific [`NodeOptions`] from the given `run` command.
+fn node_options(run: &RunCmd) -> std::result::Result<NodeOptions, String> {
+	if run.validation_data_fetch_attempts == 0 {
+		return Err("`--validation-data-fetch-attempts` needs to be at least 1".into());
+	}
+
+	match run.base.max_runtime_instances {
+		Some(0) => return Err("`--max-runtime-instances` needs to be at least 1".into()),
+		Some(n) if n > MAX_RUNTIME_INSTANCES => {
+			return Err(format!(
+				"`--max-runtime-instances` can be at most {}",
+				MAX_RUNTIME_INSTANCES,
+			))
+		}
+		_ => {}
+	}
+
+	let block_time = run.dev_block_time.map(Duration::from_millis);
+	let dev_sealing = match (run.sealing, block_time) {
+		(Some(Sealing::Instant), None) => Some(DevSealing::Instant),
+		(Some(Sealing::Manual), None) => Some(DevSealing::Manual),
+		(Some(Sealing::Slot), Some(block_time)) | (None, Some(block_time)) => {
+			Some(DevSealing::Slot(block_time))
+		}
+		(Some(Sealing::Slot), None) => {
+			return Err("`--sealing slot` requires `--dev-block-time`".into())
+		}
+		(Some(_), Some(_)) => {
+			return Err("`--dev-block-time` can only be used with `--sealing slot`".into())
+		}
+		(None, None) => run.replay_relay_heads.clone().map(DevSealing::Replay),
+	};
+
+	let transaction_ttl = match run.tx_ttl {
+		Some(0) => return Err("`--tx-ttl` needs to be at least 1".into()),
+		Some(ttl) => Some(TransactionTtl {
+			ttl: Duration::from_secs(ttl),
+			ban: Duration::from_secs(run.tx_ban_seconds),
+		}),
+		None => None,
+	};
+
+	if run.max_extrinsics_per_block == Some(0) {
+		return Err("`--max-extrinsics-per-block` needs to be at least 1".into());
+	}
+
+	if !(run.proposal_block_fill_ratio > 0.0 && run.proposal_block_fill_ratio <= 1.0) {
+		return Err("`--proposal-block-fill-ratio` needs to be greater than 0 and at most 1".into());
+	}
+
+	if run.runtime_heap_pages == Some(0) {
+		return Err("`--runtime-heap-pages` needs to be at least 1".into());
+	}
+
+	if run.max_downward_messages_per_block == Some(0) {
+		return Err("`--max-dmp-messages-per-block` needs to be at least 1".into());
+	}
+
+	if run.rpc_max_subscriptions_per_connection == Some(0) {
+		return Err("`--rpc-max-subscriptions-per-connection` needs to be at least 1".into());
+	}
+
+	if run.rpc_max_subscriptions == Some(0) {
+		return Err("`--rpc-max-subscriptions` needs to be at least 1".into());
+	}
+
+	if run.authoring_backoff_threshold == Some(0) {
+		return Err("`--authoring-backoff-threshold` needs to be at least 1".into());
+	}
+
+	let collate_on_para_parent = match &run.collate_on_para_parent {
+		Some(hash) => {
+			let hash = crate::export_state::parse_hash(hash).map_err(|e| e.to_string())?;
+			warn!(
+				"Building every collation on the parachain block `{:?}`, the relay chain rejects \
+				them unless it is the included head",
+				hash,
+			);
+			Some(hash)
+		}
+		None => None,
+	};
+
+	if run.keep_recent_collations > MAX_KEEP_RECENT_COLLATIONS {
+		return Err(format!(
+			"`--keep-recent-collations` can be at most {}",
+			MAX_KEEP_RECENT_COLLATIONS,
+		));
+	}
+
+	if run.pov_archive_keep == 0 {
+		return Err("`--pov-archive-keep` needs to be at least 1".into());
+	}
+
+	if run.self_validation_timeout_ms == 0 {
+		return Err("`--self-validation-timeout-ms` needs to be at least 1".into());
+	}
+
+	if run.finality_stall_warn_secs == 0 {
+		return Err("`--finality-stall-warn-secs` needs to be at least 1".into());
+	}
+
+	if run.empty_block_interval == 0 {
+		return Err("`--empty-block-interval` needs to be at least 1".into());
+	}
+
+	if run.relay_bootstrap_timeout == Some(0) {
+		return Err("`--relay-bootstrap-timeout` needs to be at least 1".into());
+	}
+
+	Ok(NodeOptions {
+		fetch_retry: FetchRetry {
+			attempts: run.validation_data_fetch_attempts,
+			delay: Duration::from_millis(run.validation_data_fetch_delay),
+		},
+		dev_sealing,
+		keep_recent_collations: run.keep_recent_collations,
+		keep_recent_rejections: run.keep_recent_rejections,
+		transaction_ttl,
+		self_validate_collations: run.self_validate_collations,
+		self_validation_timeout: Duration::from_millis(run.self_validation_timeout_ms),
+		pov_cache_size: run.pov_cache_size,
+		finality_stall_warn: Duration::from_secs(run.finality_stall_warn_secs),
+		finality_confirmations: run.finality_confirmations,
+		record_relay_heads: run.record_relay_heads.clone(),
+		hardware_benchmarks: !run.no_hardware_benchmarks,
+		full_node_announces: run.gossip_to == GossipTo::All,
+		announcement_expiry_blocks: run.advertisement_expiry_blocks,
+		max_extrinsics_per_block: run.max_extrinsics_per_block,
+		proposal_block_fill_ratio: run.proposal_block_fill_ratio,
+		runtime_heap_pages: run.runtime_heap_pages.unwrap_or(VALIDATION_HEAP_PAGES),
+		strict_host_functions: run.strict_host_functions,
+		max_downward_messages_per_block: run.max_downward_messages_per_block,
+		rpc_max_subscriptions_per_connection: run.rpc_max_subscriptions_per_connection,
+		rpc_max_subscriptions: run.rpc_max_subscriptions,
+		authoring_backoff_threshold: run.authoring_backoff_threshold.and_then(NonZeroU32::new),
+		collate_on: run.collate_on,
+		collate_on_para_parent,
+		collation_start_delay: run.collation_start_delay.map(Duration::from_secs),
+		produce_empty_blocks: run.produce_empty_blocks,
+		empty_block_interval: Duration::from_secs(run.empty_block_interval),
+		pov_archive: run
+			.pov_archive_path
+			.clone()
+			.map(|dir| (dir, run.pov_archive_keep)),
+		relay_bootstrap: run
+			.relay_bootstrap_timeout
+			.map(|secs| (Duration::from_secs(secs), run.relay_bootstrap_on_fail)),
+		event_socket: run.event_socket.clone(),
+	})
+}
+
 fn use_contracts_runtime(chain_spec: &Box<dyn ChainSpec>) -> bool {
 	chain_spec.id().starts_with("trick") || chain_spec.id().starts_with("contracts")
 }
@@ -216,11 +632,17 @@ pub fn run() -> Result<()> {
 		Some(Subcommand::ExportGenesisState(params)) => {
 			sc_cli::init_logger("");
 
-			let block = generate_genesis_state(&load_spec(
+			let chain_spec = load_spec(
 				&params.chain.clone().unwrap_or_default(),
 				params.parachain_id.into(),
-			)?)?;
-			let header_hex = format!("0x{:?}", HexDisplay::from(&block.header().encode()));
+				cli.genesis_config.as_deref(),
+			)
+			.map_err(|e| format!("Failed to load the chain spec: {}", e))?;
+			let block = generate_genesis_state_with_timeout(
+				chain_spec,
+				Duration::from_secs(params.timeout),
+			)?;
+			let header_hex = crate::export_state::head_data_hex(block.header());
 
 			if let Some(output) = &params.output {
 				std::fs::write(output, header_hex)?;
@@ -235,6 +657,7 @@ pub fn run() -> Result<()> {
 
 			let wasm_file =
 				extract_genesis_wasm(&cli.load_spec(&params.chain.clone().unwrap_or_default())?)?;
+			info!("Genesis wasm: {} bytes", wasm_file.len());
 
 			if let Some(output) = &params.output {
 				std::fs::write(output, wasm_file)?;
@@ -244,20 +667,194 @@ pub fn run() -> Result<()> {
 
 			Ok(())
 		}
+		Some(Subcommand::ExportGenesisBundle(params)) => {
+			sc_cli::init_logger("");
+
+			let para_id = params.parachain_id.into();
+			let chain_spec = load_spec(
+				&params.chain.clone().unwrap_or_default(),
+				para_id,
+				cli.genesis_config.as_deref(),
+			)
+			.map_err(|e| format!("Failed to load the chain spec: {}", e))?;
+			let bundle = crate::genesis_bundle::GenesisBundle::build(
+				chain_spec,
+				para_id,
+				Duration::from_secs(params.timeout),
+			)?;
+			bundle.write(&params.output)?;
+
+			info!(
+				"Genesis bundle of parachain {:?} written to `{}`",
+				para_id,
+				params.output.display(),
+			);
+
+			Ok(())
+		}
+		Some(Subcommand::ExportState(params)) => {
+			let runner = cli.create_runner(params)?;
+
+			if use_contracts_runtime(&runner.config().chain_spec) {
+				runner.sync_run(|mut config| {
+					let partial = crate::service::new_partial::<
+						parachain_contracts_runtime::RuntimeApi,
+						crate::service::ContractsRuntimeExecutor,
+					>(&mut config)?;
+
+					crate::export_state::export_state(&*partial.client, params)
+				})
+			} else {
+				runner.sync_run(|mut config| {
+					let partial = crate::service::new_partial::<
+						parachain_runtime::RuntimeApi,
+						crate::service::RuntimeExecutor,
+					>(&mut config)?;
+
+					crate::export_state::export_state(&*partial.client, params)
+				})
+			}
+		}
+		Some(Subcommand::ExportHead(params)) => {
+			let runner = cli.create_runner(params)?;
+
+			if use_contracts_runtime(&runner.config().chain_spec) {
+				runner.sync_run(|mut config| {
+					let partial = crate::service::new_partial::<
+						parachain_contracts_runtime::RuntimeApi,
+						crate::service::ContractsRuntimeExecutor,
+					>(&mut config)?;
+
+					crate::export_state::export_head(&*partial.client, params)
+				})
+			} else {
+				runner.sync_run(|mut config| {
+					let partial = crate::service::new_partial::<
+						parachain_runtime::RuntimeApi,
+						crate::service::RuntimeExecutor,
+					>(&mut config)?;
+
+					crate::export_state::export_head(&*partial.client, params)
+				})
+			}
+		}
+		Some(Subcommand::CheckRegistration(params)) => {
+			sc_cli::init_logger("");
+
+			crate::registration::check_registration(params)
+		}
+		Some(Subcommand::CheckSpec(params)) => {
+			sc_cli::init_logger("");
+
+			let chain_spec = cli.load_spec(&params.chain.clone().unwrap_or_default())?;
+			let relay_chain_cli = RelayChainCli::new(
+				None,
+				None,
+				cli.run.relay_chain_sha256.clone(),
+				relay_chain_genesis_hash(&cli.run)?,
+				[RelayChainCli::executable_name().to_string()].iter(),
+			);
+
+			crate::check_spec::check_spec(&chain_spec, |id| relay_chain_cli.load_spec(id))
+		}
+		Some(Subcommand::Doctor(params)) => {
+			sc_cli::init_logger("");
+
+			let chain_spec = cli.load_spec(&params.chain.clone().unwrap_or_default())?;
+
+			crate::doctor::doctor(&chain_spec, params)
+		}
+		Some(Subcommand::ReplayCollation(params)) => {
+			let runner = cli.create_runner(params)?;
+
+			if use_contracts_runtime(&runner.config().chain_spec) {
+				runner.sync_run(|mut config| {
+					let wasm_execution = config.wasm_method;
+					let partial = crate::service::new_partial::<
+						parachain_contracts_runtime::RuntimeApi,
+						crate::service::ContractsRuntimeExecutor,
+					>(&mut config)?;
+
+					crate::replay_collation::replay_collation::<
+						_,
+						_,
+						parachain_contracts_runtime::UncheckedExtrinsic,
+					>(&*partial.client, wasm_execution, params)
+				})
+			} else {
+				runner.sync_run(|mut config| {
+					let wasm_execution = config.wasm_method;
+					let partial = crate::service::new_partial::<
+						parachain_runtime::RuntimeApi,
+						crate::service::RuntimeExecutor,
+					>(&mut config)?;
+
+					crate::replay_collation::replay_collation::<
+						_,
+						_,
+						parachain_runtime::UncheckedExtrinsic,
+					>(&*partial.client, wasm_execution, params)
+				})
+			}
+		}
+		Some(Subcommand::BenchmarkImport(params)) => {
+			let runner = cli.create_runner(params)?;
+
+			if use_contracts_runtime(&runner.config().chain_spec) {
+				runner.async_run(|mut config| {
+					let partial = crate::service::new_partial::<
+						parachain_contracts_runtime::RuntimeApi,
+						crate::service::ContractsRuntimeExecutor,
+					>(&mut config)?;
+					let benchmark = crate::benchmark_import::benchmark_import(
+						partial.client,
+						partial.import_queue,
+						params,
+					);
+
+					Ok((benchmark, partial.task_manager))
+				})
+			} else {
+				runner.async_run(|mut config| {
+					let partial = crate::service::new_partial::<
+						parachain_runtime::RuntimeApi,
+						crate::service::RuntimeExecutor,
+					>(&mut config)?;
+					let benchmark = crate::benchmark_import::benchmark_import(
+						partial.client,
+						partial.import_queue,
+						params,
+					);
+
+					Ok((benchmark, partial.task_manager))
+				})
+			}
+		}
 		None => {
 			let runner = cli.create_runner(&*cli.run)?;
 
-			runner.run_node_until_exit(|config| {
-				// TODO
-				let key = Arc::new(sp_core::Pair::generate().0);
+			runner.run_node_until_exit(|mut config| {
+				apply_para_network_options(&cli.run, &mut config.network)?;
 
 				let extension = chain_spec::Extensions::try_get(&config.chain_spec);
 				let relay_chain_id = extension.map(|e| e.relay_chain.clone());
 				let para_id = extension.map(|e| e.para_id);
 
+				let role = node_role(&cli)?;
+				let key = Arc::new(collator_key(&cli, para_id)?);
+				if let Some(pruning) = archive_pruning(&cli.run, role)? {
+					config.pruning = pruning;
+				}
+				disable_unavailable_metrics(&mut config.prometheus_config, "Parachain");
+
 				let polkadot_cli = RelayChainCli::new(
-					config.base_path.as_ref().map(|x| x.path().join("polkadot")),
+					relay_chain_base_path(
+						config.base_path.as_ref().map(|x| x.path()),
+						cli.run.relay_base_path.clone(),
+					),
 					relay_chain_id,
+					cli.run.relay_chain_sha256.clone(),
+					relay_chain_genesis_hash(&cli.run).map_err(|e| e.to_string())?,
 					[RelayChainCli::executable_name().to_string()]
 						.iter()
 						.chain(cli.relaychain_args.iter()),
@@ -270,21 +867,23 @@ pub fn run() -> Result<()> {
 
 				let block =
 					generate_genesis_state(&config.chain_spec).map_err(|e| format!("{:?}", e))?;
-				let genesis_state = format!("0x{:?}", HexDisplay::from(&block.header().encode()));
+				let genesis_state = crate::export_state::head_data_hex(block.header());
 
 				let task_executor = config.task_executor.clone();
-				let polkadot_config =
+				let mut polkadot_config =
 					SubstrateCli::create_configuration(&polkadot_cli, &polkadot_cli, task_executor)
 						.map_err(|err| format!("Relay chain argument error: {}", err))?;
-				let collator = cli.run.base.validator || cli.collator;
+				polkadot_config.role =
+					relay_chain_role(cli.run.relay_chain_role, &polkadot_config.role)?;
+				disable_unavailable_metrics(&mut polkadot_config.prometheus_config, "Relay chain");
+				let collator = role == NodeRole::Collator;
+				let options = node_options(&cli.run)?;
 
 				info!("Parachain id: {:?}", id);
 				info!("Parachain Account: {}", parachain_account);
 				info!("Parachain genesis state: {}", genesis_state);
-				info!(
-					"Is collating: {}",
-					if collator { "yes" } else { "no" }
-				);
+				info!("Relay chain role: {:?}", polkadot_config.role);
+				info!("Node role: {}", role);
 
 				if use_contracts_runtime(&config.chain_spec) {
 					crate::service::start_contracts_node(
@@ -293,6 +892,7 @@ pub fn run() -> Result<()> {
 						polkadot_config,
 						id,
 						collator,
+						options,
 						false,
 					)
 				} else {
@@ -302,6 +902,7 @@ pub fn run() -> Result<()> {
 						polkadot_config,
 						id,
 						collator,
+						options,
 						false,
 					)
 					.map(|r| r.0)
@@ -311,6 +912,54 @@ pub fn run() -> Result<()> {
 	}
 }
 
+impl CliConfiguration for ExportStateCommand {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		Some(&self.pruning_params)
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
+
+impl CliConfiguration for ExportHeadCommand {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		Some(&self.pruning_params)
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
+
+impl CliConfiguration for ReplayCollationCommand {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn import_params(&self) -> Option<&ImportParams> {
+		Some(&self.import_params)
+	}
+}
+
+impl CliConfiguration for BenchmarkImportCommand {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn import_params(&self) -> Option<&ImportParams> {
+		Some(&self.import_params)
+	}
+}
+
 impl DefaultConfigurationValues for RelayChainCli {
 	fn p2p_listen_port() -> u16 {
 		30334
@@ -431,3 +1080,160 @@ impl CliConfiguration<Self> for RelayChainCli {
 		self.base.base.announce_block()
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn relay_chain_data_is_stored_in_subdirectory_by_default() {
+		assert_eq!(
+			Some(PathBuf::from("/data/polkadot")),
+			relay_chain_base_path(Some(Path::new("/data")), None),
+		);
+		assert_eq!(None, relay_chain_base_path(None, None));
+	}
+
+	#[test]
+	fn relay_base_path_overrides_subdirectory() {
+		assert_eq!(
+			Some(PathBuf::from("/relay")),
+			relay_chain_base_path(Some(Path::new("/data")), Some("/relay".into())),
+		);
+	}
+
+	#[test]
+	fn metrics_are_disabled_if_the_port_is_in_use() {
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let taken = listener.local_addr().unwrap();
+
+		let mut prometheus = Some(PrometheusConfig::new_with_default_registry(taken));
+		disable_unavailable_metrics(&mut prometheus, "Parachain");
+		assert!(prometheus.is_none());
+
+		drop(listener);
+		let mut prometheus = Some(PrometheusConfig::new_with_default_registry(taken));
+		disable_unavailable_metrics(&mut prometheus, "Parachain");
+		assert_eq!(Some(taken), prometheus.map(|config| config.port));
+	}
+
+	fn cli(args: &[&str]) -> Cli {
+		Cli::from_iter(["rococo-collator"].iter().chain(args.iter()))
+	}
+
+	#[test]
+	fn reserved_only_requires_a_reserved_node() {
+		let mut network = NetworkConfiguration::new_local();
+		assert!(apply_para_network_options(&cli(&["--para-reserved-only"]).run, &mut network)
+			.is_err());
+
+		let reserved = "/ip4/127.0.0.1/tcp/30333/p2p/\
+			QmSk5HQbn6LhUwDiNMseVUjuRYhEtYj4aUZ6WfWoGURpdV";
+		let run = cli(&["--para-reserved-only", "--para-reserved-nodes", reserved]).run;
+		let mut network = NetworkConfiguration::new_local();
+		apply_para_network_options(&run, &mut network).unwrap();
+		assert_eq!(NonReservedPeerMode::Deny, network.non_reserved_mode);
+		assert_eq!(1, network.reserved_nodes.len());
+
+		let mut network = NetworkConfiguration::new_local();
+		apply_para_network_options(&cli(&[]).run, &mut network).unwrap();
+		assert_eq!(NonReservedPeerMode::Accept, network.non_reserved_mode);
+	}
+
+	#[test]
+	fn role_defaults_to_the_legacy_flags() {
+		assert_eq!(Ok(NodeRole::Collator), node_role(&cli(&["--collator"])));
+		assert_eq!(Ok(NodeRole::Collator), node_role(&cli(&["--validator"])));
+		assert_eq!(Ok(NodeRole::Full), node_role(&cli(&[])));
+		assert_eq!(Ok(NodeRole::Full), node_role(&cli(&["--role", "full"])));
+		assert!(Cli::try_from_iter(&["rococo-collator", "--role", "full", "--collator"]).is_err());
+	}
+
+	#[test]
+	fn parachain_node_can_not_be_a_light_client() {
+		assert!(node_role(&cli(&["--light"])).is_err());
+		assert!(Cli::try_from_iter(&["rococo-collator", "--role", "light"]).is_err());
+	}
+
+	#[test]
+	fn collator_role_requires_key_and_para_id() {
+		let with_key = cli(&["--role", "collator", "--collator-key", "//Alice"]);
+
+		assert!(collator_key(&cli(&["--role", "collator"]), Some(100)).is_err());
+		assert!(collator_key(&with_key, None).is_err());
+		assert!(collator_key(&with_key, Some(100)).is_ok());
+		assert!(collator_key(&cli(&["--collator"]), None).is_ok());
+	}
+
+	#[test]
+	fn relay_chain_never_runs_as_validator() {
+		let authority = Role::Authority {
+			sentry_nodes: Vec::new(),
+		};
+
+		assert!(relay_chain_role(RelayChainRole::Full, &authority).is_err());
+		assert!(matches!(
+			relay_chain_role(RelayChainRole::Full, &Role::Full),
+			Ok(Role::Full)
+		));
+		assert!(Cli::try_from_iter(&["rococo-collator", "--relay-chain-role", "light"]).is_err());
+	}
+
+	#[test]
+	fn archive_mode_keeps_all_state() {
+		let pruning =
+			|args: &[&str]| archive_pruning(&cli(args).run, node_role(&cli(args)).unwrap());
+
+		assert!(matches!(pruning(&[]), Ok(None)));
+		assert!(matches!(pruning(&["--archive"]), Ok(Some(PruningMode::ArchiveAll))));
+		assert!(matches!(
+			pruning(&["--archive", "--pruning", "archive"]),
+			Ok(Some(PruningMode::ArchiveAll))
+		));
+		assert!(pruning(&["--archive", "--pruning", "256"]).is_err());
+		assert!(pruning(&["--archive", "--collator"]).is_err());
+	}
+
+	#[test]
+	fn proposal_block_fill_ratio_is_a_share() {
+		let ratio = |ratio| node_options(&cli(&["--proposal-block-fill-ratio", ratio]).run);
+
+		assert!(ratio("0.5").is_ok());
+		assert!(ratio("1").is_ok());
+		assert!(ratio("0").is_err());
+		assert!(ratio("1.5").is_err());
+		assert!(ratio("NaN").is_err());
+	}
+
+	#[test]
+	fn node_options_default_to_the_command_line_defaults() {
+		assert_eq!(Ok(NodeOptions::default()), node_options(&cli(&[]).run));
+	}
+
+	#[test]
+	fn authoring_backoff_is_opt_in() {
+		let threshold = |args: &[&str]| {
+			node_options(&cli(args).run).map(|options| options.authoring_backoff_threshold)
+		};
+
+		assert_eq!(Ok(None), threshold(&[]));
+		assert_eq!(
+			Ok(NonZeroU32::new(4)),
+			threshold(&["--authoring-backoff-threshold", "4"]),
+		);
+		assert!(threshold(&["--authoring-backoff-threshold", "0"]).is_err());
+	}
+
+	#[test]
+	fn kept_recent_collations_are_bounded() {
+		let keep = |keep: usize| {
+			node_options(&cli(&["--keep-recent-collations", &keep.to_string()]).run)
+				.map(|options| options.keep_recent_collations)
+		};
+
+		assert_eq!(Ok(0), keep(0));
+		assert_eq!(Ok(MAX_KEEP_RECENT_COLLATIONS), keep(MAX_KEEP_RECENT_COLLATIONS));
+		assert!(keep(MAX_KEEP_RECENT_COLLATIONS + 1).is_err());
+		assert!(keep(usize::MAX).is_err());
+	}
+}
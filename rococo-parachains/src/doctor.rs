@@ -0,0 +1,265 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Checks whether a parachain is ready to produce blocks on a relay chain.
+//!
+//! Every failed check is printed with a hint on how to fix it.
+
+use crate::{
+	chain_spec::Extensions,
+	cli::DoctorCommand,
+	command::{extract_genesis_wasm, generate_genesis_state},
+	registration::{print_report, relay_rpc_call, relay_runtime_call, Check},
+};
+use codec::{Decode, Encode};
+use polkadot_primitives::v0::{
+	Chain, CollatorId, CollatorPair, DutyRoster, Id as ParaId, LocalValidationData, Retriable,
+	ValidationCode,
+};
+use rococo_parachain_primitives::Header;
+use sc_cli::Result;
+use sc_service::ChainSpec;
+use serde_json::json;
+use sp_core::{bytes::to_hex, hashing::blake2_256, Pair};
+use sp_runtime::traits::Block as BlockT;
+
+const FAILURE: &str = "The parachain is not ready to produce blocks";
+
+/// A check whose failure is printed together with the given `hint`.
+fn check(
+	name: &'static str,
+	result: std::result::Result<String, String>,
+	hint: &str,
+) -> Check {
+	Check {
+		name,
+		result: result.map_err(|e| format!("{}\n       hint: {}", e, hint)),
+	}
+}
+
+/// Check that the head of the parachain on the relay chain is the `genesis_head`, or a block that
+/// was included since the registration.
+fn check_genesis_head(
+	relay_head: &[u8],
+	genesis_head: &[u8],
+) -> std::result::Result<String, String> {
+	if relay_head == genesis_head {
+		return Ok("the relay chain has the genesis head, no block was included yet".into());
+	}
+
+	let header = Header::decode(&mut &relay_head[..])
+		.map_err(|e| format!("the head on the relay chain is not a parachain header: {:?}", e))?;
+
+	if header.number == 0 {
+		Err("the relay chain has a different genesis head".into())
+	} else {
+		Ok(format!("block #{} was included since the registration", header.number))
+	}
+}
+
+/// Check that validators are assigned to `para_id` in the duty `roster`.
+///
+/// For a parathread, `claim` is the collator that claimed the parathread, which needs to be the
+/// collator `key` of this node.
+fn check_core(
+	roster: &DutyRoster,
+	para_id: ParaId,
+	claim: Option<&CollatorId>,
+	key: Option<&CollatorId>,
+) -> std::result::Result<String, String> {
+	let assigned = roster
+		.validator_duty
+		.iter()
+		.filter(|duty| **duty == Chain::Parachain(para_id))
+		.count();
+
+	if assigned == 0 {
+		return Err(format!("no validator is assigned to {:?}", para_id));
+	}
+
+	match (claim, key) {
+		(Some(claim), Some(key)) if claim != key => Err(format!(
+			"the parathread is claimed by the collator {}, not by this collator key",
+			claim,
+		)),
+		_ => Ok(format!("{} validator(s) assigned to {:?}", assigned, para_id)),
+	}
+}
+
+/// Run all checks against the relay chain and print a report.
+///
+/// Returns an error if any of the checks failed.
+pub fn doctor(chain_spec: &Box<dyn ChainSpec>, params: &DoctorCommand) -> Result<()> {
+	let relay_rpc = &params.relay_rpc;
+
+	let mut checks = vec![check(
+		"relay chain reachable",
+		relay_rpc_call(relay_rpc, "system_chain", json!([]))
+			.map(|chain| format!("{} at {}", chain, relay_rpc)),
+		"check that the relay chain node runs and that `--relay-rpc` is its HTTP RPC endpoint",
+	)];
+
+	let para_id = params
+		.parachain_id
+		.or_else(|| Extensions::try_get(chain_spec).map(|e| e.para_id));
+	if para_id.is_none() {
+		checks.push(check(
+			"para id",
+			Err("no para id given".into()),
+			"set `--parachain-id` or use a chain spec that declares the para id",
+		));
+	}
+
+	// The other checks can not succeed without the relay chain and the para id.
+	let para_id = match para_id {
+		Some(para_id) if checks.iter().all(|check| check.result.is_ok()) => ParaId::from(para_id),
+		_ => return print_report(&checks, FAILURE),
+	};
+
+	let active = relay_runtime_call::<Vec<(ParaId, Option<(CollatorId, Retriable)>)>>(
+		relay_rpc,
+		"ParachainHost_active_parachains",
+		&[],
+	);
+	let claim = active.as_ref().ok().and_then(|active| {
+		active
+			.iter()
+			.find(|(id, _)| *id == para_id)
+			.and_then(|(_, claim)| claim.as_ref().map(|(collator, _)| collator.clone()))
+	});
+	checks.push(check(
+		"para id registered",
+		active.and_then(|active| {
+			if active.iter().any(|(id, _)| *id == para_id) {
+				Ok(format!("{:?} is active", para_id))
+			} else {
+				Err(format!("{:?} is not an active parachain", para_id))
+			}
+		}),
+		"register the parachain with the output of `export-genesis-state` and \
+		`export-genesis-wasm`, it becomes active at the next session",
+	));
+
+	checks.push(check(
+		"genesis head",
+		relay_runtime_call::<Option<LocalValidationData>>(
+			relay_rpc,
+			"ParachainHost_local_validation_data",
+			&para_id.encode(),
+		)
+		.and_then(|data| data.ok_or_else(|| "the relay chain has no head".to_string()))
+		.and_then(|data| {
+			let genesis = generate_genesis_state(chain_spec)
+				.map_err(|e| format!("failed to build the genesis: {}", e))?;
+
+			check_genesis_head(&data.parent_head.0, &genesis.header().encode())
+		}),
+		"register the parachain with the genesis state of this chain spec",
+	));
+
+	checks.push(check(
+		"validation code",
+		relay_runtime_call::<Option<ValidationCode>>(
+			relay_rpc,
+			"ParachainHost_parachain_code",
+			&para_id.encode(),
+		)
+		.and_then(|code| code.ok_or_else(|| "the relay chain has no validation code".to_string()))
+		.and_then(|code| {
+			let genesis = extract_genesis_wasm(chain_spec)
+				.map_err(|e| format!("failed to get the genesis wasm: {}", e))?;
+			let (relay, genesis) = (blake2_256(&code.0), blake2_256(&genesis));
+
+			if relay == genesis {
+				Ok(format!("hash {}", to_hex(&relay, false)))
+			} else {
+				Err(format!(
+					"the relay chain has hash {}, the chain spec hash {}",
+					to_hex(&relay, false),
+					to_hex(&genesis, false),
+				))
+			}
+		}),
+		"register the parachain with the uncompressed `export-genesis-wasm` of this chain spec, \
+		a runtime upgrade of the parachain also changes the hash",
+	));
+
+	let key = params
+		.collator_key
+		.as_ref()
+		.ok_or_else(|| "no collator key given".to_string())
+		.and_then(|suri| {
+			CollatorPair::from_string(suri, None)
+				.map(|pair| pair.public())
+				.map_err(|e| format!("invalid collator key: {:?}", e))
+		});
+	checks.push(check(
+		"collator key",
+		key.as_ref().map(|key| format!("{}", key)).map_err(Clone::clone),
+		"set `--collator-key` to the secret URI the collator is started with",
+	));
+
+	checks.push(check(
+		"core assigned",
+		relay_runtime_call::<DutyRoster>(relay_rpc, "ParachainHost_duty_roster", &[]).and_then(
+			|roster| check_core(&roster, para_id, claim.as_ref(), key.as_ref().ok()),
+		),
+		"an active parachain is assigned validators at the next session, a parathread needs to \
+		be claimed by this collator key",
+	));
+
+	print_report(&checks, FAILURE)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn genesis_head_or_included_block_matches() {
+		let header = |number| Header {
+			number,
+			parent_hash: Default::default(),
+			state_root: Default::default(),
+			extrinsics_root: Default::default(),
+			digest: Default::default(),
+		};
+		let genesis = header(0).encode();
+
+		assert!(check_genesis_head(&genesis, &genesis).is_ok());
+		assert!(check_genesis_head(&header(5).encode(), &genesis).is_ok());
+
+		let mut other = header(0);
+		other.state_root = [1; 32].into();
+		assert!(check_genesis_head(&other.encode(), &genesis).is_err());
+		assert!(check_genesis_head(&[1, 2, 3], &genesis).is_err());
+	}
+
+	#[test]
+	fn core_needs_validators_and_the_claim() {
+		let para_id = ParaId::from(100);
+		let roster = DutyRoster {
+			validator_duty: vec![Chain::Relay, Chain::Parachain(para_id)],
+		};
+		let collator = |suri| CollatorPair::from_string(suri, None).unwrap().public();
+		let (alice, bob) = (collator("//Alice"), collator("//Bob"));
+
+		assert!(check_core(&roster, para_id, None, None).is_ok());
+		assert!(check_core(&roster, para_id, Some(&alice), Some(&alice)).is_ok());
+		assert!(check_core(&roster, para_id, Some(&alice), Some(&bob)).is_err());
+		assert!(check_core(&roster, 200.into(), None, None).is_err());
+	}
+}
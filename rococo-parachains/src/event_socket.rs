@@ -0,0 +1,145 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Streaming of the collation events to a Unix domain socket.
+//!
+//! Every client that connects to the socket receives the events from then on, one JSON object per
+//! line, in the format of the `cumulus_subscribeCollations` RPC subscription. Submitted, rejected
+//! and included collations are streamed, a collation is included once the relay chain finalized
+//! the block that includes it.
+
+use cumulus_rpc::CollationEvent;
+use futures::{Stream, StreamExt};
+use log::{debug, warn};
+use parking_lot::Mutex;
+use rococo_parachain_primitives::{Block, Hash};
+
+use std::{
+	io::{self, Write},
+	os::unix::{
+		fs::FileTypeExt,
+		net::{UnixListener, UnixStream},
+	},
+	path::Path,
+	sync::Arc,
+	time::Duration,
+};
+
+/// How long writing an event to a client may take before the client is disconnected.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Bind the event socket at `path`.
+///
+/// A socket that is left over from a previous run is replaced, any other file at `path` is not.
+pub fn bind(path: &Path) -> io::Result<UnixListener> {
+	if std::fs::symlink_metadata(path).map_or(false, |meta| meta.file_type().is_socket()) {
+		std::fs::remove_file(path)?;
+	}
+
+	UnixListener::bind(path)
+}
+
+/// Stream the given collation `events` to every client that connects to `listener`.
+///
+/// The clients are accepted on a separate thread. A client that does not read the events fast
+/// enough is disconnected.
+pub async fn serve_events(
+	listener: UnixListener,
+	events: impl Stream<Item = cumulus_collator::CollationEvent> + Unpin,
+) {
+	let clients = Arc::new(Mutex::new(Vec::<UnixStream>::new()));
+
+	let accepted = clients.clone();
+	std::thread::spawn(move || {
+		for client in listener.incoming() {
+			let client = client
+				.and_then(|client| client.set_write_timeout(Some(WRITE_TIMEOUT)).map(|_| client));
+
+			match client {
+				Ok(client) => accepted.lock().push(client),
+				Err(e) => warn!("Failed to accept a client of the event socket: {}", e),
+			}
+		}
+	});
+
+	let mut events = events.map(CollationEvent::<Hash>::from_status::<Block>);
+
+	while let Some(event) = events.next().await {
+		let mut line = match serde_json::to_vec(&event) {
+			Ok(line) => line,
+			Err(e) => {
+				warn!("Failed to encode a collation event: {}", e);
+				continue;
+			}
+		};
+		line.push(b'\n');
+
+		clients.lock().retain(|mut client| match client.write_all(&line) {
+			Ok(()) => true,
+			Err(e) => {
+				debug!("Disconnecting a client of the event socket: {}", e);
+				false
+			}
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use cumulus_collator::{CollationResult, RelayParent};
+	use futures::channel::mpsc;
+	use std::io::{BufRead, BufReader};
+
+	#[test]
+	fn connected_clients_receive_the_events() {
+		let path = std::env::temp_dir()
+			.join(format!("cumulus-event-socket-{}.sock", std::process::id()));
+		// A socket that is left over from a previous run is replaced.
+		drop(bind(&path).unwrap());
+		let listener = bind(&path).unwrap();
+
+		let (sink, events) = mpsc::unbounded();
+		let server = std::thread::spawn(move || {
+			futures::executor::block_on(serve_events(listener, events))
+		});
+
+		let client = UnixStream::connect(&path).unwrap();
+		// Wait for the server to accept the client before the event is sent.
+		std::thread::sleep(Duration::from_millis(100));
+
+		sink.unbounded_send(cumulus_collator::CollationEvent {
+			relay_parent: RelayParent {
+				hash: Default::default(),
+				number: 7,
+			},
+			head_data: None,
+			pov_size: None,
+			result: CollationResult::Rejected,
+		})
+		.unwrap();
+
+		let mut line = String::new();
+		BufReader::new(client).read_line(&mut line).unwrap();
+		let event: CollationEvent<Hash> = serde_json::from_str(&line).unwrap();
+		assert_eq!(7, event.relay_parent.number);
+		assert_eq!(cumulus_rpc::CollationResult::Rejected, event.result);
+
+		drop(sink);
+		server.join().unwrap();
+		std::fs::remove_file(&path).unwrap();
+	}
+}
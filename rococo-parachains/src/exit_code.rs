@@ -0,0 +1,111 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The exit codes of the collator binary.
+//!
+//! Every failure class has its own exit code, so a supervisor can decide whether restarting the
+//! node may help:
+//!
+//! | Code | Class         | Restart |
+//! |------|---------------|---------|
+//! | 1    | `failure`     | maybe   |
+//! | 2    | `config`      | no      |
+//! | 3    | `relay-chain` | yes     |
+//! | 4    | `database`    | no      |
+//! | 5    | `transient`   | yes     |
+
+use cumulus_service::RELAY_CHAIN_START_ERROR;
+
+/// The class of the error the collator exited with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+	/// Any failure that does not belong to one of the other classes.
+	Failure = 1,
+	/// The command line arguments or the chain spec are invalid.
+	Config = 2,
+	/// The embedded relay chain node could not be started.
+	RelayChain = 3,
+	/// The database could not be opened or is corrupted.
+	Database = 4,
+	/// An I/O or network error, e.g. a port that is in use.
+	Transient = 5,
+}
+
+impl ExitCode {
+	/// The class of the given `error`.
+	pub fn of(error: &sc_cli::Error) -> Self {
+		use sc_cli::Error as CliError;
+		use sc_service::Error as ServiceError;
+
+		match error {
+			CliError::Cli(_) | CliError::Input(_) | CliError::InvalidListenMultiaddress => {
+				Self::Config
+			}
+			CliError::Io(_) => Self::Transient,
+			CliError::Client(sp_blockchain::Error::Backend(_)) => Self::Database,
+			CliError::Service(error) => match error {
+				ServiceError::Other(msg) if msg.starts_with(RELAY_CHAIN_START_ERROR) => {
+					Self::RelayChain
+				}
+				ServiceError::Client(sp_blockchain::Error::Backend(_)) => Self::Database,
+				ServiceError::Io(_) | ServiceError::Network(_) => Self::Transient,
+				_ => Self::Failure,
+			},
+			_ => Self::Failure,
+		}
+	}
+
+	/// The name of the class, as printed with the error.
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Failure => "failure",
+			Self::Config => "config",
+			Self::RelayChain => "relay-chain",
+			Self::Database => "database",
+			Self::Transient => "transient",
+		}
+	}
+}
+
+/// Exit the process with the exit code of `error`, printing a one line summary to stderr.
+pub fn exit_with(error: sc_cli::Error) -> ! {
+	let code = ExitCode::of(&error);
+	let summary = error.to_string().replace('\n', " ");
+
+	eprintln!("Error ({}): {}", code.name(), summary);
+	std::process::exit(code as i32)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn errors_are_classified() {
+		let relay_chain = cumulus_service::relay_chain_start_error("no peers");
+		let database = sp_blockchain::Error::Backend("corrupted".into());
+		let io = std::io::Error::new(std::io::ErrorKind::AddrInUse, "port in use");
+
+		assert_eq!(ExitCode::Config, ExitCode::of(&"`--tx-ttl` needs to be at least 1".into()));
+		assert_eq!(ExitCode::RelayChain, ExitCode::of(&relay_chain.into()));
+		assert_eq!(ExitCode::Database, ExitCode::of(&sc_service::Error::Client(database).into()));
+		assert_eq!(ExitCode::Transient, ExitCode::of(&io.into()));
+		assert_eq!(
+			ExitCode::Failure,
+			ExitCode::of(&sc_service::Error::Other("unknown".into()).into()),
+		);
+	}
+}
@@ -0,0 +1,156 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Export of the parachain state and head at a given block.
+
+use crate::cli::{ExportHeadCommand, ExportStateCommand};
+
+use codec::Encode;
+use rococo_parachain_primitives::Block;
+use sc_cli::Result;
+use sc_client_api::{Backend as BackendT, StorageProvider};
+use sp_blockchain::HeaderBackend;
+use sp_core::{
+	hexdisplay::HexDisplay,
+	storage::{well_known_keys, ChildInfo, StorageKey},
+	H256,
+};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+
+use log::info;
+use serde_json::json;
+
+use std::{collections::BTreeMap, io::Write};
+
+/// The storage of the top trie or of a child trie, as hex encoded key-value pairs.
+type HexStorage = BTreeMap<String, String>;
+
+fn to_hex(data: &[u8]) -> String {
+	format!("0x{}", HexDisplay::from(&data))
+}
+
+/// The head data of the parachain block with the given `header`, hex encoded.
+///
+/// The head data of a parachain block is its encoded header.
+pub(crate) fn head_data_hex(header: &<Block as BlockT>::Header) -> String {
+	to_hex(&header.encode())
+}
+
+/// Parse the hash of a block given on the command line.
+pub(crate) fn parse_hash(hash: &str) -> Result<H256> {
+	let bytes = sp_core::bytes::from_hex(hash)
+		.map_err(|e| format!("Invalid block hash `{}`: {:?}", hash, e))?;
+
+	if bytes.len() != H256::len_bytes() {
+		return Err(format!("Invalid block hash `{}`: expected 32 bytes", hash).into());
+	}
+
+	Ok(H256::from_slice(&bytes))
+}
+
+/// Export the state of the parachain as described by the given `params`.
+///
+/// All top level storage and all default child tries are exported. With `--raw` the output can
+/// be used as the `genesis` section of a chain spec.
+pub fn export_state<C, B>(client: &C, params: &ExportStateCommand) -> Result<()>
+where
+	C: StorageProvider<Block, B> + HeaderBackend<Block>,
+	B: BackendT<Block>,
+{
+	let hash = match &params.at {
+		Some(at) => parse_hash(at)?,
+		None => client.info().best_hash,
+	};
+	let at = BlockId::Hash(hash);
+
+	if client.header(at)?.is_none() {
+		return Err(format!("Block `{:?}` not found", hash).into());
+	}
+
+	info!("Exporting the state at block `{:?}`", hash);
+
+	let mut top = HexStorage::new();
+	let mut children_default = BTreeMap::<String, HexStorage>::new();
+	let prefix = well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX;
+
+	for (key, value) in client.storage_pairs(&at, &StorageKey(Vec::new()))? {
+		if key.0.starts_with(prefix) {
+			let child_key = &key.0[prefix.len()..];
+			let child_info = ChildInfo::new_default(child_key);
+			let mut child = HexStorage::new();
+
+			for child_storage_key in
+				client.child_storage_keys(&at, &child_info, &StorageKey(Vec::new()))?
+			{
+				if let Some(value) = client.child_storage(&at, &child_info, &child_storage_key)? {
+					child.insert(to_hex(&child_storage_key.0), to_hex(&value.0));
+				}
+			}
+
+			children_default.insert(to_hex(child_key), child);
+		} else {
+			top.insert(to_hex(&key.0), to_hex(&value.0));
+		}
+	}
+
+	let storage = json!({
+		"top": top,
+		"childrenDefault": children_default,
+	});
+	let state = if params.raw {
+		json!({ "raw": storage })
+	} else {
+		json!({
+			"block": hash,
+			"storage": storage,
+		})
+	};
+	let state = serde_json::to_vec_pretty(&state).map_err(|e| format!("{:?}", e))?;
+
+	if let Some(output) = &params.output {
+		std::fs::write(output, state)?;
+	} else {
+		std::io::stdout().write_all(&state)?;
+	}
+
+	Ok(())
+}
+
+/// Export the head data of the parachain at the block given by `params`.
+///
+/// The output has the format of `export-genesis-state`, so it can be used to register the
+/// parachain with the head data of a block other than genesis.
+pub fn export_head<C>(client: &C, params: &ExportHeadCommand) -> Result<()>
+where
+	C: HeaderBackend<Block>,
+{
+	let hash = parse_hash(&params.at)?;
+	let header = client
+		.header(BlockId::Hash(hash))?
+		.ok_or_else(|| format!("Block `{:?}` not found", hash))?;
+
+	info!("Exporting the head data of block #{} (`{:?}`)", header.number, hash);
+
+	let head_data = head_data_hex(&header);
+
+	if let Some(output) = &params.output {
+		std::fs::write(output, head_data)?;
+	} else {
+		print!("{}", head_data);
+	}
+
+	Ok(())
+}
@@ -0,0 +1,137 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Everything that is needed to register the parachain on a relay chain, exported in one step.
+
+use crate::command::{extract_genesis_wasm, generate_genesis_state_with_timeout};
+use codec::Encode;
+use cumulus_primitives::ParaId;
+use sc_cli::Result;
+use serde_json::json;
+use sp_core::{bytes::to_hex, hashing::blake2_256};
+use sp_runtime::traits::Block as BlockT;
+use std::{fs, path::Path, time::Duration};
+
+/// The file the genesis state is written to, the same as written by `export-genesis-state`.
+pub const GENESIS_STATE_FILE: &str = "genesis-state.hex";
+/// The file the hex encoded genesis wasm is written to.
+pub const GENESIS_WASM_FILE: &str = "genesis-wasm.hex";
+/// The file the hash of the validation code is written to.
+pub const VALIDATION_CODE_HASH_FILE: &str = "validation-code-hash.txt";
+/// The file the summary of the registration is written to.
+pub const REGISTRATION_FILE: &str = "registration.json";
+
+/// The genesis state and wasm of a parachain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenesisBundle {
+	/// The id of the parachain.
+	pub para_id: ParaId,
+	/// The id of the chain spec the bundle was built from.
+	pub chain_id: String,
+	/// The encoded genesis header.
+	pub genesis_state: Vec<u8>,
+	/// The genesis wasm, which is registered as the validation code.
+	pub genesis_wasm: Vec<u8>,
+}
+
+impl GenesisBundle {
+	/// Build the bundle of `chain_spec`, giving up on the genesis state after `timeout`.
+	pub fn build(
+		chain_spec: Box<dyn sc_service::ChainSpec>,
+		para_id: ParaId,
+		timeout: Duration,
+	) -> Result<Self> {
+		let chain_id = chain_spec.id().to_string();
+		let genesis_wasm = extract_genesis_wasm(&chain_spec)?;
+		let block = generate_genesis_state_with_timeout(chain_spec, timeout)?;
+
+		Ok(Self {
+			para_id,
+			chain_id,
+			genesis_state: block.header().encode(),
+			genesis_wasm,
+		})
+	}
+
+	/// The hash of the validation code, as the relay chain computes it.
+	pub fn validation_code_hash(&self) -> [u8; 32] {
+		blake2_256(&self.genesis_wasm)
+	}
+
+	/// Write the bundle to the directory `dir`, which is created if it does not exist.
+	pub fn write(&self, dir: &Path) -> Result<()> {
+		fs::create_dir_all(dir)?;
+
+		let validation_code_hash = to_hex(&self.validation_code_hash(), false);
+		let registration = json!({
+			"paraId": u32::from(self.para_id),
+			"chainId": self.chain_id,
+			"genesisState": GENESIS_STATE_FILE,
+			"genesisWasm": GENESIS_WASM_FILE,
+			"genesisStateSize": self.genesis_state.len(),
+			"genesisWasmSize": self.genesis_wasm.len(),
+			"validationCodeHash": validation_code_hash,
+		});
+
+		fs::write(dir.join(GENESIS_STATE_FILE), to_hex(&self.genesis_state, false))?;
+		fs::write(dir.join(GENESIS_WASM_FILE), to_hex(&self.genesis_wasm, false))?;
+		fs::write(dir.join(VALIDATION_CODE_HASH_FILE), validation_code_hash)?;
+		fs::write(
+			dir.join(REGISTRATION_FILE),
+			serde_json::to_string_pretty(&registration)
+				.map_err(|e| format!("Failed to encode the registration summary: {}", e))?,
+		)?;
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Decode;
+	use rococo_parachain_primitives::Header;
+	use serde_json::Value;
+	use sp_core::bytes::from_hex;
+
+	#[test]
+	fn bundle_matches_the_individual_exports() {
+		let chain_spec = || Box::new(crate::chain_spec::get_chain_spec(100.into())) as Box<_>;
+		let bundle =
+			GenesisBundle::build(chain_spec(), 100.into(), Duration::from_secs(60)).unwrap();
+
+		let dir =
+			std::env::temp_dir().join(format!("cumulus-genesis-bundle-{}", std::process::id()));
+		bundle.write(&dir).unwrap();
+
+		let read = |file| fs::read_to_string(dir.join(file)).unwrap();
+		let genesis_state = from_hex(&read(GENESIS_STATE_FILE)).unwrap();
+		let genesis_wasm = from_hex(&read(GENESIS_WASM_FILE)).unwrap();
+		let validation_code_hash = read(VALIDATION_CODE_HASH_FILE);
+		let registration: Value = serde_json::from_str(&read(REGISTRATION_FILE)).unwrap();
+		let _ = fs::remove_dir_all(&dir);
+
+		let expected_state = crate::command::generate_genesis_state(&chain_spec()).unwrap();
+		assert_eq!(expected_state.header().encode(), genesis_state);
+		assert_eq!(extract_genesis_wasm(&chain_spec()).unwrap(), genesis_wasm);
+		assert_eq!(to_hex(&blake2_256(&genesis_wasm), false), validation_code_hash);
+		assert!(Header::decode(&mut &genesis_state[..]).is_ok());
+
+		assert_eq!(100, registration["paraId"]);
+		assert_eq!(validation_code_hash, registration["validationCodeHash"]);
+		assert_eq!(genesis_wasm.len(), registration["genesisWasmSize"].as_u64().unwrap() as usize);
+	}
+}
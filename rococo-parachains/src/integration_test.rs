@@ -14,24 +14,34 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-use codec::Encode;
+use crate::{
+	genesis_bundle::GenesisBundle,
+	service::{NodeOptions, RuntimeExecutor},
+};
+use cumulus_test_registration::{submit_register_para, RegisterParaParams};
 use futures::future;
-use polkadot_primitives::v0::{Id as ParaId, Info, Scheduling};
-use polkadot_runtime_common::registrar;
+use polkadot_primitives::v0::{Block as PBlock, Id as ParaId};
+use polkadot_service::AbstractClient;
 use polkadot_test_runtime_client::Sr25519Keyring;
+use polkadot_test_service::PolkadotTestNode;
+use rococo_parachain_primitives::Block;
 use sc_chain_spec::ChainSpec;
 use sc_client_api::execution_extensions::ExecutionStrategies;
 use sc_informant::OutputFormat;
-use sc_network::{config::TransportConfig, multiaddr};
+use sc_network::{
+	config::{NodeKeyConfig, Secret, TransportConfig},
+	multiaddr, NetworkService, PeerId,
+};
 use sc_service::{
 	config::{
 		DatabaseConfig, KeystoreConfig, MultiaddrWithPeerId, NetworkConfiguration,
 		OffchainWorkerConfig, PruningMode, WasmExecutionMethod,
 	},
-	BasePath, Configuration, Error as ServiceError, Role, TaskExecutor,
+	BasePath, Configuration, Error as ServiceError, Role, TFullBackend, TFullClient, TaskExecutor,
+	TaskManager,
 };
-use sp_api::BlockT;
-use std::sync::Arc;
+use sp_core::H256;
+use std::{io, path::Path, sync::Arc, time::Duration};
 use substrate_test_client::BlockchainEventsExt;
 use substrate_test_runtime_client::AccountKeyring::*;
 
@@ -41,12 +51,63 @@ async fn integration_test(task_executor: TaskExecutor) {
 	let para_id = ParaId::from(100);
 
 	// generate parachain spec
-	let spec = Box::new(crate::chain_spec::get_chain_spec(para_id));
+	let spec = crate::chain_spec::get_chain_spec(para_id);
 
-	// start alice
-	let alice = polkadot_test_service::run_test_node(task_executor.clone(), Alice, || {}, vec![]);
+	let network = spawn_test_network(task_executor, para_id, spec.clone()).await;
+
+	// export genesis state and wasm
+	let bundle = GenesisBundle::build(Box::new(spec), para_id, Duration::from_secs(60)).unwrap();
+
+	network
+		.register_parachain(para_id, bundle.genesis_wasm, bundle.genesis_state)
+		.await
+		.unwrap();
+
+	network.charlie.client.wait_for_blocks(4).await;
+
+	// run cumulus dave (not a validator)
+	//
+	// a collator running in non-validator mode should be able to sync blocks from the tip of the
+	// parachain
+	let dave = network.spawn_cumulus_node(Dave, false, vec![network.charlie.addr.clone()]);
+	dave.client.wait_for_blocks(4).await;
+
+	dave.task_manager.clean_shutdown();
+	network.clean_shutdown();
+}
+
+/// The client of the parachain nodes started by the tests.
+type ParachainClient = TFullClient<Block, parachain_runtime::RuntimeApi, RuntimeExecutor>;
 
-	// start bob
+/// A running Cumulus node.
+pub struct CumulusTestNode {
+	pub task_manager: TaskManager,
+	pub client: Arc<ParachainClient>,
+	pub network: Arc<NetworkService<Block, H256>>,
+	/// The address other parachain nodes can use to connect to this node.
+	pub addr: MultiaddrWithPeerId,
+}
+
+/// A relay chain with the validators Alice and Bob and the Cumulus collator Charlie.
+pub struct TestNetwork<C> {
+	pub alice: PolkadotTestNode<TaskManager, C>,
+	pub bob: PolkadotTestNode<TaskManager, C>,
+	pub charlie: CumulusTestNode,
+	task_executor: TaskExecutor,
+	para_id: ParaId,
+	spec: crate::chain_spec::ChainSpec,
+}
+
+/// Spawn the relay chain validators Alice and Bob and the collator Charlie for `para_id`.
+///
+/// Returns once Alice and Bob produce blocks. Charlie only starts producing blocks after the
+/// parachain is registered with [`TestNetwork::register_parachain`].
+pub async fn spawn_test_network(
+	task_executor: TaskExecutor,
+	para_id: ParaId,
+	spec: crate::chain_spec::ChainSpec,
+) -> TestNetwork<impl AbstractClient<PBlock, TFullBackend<PBlock>>> {
+	let alice = polkadot_test_service::run_test_node(task_executor.clone(), Alice, || {}, vec![]);
 	let bob = polkadot_test_service::run_test_node(
 		task_executor.clone(),
 		Bob,
@@ -57,73 +118,129 @@ async fn integration_test(task_executor: TaskExecutor) {
 	// ensure alice and bob can produce blocks
 	future::join(alice.wait_for_blocks(2), bob.wait_for_blocks(2)).await;
 
-	// export genesis state
-	let block = crate::command::generate_genesis_state(&(spec.clone() as Box<_>)).unwrap();
-	let genesis_state = block.header().encode();
-
-	// create and sign transaction to register parachain
-	let function = polkadot_test_runtime::Call::Sudo(pallet_sudo::Call::sudo(Box::new(
-		polkadot_test_runtime::Call::Registrar(registrar::Call::register_para(
-			para_id,
-			Info {
-				scheduling: Scheduling::Always,
-			},
-			parachain_runtime::WASM_BINARY
-				.expect("You need to build the WASM binary to run this test!")
-				.to_vec()
-				.into(),
-			genesis_state.into(),
-		)),
-	)));
-
-	// register parachain
-	let _ = alice.call_function(function, Alice).await.unwrap();
-
-	// run cumulus charlie (a validator)
-	let key = Arc::new(sp_core::Pair::generate().0);
-	let polkadot_config = polkadot_test_service::node_config(
-		|| {},
+	let charlie = spawn_cumulus_node(
 		task_executor.clone(),
-		Charlie,
 		vec![alice.addr.clone(), bob.addr.clone()],
+		para_id,
+		Box::new(spec.clone()),
+		Charlie,
+		true,
+		vec![],
 	);
-	let charlie_config =
-		parachain_config(task_executor.clone(), Charlie, vec![], spec.clone()).unwrap();
-	let multiaddr = charlie_config.network.listen_addresses[0].clone();
-	let (charlie_task_manager, charlie_client, charlie_network) =
-		crate::service::start_node(charlie_config, key, polkadot_config, para_id, true, true)
-			.unwrap();
-	charlie_client.wait_for_blocks(4).await;
-	let peer_id = charlie_network.local_peer_id().clone();
-	let charlie_addr = MultiaddrWithPeerId { multiaddr, peer_id };
 
-	// run cumulus dave (not a validator)
-	//
-	// a collator running in non-validator mode should be able to sync blocks from the tip of the
-	// parachain
-	let key = Arc::new(sp_core::Pair::generate().0);
-	let polkadot_config = polkadot_test_service::node_config(
-		|| {},
-		task_executor.clone(),
-		Dave,
-		vec![alice.addr.clone(), bob.addr.clone()],
-	);
-	let dave_config = parachain_config(
-		task_executor.clone(),
-		Dave,
-		vec![charlie_addr],
-		spec.clone(),
+	TestNetwork {
+		alice,
+		bob,
+		charlie,
+		task_executor,
+		para_id,
+		spec,
+	}
+}
+
+impl<C> TestNetwork<C>
+where
+	C: AbstractClient<PBlock, TFullBackend<PBlock>>,
+{
+	/// Register the parachain `para_id` with the given `wasm` and `genesis_state` on the relay
+	/// chain.
+	pub async fn register_parachain(
+		&self,
+		para_id: ParaId,
+		wasm: Vec<u8>,
+		genesis_state: Vec<u8>,
+	) -> Result<(), String> {
+		let params =
+			RegisterParaParams::new(&*self.alice.client, para_id, wasm, genesis_state)?;
+
+		submit_register_para(&self.alice, params).await.map(|_| ())
+	}
+
+	/// Spawn another Cumulus node of the parachain, connected to Alice and Bob.
+	pub fn spawn_cumulus_node(
+		&self,
+		key: Sr25519Keyring,
+		validator: bool,
+		boot_nodes: Vec<MultiaddrWithPeerId>,
+	) -> CumulusTestNode {
+		spawn_cumulus_node(
+			self.task_executor.clone(),
+			vec![self.alice.addr.clone(), self.bob.addr.clone()],
+			self.para_id,
+			Box::new(self.spec.clone()),
+			key,
+			validator,
+			boot_nodes,
+		)
+	}
+
+	/// Shut down all nodes of the network.
+	pub fn clean_shutdown(self) {
+		self.alice.task_manager.clean_shutdown();
+		self.bob.task_manager.clean_shutdown();
+		self.charlie.task_manager.clean_shutdown();
+	}
+}
+
+/// Spawn a Cumulus node for `para_id` whose relay chain node connects to the given `relay_nodes`.
+fn spawn_cumulus_node(
+	task_executor: TaskExecutor,
+	relay_nodes: Vec<MultiaddrWithPeerId>,
+	para_id: ParaId,
+	spec: Box<dyn ChainSpec>,
+	key: Sr25519Keyring,
+	validator: bool,
+	boot_nodes: Vec<MultiaddrWithPeerId>,
+) -> CumulusTestNode {
+	let collator_key = Arc::new(sp_core::Pair::generate().0);
+	let polkadot_config =
+		polkadot_test_service::node_config(|| {}, task_executor.clone(), key, relay_nodes);
+	let config = parachain_config(task_executor, key, boot_nodes, spec).unwrap();
+	let addr = MultiaddrWithPeerId {
+		multiaddr: config.network.listen_addresses[0].clone(),
+		peer_id: peer_id_from_base_path(config.base_path.as_ref().unwrap().path()).unwrap(),
+	};
+	let (task_manager, client, network) = crate::service::start_node(
+		config,
+		collator_key,
+		polkadot_config,
+		para_id,
+		validator,
+		// The collator is started right after the registration, do not collate before the relay
+		// chain node is synced.
+		NodeOptions {
+			collation_start_delay: Some(Duration::from_secs(0)),
+			hardware_benchmarks: false,
+			..Default::default()
+		},
+		true,
 	)
 	.unwrap();
-	let (dave_task_manager, dave_client, _dave_network) =
-		crate::service::start_node(dave_config, key, polkadot_config, para_id, false, true)
-			.unwrap();
-	dave_client.wait_for_blocks(4).await;
-
-	alice.task_manager.clean_shutdown();
-	bob.task_manager.clean_shutdown();
-	charlie_task_manager.clean_shutdown();
-	dave_task_manager.clean_shutdown();
+
+	CumulusTestNode {
+		task_manager,
+		client,
+		network,
+		addr,
+	}
+}
+
+/// The file in the network directory of the base path the node key is stored in.
+const NODE_KEY_ED25519_FILE: &str = "secret_ed25519";
+
+/// The node key of a node that uses the given base path.
+fn node_key_config(base_path: &Path) -> NodeKeyConfig {
+	NodeKeyConfig::Ed25519(Secret::File(base_path.join("network").join(NODE_KEY_ED25519_FILE)))
+}
+
+/// Returns the peer id of the node that uses the given base path.
+///
+/// If no node key is stored in the base path yet, a new one is generated and stored, so the peer id
+/// can be known before the node is started.
+pub fn peer_id_from_base_path(base_path: &Path) -> io::Result<PeerId> {
+	node_key_config(base_path)
+		.into_keypair()
+		.map(|keypair| keypair.public().into_peer_id())
 }
 
 pub fn parachain_config(
@@ -152,6 +269,8 @@ pub fn parachain_config(
 
 	network_config.boot_nodes = boot_nodes;
 
+	network_config.node_key = node_key_config(&root);
+
 	network_config.allow_non_globals_in_dht = false;
 
 	network_config
@@ -19,14 +19,27 @@
 #![warn(missing_docs)]
 #![warn(unused_extern_crates)]
 
+mod benchmark_import;
 mod chain_spec;
+mod check_spec;
 #[macro_use]
 mod service;
 mod cli;
 mod command;
+mod doctor;
+mod event_socket;
+mod exit_code;
+mod export_state;
+mod genesis_bundle;
+mod registration;
+mod relay_genesis;
+mod remote_chain_spec;
+mod replay_collation;
 #[cfg(test)]
 mod integration_test;
 
-fn main() -> sc_cli::Result<()> {
-	command::run()
+fn main() {
+	if let Err(error) = command::run() {
+		exit_code::exit_with(error)
+	}
 }
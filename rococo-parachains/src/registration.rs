@@ -0,0 +1,172 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Checks whether a parachain can be registered on a relay chain, without registering it.
+
+use crate::cli::CheckRegistrationCommand;
+use codec::Decode;
+use polkadot_primitives::v0::{CollatorId, GlobalValidationData, Id as ParaId, Retriable};
+use rococo_parachain_primitives::Header;
+use sc_cli::Result;
+use serde_json::{json, Value};
+use sp_core::bytes::{from_hex, to_hex};
+
+/// Call the RPC `method` of the relay chain node behind `relay_rpc` and return the result.
+pub(crate) fn relay_rpc_call(
+	relay_rpc: &str,
+	method: &str,
+	params: Value,
+) -> std::result::Result<Value, String> {
+	let response = ureq::post(relay_rpc).send_json(json!({
+		"jsonrpc": "2.0",
+		"id": 1,
+		"method": method,
+		"params": params,
+	}));
+
+	if !response.ok() {
+		return Err(format!("Request to `{}` failed: {}", relay_rpc, response.status_line()));
+	}
+
+	let response: Value = response
+		.into_json()
+		.map_err(|e| format!("Invalid response from `{}`: {:?}", relay_rpc, e))?;
+
+	match response.get("result") {
+		Some(result) => Ok(result.clone()),
+		None => Err(format!("`{}` failed: {}", method, response["error"])),
+	}
+}
+
+/// Call the runtime api `method` of the relay chain behind `relay_rpc` at the best block, with the
+/// encoded `args`.
+pub(crate) fn relay_runtime_call<T: Decode>(
+	relay_rpc: &str,
+	method: &str,
+	args: &[u8],
+) -> std::result::Result<T, String> {
+	let response = relay_rpc_call(relay_rpc, "state_call", json!([method, to_hex(args, false)]))?;
+
+	let result = match response.as_str() {
+		Some(result) => result,
+		None => return Err(format!("`{}` returned no hex: {}", method, response)),
+	};
+
+	let encoded =
+		from_hex(result).map_err(|e| format!("`{}` returned invalid hex: {:?}", method, e))?;
+
+	T::decode(&mut &encoded[..]).map_err(|e| format!("Could not decode `{}`: {:?}", method, e))
+}
+
+/// The outcome of a single check.
+pub(crate) struct Check {
+	pub(crate) name: &'static str,
+	pub(crate) result: std::result::Result<String, String>,
+}
+
+/// Print a report of the given `checks`.
+///
+/// Returns an error with the given `failure` message if any of the checks failed.
+pub(crate) fn print_report(checks: &[Check], failure: &str) -> Result<()> {
+	let mut failed = false;
+	for check in checks {
+		match &check.result {
+			Ok(msg) => println!("[PASS] {}: {}", check.name, msg),
+			Err(msg) => {
+				failed = true;
+				println!("[FAIL] {}: {}", check.name, msg);
+			}
+		}
+	}
+
+	if failed {
+		Err(failure.into())
+	} else {
+		Ok(())
+	}
+}
+
+/// Run all checks and print a report.
+///
+/// Returns an error if any of the checks failed.
+pub fn check_registration(params: &CheckRegistrationCommand) -> Result<()> {
+	let para_id = ParaId::from(params.para_id);
+	let genesis_state = std::fs::read_to_string(&params.genesis_state)?;
+	let genesis_wasm = std::fs::read(&params.genesis_wasm)?;
+	let genesis_head = from_hex(genesis_state.trim());
+
+	let mut checks = vec![Check {
+		name: "genesis state",
+		result: genesis_head
+			.as_ref()
+			.map_err(|e| format!("not valid hex: {:?}", e))
+			.and_then(|head| {
+				Header::decode(&mut &head[..])
+					.map(|header| format!("header of block #{}", header.number))
+					.map_err(|e| format!("not a parachain header: {:?}", e))
+			}),
+	}];
+
+	checks.push(Check {
+		name: "para id",
+		result: relay_runtime_call::<Vec<(ParaId, Option<(CollatorId, Retriable)>)>>(
+			&params.relay_rpc,
+			"ParachainHost_active_parachains",
+			&[],
+		)
+		.and_then(|active| {
+			if active.iter().any(|(id, _)| *id == para_id) {
+				Err(format!("{:?} is already registered", para_id))
+			} else {
+				Ok(format!("{:?} is free", para_id))
+			}
+		}),
+	});
+
+	match relay_runtime_call::<GlobalValidationData>(
+		&params.relay_rpc,
+		"ParachainHost_global_validation_data",
+		&[],
+	) {
+		Ok(limits) => {
+			checks.push(Check {
+				name: "genesis wasm size",
+				result: check_size(genesis_wasm.len(), limits.max_code_size),
+			});
+
+			if let Ok(head) = &genesis_head {
+				checks.push(Check {
+					name: "genesis state size",
+					result: check_size(head.len(), limits.max_head_data_size),
+				});
+			}
+		}
+		Err(e) => checks.push(Check {
+			name: "relay chain limits",
+			result: Err(e),
+		}),
+	}
+
+	print_report(&checks, "Registration check failed")
+}
+
+fn check_size(size: usize, max: u32) -> std::result::Result<String, String> {
+	if size > max as usize {
+		Err(format!("{} bytes exceeds the relay chain limit of {} bytes", size, max))
+	} else {
+		Ok(format!("{} bytes of at most {} bytes", size, max))
+	}
+}
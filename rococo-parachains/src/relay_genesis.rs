@@ -0,0 +1,103 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Verification of the genesis hash of the relay chain spec.
+//!
+//! A relay chain spec of the wrong network otherwise only shows once the relay chain node fails
+//! to sync with its peers. The genesis hashes of the public networks are embedded, the one of
+//! any other relay chain can be given with `--relay-chain-genesis-hash`.
+
+use crate::command::generate_genesis_block;
+use log::info;
+use polkadot_primitives::v0::Block as PBlock;
+use sc_service::ChainSpec;
+use sp_core::H256;
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT};
+
+/// The chain spec ids of the public relay chains with their genesis hash.
+const KNOWN_RELAY_CHAINS: [(&str, &str); 3] = [
+	("polkadot", "91b171bb158e2d3848fa23a9f1c25182fb8e20313b2c1eb49219da7a70ce90c3"),
+	("ksmcc3", "b0a8d493285c2df73290dfb7e61f870f17b41801197a149ca93654499ea3dafe"),
+	("westend2", "e143f23803ac50e8f6f8e62695d1ce9e4e1d68aa36c1cd2cfd15340213f3423e"),
+];
+
+/// The genesis hash the relay chain spec with the given `id` needs to have.
+///
+/// The `configured` hash takes precedence over the embedded one of a public relay chain.
+fn expected_genesis_hash(id: &str, configured: Option<H256>) -> Option<H256> {
+	configured.or_else(|| {
+		KNOWN_RELAY_CHAINS
+			.iter()
+			.find(|(known, _)| *known == id)
+			.and_then(|(_, hash)| hash.parse().ok())
+	})
+}
+
+/// Check that the genesis hash `actual` of the relay chain spec `id` is the `expected` one.
+fn check_genesis_hash(id: &str, actual: H256, expected: H256) -> Result<(), String> {
+	if actual == expected {
+		Ok(())
+	} else {
+		Err(format!(
+			"relay chain spec genesis hash mismatch: `{}` has {:?}, expected {:?}",
+			id, actual, expected,
+		))
+	}
+}
+
+/// Verify that the relay `chain_spec` has the expected genesis hash.
+///
+/// Relay chain specs without an expected genesis hash, neither embedded nor `configured`, are
+/// not verified.
+pub fn verify_genesis_hash(
+	chain_spec: &Box<dyn ChainSpec>,
+	configured: Option<H256>,
+) -> Result<(), String> {
+	let expected = match expected_genesis_hash(chain_spec.id(), configured) {
+		Some(expected) => expected,
+		None => return Ok(()),
+	};
+
+	let actual = generate_genesis_block::<PBlock>(chain_spec)
+		.map_err(|e| format!("Failed to build the genesis of the relay chain spec: {}", e))?
+		.header()
+		.hash();
+	check_genesis_hash(chain_spec.id(), actual, expected)?;
+
+	info!("Relay chain spec `{}` has the expected genesis hash {:?}", chain_spec.id(), actual);
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn configured_genesis_hash_takes_precedence() {
+		let configured = H256::repeat_byte(1);
+		let polkadot = expected_genesis_hash("polkadot", None).unwrap();
+
+		assert_eq!(Some(configured), expected_genesis_hash("polkadot", Some(configured)));
+		assert_eq!(Some(configured), expected_genesis_hash("rococo_local", Some(configured)));
+		assert_eq!(None, expected_genesis_hash("rococo_local", None));
+
+		assert!(check_genesis_hash("polkadot", polkadot, polkadot).is_ok());
+		assert!(check_genesis_hash("polkadot", configured, polkadot)
+			.unwrap_err()
+			.starts_with("relay chain spec genesis hash mismatch"));
+	}
+}
@@ -0,0 +1,117 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Loading chain specs over HTTP.
+
+use log::{info, warn};
+use sp_core::{hashing::sha2_256, hexdisplay::HexDisplay};
+use std::{
+	io::Read,
+	path::{Path, PathBuf},
+};
+
+/// The directory in the base path downloaded chain specs are cached in.
+const CACHE_DIR: &str = "chain-specs";
+
+/// Returns whether the given chain id is a `http(s)://` URL.
+pub fn is_url(id: &str) -> bool {
+	id.starts_with("http://") || id.starts_with("https://")
+}
+
+/// Download the chain spec at `url` into the cache in `base_path` and return the path to it.
+///
+/// If `sha256` is given, the downloaded chain spec needs to match the checksum. If the download
+/// fails, the cached chain spec of a previous download is used.
+pub fn fetch(url: &str, base_path: &Path, sha256: Option<&str>) -> Result<PathBuf, String> {
+	let cache_dir = base_path.join(CACHE_DIR);
+	let cached = cache_dir.join(format!("{:?}.json", HexDisplay::from(&sha2_256(url.as_bytes()))));
+
+	let spec = match download(url) {
+		Ok(spec) => spec,
+		Err(e) if cached.exists() => {
+			warn!("{}, using the cached chain spec at {}", e, cached.display());
+
+			let spec = std::fs::read(&cached)
+				.map_err(|e| format!("Could not read cached chain spec: {}", e))?;
+			verify_checksum(&spec, sha256)?;
+
+			return Ok(cached);
+		}
+		Err(e) => return Err(e),
+	};
+
+	verify_checksum(&spec, sha256)?;
+
+	std::fs::create_dir_all(&cache_dir)
+		.and_then(|_| std::fs::write(&cached, &spec))
+		.map_err(|e| format!("Could not cache chain spec at {}: {}", cached.display(), e))?;
+
+	info!("Downloaded chain spec from {} to {}", url, cached.display());
+
+	Ok(cached)
+}
+
+fn download(url: &str) -> Result<Vec<u8>, String> {
+	let response = ureq::get(url).call();
+
+	if !response.ok() {
+		return Err(format!(
+			"Downloading chain spec from {} failed: {}",
+			url,
+			response.status_line(),
+		));
+	}
+
+	let mut spec = Vec::new();
+	response
+		.into_reader()
+		.read_to_end(&mut spec)
+		.map_err(|e| format!("Downloading chain spec from {} failed: {}", url, e))?;
+
+	Ok(spec)
+}
+
+fn verify_checksum(spec: &[u8], sha256: Option<&str>) -> Result<(), String> {
+	let expected = match sha256 {
+		Some(expected) => expected.trim_start_matches("0x").to_lowercase(),
+		None => return Ok(()),
+	};
+	let actual = format!("{:?}", HexDisplay::from(&sha2_256(spec)));
+
+	if actual == expected {
+		Ok(())
+	} else {
+		Err(format!(
+			"Chain spec checksum mismatch: expected {}, got {}",
+			expected, actual,
+		))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn checksum_is_verified() {
+		let checksum = format!("0x{:?}", HexDisplay::from(&sha2_256(b"spec")));
+
+		assert!(verify_checksum(b"spec", None).is_ok());
+		assert!(verify_checksum(b"spec", Some(&checksum)).is_ok());
+		assert!(verify_checksum(b"spec", Some(&checksum.to_uppercase()[2..])).is_ok());
+		assert!(verify_checksum(b"other", Some(&checksum)).is_err());
+	}
+}
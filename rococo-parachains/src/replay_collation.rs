@@ -0,0 +1,303 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Replay of a single collation, for debugging validation failures.
+//!
+//! The collation is rebuilt on top of the parachain block it was built on, with the inherents of
+//! the archived collation followed by the transactions of a snapshot of the pool. Both the
+//! archived and the rebuilt collation are run through `validate_block`, and the report shows
+//! where the rebuilt collation diverges from the archived one.
+
+use crate::{
+	cli::ReplayCollationCommand,
+	export_state::parse_hash,
+	registration::{print_report, Check},
+};
+use codec::{Decode, Encode};
+use cumulus_collator::{read_archived_pov, validate_collation};
+use cumulus_runtime::ParachainBlockData;
+use polkadot_primitives::v0::{BlockData, HeadData};
+use rococo_parachain_primitives::{Block, Header};
+use sc_block_builder::BlockBuilderProvider;
+use sc_cli::Result;
+use sc_client_api::{Backend as BackendT, StateBackendFor, StorageProvider};
+use sc_executor::WasmExecutionMethod;
+use sp_api::{ApiExt, ProvideRuntimeApi};
+use sp_block_builder::BlockBuilder as BlockBuilderApi;
+use sp_blockchain::HeaderBackend;
+use sp_core::{
+	bytes::from_hex,
+	storage::{well_known_keys, StorageKey},
+	H256,
+};
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, Extrinsic as ExtrinsicT, Header as HeaderT},
+};
+
+use std::path::Path;
+
+type Extrinsic = <Block as BlockT>::Extrinsic;
+
+/// Read the transactions of the pool from `path`, as returned by `author_pendingExtrinsics`.
+fn read_pool(path: &Path) -> std::result::Result<Vec<Extrinsic>, String> {
+	let pool = std::fs::read_to_string(path)
+		.map_err(|e| format!("Failed to read `{}`: {}", path.display(), e))?;
+
+	parse_pool(&pool).map_err(|e| format!("Invalid pool in `{}`: {}", path.display(), e))
+}
+
+/// Parse the transactions of the pool from a JSON array of hex encoded transactions.
+fn parse_pool(pool: &str) -> std::result::Result<Vec<Extrinsic>, String> {
+	let pool = serde_json::from_str::<Vec<String>>(pool).map_err(|e| e.to_string())?;
+
+	pool.iter()
+		.enumerate()
+		.map(|(i, xt)| {
+			from_hex(xt)
+				.map_err(|e| format!("{:?}", e))
+				.and_then(|xt| Extrinsic::decode(&mut &xt[..]).map_err(|e| format!("{:?}", e)))
+				.map_err(|e| format!("transaction #{} is invalid: {}", i, e))
+		})
+		.collect()
+}
+
+/// The inherents of the block with the given `extrinsics`, which are decoded as `Xt`.
+///
+/// The inherents are the unsigned extrinsics the block starts with.
+fn inherents<Xt: Decode + ExtrinsicT>(extrinsics: &[Extrinsic]) -> Vec<Extrinsic> {
+	extrinsics
+		.iter()
+		.take_while(|xt| {
+			Xt::decode(&mut &xt.encode()[..])
+				.ok()
+				.and_then(|xt| xt.is_signed())
+				== Some(false)
+		})
+		.cloned()
+		.collect()
+}
+
+/// Compare the extrinsics of the `rebuilt` collation with the ones of the `archived` collation.
+fn compare_extrinsics(
+	archived: &[Extrinsic],
+	rebuilt: &[Extrinsic],
+) -> std::result::Result<String, String> {
+	match archived.iter().zip(rebuilt).position(|(archived, rebuilt)| archived != rebuilt) {
+		Some(index) => Err(format!("extrinsic #{} differs", index)),
+		None if archived.len() != rebuilt.len() => Err(format!(
+			"the archived collation has {} extrinsics, the rebuilt one {}",
+			archived.len(),
+			rebuilt.len(),
+		)),
+		None => Ok(format!("{} extrinsics", rebuilt.len())),
+	}
+}
+
+/// Compare the header of the `rebuilt` collation with the one of the `archived` collation.
+fn compare_headers(archived: &Header, rebuilt: &Header) -> std::result::Result<String, String> {
+	if archived == rebuilt {
+		return Ok(format!("{:?}", rebuilt.hash()));
+	}
+
+	let differences = [
+		("parent hash", archived.parent_hash != rebuilt.parent_hash),
+		("number", archived.number != rebuilt.number),
+		("extrinsics root", archived.extrinsics_root != rebuilt.extrinsics_root),
+		("state root", archived.state_root != rebuilt.state_root),
+		("digest", archived.digest != rebuilt.digest),
+	]
+	.iter()
+	.filter(|(_, differs)| *differs)
+	.map(|(name, _)| *name)
+	.collect::<Vec<_>>();
+
+	Err(format!("the {} differ", differences.join(", ")))
+}
+
+/// Build a collation on top of `parent` with the `inherents` followed by the transactions of the
+/// `pool`.
+///
+/// Returns the collation and the errors of the transactions that could not be included.
+fn rebuild<C, B>(
+	client: &C,
+	parent: H256,
+	inherents: Vec<Extrinsic>,
+	pool: Vec<Extrinsic>,
+) -> std::result::Result<(ParachainBlockData<Block>, Vec<String>), String>
+where
+	B: BackendT<Block>,
+	C: BlockBuilderProvider<B, Block, C> + ProvideRuntimeApi<Block>,
+	C::Api: BlockBuilderApi<Block, Error = sp_blockchain::Error>
+		+ ApiExt<Block, StateBackend = StateBackendFor<B, Block>>,
+{
+	let mut builder = client
+		.new_block_at(&BlockId::Hash(parent), Default::default(), true)
+		.map_err(|e| format!("Failed to start a block on top of `{:?}`: {}", parent, e))?;
+
+	for (index, inherent) in inherents.into_iter().enumerate() {
+		builder
+			.push(inherent)
+			.map_err(|e| format!("Inherent #{} of the archived collation failed: {}", index, e))?;
+	}
+
+	let mut failed = Vec::new();
+	for (index, xt) in pool.into_iter().enumerate() {
+		if let Err(e) = builder.push(xt) {
+			failed.push(format!("transaction #{}: {}", index, e));
+		}
+	}
+
+	let built = builder.build().map_err(|e| format!("Failed to build the block: {}", e))?;
+	let proof = built
+		.proof
+		.ok_or_else(|| "The block builder did not return the requested proof".to_string())?;
+	let (header, extrinsics) = built.block.deconstruct();
+
+	Ok((ParachainBlockData::new(header, extrinsics, proof), failed))
+}
+
+/// Replay the collation described by the given `params` and print a report.
+///
+/// The validation function is executed with `wasm_execution`. `Xt` is the extrinsic type of the
+/// parachain runtime, to tell the inherents of the archived collation apart. Returns an error if
+/// the rebuilt collation diverges from the archived one or fails the validation.
+pub fn replay_collation<C, B, Xt>(
+	client: &C,
+	wasm_execution: WasmExecutionMethod,
+	params: &ReplayCollationCommand,
+) -> Result<()>
+where
+	B: BackendT<Block>,
+	C: BlockBuilderProvider<B, Block, C>
+		+ ProvideRuntimeApi<Block>
+		+ StorageProvider<Block, B>
+		+ HeaderBackend<Block>,
+	C::Api: BlockBuilderApi<Block, Error = sp_blockchain::Error>
+		+ ApiExt<Block, StateBackend = StateBackendFor<B, Block>>,
+	Xt: Decode + ExtrinsicT,
+{
+	let relay_parent = parse_hash(&params.relay_parent)?;
+	let archived = read_archived_pov(&params.validation_data)?;
+	if archived.relay_parent != relay_parent {
+		return Err(format!(
+			"`{}` was built on the relay parent `{:?}`, not on `{:?}`",
+			params.validation_data.display(),
+			archived.relay_parent,
+			relay_parent,
+		)
+		.into());
+	}
+
+	let pool = read_pool(&params.pool)?;
+	let archived_collation = ParachainBlockData::<Block>::decode(&mut &archived.block_data.0[..])
+		.map_err(|e| {
+			format!("Invalid collation in `{}`: {:?}", params.validation_data.display(), e)
+		})?;
+
+	let parent = Header::decode(&mut &archived.local_validation.parent_head.0[..])
+		.map_err(|e| format!("Invalid parent head in the validation data: {:?}", e))?
+		.hash();
+	let at = BlockId::Hash(parent);
+	if client.header(at)?.is_none() {
+		return Err(format!(
+			"Parent block `{:?}` not found, sync the parachain up to the parent first",
+			parent,
+		)
+		.into());
+	}
+
+	let code = client
+		.storage(&at, &StorageKey(well_known_keys::CODE.to_vec()))?
+		.ok_or_else(|| format!("No validation code found at `{:?}`", parent))?
+		.0;
+
+	let validate = |block_data: &BlockData, head_data: &HeadData| {
+		validate_collation(
+			&code,
+			wasm_execution,
+			block_data,
+			head_data,
+			&archived.global_validation,
+			&archived.local_validation,
+		)
+		.map(|()| "passes the validation".to_string())
+	};
+
+	let mut checks = vec![Check {
+		name: "archived collation",
+		result: validate(&archived.block_data, &archived.head_data),
+	}];
+
+	let (rebuilt, failed) = rebuild(
+		client,
+		parent,
+		inherents::<Xt>(archived_collation.extrinsics()),
+		pool,
+	)?;
+
+	checks.push(Check {
+		name: "pool",
+		result: Ok(if failed.is_empty() {
+			"all transactions were included".into()
+		} else {
+			format!("not included: {}", failed.join("; "))
+		}),
+	});
+	checks.push(Check {
+		name: "extrinsics",
+		result: compare_extrinsics(archived_collation.extrinsics(), rebuilt.extrinsics()),
+	});
+	checks.push(Check {
+		name: "header",
+		result: compare_headers(archived_collation.header(), rebuilt.header()),
+	});
+
+	let head_data = HeadData(rebuilt.header().encode());
+	checks.push(Check {
+		name: "rebuilt collation",
+		result: validate(&BlockData(rebuilt.encode()), &head_data),
+	});
+
+	print_report(&checks, "The replayed collation diverges")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_runtime::OpaqueExtrinsic;
+
+	#[test]
+	fn pool_is_parsed_from_pending_extrinsics() {
+		let pool = parse_pool(r#"["0x0c010203", "0x00"]"#).unwrap();
+		assert_eq!(2, pool.len());
+
+		assert!(parse_pool(r#"["0x0c01"]"#).is_err());
+		assert!(parse_pool(r#"{"result": []}"#).is_err());
+	}
+
+	#[test]
+	fn first_differing_extrinsic_is_reported() {
+		let xt = |byte| OpaqueExtrinsic(vec![byte]);
+
+		assert!(compare_extrinsics(&[xt(1), xt(2)], &[xt(1), xt(2)]).is_ok());
+		assert_eq!(
+			Err("extrinsic #1 differs".to_string()),
+			compare_extrinsics(&[xt(1), xt(2)], &[xt(1), xt(3)]),
+		);
+		assert!(compare_extrinsics(&[xt(1), xt(2)], &[xt(1)]).is_err());
+	}
+}
@@ -14,27 +14,62 @@
 // You should have received a copy of the GNU General Public License
 // along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
 
+use crate::event_socket;
 use ansi_term::Color;
-use cumulus_collator::CollatorBuilder;
+use codec::{Decode, Encode};
+use cumulus_collator::{
+	dev::{self, DevAuthoring, DevCommand},
+	monitor_finality, read_relay_heads, AuthorInherent, CollateOn, CollatorBuilder,
+	CollatorStatus, EmptyBlocks, ParachainInherentExtension, PovArchive, ProduceEmptyBlocks,
+	RelayHeadRecorder, DEFAULT_EMPTY_BLOCK_INTERVAL, DEFAULT_KEEP_RECENT_COLLATIONS,
+	DEFAULT_KEEP_RECENT_REJECTIONS, DEFAULT_POV_CACHE_SIZE, DEFAULT_SELF_VALIDATION_TIMEOUT,
+	VALIDATION_HEAP_PAGES, VALIDATION_WASM_EXECUTION,
+};
+use cumulus_consensus::{BackedHead, FetchRetry, ParachainBlockImport};
 use cumulus_network::DelayedBlockAnnounceValidator;
+use cumulus_rpc::{
+	author::{ParachainAuthor, ParachainAuthorApi},
+	code::{RuntimeCode, RuntimeCodeApi},
+	dev::{Dev, DevApi},
+	era::{MortalEra, MortalEraApi},
+	Cumulus, CumulusApi, SubscriptionLimits,
+};
 use cumulus_service::{
-	prepare_node_config, start_collator, start_full_node, StartCollatorParams, StartFullNodeParams,
+	check_hardware, check_host_functions, check_runtime_apis, prepare_node_config,
+	purge_transactions, relay_chain_bootstrap, relay_chain_start_error, start_collator,
+	start_full_node, CountLimitedPool, RelayBootstrapOnFail, RpcExtension, RpcExtensionBuilder,
+	RuntimeApiRequirement, StartCollatorParams, StartFullNodeParams, TransactionTtl,
 };
+use futures::{channel::mpsc, FutureExt, StreamExt};
+use jsonrpc_pubsub::manager::SubscriptionManager;
+use log::{debug, info, warn};
 use polkadot_primitives::v0::CollatorPair;
 use rococo_parachain_primitives::Block;
-use sc_client_api::{Backend as BackendT, BlockBackend, Finalizer, UsageProvider};
-use sc_executor::native_executor_instance;
+use sc_client_api::{
+	Backend as BackendT, BlockBackend, BlockchainEvents, Finalizer, StorageProvider,
+	UsageProvider,
+};
+use sc_executor::{native_executor_instance, WasmExecutionMethod};
 pub use sc_executor::NativeExecutor;
 use sc_informant::OutputFormat;
 use sc_network::NetworkService;
+use sc_rpc::DenyUnsafe;
 use sc_service::{Configuration, PartialComponents, Role, TFullBackend, TFullClient, TaskManager};
 use sp_api::ConstructRuntimeApi;
 use sp_blockchain::HeaderBackend;
-use sp_consensus::{BlockImport, Environment, Error as ConsensusError, Proposer};
-use sp_core::{crypto::Pair, H256};
-use sp_runtime::traits::{BlakeTwo256, Block as BlockT};
+use sp_consensus::{BlockImport, BlockOrigin, Environment, Error as ConsensusError, Proposer};
+use sp_core::{
+	crypto::Pair,
+	storage::{well_known_keys, StorageKey},
+	H256,
+};
+use sp_runtime::{
+	generic::BlockId,
+	traits::{BlakeTwo256, Block as BlockT},
+};
+use sp_transaction_pool::TransactionPool;
 use sp_trie::PrefixedMemoryDB;
-use std::sync::Arc;
+use std::{num::NonZeroU32, path::PathBuf, sync::Arc, time::Duration};
 
 // Native executor instance.
 native_executor_instance!(
@@ -50,6 +85,137 @@ native_executor_instance!(
 	parachain_contracts_runtime::native_version,
 );
 
+/// The default of [`NodeOptions::finality_stall_warn`].
+const DEFAULT_FINALITY_STALL_WARN: Duration = Duration::from_secs(120);
+
+/// The default of [`NodeOptions::proposal_block_fill_ratio`].
+const DEFAULT_PROPOSAL_BLOCK_FILL_RATIO: f64 = 0.75;
+
+/// Cumulus specific options of the node that are not part of the Substrate [`Configuration`].
+///
+/// The defaults are the ones of the command line, which is checked by a test of the `command`
+/// module.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeOptions {
+	/// How fetching the validation data from the relay chain is retried.
+	pub fetch_retry: FetchRetry,
+	/// Author blocks without connecting to a relay chain.
+	///
+	/// Only intended for development.
+	pub dev_sealing: Option<DevSealing>,
+	/// How many recently built collations are kept in memory.
+	pub keep_recent_collations: usize,
+	/// How many recently rejected collations are kept.
+	pub keep_recent_rejections: usize,
+	/// How long transactions live in the transaction pool or `None` to keep them until they
+	/// are included or become invalid.
+	pub transaction_ttl: Option<TransactionTtl>,
+	/// Validate every collation with the local validation function before submitting it.
+	pub self_validate_collations: bool,
+	/// Drop a collation if its self validation takes longer than this.
+	pub self_validation_timeout: Duration,
+	/// How many PoVs that passed the self validation are remembered.
+	pub pov_cache_size: usize,
+	/// Warn if the finalized head does not advance for this long, while the best block does.
+	pub finality_stall_warn: Duration,
+	/// Report a parachain block as final once the relay chain block that included it has this
+	/// many descendants, instead of once it is finalized.
+	pub finality_confirmations: Option<u32>,
+	/// Record the relay chain heads the collator receives to this file.
+	pub record_relay_heads: Option<PathBuf>,
+	/// Benchmark the hardware at startup and warn if it is too slow to collate reliably.
+	pub hardware_benchmarks: bool,
+	/// Announce new best blocks to the peers when not collating.
+	pub full_node_announces: bool,
+	/// Withdraw the announcement of a collated block when its relay parent is more than this
+	/// many blocks behind the best relay chain block.
+	pub announcement_expiry_blocks: Option<u32>,
+	/// The maximum number of extrinsics from the transaction pool per block.
+	pub max_extrinsics_per_block: Option<usize>,
+	/// The share of the maximum block length the transactions of a proposed block may fill.
+	pub proposal_block_fill_ratio: f64,
+	/// The heap pages of the parachain runtime, if the runtime does not declare them.
+	pub runtime_heap_pages: u64,
+	/// Refuse to start if the runtime requires host functions the node does not provide.
+	pub strict_host_functions: bool,
+	/// The maximum number of downward messages that are processed per block.
+	pub max_downward_messages_per_block: Option<usize>,
+	/// The maximum number of Cumulus RPC subscriptions per connection.
+	pub rpc_max_subscriptions_per_connection: Option<usize>,
+	/// The maximum number of Cumulus RPC subscriptions of all connections.
+	pub rpc_max_subscriptions: Option<usize>,
+	/// Back off once this many collations on the same parachain head were not included.
+	pub authoring_backoff_threshold: Option<NonZeroU32>,
+	/// Which relay chain blocks trigger a collation attempt.
+	pub collate_on: CollateOn,
+	/// Build every collation on this parachain block instead of the included head.
+	pub collate_on_para_parent: Option<H256>,
+	/// Hold off the first collation for this long and until the relay chain is synced.
+	pub collation_start_delay: Option<Duration>,
+	/// When a block without transactions is built.
+	pub produce_empty_blocks: ProduceEmptyBlocks,
+	/// With [`ProduceEmptyBlocks::Timeout`], build an empty block if no block was built for this
+	/// long.
+	pub empty_block_interval: Duration,
+	/// Archive the produced PoVs to this directory, keeping at most this many of them.
+	pub pov_archive: Option<(PathBuf, usize)>,
+	/// How long the relay chain node may take to find a peer and what happens if it does not.
+	pub relay_bootstrap: Option<(Duration, RelayBootstrapOnFail)>,
+	/// Stream the collation events as JSON lines to the Unix domain socket at this path.
+	pub event_socket: Option<PathBuf>,
+}
+
+impl Default for NodeOptions {
+	fn default() -> Self {
+		Self {
+			fetch_retry: Default::default(),
+			dev_sealing: None,
+			keep_recent_collations: DEFAULT_KEEP_RECENT_COLLATIONS,
+			keep_recent_rejections: DEFAULT_KEEP_RECENT_REJECTIONS,
+			transaction_ttl: None,
+			self_validate_collations: false,
+			self_validation_timeout: DEFAULT_SELF_VALIDATION_TIMEOUT,
+			pov_cache_size: DEFAULT_POV_CACHE_SIZE,
+			finality_stall_warn: DEFAULT_FINALITY_STALL_WARN,
+			finality_confirmations: None,
+			record_relay_heads: None,
+			hardware_benchmarks: true,
+			full_node_announces: true,
+			announcement_expiry_blocks: None,
+			max_extrinsics_per_block: None,
+			proposal_block_fill_ratio: DEFAULT_PROPOSAL_BLOCK_FILL_RATIO,
+			runtime_heap_pages: VALIDATION_HEAP_PAGES,
+			strict_host_functions: false,
+			max_downward_messages_per_block: None,
+			rpc_max_subscriptions_per_connection: None,
+			rpc_max_subscriptions: None,
+			authoring_backoff_threshold: None,
+			collate_on: CollateOn::default(),
+			collate_on_para_parent: None,
+			collation_start_delay: None,
+			produce_empty_blocks: ProduceEmptyBlocks::default(),
+			empty_block_interval: DEFAULT_EMPTY_BLOCK_INTERVAL,
+			pov_archive: None,
+			relay_bootstrap: None,
+			event_socket: None,
+		}
+	}
+}
+
+/// How blocks are authored when running without a relay chain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DevSealing {
+	/// Author a block for every transaction that is imported into the transaction pool.
+	Instant,
+	/// Author and finalize blocks on demand, using the `cumulus_createBlock` and
+	/// `cumulus_finalizeBlock` RPC methods.
+	Manual,
+	/// Author a block every given interval.
+	Slot(Duration),
+	/// Author a block for every relay chain head recorded to the given file.
+	Replay(PathBuf),
+}
+
 /// Starts a `ServiceBuilder` for a full service.
 ///
 /// Use this macro if you don't actually need the full service, but just the builder in order to
@@ -63,7 +229,7 @@ pub fn new_partial<RuntimeApi, Executor>(
 		(),
 		sp_consensus::import_queue::BasicQueue<Block, PrefixedMemoryDB<BlakeTwo256>>,
 		sc_transaction_pool::FullPool<Block, TFullClient<Block, RuntimeApi, Executor>>,
-		(),
+		BackedHead<Block>,
 	>,
 	sc_service::Error,
 >
@@ -99,9 +265,11 @@ where
 		client.clone(),
 	);
 
+	let backed_head = BackedHead::default();
+
 	let import_queue = cumulus_consensus::import_queue::import_queue(
 		client.clone(),
-		client.clone(),
+		ParachainBlockImport::new(client.clone(), client.clone(), backed_head.clone()),
 		inherent_data_providers.clone(),
 		&task_manager.spawn_handle(),
 		registry.clone(),
@@ -116,7 +284,7 @@ where
 		transaction_pool,
 		inherent_data_providers,
 		select_chain: (),
-		other: (),
+		other: backed_head,
 	};
 
 	Ok(params)
@@ -132,6 +300,7 @@ pub fn start_test_collator<'a, Block, PF, BI, BS, Client, Backend>(
 		para_id,
 		proposer_factory,
 		inherent_data_providers,
+		inherent_extension,
 		block_import,
 		block_status,
 		announce_block,
@@ -140,6 +309,23 @@ pub fn start_test_collator<'a, Block, PF, BI, BS, Client, Backend>(
 		task_manager,
 		polkadot_config,
 		collator_key,
+		collator_status,
+		fetch_retry,
+		prometheus_registry,
+		backed_head,
+		self_validate_collations,
+		self_validation_timeout,
+		wasm_execution,
+		pov_cache_size,
+		relay_head_recorder,
+		pov_archive,
+		announcement_expiry_blocks,
+		max_downward_messages_per_block,
+		authoring_backoff_threshold,
+		collate_on,
+		forced_parent,
+		collation_start_delay,
+		empty_blocks,
 	}: StartCollatorParams<'a, Block, PF, BI, BS, Client>,
 ) -> sc_service::error::Result<()>
 where
@@ -156,6 +342,7 @@ where
 	Client: Finalizer<Block, Backend>
 		+ UsageProvider<Block>
 		+ HeaderBackend<Block>
+		+ StorageProvider<Block, Backend>
 		+ Send
 		+ Sync
 		+ BlockBackend<Block>
@@ -172,7 +359,25 @@ where
 		client,
 		announce_block,
 		block_announce_validator,
-	);
+		collator_status,
+		fetch_retry,
+		prometheus_registry,
+		backed_head,
+	)
+	.with_inherent_extension(inherent_extension)
+	.with_self_validation(self_validate_collations)
+	.with_self_validation_timeout(self_validation_timeout)
+	.with_wasm_execution(wasm_execution)
+	.with_pov_cache_size(pov_cache_size)
+	.with_relay_head_recorder(relay_head_recorder)
+	.with_pov_archive(pov_archive)
+	.with_announcement_expiry(announcement_expiry_blocks)
+	.with_max_downward_messages(max_downward_messages_per_block)
+	.with_authoring_backoff(authoring_backoff_threshold)
+	.with_collate_on(collate_on)
+	.with_forced_parent(forced_parent)
+	.with_collation_start_delay(collation_start_delay)
+	.with_empty_blocks(empty_blocks);
 
 	let (polkadot_future, polkadot_task_manager) = {
 		let (task_manager, client, handles, _network, _rpc_handlers) =
@@ -182,7 +387,8 @@ where
 				None,
 				false,
 				6000,
-			)?;
+			)
+			.map_err(relay_chain_start_error)?;
 
 		let test_client = polkadot_test_service::TestClient(client);
 
@@ -207,6 +413,111 @@ where
 	Ok(())
 }
 
+/// The runtime apis the node uses and whether it can work without them.
+fn runtime_api_requirements() -> Vec<RuntimeApiRequirement> {
+	vec![
+		RuntimeApiRequirement::of::<dyn sp_api::Core<Block>>("Core", true),
+		RuntimeApiRequirement::of::<dyn sp_block_builder::BlockBuilder<Block>>(
+			"BlockBuilder",
+			true,
+		),
+		RuntimeApiRequirement::of::<
+			dyn sp_transaction_pool::runtime_api::TaggedTransactionQueue<Block>,
+		>("TaggedTransactionQueue", true),
+		RuntimeApiRequirement::of::<dyn sp_api::Metadata<Block>>("Metadata", false),
+		RuntimeApiRequirement::of::<dyn sp_session::SessionKeys<Block>>("SessionKeys", false),
+		RuntimeApiRequirement::of::<dyn sp_offchain::OffchainWorkerApi<Block>>(
+			"OffchainWorkerApi",
+			false,
+		),
+	]
+}
+
+/// Log the heap pages the parachain runtime is executed with and warn if the relay chain
+/// validators execute the validation function with different ones.
+///
+/// The heap pages declared by the runtime in `:heappages` take precedence over the configured
+/// `heap_pages`.
+fn check_heap_pages<C>(client: &C, heap_pages: u64)
+where
+	C: StorageProvider<Block, TFullBackend<Block>> + HeaderBackend<Block>,
+{
+	let declared = client
+		.storage(
+			&BlockId::Hash(client.info().best_hash),
+			&StorageKey(well_known_keys::HEAP_PAGES.to_vec()),
+		)
+		.ok()
+		.flatten()
+		.and_then(|pages| u64::decode(&mut &pages.0[..]).ok());
+
+	let effective = match declared {
+		Some(declared) if declared != heap_pages => {
+			warn!(
+				"The runtime declares {} heap pages, which are used instead of \
+				`--runtime-heap-pages {}`",
+				declared, heap_pages,
+			);
+			declared
+		}
+		_ => heap_pages,
+	};
+
+	info!("Parachain executor: {} heap pages", effective);
+
+	if effective != VALIDATION_HEAP_PAGES {
+		warn!(
+			"The relay chain validators execute the validation function with {} heap pages, but \
+			the parachain runtime is executed with {}. Blocks may be built that fail the \
+			validation.",
+			VALIDATION_HEAP_PAGES, effective,
+		);
+	}
+}
+
+/// Log the wasm execution method the parachain runtime is executed with and warn if the relay
+/// chain validators execute the validation function with a different one.
+///
+/// The self validation of the collations uses the same method as the parachain runtime.
+fn check_wasm_execution(method: WasmExecutionMethod) {
+	info!("Parachain executor: {:?} wasm execution", method);
+
+	if method != VALIDATION_WASM_EXECUTION {
+		warn!(
+			"The relay chain validators execute the validation function {:?}, but the parachain \
+			runtime is executed {:?}. Both must give the same results, otherwise blocks may be \
+			built that fail the validation.",
+			VALIDATION_WASM_EXECUTION, method,
+		);
+	}
+}
+
+/// Log how much of the `max_block_length` the blocks built by this node fill.
+async fn log_block_fill<C>(client: Arc<C>, max_block_length: u32)
+where
+	C: BlockchainEvents<Block> + BlockBackend<Block>,
+{
+	let mut imported = client.import_notification_stream();
+
+	while let Some(notification) = imported.next().await {
+		if notification.origin != BlockOrigin::Own {
+			continue;
+		}
+
+		let size = match client.block_body(&BlockId::Hash(notification.hash)) {
+			Ok(Some(extrinsics)) => extrinsics.iter().map(Encode::encoded_size).sum::<usize>(),
+			_ => continue,
+		};
+
+		debug!(
+			"Built block #{} with {} bytes of extrinsics ({:.1}% of the maximum block length)",
+			notification.header.number,
+			size,
+			size as f64 * 100.0 / max_block_length as f64,
+		);
+	}
+}
+
 /// Start a node with the given parachain `Configuration` and relay chain `Configuration`.
 ///
 /// This is the actual implementation that is abstract over the executor and the runtime api.
@@ -217,6 +528,8 @@ fn start_node_impl<RuntimeApi, Executor, RB>(
 	id: polkadot_primitives::v0::Id,
 	validator: bool,
 	rpc_ext_builder: RB,
+	max_block_length: u32,
+	options: NodeOptions,
 	test: bool,
 ) -> sc_service::error::Result<(
 	TaskManager,
@@ -239,11 +552,7 @@ where
 		+ sp_block_builder::BlockBuilder<Block>,
 	sc_client_api::StateBackendFor<TFullBackend<Block>, Block>: sp_api::StateBackend<BlakeTwo256>,
 	Executor: sc_executor::NativeExecutionDispatch + 'static,
-	RB: Fn(
-			Arc<TFullClient<Block, RuntimeApi, Executor>>,
-		) -> jsonrpc_core::IoHandler<sc_rpc::Metadata>
-		+ Send
-		+ 'static,
+	RB: RpcExtensionBuilder<TFullClient<Block, RuntimeApi, Executor>>,
 {
 	if matches!(parachain_config.role, Role::Light) {
 		return Err("Light client not supported!".into());
@@ -251,6 +560,15 @@ where
 
 	let mut parachain_config = prepare_node_config(parachain_config);
 
+	info!(
+		"Parachain executor: at most {} runtime instances",
+		parachain_config.max_runtime_instances,
+	);
+	let wasm_execution = parachain_config.wasm_method;
+	check_wasm_execution(wasm_execution);
+
+	parachain_config.default_heap_pages = Some(options.runtime_heap_pages);
+
 	parachain_config.informant_output_format = OutputFormat {
 		enable_color: true,
 		prefix: format!("[{}] ", Color::Yellow.bold().paint("Parachain")),
@@ -268,6 +586,35 @@ where
 
 	let client = params.client.clone();
 	let backend = params.backend.clone();
+	let backed_head = params.other.clone();
+
+	let runtime_version = client.runtime_version_at(&BlockId::Hash(client.info().best_hash))?;
+	check_runtime_apis(&runtime_version, &runtime_api_requirements())
+		.map_err(sc_service::Error::Other)?;
+	check_heap_pages(&*client, options.runtime_heap_pages);
+
+	let code = client
+		.storage(
+			&BlockId::Hash(client.info().best_hash),
+			&StorageKey(well_known_keys::CODE.to_vec()),
+		)?
+		.ok_or_else(|| sc_service::Error::Other("The runtime code is missing".into()))?;
+	check_host_functions::<(sp_io::SubstrateHostFunctions, Executor::ExtendHostFunctions)>(
+		&code.0,
+		options.strict_host_functions,
+	)
+	.map_err(sc_service::Error::Other)?;
+
+	if validator && options.dev_sealing.is_none() && options.hardware_benchmarks {
+		let directory = parachain_config
+			.base_path
+			.as_ref()
+			.map(|p| p.path().to_path_buf())
+			.unwrap_or_else(std::env::temp_dir);
+
+		check_hardware(&directory, &Default::default());
+	}
+
 	let block_announce_validator = DelayedBlockAnnounceValidator::new();
 	let block_announce_validator_builder = {
 		let block_announce_validator = block_announce_validator.clone();
@@ -291,10 +638,60 @@ where
 			finality_proof_provider: None,
 		})?;
 
+	let collator_status = Arc::new(
+		CollatorStatus::with_keep_recent_collations(options.keep_recent_collations)
+			.with_keep_recent_rejections(options.keep_recent_rejections)
+			.with_finality_confirmations(options.finality_confirmations),
+	);
+	let (dev_command_sink, dev_commands) = match options.dev_sealing {
+		Some(DevSealing::Manual) => {
+			let (sink, stream) = mpsc::channel::<DevCommand<<Block as BlockT>::Hash>>(16);
+			(Some(sink), Some(stream))
+		}
+		_ => (None, None),
+	};
+
 	let rpc_extensions_builder = {
 		let client = client.clone();
+		let transaction_pool = transaction_pool.clone();
+		let collator_status = collator_status.clone();
+		let subscription_executor =
+			sc_rpc::SubscriptionTaskExecutor::new(task_manager.spawn_handle());
+		// Shared by all RPC servers, so the total limit holds over all of them.
+		let subscription_limits = SubscriptionLimits::new(
+			options.rpc_max_subscriptions_per_connection,
+			options.rpc_max_subscriptions,
+		);
+
+		Box::new(move |deny_unsafe| {
+			let mut io = RpcExtension::default();
+			rpc_ext_builder.extend_rpc(&mut io, client.clone(), deny_unsafe);
 
-		Box::new(move |_deny_unsafe| rpc_ext_builder(client.clone()))
+			io.extend_with(CumulusApi::to_delegate(
+				Cumulus::new(
+					client.clone(),
+					collator_status.clone(),
+					SubscriptionManager::new(Arc::new(subscription_executor.clone())),
+					deny_unsafe,
+				)
+				.with_subscription_limits(subscription_limits.clone()),
+			));
+			// Replaces `author_submitAndWatchExtrinsic`, to report the parachain finality.
+			io.extend_with(ParachainAuthorApi::to_delegate(
+				ParachainAuthor::new(
+					client.clone(),
+					transaction_pool.clone(),
+					collator_status.clone(),
+					SubscriptionManager::new(Arc::new(subscription_executor.clone())),
+				)
+				.with_subscription_limits(subscription_limits.clone()),
+			));
+			io.extend_with(RuntimeCodeApi::to_delegate(RuntimeCode::new(client.clone())));
+			if let Some(dev_command_sink) = dev_command_sink.clone() {
+				io.extend_with(DevApi::to_delegate(Dev::new(dev_command_sink)));
+			}
+			io
+		})
 	};
 
 	sc_service::spawn_tasks(sc_service::SpawnTasksParams {
@@ -313,23 +710,166 @@ where
 		system_rpc_tx,
 	})?;
 
-	let announce_block = {
-		let network = network.clone();
-		Arc::new(move |hash, data| network.announce_block(hash, data))
-	};
+	task_manager.spawn_handle().spawn(
+		"cumulus-monitor-finality",
+		monitor_finality(
+			client.clone(),
+			collator_status.clone(),
+			options.finality_stall_warn,
+		),
+	);
 
-	if validator {
+	if options.dev_sealing.is_none() {
+		if let Some((timeout, on_fail)) = options.relay_bootstrap {
+			task_manager.spawn_essential_handle().spawn(
+				"cumulus-relay-chain-bootstrap",
+				relay_chain_bootstrap(collator_status.clone(), timeout, on_fail),
+			);
+		}
+	}
+
+	if let Some(path) = options.event_socket.as_ref() {
+		let listener = event_socket::bind(path).map_err(|e| {
+			sc_service::Error::Other(format!(
+				"Failed to bind the event socket `{}`: {}",
+				path.display(),
+				e,
+			))
+		})?;
+
+		info!("Streaming the collation events to `{}`", path.display());
+
+		task_manager.spawn_handle().spawn(
+			"cumulus-event-socket",
+			event_socket::serve_events(listener, collator_status.collation_events()),
+		);
+	}
+
+	let max_proposal_bytes = (options.proposal_block_fill_ratio * max_block_length as f64) as usize;
+	if options.dev_sealing.is_some() || validator {
+		info!(
+			"Proposing blocks with at most {} bytes of transactions ({:.0}% of {} bytes)",
+			max_proposal_bytes,
+			options.proposal_block_fill_ratio * 100.0,
+			max_block_length,
+		);
+
+		task_manager.spawn_handle().spawn(
+			"cumulus-block-fill",
+			log_block_fill(client.clone(), max_block_length),
+		);
+	}
+
+	if let Some(transaction_ttl) = options.transaction_ttl {
+		task_manager.spawn_handle().spawn(
+			"cumulus-purge-transactions",
+			purge_transactions(transaction_pool.clone(), transaction_ttl),
+		);
+	}
+
+	let announce_block: Arc<dyn Fn(H256, Vec<u8>) + Send + Sync> =
+		if validator || options.full_node_announces {
+			let network = network.clone();
+			Arc::new(move |hash, data| network.announce_block(hash, data))
+		} else {
+			info!("Not announcing parachain blocks, only collators gossip them");
+			Arc::new(|_, _| ())
+		};
+
+	// The collator id encodes the same as the `AccountId` of its key, so the runtime credits the
+	// blocks to the account of `--collator-key`.
+	let author_inherent: Arc<dyn ParachainInherentExtension> =
+		Arc::new(AuthorInherent::new(collator_key.public()));
+
+	if let Some(sealing) = options.dev_sealing {
+		let proposer_factory = sc_basic_authorship::ProposerFactory::new(
+			client.clone(),
+			Arc::new(
+				CountLimitedPool::new(transaction_pool.clone(), options.max_extrinsics_per_block)
+					.with_max_bytes(Some(max_proposal_bytes)),
+			),
+			prometheus_registry.as_ref(),
+		);
+		let authoring = DevAuthoring::new(
+			proposer_factory,
+			params.inherent_data_providers,
+			client.clone(),
+			client.clone(),
+		)
+		.with_inherent_extension(author_inherent);
+
+		info!("Authoring blocks without a relay chain ({:?})", sealing);
+
+		let authoring_future = match sealing {
+			DevSealing::Instant => dev::run_instant(authoring, transaction_pool).boxed(),
+			DevSealing::Manual => dev::run_manual(
+				authoring,
+				transaction_pool,
+				dev_commands.expect("Commands are created for manual sealing; qed"),
+			)
+			.boxed(),
+			DevSealing::Slot(block_time) => {
+				dev::run_with_block_time(authoring, block_time).boxed()
+			}
+			DevSealing::Replay(path) => {
+				let heads = read_relay_heads(&path).map_err(sc_service::Error::Other)?;
+
+				info!("Replaying {} relay chain heads from `{}`", heads.len(), path.display());
+
+				dev::run_replay(authoring, heads).boxed()
+			}
+		};
+
+		task_manager
+			.spawn_essential_handle()
+			.spawn("cumulus-dev-authoring", authoring_future);
+	} else if validator {
 		let proposer_factory = sc_basic_authorship::ProposerFactory::new(
 			client.clone(),
-			transaction_pool,
+			Arc::new(
+				CountLimitedPool::new(transaction_pool, options.max_extrinsics_per_block)
+					.with_max_bytes(Some(max_proposal_bytes)),
+			),
 			prometheus_registry.as_ref(),
 		);
 
+		let relay_head_recorder = options
+			.record_relay_heads
+			.as_ref()
+			.map(|path| {
+				RelayHeadRecorder::create(path).map_err(|e| {
+					sc_service::Error::Other(format!(
+						"Failed to create `{}` to record the relay chain heads: {}",
+						path.display(),
+						e,
+					))
+				})
+			})
+			.transpose()?
+			.map(Arc::new);
+
+		let pov_archive = options
+			.pov_archive
+			.as_ref()
+			.map(|(dir, keep)| {
+				PovArchive::open(dir, *keep).map_err(|e| {
+					sc_service::Error::Other(format!(
+						"Failed to open the PoV archive `{}`: {}",
+						dir.display(),
+						e,
+					))
+				})
+			})
+			.transpose()?
+			.map(Arc::new);
+
+		let pool = transaction_pool.clone();
 		let params = StartCollatorParams {
 			para_id: id,
 			block_import: client.clone(),
 			proposer_factory,
 			inherent_data_providers: params.inherent_data_providers,
+			inherent_extension: author_inherent,
 			block_status: client.clone(),
 			announce_block,
 			client: client.clone(),
@@ -337,6 +877,27 @@ where
 			task_manager: &mut task_manager,
 			polkadot_config,
 			collator_key,
+			collator_status,
+			fetch_retry: options.fetch_retry,
+			prometheus_registry: prometheus_registry.clone(),
+			backed_head,
+			self_validate_collations: options.self_validate_collations,
+			self_validation_timeout: options.self_validation_timeout,
+			wasm_execution,
+			pov_cache_size: options.pov_cache_size,
+			relay_head_recorder,
+			pov_archive,
+			announcement_expiry_blocks: options.announcement_expiry_blocks,
+			max_downward_messages_per_block: options.max_downward_messages_per_block,
+			authoring_backoff_threshold: options.authoring_backoff_threshold,
+			collate_on: options.collate_on,
+			forced_parent: options.collate_on_para_parent,
+			collation_start_delay: options.collation_start_delay,
+			empty_blocks: Some(EmptyBlocks::new(
+				options.produce_empty_blocks,
+				options.empty_block_interval,
+				Arc::new(move || pool.status().ready > 0),
+			)),
 		};
 
 		if test {
@@ -353,6 +914,10 @@ where
 			block_announce_validator,
 			task_manager: &mut task_manager,
 			para_id: id,
+			collator_status,
+			fetch_retry: options.fetch_retry,
+			prometheus_registry: prometheus_registry.clone(),
+			backed_head,
 		};
 
 		start_full_node(params)?;
@@ -363,6 +928,51 @@ where
 	Ok((task_manager, client, network))
 }
 
+/// The RPC methods of the test parachain, in addition to the built-in ones.
+struct TestRpcExtensions;
+
+impl RpcExtensionBuilder<TFullClient<Block, parachain_runtime::RuntimeApi, RuntimeExecutor>>
+	for TestRpcExtensions
+{
+	fn extend_rpc(
+		&self,
+		io: &mut RpcExtension,
+		client: Arc<TFullClient<Block, parachain_runtime::RuntimeApi, RuntimeExecutor>>,
+		_: DenyUnsafe,
+	) {
+		io.extend_with(MortalEraApi::to_delegate(MortalEra::new(
+			client,
+			parachain_runtime::BlockHashCount::get().into(),
+		)));
+	}
+}
+
+/// The RPC methods of the contracts parachain, in addition to the built-in ones.
+struct ContractsRpcExtensions;
+
+impl
+	RpcExtensionBuilder<
+		TFullClient<Block, parachain_contracts_runtime::RuntimeApi, ContractsRuntimeExecutor>,
+	> for ContractsRpcExtensions
+{
+	fn extend_rpc(
+		&self,
+		io: &mut RpcExtension,
+		client: Arc<
+			TFullClient<Block, parachain_contracts_runtime::RuntimeApi, ContractsRuntimeExecutor>,
+		>,
+		_: DenyUnsafe,
+	) {
+		use cumulus_pallet_contracts_rpc::{Contracts, ContractsApi};
+
+		io.extend_with(ContractsApi::to_delegate(Contracts::new(client.clone())));
+		io.extend_with(MortalEraApi::to_delegate(MortalEra::new(
+			client,
+			parachain_contracts_runtime::BlockHashCount::get().into(),
+		)));
+	}
+}
+
 /// Start a normal parachain node.
 pub fn start_node(
 	parachain_config: Configuration,
@@ -370,6 +980,7 @@ pub fn start_node(
 	polkadot_config: polkadot_collator::Configuration,
 	id: polkadot_primitives::v0::Id,
 	validator: bool,
+	options: NodeOptions,
 	test: bool,
 ) -> sc_service::error::Result<(
 	TaskManager,
@@ -382,7 +993,9 @@ pub fn start_node(
 		polkadot_config,
 		id,
 		validator,
-		|_| Default::default(),
+		TestRpcExtensions,
+		parachain_runtime::MaximumBlockLength::get(),
+		options,
 		test,
 	)
 }
@@ -394,6 +1007,7 @@ pub fn start_contracts_node(
 	polkadot_config: polkadot_collator::Configuration,
 	id: polkadot_primitives::v0::Id,
 	validator: bool,
+	options: NodeOptions,
 	test: bool,
 ) -> sc_service::error::Result<TaskManager> {
 	start_node_impl::<parachain_contracts_runtime::RuntimeApi, ContractsRuntimeExecutor, _>(
@@ -402,13 +1016,9 @@ pub fn start_contracts_node(
 		polkadot_config,
 		id,
 		validator,
-		|client| {
-			let mut io = jsonrpc_core::IoHandler::default();
-
-			use cumulus_pallet_contracts_rpc::{Contracts, ContractsApi};
-			io.extend_with(ContractsApi::to_delegate(Contracts::new(client)));
-			io
-		},
+		ContractsRpcExtensions,
+		parachain_contracts_runtime::MaximumBlockLength::get(),
+		options,
 		test,
 	)
 	.map(|r| r.0)
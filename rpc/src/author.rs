@@ -0,0 +1,436 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! RPC methods to submit a transaction and watch its status on the parachain.
+//!
+//! The methods take the place of the Substrate methods of the same name, as RPC extensions are
+//! registered last. The status updates are the same, except that a transaction is `finalized`
+//! once a final parachain block includes it, as reported by `cumulus_subscribeParachainFinality`.
+//! With `--finality-confirmations` this is before the relay chain finalized the block that
+//! included the parachain block.
+
+use std::sync::Arc;
+
+use codec::Decode;
+use cumulus_collator::CollatorStatus;
+use cumulus_primitives::HeadData;
+use futures::{future, stream, FutureExt, Stream, StreamExt, TryFutureExt, TryStreamExt};
+use jsonrpc_core::{
+	futures::{future::Executor as Executor01, Future, Sink},
+	Result,
+};
+use jsonrpc_derive::rpc;
+use jsonrpc_pubsub::{
+	manager::SubscriptionManager, typed::Subscriber, PubSubMetadata, SubscriptionId,
+};
+use log::warn;
+use sc_rpc::author::error::Error;
+use serde::Serialize;
+use sp_blockchain::HeaderBackend;
+use sp_core::Bytes;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, Header as HeaderT},
+};
+use sp_transaction_pool::{
+	error::IntoPoolError, BlockHash, TransactionFor, TransactionPool, TransactionSource,
+	TransactionStatus, TxHash,
+};
+
+use crate::{subscription_limits::Slot, SubscriptionLimits};
+
+pub use self::gen_client::Client as ParachainAuthorClient;
+
+/// RPC methods to submit a transaction and watch its status on the parachain.
+#[rpc]
+pub trait ParachainAuthorApi<Hash, BlockHash> {
+	/// RPC metadata
+	type Metadata;
+
+	/// Submit an extrinsic and watch its status until it is finalized or dropped.
+	///
+	/// The extrinsic is `finalized` once a final parachain block includes it. Rejected once the
+	/// subscription limits of the node are reached, the extrinsic is not submitted then.
+	#[pubsub(
+		subscription = "author_extrinsicUpdate",
+		subscribe,
+		name = "author_submitAndWatchExtrinsic"
+	)]
+	fn watch_extrinsic(
+		&self,
+		metadata: Self::Metadata,
+		subscriber: Subscriber<TransactionStatus<Hash, BlockHash>>,
+		extrinsic: Bytes,
+	);
+
+	/// Unsubscribe from the status of an extrinsic.
+	#[pubsub(
+		subscription = "author_extrinsicUpdate",
+		unsubscribe,
+		name = "author_unwatchExtrinsic"
+	)]
+	fn unwatch_extrinsic(
+		&self,
+		metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> Result<bool>;
+}
+
+/// Turns the status updates of the transaction pool into the ones sent to the subscriber.
+///
+/// The transaction pool reports a transaction as finalized once its block is finalized, which is
+/// replaced by the parachain finality.
+struct StatusTracker<Hash, BlockHash> {
+	/// The block the transaction is in.
+	in_block: Option<BlockHash>,
+	/// Whether the transaction reached its final status.
+	done: bool,
+	_marker: std::marker::PhantomData<Hash>,
+}
+
+impl<Hash, BlockHash: Clone + PartialEq> StatusTracker<Hash, BlockHash> {
+	fn new() -> Self {
+		Self {
+			in_block: None,
+			done: false,
+			_marker: Default::default(),
+		}
+	}
+
+	/// Returns the update to send for the `status` of the transaction pool.
+	fn on_status(
+		&mut self,
+		status: TransactionStatus<Hash, BlockHash>,
+	) -> Option<TransactionStatus<Hash, BlockHash>> {
+		match status {
+			TransactionStatus::Finalized(_) => return None,
+			TransactionStatus::InBlock(ref hash) => self.in_block = Some(hash.clone()),
+			TransactionStatus::Retracted(ref hash) if self.in_block.as_ref() == Some(hash) => {
+				self.in_block = None
+			}
+			TransactionStatus::Usurped(_)
+			| TransactionStatus::FinalityTimeout(_)
+			| TransactionStatus::Dropped
+			| TransactionStatus::Invalid => self.done = true,
+			_ => {}
+		}
+
+		Some(status)
+	}
+
+	/// Returns the update to send for a new final parachain head.
+	///
+	/// `is_final` returns whether the given block is the final head or one of its ancestors.
+	fn on_final_head(
+		&mut self,
+		is_final: impl FnOnce(&BlockHash) -> bool,
+	) -> Option<TransactionStatus<Hash, BlockHash>> {
+		match self.in_block.clone() {
+			Some(hash) if is_final(&hash) => {
+				self.done = true;
+				Some(TransactionStatus::Finalized(hash))
+			}
+			_ => None,
+		}
+	}
+}
+
+/// Returns whether `block` is the `final_head` or one of its ancestors.
+fn is_final<Block, C>(client: &C, block: &Block::Hash, final_head: &Block::Header) -> bool
+where
+	Block: BlockT,
+	C: HeaderBackend<Block>,
+{
+	let number = match client.number(*block) {
+		Ok(Some(number)) => number,
+		_ => return false,
+	};
+
+	let mut header = final_head.clone();
+	loop {
+		if header.hash() == *block {
+			return true;
+		}
+
+		if *header.number() <= number {
+			return false;
+		}
+
+		header = match client.header(BlockId::Hash(*header.parent_hash())) {
+			Ok(Some(header)) => header,
+			_ => return false,
+		};
+	}
+}
+
+enum WatchEvent<Status, Header> {
+	Status(Status),
+	FinalHead(Header),
+}
+
+/// Send the status updates of a transaction to the `subscriber`, until its final status.
+///
+/// `is_final` returns whether the given block is final under the given final head. The `slot` of
+/// the subscription is released once the subscription ends.
+fn send_updates<Hash, BlockHash, Head>(
+	subscriptions: &SubscriptionManager,
+	subscriber: Subscriber<TransactionStatus<Hash, BlockHash>>,
+	statuses: impl Stream<Item = TransactionStatus<Hash, BlockHash>> + Send + 'static,
+	final_heads: impl Stream<Item = Head> + Send + 'static,
+	is_final: impl Fn(&BlockHash, &Head) -> bool + Send + 'static,
+	slot: Slot,
+) where
+	Hash: Serialize + Send + 'static,
+	BlockHash: Serialize + Clone + PartialEq + Send + 'static,
+	Head: Send + 'static,
+{
+	let events = stream::select(
+		statuses.map(WatchEvent::Status),
+		final_heads.map(WatchEvent::FinalHead),
+	)
+	.boxed();
+
+	// Ends right after the final status, the streams of the pool and the final heads do not.
+	let updates = stream::unfold(
+		(events, StatusTracker::new(), is_final),
+		|(mut events, mut tracker, is_final)| async move {
+			while !tracker.done {
+				let update = match events.next().await? {
+					WatchEvent::Status(status) => tracker.on_status(status),
+					WatchEvent::FinalHead(head) => {
+						tracker.on_final_head(|block| is_final(block, &head))
+					}
+				};
+
+				if let Some(update) = update {
+					return Some((update, (events, tracker, is_final)));
+				}
+			}
+
+			None
+		},
+	)
+	.map(|status| Ok::<_, ()>(Ok(status)))
+	.boxed()
+	.compat();
+
+	subscriptions.add(subscriber, |sink| {
+		sink.sink_map_err(|e| warn!("Error sending notifications: {:?}", e))
+			.send_all(updates)
+			.map(move |_| drop(slot))
+	});
+}
+
+/// An implementation of [`ParachainAuthorApi`].
+pub struct ParachainAuthor<P, C> {
+	client: Arc<C>,
+	pool: Arc<P>,
+	collator_status: Arc<CollatorStatus>,
+	subscriptions: SubscriptionManager,
+	subscription_limits: SubscriptionLimits,
+}
+
+impl<P, C> ParachainAuthor<P, C> {
+	/// Create new `ParachainAuthor` that submits to the transaction `pool` and follows the final
+	/// parachain heads of the `collator_status`.
+	pub fn new(
+		client: Arc<C>,
+		pool: Arc<P>,
+		collator_status: Arc<CollatorStatus>,
+		subscriptions: SubscriptionManager,
+	) -> Self {
+		ParachainAuthor {
+			client,
+			pool,
+			collator_status,
+			subscriptions,
+			subscription_limits: Default::default(),
+		}
+	}
+
+	/// Reject new subscriptions once the given `limits` are reached.
+	///
+	/// By default the number of subscriptions is not limited.
+	pub fn with_subscription_limits(mut self, limits: SubscriptionLimits) -> Self {
+		self.subscription_limits = limits;
+		self
+	}
+}
+
+impl<P, C> ParachainAuthorApi<TxHash<P>, BlockHash<P>> for ParachainAuthor<P, C>
+where
+	P: TransactionPool + Sync + Send + 'static,
+	C: HeaderBackend<P::Block> + Send + Sync + 'static,
+{
+	type Metadata = sc_rpc::Metadata;
+
+	fn watch_extrinsic(
+		&self,
+		metadata: Self::Metadata,
+		subscriber: Subscriber<TransactionStatus<TxHash<P>, BlockHash<P>>>,
+		extrinsic: Bytes,
+	) {
+		let slot = match self.subscription_limits.reserve(metadata.session().as_ref()) {
+			Ok(slot) => slot,
+			Err(e) => {
+				let _ = subscriber.reject(e);
+				return;
+			}
+		};
+
+		let submit = || -> std::result::Result<_, Error> {
+			let extrinsic = TransactionFor::<P>::decode(&mut &extrinsic[..])?;
+			let best = self.client.info().best_hash;
+
+			Ok(self
+				.pool
+				.submit_and_watch(&BlockId::hash(best), TransactionSource::External, extrinsic)
+				.map_err(|e| {
+					e.into_pool_error()
+						.map(Error::from)
+						.unwrap_or_else(|e| Error::Verification(Box::new(e)))
+				}))
+		};
+
+		let submitted = match submit() {
+			Ok(submitted) => submitted,
+			Err(e) => {
+				let _ = subscriber.reject(e.into());
+				return;
+			}
+		};
+
+		let client = self.client.clone();
+		let final_heads = self.collator_status.final_heads();
+		let subscriptions = self.subscriptions.clone();
+
+		// The slot is released if the submission fails, as `slot` is dropped with the future.
+		let future = submitted.map(move |submitted| {
+			let statuses = match submitted {
+				Ok(statuses) => statuses,
+				Err(e) => {
+					warn!("Failed to submit extrinsic: {}", e);
+					let _ = subscriber.reject(e.into());
+					return;
+				}
+			};
+
+			let final_heads = final_heads.filter_map(|head| {
+				future::ready(HeadData::<P::Block>::decode(&mut &head.0[..]).ok().map(|h| h.header))
+			});
+
+			send_updates(
+				&subscriptions,
+				subscriber,
+				statuses,
+				final_heads,
+				move |block, head| is_final::<P::Block, _>(&*client, block, head),
+				slot,
+			);
+		});
+
+		let _ = self
+			.subscriptions
+			.executor()
+			.execute(Box::new(future.map(|()| Ok::<_, ()>(())).boxed().compat()));
+	}
+
+	fn unwatch_extrinsic(
+		&self,
+		_metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> Result<bool> {
+		Ok(self.subscriptions.cancel(id))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::channel::mpsc;
+	use jsonrpc_core::futures::Stream as _;
+	use sc_rpc::SubscriptionTaskExecutor;
+	use sp_core::testing::TaskExecutor;
+
+	type Tracker = StatusTracker<u64, u64>;
+
+	#[test]
+	fn finalized_once_a_final_head_includes_the_block() {
+		let mut tracker = Tracker::new();
+
+		assert_eq!(Some(TransactionStatus::Ready), tracker.on_status(TransactionStatus::Ready));
+		assert_eq!(None, tracker.on_final_head(|_| true));
+		assert!(tracker.on_status(TransactionStatus::InBlock(1)).is_some());
+
+		// The finality of the transaction pool is replaced by the parachain finality.
+		assert_eq!(None, tracker.on_status(TransactionStatus::Finalized(1)));
+		assert_eq!(None, tracker.on_final_head(|_| false));
+		assert!(!tracker.done);
+
+		assert_eq!(Some(TransactionStatus::Finalized(1)), tracker.on_final_head(|b| *b == 1));
+		assert!(tracker.done);
+	}
+
+	#[test]
+	fn retracted_block_is_not_finalized() {
+		let mut tracker = Tracker::new();
+
+		tracker.on_status(TransactionStatus::InBlock(1));
+		tracker.on_status(TransactionStatus::Retracted(1));
+		assert_eq!(None, tracker.on_final_head(|_| true));
+
+		tracker.on_status(TransactionStatus::InBlock(2));
+		assert_eq!(Some(TransactionStatus::Finalized(2)), tracker.on_final_head(|_| true));
+
+		let mut tracker = Tracker::new();
+		assert!(tracker.on_status(TransactionStatus::Dropped).is_some());
+		assert!(tracker.done);
+	}
+
+	#[test]
+	fn slot_is_released_once_the_extrinsic_is_finalized() {
+		let executor = SubscriptionTaskExecutor::new(TaskExecutor::new());
+		let subscriptions = SubscriptionManager::new(Arc::new(executor));
+		let limits = SubscriptionLimits::new(None, Some(1));
+		let (statuses, status_updates) = mpsc::unbounded();
+		let (final_heads, final_head_updates) = mpsc::unbounded();
+		let (subscriber, _id, transport) = Subscriber::new_test("author_extrinsicUpdate");
+
+		send_updates(
+			&subscriptions,
+			subscriber,
+			status_updates,
+			final_head_updates,
+			|block: &u64, head: &u64| block <= head,
+			limits.reserve(None).unwrap(),
+		);
+		assert_eq!(1, limits.active());
+
+		statuses.unbounded_send(TransactionStatus::<u64, u64>::InBlock(1)).unwrap();
+		let (in_block, transport) = transport.into_future().wait().map_err(|_| ()).unwrap();
+		assert!(in_block.unwrap().contains(r#""inBlock":1"#));
+
+		// Ends once the subscription dropped its sink, although `statuses` and `final_heads` are
+		// still open.
+		final_heads.unbounded_send(2).unwrap();
+		let updates = transport.collect().wait().unwrap();
+		assert_eq!(1, updates.len());
+		assert!(updates[0].contains(r#""finalized":1"#));
+
+		assert_eq!(0, limits.active());
+		assert!(limits.reserve(None).is_ok());
+	}
+}
@@ -0,0 +1,144 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! RPC method to fetch the hash of the runtime code of the parachain.
+
+use std::{marker::PhantomData, sync::Arc};
+
+use jsonrpc_core::{Error, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use sc_client_api::{Backend as BackendT, StorageProvider};
+use sp_blockchain::HeaderBackend;
+use sp_core::storage::{well_known_keys, StorageKey};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+
+pub use self::gen_client::Client as RuntimeCodeClient;
+
+/// The error code returned when the runtime code could not be read.
+const RUNTIME_CODE_ERROR: i64 = 3000;
+
+/// RPC method to fetch the hash of the runtime code of the parachain.
+#[rpc]
+pub trait RuntimeCodeApi<Hash> {
+	/// Returns the blake2 hash of the runtime code (`:code`) at the block `at`, or at the best
+	/// block if `None`.
+	///
+	/// The hash changes in the block that enacts a runtime upgrade.
+	#[rpc(name = "cumulus_runtimeCodeHash")]
+	fn runtime_code_hash(&self, at: Option<Hash>) -> Result<Hash>;
+}
+
+/// An implementation of [`RuntimeCodeApi`].
+pub struct RuntimeCode<C, B, BE> {
+	client: Arc<C>,
+	_marker: PhantomData<(B, BE)>,
+}
+
+impl<C, B, BE> RuntimeCode<C, B, BE> {
+	/// Create new `RuntimeCode` with the given reference to the client.
+	pub fn new(client: Arc<C>) -> Self {
+		RuntimeCode {
+			client,
+			_marker: Default::default(),
+		}
+	}
+}
+
+fn runtime_code_error(message: impl Into<String>) -> Error {
+	Error {
+		code: ErrorCode::ServerError(RUNTIME_CODE_ERROR),
+		message: message.into(),
+		data: None,
+	}
+}
+
+impl<C, Block, BE> RuntimeCodeApi<Block::Hash> for RuntimeCode<C, Block, BE>
+where
+	Block: BlockT,
+	BE: BackendT<Block> + Send + Sync + 'static,
+	C: StorageProvider<Block, BE> + HeaderBackend<Block> + Send + Sync + 'static,
+{
+	fn runtime_code_hash(&self, at: Option<Block::Hash>) -> Result<Block::Hash> {
+		let at = at.unwrap_or_else(|| self.client.info().best_hash);
+
+		self.client
+			.storage_hash(
+				&BlockId::Hash(at),
+				&StorageKey(well_known_keys::CODE.to_vec()),
+			)
+			.map_err(|e| runtime_code_error(format!("Failed to read the runtime code: {:?}", e)))?
+			.ok_or_else(|| runtime_code_error(format!("No runtime code found at `{:?}`", at)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jsonrpc_core::MetaIoHandler;
+	use serde_json::json;
+	use sp_core::{blake2_256, H256};
+	use test_client::{
+		runtime::Block, Backend, Client, DefaultTestClientBuilderExt, TestClientBuilder,
+		TestClientBuilderExt,
+	};
+
+	/// Call `method` of `api` with `params` and return the response.
+	fn call(
+		api: RuntimeCode<Client, Block, Backend>,
+		method: &str,
+		params: serde_json::Value,
+	) -> serde_json::Value {
+		let mut io = MetaIoHandler::<sc_rpc::Metadata>::default();
+		io.extend_with(RuntimeCodeApi::to_delegate(api));
+
+		let request = json!({"jsonrpc": "2.0", "method": method, "params": params, "id": 1});
+		let response = io
+			.handle_request_sync(&request.to_string(), Default::default())
+			.unwrap();
+
+		serde_json::from_str(&response).unwrap()
+	}
+
+	#[test]
+	fn runtime_code_hash_is_the_hash_of_the_code() {
+		let client = Arc::new(TestClientBuilder::new().build());
+		let genesis_hash = client.info().genesis_hash;
+		let code_key = StorageKey(well_known_keys::CODE.to_vec());
+		let code = client
+			.storage(&BlockId::Hash(genesis_hash), &code_key)
+			.unwrap()
+			.expect("The genesis block has a runtime code");
+		let code_hash = H256::from(blake2_256(&code.0));
+
+		let runtime_code_hash = |params| {
+			call(
+				RuntimeCode::new(client.clone()),
+				"cumulus_runtimeCodeHash",
+				params,
+			)
+		};
+
+		assert_eq!(json!(code_hash), runtime_code_hash(json!([]))["result"]);
+		assert_eq!(
+			json!(code_hash),
+			runtime_code_hash(json!([genesis_hash]))["result"]
+		);
+
+		// The runtime code of an unknown block can not be read.
+		let response = runtime_code_hash(json!([H256::repeat_byte(1)]));
+		assert_eq!(json!(RUNTIME_CODE_ERROR), response["error"]["code"]);
+	}
+}
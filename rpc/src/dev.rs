@@ -0,0 +1,113 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! RPC methods to control the block authoring of a node that runs with `--sealing manual`.
+
+use cumulus_collator::dev::DevCommand;
+use futures::{
+	channel::{mpsc, oneshot},
+	FutureExt, SinkExt, TryFutureExt,
+};
+use jsonrpc_core::{Error, ErrorCode};
+use jsonrpc_derive::rpc;
+use serde::{de::DeserializeOwned, Serialize};
+
+pub use self::gen_client::Client as DevClient;
+
+/// The error code returned when authoring or finalizing a block failed.
+const DEV_AUTHORING_ERROR: i64 = 2000;
+
+/// Future that resolves to the result of an RPC call.
+pub type FutureResult<T> = Box<dyn jsonrpc_core::futures::Future<Item = T, Error = Error> + Send>;
+
+/// RPC methods to author and finalize blocks on demand.
+#[rpc]
+pub trait DevApi<Hash> {
+	/// Author a new block on top of the current best block and return its hash.
+	///
+	/// - `create_empty`: Author the block even if there are no transactions ready for inclusion.
+	/// - `finalize`: Finalize the block directly after it was imported.
+	#[rpc(name = "cumulus_createBlock")]
+	fn create_block(&self, create_empty: bool, finalize: bool) -> FutureResult<Hash>;
+
+	/// Finalize the block with the given `hash`.
+	#[rpc(name = "cumulus_finalizeBlock")]
+	fn finalize_block(&self, hash: Hash) -> FutureResult<bool>;
+}
+
+/// An implementation of [`DevApi`] that forwards the calls to the dev authoring.
+pub struct Dev<Hash> {
+	commands: mpsc::Sender<DevCommand<Hash>>,
+}
+
+impl<Hash> Dev<Hash> {
+	/// Create new `Dev` that sends the commands to the given `commands` sink.
+	pub fn new(commands: mpsc::Sender<DevCommand<Hash>>) -> Self {
+		Dev { commands }
+	}
+}
+
+fn dev_error(message: impl Into<String>) -> Error {
+	Error {
+		code: ErrorCode::ServerError(DEV_AUTHORING_ERROR),
+		message: message.into(),
+		data: None,
+	}
+}
+
+/// Send a command to the dev authoring and wait for the result.
+async fn send_command<Hash, T>(
+	mut commands: mpsc::Sender<DevCommand<Hash>>,
+	command: impl FnOnce(oneshot::Sender<Result<T, String>>) -> DevCommand<Hash>,
+) -> Result<T, Error> {
+	let (sender, receiver) = oneshot::channel();
+
+	commands
+		.send(command(sender))
+		.await
+		.map_err(|_| dev_error("Dev authoring is not running"))?;
+
+	receiver
+		.await
+		.map_err(|_| dev_error("Dev authoring dropped the request"))?
+		.map_err(dev_error)
+}
+
+impl<Hash> DevApi<Hash> for Dev<Hash>
+where
+	Hash: Serialize + DeserializeOwned + Send + 'static,
+{
+	fn create_block(&self, create_empty: bool, finalize: bool) -> FutureResult<Hash> {
+		let future = send_command(self.commands.clone(), move |sender| {
+			DevCommand::CreateBlock {
+				create_empty,
+				finalize,
+				sender,
+			}
+		});
+
+		Box::new(future.boxed().compat())
+	}
+
+	fn finalize_block(&self, hash: Hash) -> FutureResult<bool> {
+		let future = send_command(self.commands.clone(), move |sender| {
+			DevCommand::FinalizeBlock { hash, sender }
+		})
+		.map_ok(|()| true);
+
+		Box::new(future.boxed().compat())
+	}
+}
@@ -0,0 +1,121 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! RPC method to pick a valid era for mortal transactions.
+
+use std::{marker::PhantomData, sync::Arc};
+
+use jsonrpc_core::Result;
+use jsonrpc_derive::rpc;
+use serde::{Deserialize, Serialize};
+use sp_blockchain::HeaderBackend;
+use sp_runtime::traits::{Block as BlockT, NumberFor};
+
+pub use self::gen_client::Client as MortalEraClient;
+
+/// The shortest period of a mortal era.
+const MIN_PERIOD: u64 = 4;
+/// The longest period of a mortal era.
+const MAX_PERIOD: u64 = 1 << 16;
+
+/// The recommended window of a mortal transaction.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct MortalEraWindow<BlockNumber> {
+	/// The period of the era.
+	pub period: u64,
+	/// The block the era starts at.
+	pub current_block: BlockNumber,
+}
+
+/// RPC method to pick a valid era for mortal transactions.
+#[rpc]
+pub trait MortalEraApi<BlockNumber> {
+	/// Returns the recommended `period` and `currentBlock` to construct a mortal era.
+	///
+	/// The period is derived from the `BlockHashCount` of the runtime, so the block the era
+	/// refers to is still known to the chain when the transaction is validated.
+	#[rpc(name = "system_mortalEraWindow")]
+	fn mortal_era_window(&self) -> Result<MortalEraWindow<BlockNumber>>;
+}
+
+/// The longest period of a mortal era that is valid with the given `block_hash_count`.
+///
+/// Era periods are powers of two between 4 and 65536 and the chain only knows the hashes of the
+/// last `block_hash_count` blocks.
+fn mortal_era_period(block_hash_count: u64) -> u64 {
+	block_hash_count
+		.checked_next_power_of_two()
+		.map(|c| c / 2)
+		.unwrap_or(MAX_PERIOD)
+		.max(MIN_PERIOD)
+		.min(MAX_PERIOD)
+}
+
+/// An implementation of [`MortalEraApi`].
+pub struct MortalEra<C, B> {
+	client: Arc<C>,
+	block_hash_count: u64,
+	_marker: PhantomData<B>,
+}
+
+impl<C, B> MortalEra<C, B> {
+	/// Create new `MortalEra` for a runtime with the given `block_hash_count`.
+	pub fn new(client: Arc<C>, block_hash_count: u64) -> Self {
+		MortalEra {
+			client,
+			block_hash_count,
+			_marker: Default::default(),
+		}
+	}
+}
+
+impl<C, Block> MortalEraApi<NumberFor<Block>> for MortalEra<C, Block>
+where
+	Block: BlockT,
+	C: HeaderBackend<Block> + Send + Sync + 'static,
+{
+	fn mortal_era_window(&self) -> Result<MortalEraWindow<NumberFor<Block>>> {
+		Ok(MortalEraWindow {
+			period: mortal_era_period(self.block_hash_count),
+			current_block: self.client.info().best_number,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn period_is_a_power_of_two_within_block_hash_count() {
+		assert_eq!(128, mortal_era_period(250));
+		assert_eq!(1024, mortal_era_period(2400));
+		assert_eq!(4, mortal_era_period(1));
+		assert_eq!(65536, mortal_era_period(u64::max_value()));
+	}
+
+	#[test]
+	fn mortal_era_window_should_serialize_deserialize_properly() {
+		let expected = r#"{"period":128,"currentBlock":7}"#;
+		let window: MortalEraWindow<u32> = serde_json::from_str(expected).unwrap();
+		assert_eq!(window.current_block, 7);
+
+		let actual = serde_json::to_string(&window).unwrap();
+		assert_eq!(actual, expected);
+	}
+}
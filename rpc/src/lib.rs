@@ -0,0 +1,791 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Cumulus specific RPC methods.
+//!
+//! These methods give insight into what the node is doing as a parachain node, e.g. on which relay
+//! chain block the collator is building.
+//!
+//! The RPC server of a parachain node serves the parachain. The standard methods, e.g.
+//! `state_getRuntimeVersion`, always refer to the parachain, also if the node embeds a relay chain
+//! node. The relay chain is only exposed by the `cumulus_relay*` methods, e.g.
+//! `cumulus_relayRuntimeVersion` returns the runtime version of the relay chain.
+
+use std::{marker::PhantomData, sync::Arc};
+
+use codec::Decode;
+use cumulus_collator::CollatorStatus;
+use cumulus_primitives::HeadData;
+use futures::{future, StreamExt, TryStreamExt};
+use jsonrpc_core::{
+	futures::{Future, Sink},
+	Result,
+};
+use jsonrpc_derive::rpc;
+use jsonrpc_pubsub::{
+	manager::SubscriptionManager, typed::Subscriber, PubSubMetadata, SubscriptionId,
+};
+use log::warn;
+use polkadot_primitives::v0::{BlockNumber as PBlockNumber, Hash as PHash, ValidatorId};
+use sc_rpc::DenyUnsafe;
+use serde::{Deserialize, Serialize};
+use sp_blockchain::HeaderBackend;
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT, NumberFor, Saturating};
+use sp_version::RuntimeVersion;
+
+pub mod author;
+pub mod code;
+pub mod dev;
+pub mod era;
+mod subscription_limits;
+
+pub use subscription_limits::SubscriptionLimits;
+
+pub use self::gen_client::Client as CumulusClient;
+
+/// A relay chain block the collator built a collation on.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct RelayParent {
+	/// The hash of the relay chain block.
+	pub hash: PHash,
+	/// The number of the relay chain block.
+	pub number: PBlockNumber,
+}
+
+impl From<cumulus_collator::RelayParent> for RelayParent {
+	fn from(r: cumulus_collator::RelayParent) -> Self {
+		Self {
+			hash: r.hash,
+			number: r.number,
+		}
+	}
+}
+
+/// A relay chain core the parachain is assigned to.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct AssignedCore {
+	/// The index of the core.
+	pub core_index: u32,
+	/// The number of the relay chain block the assignment was observed at.
+	pub relay_block: PBlockNumber,
+}
+
+impl From<cumulus_collator::AssignedCore> for AssignedCore {
+	fn from(c: cumulus_collator::AssignedCore) -> Self {
+		Self {
+			core_index: c.core_index,
+			relay_block: c.relay_block,
+		}
+	}
+}
+
+/// Statistics of the cache of the relay chain runtime api results.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct RelayCallCacheStats {
+	/// The number of calls that were served from the cache.
+	pub hits: u64,
+	/// The number of calls that were made to the relay chain runtime.
+	pub misses: u64,
+	/// The number of results that were dropped because a new best relay chain block was imported.
+	pub invalidated: u64,
+	/// The number of results that are currently cached.
+	pub entries: u64,
+}
+
+impl From<cumulus_collator::RelayCallCacheStats> for RelayCallCacheStats {
+	fn from(s: cumulus_collator::RelayCallCacheStats) -> Self {
+		Self {
+			hits: s.hits,
+			misses: s.misses,
+			invalidated: s.invalidated,
+			entries: s.entries,
+		}
+	}
+}
+
+/// The collations that were built on the included parachain head and are not included yet.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct UnincludedSegment {
+	/// The number of collations that were built on the included head.
+	pub length: u32,
+	/// The authoring backoff threshold, the length at which the collator starts backing off, or
+	/// `None` if it never does.
+	pub backoff_threshold: Option<u32>,
+	/// The relay parent of the oldest of these collations.
+	pub oldest_relay_parent: Option<RelayParent>,
+}
+
+impl From<cumulus_collator::UnincludedSegment> for UnincludedSegment {
+	fn from(s: cumulus_collator::UnincludedSegment) -> Self {
+		Self {
+			length: s.length,
+			backoff_threshold: s.backoff_threshold,
+			oldest_relay_parent: s.oldest_relay_parent.map(Into::into),
+		}
+	}
+}
+
+/// The relay chain block that included a parachain block.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct Inclusion {
+	/// The hash of the relay chain block.
+	pub relay_block: PHash,
+	/// The number of the relay chain block.
+	pub relay_number: PBlockNumber,
+}
+
+impl From<cumulus_collator::Inclusion> for Inclusion {
+	fn from(i: cumulus_collator::Inclusion) -> Self {
+		Self {
+			relay_block: i.relay_block,
+			relay_number: i.relay_number,
+		}
+	}
+}
+
+/// The heights of the parachain and the relay chain the node is following.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct BestBlockStats<BlockNumber> {
+	/// The number of the best parachain block.
+	pub para_best: BlockNumber,
+	/// The number of the last finalized parachain block.
+	pub para_finalized: BlockNumber,
+	/// The number of the best relay chain block or `None` if no relay chain is followed.
+	pub relay_best: Option<PBlockNumber>,
+	/// The number of the last finalized relay chain block or `None` if no relay chain is followed.
+	pub relay_finalized: Option<PBlockNumber>,
+	/// Whether the relay chain node is connected to any peers.
+	pub relay_connected: bool,
+}
+
+/// How far the finalized parachain block lags behind.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct FinalityLag<BlockNumber> {
+	/// The number of blocks between the best and the finalized parachain block.
+	pub blocks: BlockNumber,
+	/// The seconds since the finalized parachain block last advanced or `None` if the finality
+	/// is not monitored.
+	pub seconds_since_finalized: Option<u64>,
+}
+
+/// What happened to a collation.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum CollationResult {
+	/// The collation was built and handed over to the relay chain node.
+	Submitted,
+	/// Building the collation failed.
+	Rejected,
+	/// The parachain block was included in a finalized relay chain block.
+	Included,
+}
+
+impl From<cumulus_collator::CollationResult> for CollationResult {
+	fn from(r: cumulus_collator::CollationResult) -> Self {
+		match r {
+			cumulus_collator::CollationResult::Submitted => Self::Submitted,
+			cumulus_collator::CollationResult::Rejected => Self::Rejected,
+			cumulus_collator::CollationResult::Included => Self::Included,
+		}
+	}
+}
+
+/// An event in the lifecycle of a collation.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct CollationEvent<Hash> {
+	/// The hash of the parachain block or `None` if no block was built.
+	pub para_block: Option<Hash>,
+	/// The relay chain block the collation was built on.
+	pub relay_parent: RelayParent,
+	/// The size of the PoV in bytes or `None` if no block was built.
+	pub pov_size: Option<u64>,
+	/// What happened to the collation.
+	pub result: CollationResult,
+}
+
+impl<Hash> CollationEvent<Hash> {
+	/// Convert the event of the collator status, the parachain block is hashed as `Block`.
+	pub fn from_status<Block: BlockT<Hash = Hash>>(e: cumulus_collator::CollationEvent) -> Self {
+		Self {
+			para_block: e
+				.head_data
+				.and_then(|h| HeadData::<Block>::decode(&mut &h.0[..]).ok())
+				.map(|h| h.header.hash()),
+			relay_parent: e.relay_parent.into(),
+			pov_size: e.pov_size.map(|s| s as u64),
+			result: e.result.into(),
+		}
+	}
+}
+
+/// A parachain block that became final.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct FinalBlock<Hash, BlockNumber> {
+	/// The hash of the parachain block.
+	pub hash: Hash,
+	/// The number of the parachain block.
+	pub number: BlockNumber,
+}
+
+/// A collation that was recently rejected.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct Rejection<Hash> {
+	/// The hash of the parachain block or `None` if no block was built.
+	pub para_block: Option<Hash>,
+	/// The relay chain block the collation was built on.
+	pub relay_parent: RelayParent,
+	/// Why the collation was rejected.
+	pub reason: String,
+	/// When the collation was rejected, in milliseconds since the unix epoch.
+	pub timestamp: u64,
+}
+
+impl<Hash> Rejection<Hash> {
+	fn from_status<Block: BlockT<Hash = Hash>>(r: cumulus_collator::Rejection) -> Self {
+		Self {
+			para_block: r
+				.head_data
+				.and_then(|h| HeadData::<Block>::decode(&mut &h.0[..]).ok())
+				.map(|h| h.header.hash()),
+			relay_parent: r.relay_parent.into(),
+			reason: r.reason,
+			timestamp: r.timestamp,
+		}
+	}
+}
+
+/// The health of the node as a parachain node.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct Health {
+	/// Whether building collations was paused with `cumulus_pauseCollation`.
+	pub collation_paused: bool,
+	/// Whether the relay chain node is connected to any peers.
+	pub relay_connected: bool,
+}
+
+/// Cumulus RPC methods.
+#[rpc]
+pub trait CumulusApi<BlockNumber, Hash> {
+	/// RPC metadata
+	type Metadata;
+
+	/// Returns the relay parent the collator last built a collation on.
+	///
+	/// Returns `None` if the node did not build any collation yet, e.g. because it is not running
+	/// as a collator.
+	#[rpc(name = "cumulus_currentRelayParent")]
+	fn current_relay_parent(&self) -> Result<Option<RelayParent>>;
+
+	/// Returns the best and finalized heights of the parachain and the relay chain.
+	#[rpc(name = "cumulus_bestBlockStats")]
+	fn best_block_stats(&self) -> Result<BestBlockStats<BlockNumber>>;
+
+	/// Returns the relay chain cores the parachain is assigned to at the best relay chain block.
+	///
+	/// Returns an empty list if the parachain is not scheduled or the node is not running as a
+	/// collator.
+	#[rpc(name = "cumulus_assignedCores")]
+	fn assigned_cores(&self) -> Result<Vec<AssignedCore>>;
+
+	/// Returns the relay chain validators that back the collations of the parachain at the best
+	/// relay chain block.
+	///
+	/// Returns an empty list if no validator is assigned to the parachain or the node is not
+	/// running as a collator.
+	#[rpc(name = "cumulus_backingGroup")]
+	fn backing_group(&self) -> Result<Vec<ValidatorId>>;
+
+	/// Returns the relay chain block that included the parachain block `para_block`.
+	///
+	/// Returns `None` if the block is not included yet or was included too long ago.
+	#[rpc(name = "cumulus_inclusionOf")]
+	fn inclusion_of(&self, para_block: Hash) -> Result<Option<Inclusion>>;
+
+	/// Returns the collations that were built on the parachain head that is included in the
+	/// relay chain and are not included yet.
+	///
+	/// The collator backs off once `length` reaches `backoffThreshold`. The segment is empty if
+	/// the node is not running as a collator.
+	#[rpc(name = "cumulus_unincludedSegment")]
+	fn unincluded_segment(&self) -> Result<UnincludedSegment>;
+
+	/// Returns how far the finalized parachain block lags behind the best block.
+	#[rpc(name = "cumulus_finalityLag")]
+	fn finality_lag(&self) -> Result<FinalityLag<BlockNumber>>;
+
+	/// Returns the health of the node as a parachain node.
+	#[rpc(name = "cumulus_health")]
+	fn health(&self) -> Result<Health>;
+
+	/// Returns the runtime version of the best relay chain block.
+	///
+	/// Use `state_getRuntimeVersion` for the runtime version of the parachain, e.g. to build
+	/// parachain extrinsics. Returns `None` if the node did not see a relay chain block yet.
+	#[rpc(name = "cumulus_relayRuntimeVersion")]
+	fn relay_runtime_version(&self) -> Result<Option<RuntimeVersion>>;
+
+	/// Returns the statistics of the cache of the relay chain runtime api results.
+	///
+	/// The results are cached per relay chain block, the results of older blocks are dropped on
+	/// every new best relay chain block.
+	#[rpc(name = "cumulus_relayCallCacheStats")]
+	fn relay_call_cache_stats(&self) -> Result<RelayCallCacheStats>;
+
+	/// Returns the at most `count` most recently rejected collations with the reason why they
+	/// were rejected, oldest first.
+	///
+	/// Only the rejections the node still remembers are returned, see `--keep-recent-rejections`.
+	#[rpc(name = "cumulus_recentRejections")]
+	fn recent_rejections(&self, count: u32) -> Result<Vec<Rejection<Hash>>>;
+
+	/// Pause building collations, e.g. for maintenance of the node.
+	///
+	/// The node continues to import blocks. This is an unsafe RPC method.
+	#[rpc(name = "cumulus_pauseCollation")]
+	fn pause_collation(&self) -> Result<()>;
+
+	/// Resume building collations after they were paused with `cumulus_pauseCollation`.
+	///
+	/// This is an unsafe RPC method.
+	#[rpc(name = "cumulus_resumeCollation")]
+	fn resume_collation(&self) -> Result<()>;
+
+	/// Subscribe to the events of all collations from now on.
+	///
+	/// Rejected once the subscription limits of the node are reached, see
+	/// `--rpc-max-subscriptions-per-connection` and `--rpc-max-subscriptions`.
+	#[pubsub(
+		subscription = "cumulus_collation",
+		subscribe,
+		name = "cumulus_subscribeCollations"
+	)]
+	fn subscribe_collations(
+		&self,
+		metadata: Self::Metadata,
+		subscriber: Subscriber<CollationEvent<Hash>>,
+	);
+
+	/// Unsubscribe from the collation events.
+	#[pubsub(
+		subscription = "cumulus_collation",
+		unsubscribe,
+		name = "cumulus_unsubscribeCollations"
+	)]
+	fn unsubscribe_collations(
+		&self,
+		metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> Result<bool>;
+
+	/// Subscribe to the parachain blocks that become final from now on.
+	///
+	/// By default a block is final once the relay chain finalized the block that included it.
+	/// With `--finality-confirmations <k>` it is final once `k` best relay chain blocks were built
+	/// on the block that included it.
+	///
+	/// Rejected once the subscription limits of the node are reached.
+	#[pubsub(
+		subscription = "cumulus_parachainFinality",
+		subscribe,
+		name = "cumulus_subscribeParachainFinality"
+	)]
+	fn subscribe_parachain_finality(
+		&self,
+		metadata: Self::Metadata,
+		subscriber: Subscriber<FinalBlock<Hash, BlockNumber>>,
+	);
+
+	/// Unsubscribe from the final parachain blocks.
+	#[pubsub(
+		subscription = "cumulus_parachainFinality",
+		unsubscribe,
+		name = "cumulus_unsubscribeParachainFinality"
+	)]
+	fn unsubscribe_parachain_finality(
+		&self,
+		metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> Result<bool>;
+}
+
+/// An implementation of the Cumulus specific RPC methods.
+pub struct Cumulus<C, B> {
+	client: Arc<C>,
+	collator_status: Arc<CollatorStatus>,
+	subscriptions: SubscriptionManager,
+	subscription_limits: SubscriptionLimits,
+	deny_unsafe: DenyUnsafe,
+	_marker: PhantomData<B>,
+}
+
+impl<C, B> Cumulus<C, B> {
+	/// Create new `Cumulus` with the given reference to the client and the collator status.
+	///
+	/// `deny_unsafe` decides whether the unsafe RPC methods can be called.
+	pub fn new(
+		client: Arc<C>,
+		collator_status: Arc<CollatorStatus>,
+		subscriptions: SubscriptionManager,
+		deny_unsafe: DenyUnsafe,
+	) -> Self {
+		Cumulus {
+			client,
+			collator_status,
+			subscriptions,
+			subscription_limits: Default::default(),
+			deny_unsafe,
+			_marker: Default::default(),
+		}
+	}
+
+	/// Reject new subscriptions once the given `limits` are reached.
+	///
+	/// By default the number of subscriptions is not limited.
+	pub fn with_subscription_limits(mut self, limits: SubscriptionLimits) -> Self {
+		self.subscription_limits = limits;
+		self
+	}
+}
+
+impl<C, Block> CumulusApi<NumberFor<Block>, Block::Hash> for Cumulus<C, Block>
+where
+	Block: BlockT,
+	C: HeaderBackend<Block> + Send + Sync + 'static,
+{
+	type Metadata = sc_rpc::Metadata;
+
+	fn current_relay_parent(&self) -> Result<Option<RelayParent>> {
+		Ok(self.collator_status.relay_parent().map(Into::into))
+	}
+
+	fn best_block_stats(&self) -> Result<BestBlockStats<NumberFor<Block>>> {
+		let info = self.client.info();
+		let relay_chain_heights = self.collator_status.relay_chain_heights();
+
+		Ok(BestBlockStats {
+			para_best: info.best_number,
+			para_finalized: info.finalized_number,
+			relay_best: relay_chain_heights.map(|h| h.best),
+			relay_finalized: relay_chain_heights.map(|h| h.finalized),
+			relay_connected: self.collator_status.relay_connected(),
+		})
+	}
+
+	fn assigned_cores(&self) -> Result<Vec<AssignedCore>> {
+		Ok(self
+			.collator_status
+			.assigned_core()
+			.into_iter()
+			.map(Into::into)
+			.collect())
+	}
+
+	fn backing_group(&self) -> Result<Vec<ValidatorId>> {
+		Ok(self.collator_status.backing_group())
+	}
+
+	fn inclusion_of(&self, para_block: Block::Hash) -> Result<Option<Inclusion>> {
+		Ok(self.collator_status.inclusion_of(&para_block).map(Into::into))
+	}
+
+	fn unincluded_segment(&self) -> Result<UnincludedSegment> {
+		Ok(self.collator_status.unincluded_segment().into())
+	}
+
+	fn finality_lag(&self) -> Result<FinalityLag<NumberFor<Block>>> {
+		let info = self.client.info();
+
+		Ok(FinalityLag {
+			blocks: info.best_number.saturating_sub(info.finalized_number),
+			seconds_since_finalized: self.collator_status.finalized_head_age().map(|a| a.as_secs()),
+		})
+	}
+
+	fn health(&self) -> Result<Health> {
+		Ok(Health {
+			collation_paused: self.collator_status.is_paused(),
+			relay_connected: self.collator_status.relay_connected(),
+		})
+	}
+
+	fn relay_runtime_version(&self) -> Result<Option<RuntimeVersion>> {
+		Ok(self.collator_status.relay_runtime_version())
+	}
+
+	fn relay_call_cache_stats(&self) -> Result<RelayCallCacheStats> {
+		Ok(self.collator_status.relay_call_cache_stats().into())
+	}
+
+	fn recent_rejections(&self, count: u32) -> Result<Vec<Rejection<Block::Hash>>> {
+		Ok(self
+			.collator_status
+			.recent_rejections(count as usize)
+			.into_iter()
+			.map(Rejection::from_status::<Block>)
+			.collect())
+	}
+
+	fn pause_collation(&self) -> Result<()> {
+		self.deny_unsafe.check_if_safe()?;
+		self.collator_status.set_paused(true);
+		Ok(())
+	}
+
+	fn resume_collation(&self) -> Result<()> {
+		self.deny_unsafe.check_if_safe()?;
+		self.collator_status.set_paused(false);
+		Ok(())
+	}
+
+	fn subscribe_collations(
+		&self,
+		metadata: Self::Metadata,
+		subscriber: Subscriber<CollationEvent<Block::Hash>>,
+	) {
+		let stream = self
+			.collator_status
+			.collation_events()
+			.map(|e| Ok::<_, ()>(Ok(CollationEvent::from_status::<Block>(e))))
+			.compat();
+
+		self.subscription_limits.add(metadata.session(), subscriber, |subscriber, slot| {
+			self.subscriptions.add(subscriber, |sink| {
+				sink.sink_map_err(|e| warn!("Error sending notifications: {:?}", e))
+					.send_all(stream)
+					.map(move |_| drop(slot))
+			});
+		});
+	}
+
+	fn unsubscribe_collations(
+		&self,
+		_metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> Result<bool> {
+		Ok(self.subscriptions.cancel(id))
+	}
+
+	fn subscribe_parachain_finality(
+		&self,
+		metadata: Self::Metadata,
+		subscriber: Subscriber<FinalBlock<Block::Hash, NumberFor<Block>>>,
+	) {
+		let stream = self
+			.collator_status
+			.final_heads()
+			.filter_map(|head| future::ready(HeadData::<Block>::decode(&mut &head.0[..]).ok()))
+			.map(|head| {
+				Ok::<_, ()>(Ok(FinalBlock {
+					hash: head.header.hash(),
+					number: *head.header.number(),
+				}))
+			})
+			.compat();
+
+		self.subscription_limits.add(metadata.session(), subscriber, |subscriber, slot| {
+			self.subscriptions.add(subscriber, |sink| {
+				sink.sink_map_err(|e| warn!("Error sending notifications: {:?}", e))
+					.send_all(stream)
+					.map(move |_| drop(slot))
+			});
+		});
+	}
+
+	fn unsubscribe_parachain_finality(
+		&self,
+		_metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> Result<bool> {
+		Ok(self.subscriptions.cancel(id))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Encode;
+	use jsonrpc_core::MetaIoHandler;
+	use polkadot_primitives::v0::HeadData as PHeadData;
+	use sc_rpc::SubscriptionTaskExecutor;
+	use serde_json::json;
+	use sp_core::testing::TaskExecutor;
+	use test_client::{
+		runtime::Block, Client, DefaultTestClientBuilderExt, TestClientBuilder,
+		TestClientBuilderExt,
+	};
+
+	fn cumulus(status: Arc<CollatorStatus>, deny_unsafe: DenyUnsafe) -> Cumulus<Client, Block> {
+		let executor = SubscriptionTaskExecutor::new(TaskExecutor::new());
+
+		Cumulus::new(
+			Arc::new(TestClientBuilder::new().build()),
+			status,
+			SubscriptionManager::new(Arc::new(executor)),
+			deny_unsafe,
+		)
+	}
+
+	/// Call `method` with `params` and return the result of the response.
+	fn call(
+		api: Cumulus<Client, Block>,
+		method: &str,
+		params: serde_json::Value,
+	) -> serde_json::Value {
+		let mut io = MetaIoHandler::<sc_rpc::Metadata>::default();
+		io.extend_with(CumulusApi::to_delegate(api));
+
+		let request = json!({"jsonrpc": "2.0", "method": method, "params": params, "id": 1});
+		let response = io
+			.handle_request_sync(&request.to_string(), Default::default())
+			.unwrap();
+
+		serde_json::from_str::<serde_json::Value>(&response).unwrap()["result"].clone()
+	}
+
+	fn relay_parent(number: PBlockNumber) -> cumulus_collator::RelayParent {
+		cumulus_collator::RelayParent {
+			hash: PHash::repeat_byte(number as u8),
+			number,
+		}
+	}
+
+	#[test]
+	fn collation_is_only_paused_if_unsafe_methods_are_allowed() {
+		let status = Arc::new(CollatorStatus::new());
+
+		let api = cumulus(status.clone(), DenyUnsafe::Yes);
+		assert!(api.pause_collation().is_err());
+		assert!(!status.is_paused());
+		assert!(!api.health().unwrap().collation_paused);
+
+		let api = cumulus(status.clone(), DenyUnsafe::No);
+		api.pause_collation().unwrap();
+		assert!(status.is_paused());
+		assert!(api.health().unwrap().collation_paused);
+
+		api.resume_collation().unwrap();
+		assert!(!status.is_paused());
+	}
+
+	#[test]
+	fn inclusion_of_known_and_unknown_blocks() {
+		let status = Arc::new(CollatorStatus::new());
+		status.record_inclusion(
+			PHash::repeat_byte(1),
+			cumulus_collator::Inclusion {
+				relay_block: PHash::repeat_byte(7),
+				relay_number: 7,
+			},
+		);
+
+		let api = cumulus(status.clone(), DenyUnsafe::Yes);
+		assert_eq!(
+			Some(Inclusion {
+				relay_block: PHash::repeat_byte(7),
+				relay_number: 7,
+			}),
+			api.inclusion_of(PHash::repeat_byte(1)).unwrap(),
+		);
+		assert_eq!(None, api.inclusion_of(PHash::repeat_byte(2)).unwrap());
+
+		let api = cumulus(status, DenyUnsafe::Yes);
+		assert_eq!(
+			json!({"relayBlock": PHash::repeat_byte(7), "relayNumber": 7}),
+			call(api, "cumulus_inclusionOf", json!([PHash::repeat_byte(1)])),
+		);
+	}
+
+	#[test]
+	fn recent_rejections_are_truncated_to_count() {
+		let status = Arc::new(CollatorStatus::new());
+		let header = <Block as BlockT>::Header::new(
+			1,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Default::default(),
+		);
+		let head_data = PHeadData(HeadData::<Block> { header: header.clone() }.encode());
+
+		for number in 1..=3 {
+			status.record_rejection(relay_parent(number), None, format!("rejection {}", number));
+		}
+		status.record_rejection(relay_parent(4), Some(head_data), "rejection 4".into());
+
+		let api = cumulus(status, DenyUnsafe::Yes);
+		let reasons = |count| {
+			api.recent_rejections(count)
+				.unwrap()
+				.into_iter()
+				.map(|r| r.reason)
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(vec!["rejection 3", "rejection 4"], reasons(2));
+		assert_eq!(4, reasons(10).len());
+		assert!(reasons(0).is_empty());
+
+		let rejection = api.recent_rejections(1).unwrap().remove(0);
+		assert_eq!(Some(header.hash()), rejection.para_block);
+		assert_eq!(4, rejection.relay_parent.number);
+	}
+
+	#[test]
+	fn node_that_does_not_collate_reports_no_collations() {
+		let api = cumulus(Arc::new(CollatorStatus::new()), DenyUnsafe::Yes);
+
+		assert_eq!(None, api.current_relay_parent().unwrap());
+		assert!(api.assigned_cores().unwrap().is_empty());
+		assert_eq!(
+			UnincludedSegment {
+				length: 0,
+				backoff_threshold: None,
+				oldest_relay_parent: None,
+			},
+			api.unincluded_segment().unwrap(),
+		);
+
+		let stats = api.best_block_stats().unwrap();
+		assert_eq!((0, 0, None), (stats.para_best, stats.para_finalized, stats.relay_best));
+		assert!(!stats.relay_connected);
+
+		let api = cumulus(Arc::new(CollatorStatus::new()), DenyUnsafe::Yes);
+		assert_eq!(
+			json!({"length": 0, "backoffThreshold": null, "oldestRelayParent": null}),
+			call(api, "cumulus_unincludedSegment", json!([])),
+		);
+	}
+}
@@ -0,0 +1,236 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Limits of the number of active subscriptions of the `cumulus_subscribe*` methods and of
+//! `author_submitAndWatchExtrinsic`.
+//!
+//! A subscription holds a [`Slot`] as long as it sends notifications. The slot is released once
+//! the subscription is cancelled, its stream ends or its connection is closed.
+
+use jsonrpc_core::{Error, ErrorCode};
+use jsonrpc_pubsub::{typed::Subscriber, Session};
+use parking_lot::Mutex;
+
+use std::{
+	collections::{HashMap, HashSet},
+	sync::Arc,
+};
+
+/// The error code returned when a subscription is rejected because a limit is reached.
+const SUBSCRIPTION_LIMIT_ERROR: i64 = 4000;
+
+/// The connection of transports without sessions.
+const NO_CONNECTION: usize = 0;
+
+fn limit_error(message: String) -> Error {
+	Error {
+		code: ErrorCode::ServerError(SUBSCRIPTION_LIMIT_ERROR),
+		message,
+		data: None,
+	}
+}
+
+/// The connection of `session`, identified by the address of the session.
+fn connection(session: Option<&Arc<Session>>) -> usize {
+	session.map_or(NO_CONNECTION, |session| &**session as *const Session as usize)
+}
+
+/// The active subscriptions.
+#[derive(Default)]
+struct Active {
+	/// The connection of the subscription of every reserved slot.
+	slots: HashMap<u64, usize>,
+	/// The identifier of the next reserved slot.
+	next_slot: u64,
+	/// The connections whose subscriptions are removed once the connection is closed.
+	connections: HashSet<usize>,
+}
+
+impl Active {
+	/// The number of active subscriptions on `connection`.
+	fn on_connection(&self, connection: usize) -> usize {
+		self.slots.values().filter(|c| **c == connection).count()
+	}
+
+	fn close(&mut self, connection: usize) {
+		self.connections.remove(&connection);
+		self.slots.retain(|_, c| *c != connection);
+	}
+}
+
+/// The slot of an active subscription, released when dropped.
+///
+/// Moved into the future that forwards the notifications of the subscription, so the slot is
+/// released together with it.
+pub(crate) struct Slot {
+	active: Arc<Mutex<Active>>,
+	id: u64,
+}
+
+impl Drop for Slot {
+	fn drop(&mut self) {
+		self.active.lock().slots.remove(&self.id);
+	}
+}
+
+/// Limits the number of active subscriptions, per connection and in total.
+///
+/// Clones share the active subscriptions, so the total limit holds over all RPC servers.
+#[derive(Clone, Default)]
+pub struct SubscriptionLimits {
+	max_per_connection: Option<usize>,
+	max_total: Option<usize>,
+	active: Arc<Mutex<Active>>,
+}
+
+impl SubscriptionLimits {
+	/// Create new limits, `None` does not limit the number of subscriptions.
+	pub fn new(max_per_connection: Option<usize>, max_total: Option<usize>) -> Self {
+		Self {
+			max_per_connection,
+			max_total,
+			active: Default::default(),
+		}
+	}
+
+	/// The error a new subscription on `connection` is rejected with, if a limit is reached.
+	fn rejection(&self, active: &Active, connection: usize) -> Option<Error> {
+		let message = match (self.max_total, self.max_per_connection) {
+			(Some(max), _) if active.slots.len() >= max => {
+				format!("The node serves at most {} subscriptions", max)
+			}
+			(_, Some(max)) if active.on_connection(connection) >= max => {
+				format!("At most {} subscriptions are allowed per connection", max)
+			}
+			_ => return None,
+		};
+
+		Some(limit_error(message))
+	}
+
+	/// Reserve the slot of a new subscription on the connection of `session`.
+	///
+	/// Returns an error if a limit is reached. For subscriptions that are only added after some
+	/// work, e.g. submitting a transaction, the slot is reserved before the work is done.
+	pub(crate) fn reserve(&self, session: Option<&Arc<Session>>) -> Result<Slot, Error> {
+		let connection = connection(session);
+		let mut active = self.active.lock();
+
+		if let Some(error) = self.rejection(&active, connection) {
+			return Err(error);
+		}
+
+		if let Some(session) = session {
+			if active.connections.insert(connection) {
+				let closed = self.active.clone();
+				session.on_drop(move || closed.lock().close(connection));
+			}
+		}
+
+		let id = active.next_slot;
+		active.next_slot += 1;
+		active.slots.insert(id, connection);
+
+		Ok(Slot {
+			active: self.active.clone(),
+			id,
+		})
+	}
+
+	/// Add the subscription of `subscriber` on the connection of `session` with `subscribe`.
+	///
+	/// `subscribe` needs to keep the [`Slot`] until the subscription ends. The subscriber is
+	/// rejected instead if a limit is reached.
+	pub(crate) fn add<T>(
+		&self,
+		session: Option<Arc<Session>>,
+		subscriber: Subscriber<T>,
+		subscribe: impl FnOnce(Subscriber<T>, Slot),
+	) {
+		match self.reserve(session.as_ref()) {
+			Ok(slot) => subscribe(subscriber, slot),
+			Err(error) => {
+				let _ = subscriber.reject(error);
+			}
+		}
+	}
+
+	/// The number of active subscriptions.
+	pub fn active(&self) -> usize {
+		self.active.lock().slots.len()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jsonrpc_core::futures::sync::mpsc;
+
+	fn session() -> Arc<Session> {
+		Arc::new(Session::new(mpsc::channel(1).0))
+	}
+
+	fn subscribe(
+		limits: &SubscriptionLimits,
+		session: Option<Arc<Session>>,
+		slots: &mut Vec<Slot>,
+	) {
+		let (subscriber, _, _) = Subscriber::<()>::new_test("cumulus_test");
+		limits.add(session, subscriber, |_, slot| slots.push(slot));
+	}
+
+	#[test]
+	fn subscriptions_over_the_limits_are_rejected() {
+		let limits = SubscriptionLimits::new(None, Some(2));
+		let mut slots = Vec::new();
+		subscribe(&limits, None, &mut slots);
+		subscribe(&limits, None, &mut slots);
+		subscribe(&limits, None, &mut slots);
+		assert_eq!(2, slots.len());
+		assert_eq!(2, limits.active());
+		assert!(limits.reserve(None).is_err());
+
+		// The first subscription ended.
+		slots.remove(0);
+		assert_eq!(1, limits.active());
+
+		let slot = limits.reserve(None).unwrap();
+		assert!(limits.reserve(None).is_err());
+		drop(slot);
+		assert_eq!(1, limits.active());
+	}
+
+	#[test]
+	fn closed_connections_release_their_subscriptions() {
+		let limits = SubscriptionLimits::new(Some(1), None);
+		let (first, second) = (session(), session());
+		let mut slots = Vec::new();
+
+		subscribe(&limits, Some(first.clone()), &mut slots);
+		subscribe(&limits, Some(first.clone()), &mut slots);
+		subscribe(&limits, Some(second.clone()), &mut slots);
+		assert_eq!(2, limits.active());
+
+		drop(first);
+		assert_eq!(1, limits.active());
+		drop(second);
+		assert_eq!(0, limits.active());
+
+		// Slots of closed connections were already released.
+		drop(slots);
+		assert_eq!(0, limits.active());
+	}
+}
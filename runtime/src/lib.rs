@@ -28,6 +28,8 @@ pub use sp_std::slice;
 
 #[macro_use]
 pub mod validate_block;
+#[cfg(feature = "test-helpers")]
+pub mod test_helpers;
 
 /// The parachain block that is created on a collator and validated by a validator.
 #[derive(Encode, Decode)]
@@ -0,0 +1,108 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Helpers for testing that the blocks a parachain runtime builds pass its `validate_block`.
+
+use crate::ParachainBlockData;
+
+use parachain::primitives::{BlockData, HeadData, ValidationParams, ValidationResult};
+use sc_executor::{sp_wasm_interface::HostFunctions, WasmExecutionMethod, WasmExecutor};
+use sp_core::traits::{CallInWasm, MissingHostFunctions};
+use sp_runtime::{
+	traits::{Block as BlockT, HashFor, Header as HeaderT, Zero},
+	Storage,
+};
+use sp_state_machine::{
+	Ext, InMemoryBackend, OverlayedChanges, ProvingBackend, StorageTransactionCache,
+};
+use sp_trie::StorageProof;
+
+use codec::{Decode, Encode};
+
+/// Build a block on top of the genesis state `storage` and record the storage proof, like the
+/// collator does.
+///
+/// `build` is called with the hash of the genesis header and builds the block with the native
+/// runtime.
+///
+/// Returns the genesis header, the block and the proof of the storage read while building it.
+pub fn build_block_with_proof<B: BlockT>(
+	storage: Storage,
+	build: impl FnOnce(B::Hash) -> B,
+) -> (B::Header, B, StorageProof) {
+	let backend = InMemoryBackend::<HashFor<B>>::from(storage);
+	let parent_head = B::Header::new(
+		Zero::zero(),
+		Default::default(),
+		*backend.root(),
+		Default::default(),
+		Default::default(),
+	);
+
+	let proving_backend = ProvingBackend::new(&backend);
+	let mut overlay = OverlayedChanges::default();
+	let mut cache = StorageTransactionCache::default();
+	// No changes trie is built, so its block number type does not matter.
+	let mut ext = Ext::<_, u64, _>::new(&mut overlay, &mut cache, &proving_backend, None, None);
+
+	let block =
+		sp_externalities::set_and_run_with_externalities(&mut ext, || build(parent_head.hash()));
+
+	(parent_head, block, proving_backend.extract_proof())
+}
+
+/// Call `validate_block` of the `wasm` runtime, like a relay chain validator does.
+///
+/// # Panic
+///
+/// Panics if the validation fails.
+pub fn validate_block<B: BlockT>(
+	wasm: &[u8],
+	parent_head: &B::Header,
+	block: B,
+	storage_proof: StorageProof,
+) -> ValidationResult {
+	let (header, extrinsics) = block.deconstruct();
+	let block_data = ParachainBlockData::<B>::new(header, extrinsics, storage_proof);
+	let params = ValidationParams {
+		block_data: BlockData(block_data.encode()),
+		parent_head: HeadData(parent_head.encode()),
+		code_upgrade_allowed: None,
+		max_code_size: 1024,
+		max_head_data_size: 1024,
+		relay_chain_height: 1,
+	};
+
+	let executor = WasmExecutor::new(
+		WasmExecutionMethod::Interpreted,
+		Some(1024),
+		sp_io::SubstrateHostFunctions::host_functions(),
+		1,
+	);
+	let mut ext = sp_io::TestExternalities::default();
+
+	executor
+		.call_in_wasm(
+			wasm,
+			None,
+			"validate_block",
+			&params.encode(),
+			&mut ext.ext(),
+			MissingHostFunctions::Disallow,
+		)
+		.map(|v| ValidationResult::decode(&mut &v[..]).expect("Decode `ValidationResult`."))
+		.expect("Calls `validate_block`")
+}
@@ -0,0 +1,231 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Benchmarks of the hardware the node runs on.
+//!
+//! Collators need to build and import a block within the slot of the relay chain. On machines
+//! that are too slow the blocks are produced too late to be backed, which is hard to tell apart
+//! from other problems. The benchmarks run for a fraction of a second each and are only meant to
+//! detect machines that are clearly too slow.
+
+use log::{info, warn};
+
+use std::{
+	fmt,
+	fs::{self, File},
+	io::{self, Write},
+	path::Path,
+	time::{Duration, Instant},
+};
+
+/// How long the cpu and the memory benchmark run.
+const BENCHMARK_DURATION: Duration = Duration::from_millis(300);
+
+/// The size of the data that is hashed at once by the cpu benchmark.
+const CPU_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// The size of the buffer that is copied by the memory benchmark.
+const MEMORY_BUFFER_SIZE: usize = 64 * 1024 * 1024;
+
+/// The size of a block written by the disk benchmark.
+const DISK_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// How much data the disk benchmark writes.
+const DISK_WRITE_SIZE: usize = 64 * 1024 * 1024;
+
+/// The measured throughputs of the hardware, in MiB/s.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HardwareBenchmark {
+	/// The throughput of hashing with BLAKE2-256 on a single core.
+	pub cpu: f64,
+	/// The throughput of copying memory.
+	pub memory: f64,
+	/// The throughput of writing to the disk, or `None` if the benchmark failed.
+	pub disk: Option<f64>,
+}
+
+impl fmt::Display for HardwareBenchmark {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "cpu {:.0} MiB/s, memory {:.0} MiB/s, disk ", self.cpu, self.memory)?;
+
+		match self.disk {
+			Some(disk) => write!(f, "{:.0} MiB/s", disk),
+			None => write!(f, "unknown"),
+		}
+	}
+}
+
+/// The minimum throughputs a machine needs to collate reliably, in MiB/s.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HardwareRequirements {
+	/// The minimum throughput of hashing with BLAKE2-256 on a single core.
+	pub cpu: f64,
+	/// The minimum throughput of copying memory.
+	pub memory: f64,
+	/// The minimum throughput of writing to the disk.
+	pub disk: f64,
+}
+
+impl Default for HardwareRequirements {
+	fn default() -> Self {
+		Self {
+			cpu: 500.0,
+			memory: 5000.0,
+			disk: 200.0,
+		}
+	}
+}
+
+impl HardwareBenchmark {
+	/// Returns the names of the parts of the hardware that do not meet the `requirements`.
+	pub fn failed_requirements(&self, requirements: &HardwareRequirements) -> Vec<&'static str> {
+		let mut failed = Vec::new();
+
+		if self.cpu < requirements.cpu {
+			failed.push("cpu");
+		}
+		if self.memory < requirements.memory {
+			failed.push("memory");
+		}
+		if self.disk.map_or(false, |disk| disk < requirements.disk) {
+			failed.push("disk");
+		}
+
+		failed
+	}
+}
+
+/// Returns the throughput in MiB/s of processing `bytes` in `elapsed`.
+fn throughput(bytes: usize, elapsed: Duration) -> f64 {
+	let seconds = elapsed.as_secs_f64().max(std::f64::EPSILON);
+
+	bytes as f64 / (1024.0 * 1024.0) / seconds
+}
+
+fn benchmark_cpu() -> f64 {
+	let mut data = vec![0u8; CPU_CHUNK_SIZE];
+	let start = Instant::now();
+	let mut hashed = 0;
+
+	while start.elapsed() < BENCHMARK_DURATION {
+		// Feed the hash back, so the hashing can not be optimized away.
+		let hash = sp_core::hashing::blake2_256(&data);
+		data[..hash.len()].copy_from_slice(&hash);
+		hashed += data.len();
+	}
+
+	throughput(hashed, start.elapsed())
+}
+
+fn benchmark_memory() -> f64 {
+	let mut source = vec![1u8; MEMORY_BUFFER_SIZE];
+	let mut destination = vec![0u8; MEMORY_BUFFER_SIZE];
+	let start = Instant::now();
+	let mut copied = 0;
+
+	while start.elapsed() < BENCHMARK_DURATION {
+		destination.copy_from_slice(&source);
+		// Change the source, so the copy can not be optimized away.
+		source[0] = destination[MEMORY_BUFFER_SIZE - 1].wrapping_add(1);
+		copied += MEMORY_BUFFER_SIZE;
+	}
+
+	throughput(copied, start.elapsed())
+}
+
+fn benchmark_disk(directory: &Path) -> io::Result<f64> {
+	let path = directory.join("hardware-benchmark.tmp");
+	let chunk = vec![42u8; DISK_CHUNK_SIZE];
+	let start = Instant::now();
+
+	let result = File::create(&path).and_then(|mut file| {
+		for _ in 0..DISK_WRITE_SIZE / DISK_CHUNK_SIZE {
+			file.write_all(&chunk)?;
+		}
+		file.sync_all()
+	});
+	let elapsed = start.elapsed();
+	let _ = fs::remove_file(&path);
+
+	result.map(|_| throughput(DISK_WRITE_SIZE, elapsed))
+}
+
+/// Benchmark the hardware, writing to the disk in the given `directory`.
+pub fn benchmark_hardware(directory: &Path) -> HardwareBenchmark {
+	let disk = fs::create_dir_all(directory)
+		.and_then(|_| benchmark_disk(directory))
+		.map_err(|e| {
+			warn!(
+				target: "cumulus-service",
+				"Failed to benchmark the disk at `{}`: {}",
+				directory.display(),
+				e,
+			)
+		})
+		.ok();
+
+	HardwareBenchmark {
+		cpu: benchmark_cpu(),
+		memory: benchmark_memory(),
+		disk,
+	}
+}
+
+/// Benchmark the hardware and warn if it does not meet the `requirements` for collating.
+///
+/// The disk benchmark writes to the given `directory`, which should be on the disk the database
+/// of the node is stored on.
+pub fn check_hardware(directory: &Path, requirements: &HardwareRequirements) {
+	let benchmark = benchmark_hardware(directory);
+
+	info!(
+		target: "cumulus-service",
+		"Hardware benchmark: {}",
+		benchmark,
+	);
+
+	let failed = benchmark.failed_requirements(requirements);
+	if !failed.is_empty() {
+		warn!(
+			target: "cumulus-service",
+			"This machine may be too slow to collate reliably. The {} is below the recommended \
+			minimum of cpu {:.0} MiB/s, memory {:.0} MiB/s and disk {:.0} MiB/s.",
+			failed.join(", "),
+			requirements.cpu,
+			requirements.memory,
+			requirements.disk,
+		);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn slow_hardware_fails_requirements() {
+		let benchmark = HardwareBenchmark {
+			cpu: 100.0,
+			memory: 10_000.0,
+			disk: None,
+		};
+
+		assert_eq!(
+			vec!["cpu"],
+			benchmark.failed_requirements(&HardwareRequirements::default()),
+		);
+	}
+}
@@ -0,0 +1,126 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Check of the host functions the wasm runtime imports.
+//!
+//! The executor stubs host functions it does not provide, so a runtime that was built against a
+//! newer version of the host functions only traps once it calls one of them. Checking the imports
+//! of the runtime code up front turns this into an actionable error.
+
+use log::warn;
+use parity_wasm::elements::{External, Module};
+use sp_wasm_interface::HostFunctions;
+
+/// The wasm module the runtime imports the host functions from.
+const HOST_MODULE: &str = "env";
+
+/// The host functions the runtime `code` imports that are not in `provided`.
+fn missing_host_functions(code: &[u8], provided: &[&str]) -> Result<Vec<String>, String> {
+	let module = parity_wasm::deserialize_buffer::<Module>(code)
+		.map_err(|e| format!("Failed to decode the runtime code: {}", e))?;
+
+	Ok(module
+		.import_section()
+		.map(|section| section.entries())
+		.unwrap_or(&[])
+		.iter()
+		.filter(|entry| match entry.external() {
+			External::Function(_) => entry.module() == HOST_MODULE,
+			_ => false,
+		})
+		.map(|entry| entry.field())
+		.filter(|name| !provided.contains(name))
+		.map(Into::into)
+		.collect())
+}
+
+/// Check that the host functions `H` of the node provide every host function the runtime `code`
+/// imports.
+///
+/// Every missing host function is logged. Returns an error if any host function is missing and
+/// `strict` is set, or if the code can not be decoded.
+pub fn check_host_functions<H: HostFunctions>(code: &[u8], strict: bool) -> Result<(), String> {
+	let provided = H::host_functions();
+	let provided = provided.iter().map(|f| f.name()).collect::<Vec<_>>();
+
+	let missing = missing_host_functions(code, &provided)?;
+
+	for name in &missing {
+		warn!(
+			target: "cumulus-service",
+			"Runtime requires host function `{}` not provided by this node version. Calling it \
+			traps the block execution, upgrade the node to fix this.",
+			name,
+		);
+	}
+
+	if strict && !missing.is_empty() {
+		return Err(format!(
+			"Runtime requires {} host function(s) not provided by this node version: {}",
+			missing.len(),
+			missing.join(", "),
+		));
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use parity_wasm::builder;
+
+	/// Wasm code that imports the given functions, as pairs of the module and the field.
+	fn code(imports: &[(&str, &str)]) -> Vec<u8> {
+		let mut module = builder::module();
+
+		for (module_name, field) in imports {
+			let signature = module.push_signature(builder::signature().build_sig());
+			module.push_import(
+				builder::import()
+					.module(module_name)
+					.field(field)
+					.external()
+					.func(signature)
+					.build(),
+			);
+		}
+
+		parity_wasm::serialize(module.build()).unwrap()
+	}
+
+	#[test]
+	fn imports_that_are_not_provided_are_missing() {
+		let code = code(&[
+			("env", "ext_storage_get_version_1"),
+			("env", "ext_storage_get_version_2"),
+			("other", "ext_unrelated_version_1"),
+		]);
+
+		assert_eq!(
+			vec!["ext_storage_get_version_2".to_string()],
+			missing_host_functions(&code, &["ext_storage_get_version_1"]).unwrap(),
+		);
+
+		let provided = ["ext_storage_get_version_1", "ext_storage_get_version_2"];
+		assert!(missing_host_functions(&code, &provided).unwrap().is_empty());
+	}
+
+	#[test]
+	fn undecodable_code_is_an_error() {
+		assert!(missing_host_functions(&[1, 2, 3], &[]).is_err());
+	}
+}
@@ -18,25 +18,56 @@
 //!
 //! Provides functions for starting a collator node or a normal full node.
 
-use cumulus_collator::CollatorBuilder;
+use cumulus_collator::{
+	follow_relay_chain, record_inclusions, watch_relay_runtime, CollateOn, CollatorBuilder,
+	CollatorStatus, EmptyBlocks, ParachainInherentExtension, PovArchive, RelayHeadRecorder,
+};
+use cumulus_consensus::{BackedHead, FetchRetry, RetryingPolkadotClient};
 use cumulus_network::{DelayedBlockAnnounceValidator, JustifiedBlockAnnounceValidator};
 use cumulus_primitives::ParaId;
 use polkadot_primitives::v0::{Block as PBlock, CollatorPair};
 use polkadot_service::{AbstractClient, ClientHandle, RuntimeApiCollection};
-use sc_client_api::{Backend as BackendT, BlockBackend, Finalizer, UsageProvider};
-use sc_service::{Configuration, Role, TaskManager};
+use sc_client_api::{
+	Backend as BackendT, BlockBackend, Finalizer, StorageProvider, UsageProvider,
+};
+use sc_service::{config::WasmExecutionMethod, Configuration, Role, TaskManager};
 use sp_blockchain::{HeaderBackend, Result as ClientResult};
 use sp_consensus::{BlockImport, Environment, Error as ConsensusError, Proposer, SyncOracle};
 use sp_core::crypto::Pair;
 use sp_inherents::InherentDataProviders;
 use sp_runtime::traits::{BlakeTwo256, Block as BlockT};
-use std::{marker::PhantomData, sync::Arc};
+use std::{marker::PhantomData, num::NonZeroU32, sync::Arc, time::Duration};
+use substrate_prometheus_endpoint::Registry;
+
+mod hardware;
+mod host_functions;
+mod relay_bootstrap;
+mod rpc;
+mod runtime_api;
+mod transaction_pool;
+
+pub use hardware::{benchmark_hardware, check_hardware, HardwareBenchmark, HardwareRequirements};
+pub use host_functions::check_host_functions;
+pub use relay_bootstrap::{relay_chain_bootstrap, RelayBootstrapOnFail};
+pub use rpc::{RpcExtension, RpcExtensionBuilder};
+pub use runtime_api::{check_runtime_apis, RuntimeApiRequirement};
+pub use transaction_pool::{purge_transactions, CountLimitedPool, TransactionTtl};
+
+/// The prefix of the error that is returned when the embedded relay chain node fails to start.
+pub const RELAY_CHAIN_START_ERROR: &str = "Failed to start the relay chain node";
+
+/// Wrap the error `e` of starting the embedded relay chain node, so it can be told apart from
+/// the errors of the parachain node.
+pub fn relay_chain_start_error(e: impl std::fmt::Display) -> sc_service::Error {
+	sc_service::Error::Other(format!("{}: {}", RELAY_CHAIN_START_ERROR, e))
+}
 
 /// Parameters given to [`start_collator`].
 pub struct StartCollatorParams<'a, Block: BlockT, PF, BI, BS, Client> {
 	pub para_id: ParaId,
 	pub proposer_factory: PF,
 	pub inherent_data_providers: InherentDataProviders,
+	pub inherent_extension: Arc<dyn ParachainInherentExtension>,
 	pub block_import: BI,
 	pub block_status: Arc<BS>,
 	pub announce_block: Arc<dyn Fn(Block::Hash, Vec<u8>) + Send + Sync>,
@@ -45,6 +76,23 @@ pub struct StartCollatorParams<'a, Block: BlockT, PF, BI, BS, Client> {
 	pub task_manager: &'a mut TaskManager,
 	pub polkadot_config: Configuration,
 	pub collator_key: Arc<CollatorPair>,
+	pub collator_status: Arc<CollatorStatus>,
+	pub fetch_retry: FetchRetry,
+	pub prometheus_registry: Option<Registry>,
+	pub backed_head: BackedHead<Block>,
+	pub self_validate_collations: bool,
+	pub self_validation_timeout: Duration,
+	pub wasm_execution: WasmExecutionMethod,
+	pub pov_cache_size: usize,
+	pub relay_head_recorder: Option<Arc<RelayHeadRecorder>>,
+	pub pov_archive: Option<Arc<PovArchive>>,
+	pub announcement_expiry_blocks: Option<u32>,
+	pub max_downward_messages_per_block: Option<usize>,
+	pub authoring_backoff_threshold: Option<NonZeroU32>,
+	pub collate_on: CollateOn,
+	pub forced_parent: Option<Block::Hash>,
+	pub collation_start_delay: Option<Duration>,
+	pub empty_blocks: Option<EmptyBlocks>,
 }
 
 /// Start a collator node for a parachain.
@@ -57,6 +105,7 @@ pub fn start_collator<'a, Block, PF, BI, BS, Client, Backend>(
 		para_id,
 		proposer_factory,
 		inherent_data_providers,
+		inherent_extension,
 		block_import,
 		block_status,
 		announce_block,
@@ -65,6 +114,23 @@ pub fn start_collator<'a, Block, PF, BI, BS, Client, Backend>(
 		task_manager,
 		polkadot_config,
 		collator_key,
+		collator_status,
+		fetch_retry,
+		prometheus_registry,
+		backed_head,
+		self_validate_collations,
+		self_validation_timeout,
+		wasm_execution,
+		pov_cache_size,
+		relay_head_recorder,
+		pov_archive,
+		announcement_expiry_blocks,
+		max_downward_messages_per_block,
+		authoring_backoff_threshold,
+		collate_on,
+		forced_parent,
+		collation_start_delay,
+		empty_blocks,
 	}: StartCollatorParams<'a, Block, PF, BI, BS, Client>,
 ) -> sc_service::error::Result<()>
 where
@@ -81,6 +147,7 @@ where
 	Client: Finalizer<Block, Backend>
 		+ UsageProvider<Block>
 		+ HeaderBackend<Block>
+		+ StorageProvider<Block, Backend>
 		+ Send
 		+ Sync
 		+ BlockBackend<Block>
@@ -97,10 +164,29 @@ where
 		client,
 		announce_block,
 		block_announce_validator,
-	);
+		collator_status,
+		fetch_retry,
+		prometheus_registry,
+		backed_head,
+	)
+	.with_inherent_extension(inherent_extension)
+	.with_self_validation(self_validate_collations)
+	.with_self_validation_timeout(self_validation_timeout)
+	.with_wasm_execution(wasm_execution)
+	.with_pov_cache_size(pov_cache_size)
+	.with_relay_head_recorder(relay_head_recorder)
+	.with_pov_archive(pov_archive)
+	.with_announcement_expiry(announcement_expiry_blocks)
+	.with_max_downward_messages(max_downward_messages_per_block)
+	.with_authoring_backoff(authoring_backoff_threshold)
+	.with_collate_on(collate_on)
+	.with_forced_parent(forced_parent)
+	.with_collation_start_delay(collation_start_delay)
+	.with_empty_blocks(empty_blocks);
 
 	let (polkadot_future, polkadot_task_manager) =
-		polkadot_collator::start_collator(builder, para_id, collator_key, polkadot_config)?;
+		polkadot_collator::start_collator(builder, para_id, collator_key, polkadot_config)
+			.map_err(relay_chain_start_error)?;
 
 	task_manager
 		.spawn_essential_handle()
@@ -120,6 +206,10 @@ pub struct StartFullNodeParams<'a, Block: BlockT, Client> {
 	pub client: Arc<Client>,
 	pub announce_block: Arc<dyn Fn(Block::Hash, Vec<u8>) + Send + Sync>,
 	pub task_manager: &'a mut TaskManager,
+	pub collator_status: Arc<CollatorStatus>,
+	pub fetch_retry: FetchRetry,
+	pub prometheus_registry: Option<Registry>,
+	pub backed_head: BackedHead<Block>,
 }
 
 /// Start a full node for a parachain.
@@ -135,6 +225,10 @@ pub fn start_full_node<Block, Client, Backend>(
 		client,
 		announce_block,
 		task_manager,
+		collator_status,
+		fetch_retry,
+		prometheus_registry,
+		backed_head,
 	}: StartFullNodeParams<Block, Client>,
 ) -> sc_service::error::Result<()>
 where
@@ -150,7 +244,7 @@ where
 {
 	let is_light = matches!(polkadot_config.role, Role::Light);
 	let (polkadot_task_manager, pclient, handles) = if is_light {
-		Err("Light client not supported.".into())
+		Err(sc_service::Error::Other("Light client not supported.".into()))
 	} else {
 		polkadot_service::build_full(
 			polkadot_config,
@@ -160,12 +254,15 @@ where
 			6000,
 			None,
 		)
+		.map_err(relay_chain_start_error)
 	}?;
 
 	let polkadot_network = handles
 		.polkadot_network
 		.expect("Polkadot service is started; qed");
 
+	collator_status.set_relay_sync_oracle(Box::new(polkadot_network.clone()));
+
 	pclient.execute_with(InitParachainFullNode {
 		block_announce_validator,
 		para_id,
@@ -173,6 +270,10 @@ where
 		announce_block,
 		client,
 		task_manager,
+		collator_status,
+		fetch_retry,
+		prometheus_registry,
+		backed_head,
 		_phantom: PhantomData,
 	})?;
 
@@ -198,6 +299,10 @@ struct InitParachainFullNode<'a, Block: BlockT, Client, Backend> {
 	announce_block: Arc<dyn Fn(Block::Hash, Vec<u8>) + Send + Sync>,
 	client: Arc<Client>,
 	task_manager: &'a mut TaskManager,
+	collator_status: Arc<CollatorStatus>,
+	fetch_retry: FetchRetry,
+	prometheus_registry: Option<Registry>,
+	backed_head: BackedHead<Block>,
 	_phantom: PhantomData<Backend>,
 }
 
@@ -231,11 +336,34 @@ where
 				self.polkadot_sync_oracle,
 			)));
 
+		self.task_manager.spawn_handle().spawn(
+			"cumulus-follow-relay-chain",
+			follow_relay_chain(client.clone(), self.collator_status.clone()),
+		);
+
+		self.task_manager.spawn_handle().spawn(
+			"cumulus-record-inclusions",
+			record_inclusions(client.clone(), self.para_id, self.collator_status.clone()),
+		);
+
+		let polkadot_client = RetryingPolkadotClient::new(
+			client.clone(),
+			self.fetch_retry,
+			self.prometheus_registry.as_ref(),
+		);
+
+		let cache_client = polkadot_client.clone();
+		self.task_manager.spawn_handle().spawn(
+			"cumulus-watch-relay-runtime",
+			watch_relay_runtime(client, self.collator_status, move || cache_client.clear_cache()),
+		);
+
 		let future = cumulus_consensus::follow_polkadot(
 			self.para_id,
 			self.client,
-			client,
+			polkadot_client,
 			self.announce_block,
+			self.backed_head,
 		)?;
 		self.task_manager
 			.spawn_essential_handle()
@@ -0,0 +1,170 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Check that the embedded relay chain node connects to the relay chain network.
+//!
+//! Without any relay chain peer the node neither syncs the relay chain nor builds collations, but
+//! it also does not fail. [`relay_chain_bootstrap`] gives a clear signal if no peer was found in
+//! time.
+
+use cumulus_collator::CollatorStatus;
+
+use futures::future;
+use futures_timer::Delay;
+use log::{error, info, warn};
+
+use std::{
+	fmt,
+	str::FromStr,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+/// How often the relay chain peers are checked while bootstrapping.
+const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What the node does if it did not connect to any relay chain peer in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelayBootstrapOnFail {
+	/// Stop the node.
+	Exit,
+	/// Log the failure and keep waiting for peers, logging again after every timeout.
+	Retry,
+}
+
+impl RelayBootstrapOnFail {
+	/// The names of the variants, as parsed by `from_str`.
+	pub const VARIANTS: [&'static str; 2] = ["exit", "retry"];
+}
+
+impl FromStr for RelayBootstrapOnFail {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, String> {
+		match s {
+			"exit" => Ok(Self::Exit),
+			"retry" => Ok(Self::Retry),
+			_ => Err(format!(
+				"unknown value `{}`, expected one of: {}",
+				s,
+				Self::VARIANTS.join(", "),
+			)),
+		}
+	}
+}
+
+impl fmt::Display for RelayBootstrapOnFail {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Exit => write!(f, "exit"),
+			Self::Retry => write!(f, "retry"),
+		}
+	}
+}
+
+/// Wait for the embedded relay chain node of `status` to connect to a peer within `timeout`.
+///
+/// The future only completes if no peer was found and `on_fail` is
+/// [`RelayBootstrapOnFail::Exit`], so it should be spawned as an essential task to stop the node
+/// in this case.
+pub async fn relay_chain_bootstrap(
+	status: Arc<CollatorStatus>,
+	timeout: Duration,
+	on_fail: RelayBootstrapOnFail,
+) {
+	let mut started = Instant::now();
+
+	while !status.relay_connected() {
+		if started.elapsed() >= timeout {
+			match on_fail {
+				RelayBootstrapOnFail::Exit => {
+					error!(
+						"Could not connect to any relay chain bootnode within {} seconds, exiting",
+						timeout.as_secs(),
+					);
+					return;
+				}
+				RelayBootstrapOnFail::Retry => {
+					warn!(
+						"Could not connect to any relay chain bootnode within {} seconds, \
+						retrying",
+						timeout.as_secs(),
+					);
+					started = Instant::now();
+				}
+			}
+		}
+
+		Delay::new(CHECK_INTERVAL).await;
+	}
+
+	info!("Connected to the relay chain network");
+
+	future::pending::<()>().await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::{executor::block_on, FutureExt};
+	use sp_consensus::SyncOracle;
+
+	struct Peers(bool);
+
+	impl SyncOracle for Peers {
+		fn is_major_syncing(&mut self) -> bool {
+			false
+		}
+
+		fn is_offline(&mut self) -> bool {
+			!self.0
+		}
+	}
+
+	/// Whether the bootstrap with a timeout of 200ms completes, i.e. stops the node, within a
+	/// second.
+	fn stops_node(status: Arc<CollatorStatus>, on_fail: RelayBootstrapOnFail) -> bool {
+		let bootstrap = relay_chain_bootstrap(status, Duration::from_millis(200), on_fail);
+		let stopped = future::select(bootstrap.boxed(), Delay::new(Duration::from_secs(1)));
+
+		matches!(block_on(stopped), future::Either::Left(_))
+	}
+
+	#[test]
+	fn node_without_relay_peers_is_stopped_on_exit() {
+		let status = Arc::new(CollatorStatus::new());
+		status.set_relay_sync_oracle(Box::new(Peers(false)));
+
+		assert!(stops_node(status.clone(), RelayBootstrapOnFail::Exit));
+		assert!(!stops_node(status, RelayBootstrapOnFail::Retry));
+	}
+
+	#[test]
+	fn node_with_relay_peers_keeps_running() {
+		let status = Arc::new(CollatorStatus::new());
+		status.set_relay_sync_oracle(Box::new(Peers(true)));
+
+		assert!(!stops_node(status, RelayBootstrapOnFail::Exit));
+	}
+
+	#[test]
+	fn on_fail_round_trips() {
+		for name in RelayBootstrapOnFail::VARIANTS.iter() {
+			assert_eq!(*name, name.parse::<RelayBootstrapOnFail>().unwrap().to_string());
+		}
+		assert!("abort".parse::<RelayBootstrapOnFail>().is_err());
+	}
+}
@@ -0,0 +1,57 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Extension point for the RPC methods of a parachain node.
+//!
+//! The node always serves the Substrate (`author`, `chain`, `state`, `system`) and the Cumulus
+//! RPC methods. A parachain whose runtime has custom pallets registers the RPC methods of these
+//! pallets with a [`RpcExtensionBuilder`], without changing the node.
+
+use sc_rpc::DenyUnsafe;
+
+use std::sync::Arc;
+
+/// The handler the additional RPC methods are registered in.
+pub type RpcExtension = jsonrpc_core::IoHandler<sc_rpc::Metadata>;
+
+/// Registers the RPC methods of a parachain in addition to the built-in ones.
+///
+/// # Example
+///
+/// ```ignore
+/// use cumulus_service::{RpcExtension, RpcExtensionBuilder};
+/// use pallet_template_rpc::{Template, TemplateApi};
+///
+/// struct TemplateRpc;
+///
+/// impl<Client: Send + Sync + 'static> RpcExtensionBuilder<Client> for TemplateRpc {
+///     fn extend_rpc(&self, io: &mut RpcExtension, client: Arc<Client>, _: DenyUnsafe) {
+///         io.extend_with(TemplateApi::to_delegate(Template::new(client)));
+///     }
+/// }
+/// ```
+pub trait RpcExtensionBuilder<Client>: Send + Sync + 'static {
+	/// Register the additional RPC methods in `io`.
+	///
+	/// `deny_unsafe` tells whether unsafe RPC methods may be called through the RPC server `io`
+	/// belongs to.
+	fn extend_rpc(&self, io: &mut RpcExtension, client: Arc<Client>, deny_unsafe: DenyUnsafe);
+}
+
+/// Registers no additional RPC methods.
+impl<Client> RpcExtensionBuilder<Client> for () {
+	fn extend_rpc(&self, _: &mut RpcExtension, _: Arc<Client>, _: DenyUnsafe) {}
+}
@@ -0,0 +1,139 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Checks of the runtime api versions the node was built against.
+
+use sp_api::{RuntimeApiInfo, RuntimeVersion};
+
+use log::warn;
+
+/// A runtime api the node expects the parachain runtime to provide.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RuntimeApiRequirement {
+	/// The name of the api, used for logging.
+	pub name: &'static str,
+	/// The unique identifier of the api.
+	pub id: [u8; 8],
+	/// The version of the api the node was built against.
+	pub version: u32,
+	/// Whether the node can not work without this api.
+	pub critical: bool,
+}
+
+impl RuntimeApiRequirement {
+	/// Create the requirement for the runtime api `A` in the version the node was built against.
+	pub fn of<A: RuntimeApiInfo + ?Sized>(name: &'static str, critical: bool) -> Self {
+		Self {
+			name,
+			id: A::ID,
+			version: A::VERSION,
+			critical,
+		}
+	}
+}
+
+/// Check the apis of the given runtime `version` against the `requirements` of the node.
+///
+/// A mismatching version or a missing non-critical api only logs a warning, as the node can
+/// continue with reduced functionality. Returns an error if a critical api is missing.
+pub fn check_runtime_apis(
+	version: &RuntimeVersion,
+	requirements: &[RuntimeApiRequirement],
+) -> Result<(), String> {
+	for requirement in requirements {
+		let provided = version
+			.apis
+			.iter()
+			.find(|(id, _)| *id == requirement.id)
+			.map(|(_, version)| *version);
+
+		match provided {
+			Some(provided) if provided == requirement.version => {}
+			Some(provided) => warn!(
+				target: "cumulus-service",
+				"Runtime `{}` provides version {} of the `{}` api, but the node expects version \
+				{}. Continuing, calls to this api may fail.",
+				version.spec_name,
+				provided,
+				requirement.name,
+				requirement.version,
+			),
+			None if requirement.critical => {
+				return Err(format!(
+					"Runtime `{}` does not provide the `{}` api that is required by the node.",
+					version.spec_name, requirement.name,
+				))
+			}
+			None => warn!(
+				target: "cumulus-service",
+				"Runtime `{}` does not provide the `{}` api. Continuing without the functionality \
+				that depends on it.",
+				version.spec_name,
+				requirement.name,
+			),
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const CORE: [u8; 8] = *b"core____";
+	const SESSION_KEYS: [u8; 8] = *b"session_";
+
+	fn requirements() -> Vec<RuntimeApiRequirement> {
+		vec![
+			RuntimeApiRequirement {
+				name: "Core",
+				id: CORE,
+				version: 3,
+				critical: true,
+			},
+			RuntimeApiRequirement {
+				name: "SessionKeys",
+				id: SESSION_KEYS,
+				version: 2,
+				critical: false,
+			},
+		]
+	}
+
+	fn runtime_version(apis: Vec<([u8; 8], u32)>) -> RuntimeVersion {
+		RuntimeVersion {
+			spec_name: "test".into(),
+			apis: apis.into(),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn down_level_and_missing_non_critical_apis_are_accepted() {
+		let version = runtime_version(vec![(CORE, 3), (SESSION_KEYS, 1)]);
+		assert_eq!(Ok(()), check_runtime_apis(&version, &requirements()));
+
+		let version = runtime_version(vec![(CORE, 2)]);
+		assert_eq!(Ok(()), check_runtime_apis(&version, &requirements()));
+	}
+
+	#[test]
+	fn missing_critical_api_is_rejected() {
+		let version = runtime_version(vec![(SESSION_KEYS, 2)]);
+		assert!(check_runtime_apis(&version, &requirements()).is_err());
+	}
+}
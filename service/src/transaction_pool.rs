@@ -0,0 +1,360 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Utilities around the transaction pool of the parachain.
+//!
+//! Mortal transactions whose era ended are already removed by the maintenance of the transaction
+//! pool when a new block is imported. [`purge_transactions`] additionally removes every
+//! transaction that was not included within a configurable time to live, so the collator stops
+//! trying to include it.
+//!
+//! [`CountLimitedPool`] limits the number of transactions and the number of bytes of
+//! transactions the proposer can put into a block.
+//!
+//! The proposer includes the ready transactions in the order the pool returns them, which is by
+//! descending priority. The runtime derives the priority of a transaction from the fee it pays,
+//! including its tip, so when a block has no space for all ready transactions, the ones with the
+//! highest tips are included first.
+
+use codec::Encode;
+use sp_runtime::{generic::BlockId, traits::NumberFor};
+use sp_transaction_pool::{
+	ImportNotificationStream, InPoolTransaction, PoolFuture, PoolStatus, TransactionFor,
+	TransactionPool, TransactionSource, TransactionStatusStreamFor, TxHash,
+};
+
+use futures::{future::Future, stream, FutureExt, StreamExt};
+use futures_timer::Delay;
+use log::debug;
+
+use std::{
+	collections::HashMap,
+	pin::Pin,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+/// How often the transaction pool is checked for transactions to purge.
+const PURGE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long transactions live in the transaction pool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TransactionTtl {
+	/// Transactions that are in the pool for longer than this are purged.
+	pub ttl: Duration,
+	/// How long a purged transaction is rejected when it is submitted again.
+	///
+	/// The transaction pool itself bans removed transactions for a fixed time, so this can only
+	/// extend the ban.
+	pub ban: Duration,
+}
+
+enum Event<Hash> {
+	Imported(Hash),
+	Tick,
+}
+
+/// Purge the transactions of the given `pool` as configured by `ttl`.
+pub async fn purge_transactions<TP: TransactionPool>(pool: Arc<TP>, ttl: TransactionTtl) {
+	let imported = pool.import_notification_stream().map(Event::Imported);
+	let ticks = stream::unfold((), |_| Delay::new(PURGE_INTERVAL).map(|_| Some((Event::Tick, ()))));
+	let mut events = stream::select(imported, ticks);
+
+	let mut imported_at = HashMap::<TxHash<TP>, Instant>::new();
+	let mut banned_at = HashMap::<TxHash<TP>, Instant>::new();
+
+	while let Some(event) = events.next().await {
+		let now = Instant::now();
+
+		match event {
+			Event::Imported(hash) => {
+				if banned_at.contains_key(&hash) {
+					debug!(
+						target: "cumulus-service",
+						"Purging transaction `{:?}` from the pool: banned",
+						hash,
+					);
+					pool.remove_invalid(&[hash]);
+				} else {
+					imported_at.insert(hash, now);
+				}
+			}
+			Event::Tick => {
+				let expired = imported_at
+					.iter()
+					.filter(|(_, at)| now.duration_since(**at) >= ttl.ttl)
+					.map(|(hash, _)| hash.clone())
+					.collect::<Vec<_>>();
+
+				if !expired.is_empty() {
+					for removed in pool.remove_invalid(&expired) {
+						debug!(
+							target: "cumulus-service",
+							"Purging transaction `{:?}` from the pool: expired",
+							removed.hash(),
+						);
+
+						banned_at.insert(removed.hash().clone(), now);
+					}
+
+					for hash in &expired {
+						imported_at.remove(hash);
+					}
+				}
+
+				banned_at.retain(|_, at| now.duration_since(*at) < ttl.ban);
+			}
+		}
+	}
+}
+
+/// Yields at most `remaining` items of `inner`.
+struct Limited<I> {
+	inner: I,
+	remaining: usize,
+	limit: usize,
+}
+
+impl<I: Iterator> Iterator for Limited<I> {
+	type Item = I::Item;
+
+	fn next(&mut self) -> Option<I::Item> {
+		if self.remaining == 0 {
+			if self.limit > 0 && self.inner.next().is_some() {
+				debug!(
+					target: "cumulus-service",
+					"Block is limited to {} extrinsics by `--max-extrinsics-per-block`",
+					self.limit,
+				);
+				// Only log once per block.
+				self.limit = 0;
+			}
+
+			return None;
+		}
+
+		self.remaining -= 1;
+		self.inner.next()
+	}
+}
+
+/// Yields the items of `inner` as long as their total `size` stays within `remaining` bytes.
+struct SizeLimited<I, F> {
+	inner: I,
+	size: F,
+	remaining: usize,
+}
+
+impl<I: Iterator, F: Fn(&I::Item) -> usize> Iterator for SizeLimited<I, F> {
+	type Item = I::Item;
+
+	fn next(&mut self) -> Option<I::Item> {
+		let item = self.inner.next()?;
+		let size = (self.size)(&item);
+
+		if size > self.remaining {
+			debug!(
+				target: "cumulus-service",
+				"Block is limited to {} more bytes of extrinsics by `--proposal-block-fill-ratio`",
+				self.remaining,
+			);
+			// Keep the order of the transactions, so no smaller transaction is included instead.
+			self.remaining = 0;
+
+			return None;
+		}
+
+		self.remaining -= size;
+		Some(item)
+	}
+}
+
+type ReadyIterator<TP> =
+	Box<dyn Iterator<Item = Arc<<TP as TransactionPool>::InPoolTransaction>> + Send>;
+
+/// A transaction pool that returns at most a given number of ready transactions.
+///
+/// The proposer includes the ready transactions one after another, so handing this pool to the
+/// proposer limits the number of transactions per block. The limit keeps the transactions with
+/// the highest priority, as the ready transactions are ordered by priority. All other calls are
+/// forwarded to the wrapped pool.
+///
+/// The ready transactions can also be limited by their encoded size, see [`Self::with_max_bytes`].
+pub struct CountLimitedPool<TP> {
+	pool: Arc<TP>,
+	max_transactions: Option<usize>,
+	max_bytes: Option<usize>,
+}
+
+impl<TP> CountLimitedPool<TP> {
+	/// Create a new instance that returns at most `max_transactions` ready transactions of
+	/// `pool`, or all of them if `None`.
+	pub fn new(pool: Arc<TP>, max_transactions: Option<usize>) -> Self {
+		Self {
+			pool,
+			max_transactions,
+			max_bytes: None,
+		}
+	}
+
+	/// Only return the ready transactions whose total encoded size is at most `max_bytes`.
+	///
+	/// The inherents are not taken from the pool, so the block can still be larger than this.
+	pub fn with_max_bytes(mut self, max_bytes: Option<usize>) -> Self {
+		self.max_bytes = max_bytes;
+		self
+	}
+
+	fn limit<I, F>(
+		max_transactions: Option<usize>,
+		max_bytes: Option<usize>,
+		size: F,
+		ready: I,
+	) -> Box<dyn Iterator<Item = I::Item> + Send>
+	where
+		I: Iterator + Send + 'static,
+		F: Fn(&I::Item) -> usize + Send + 'static,
+	{
+		let ready: Box<dyn Iterator<Item = I::Item> + Send> = match max_bytes {
+			Some(max_bytes) => Box::new(SizeLimited {
+				inner: ready,
+				size,
+				remaining: max_bytes,
+			}),
+			None => Box::new(ready),
+		};
+
+		match max_transactions {
+			Some(limit) => Box::new(Limited {
+				inner: ready,
+				remaining: limit,
+				limit,
+			}),
+			None => ready,
+		}
+	}
+}
+
+/// The encoded size of the transaction `tx`.
+fn encoded_size<TP: TransactionPool>(tx: &Arc<TP::InPoolTransaction>) -> usize {
+	tx.data().encoded_size()
+}
+
+impl<TP: TransactionPool> TransactionPool for CountLimitedPool<TP> {
+	type Block = TP::Block;
+	type Hash = TP::Hash;
+	type InPoolTransaction = TP::InPoolTransaction;
+	type Error = TP::Error;
+
+	fn submit_at(
+		&self,
+		at: &BlockId<Self::Block>,
+		source: TransactionSource,
+		xts: Vec<TransactionFor<Self>>,
+	) -> PoolFuture<Vec<Result<TxHash<Self>, Self::Error>>, Self::Error> {
+		self.pool.submit_at(at, source, xts)
+	}
+
+	fn submit_one(
+		&self,
+		at: &BlockId<Self::Block>,
+		source: TransactionSource,
+		xt: TransactionFor<Self>,
+	) -> PoolFuture<TxHash<Self>, Self::Error> {
+		self.pool.submit_one(at, source, xt)
+	}
+
+	fn submit_and_watch(
+		&self,
+		at: &BlockId<Self::Block>,
+		source: TransactionSource,
+		xt: TransactionFor<Self>,
+	) -> PoolFuture<Box<TransactionStatusStreamFor<Self>>, Self::Error> {
+		self.pool.submit_and_watch(at, source, xt)
+	}
+
+	fn ready_at(
+		&self,
+		at: NumberFor<Self::Block>,
+	) -> Pin<Box<dyn Future<Output = ReadyIterator<Self>> + Send>> {
+		let (max_transactions, max_bytes) = (self.max_transactions, self.max_bytes);
+
+		self.pool
+			.ready_at(at)
+			.map(move |ready| Self::limit(max_transactions, max_bytes, encoded_size::<TP>, ready))
+			.boxed()
+	}
+
+	fn ready(&self) -> ReadyIterator<Self> {
+		Self::limit(self.max_transactions, self.max_bytes, encoded_size::<TP>, self.pool.ready())
+	}
+
+	fn remove_invalid(&self, hashes: &[TxHash<Self>]) -> Vec<Arc<Self::InPoolTransaction>> {
+		self.pool.remove_invalid(hashes)
+	}
+
+	fn status(&self) -> PoolStatus {
+		self.pool.status()
+	}
+
+	fn import_notification_stream(&self) -> ImportNotificationStream<TxHash<Self>> {
+		self.pool.import_notification_stream()
+	}
+
+	fn on_broadcasted(&self, propagations: HashMap<TxHash<Self>, Vec<String>>) {
+		self.pool.on_broadcasted(propagations)
+	}
+
+	fn hash_of(&self, xt: &TransactionFor<Self>) -> TxHash<Self> {
+		self.pool.hash_of(xt)
+	}
+
+	fn ready_transaction(&self, hash: &TxHash<Self>) -> Option<Arc<Self::InPoolTransaction>> {
+		self.pool.ready_transaction(hash)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn ready_transactions_never_exceed_the_limit() {
+		for (available, limit) in &[(100, 10), (5, 10), (10, 10), (3, 0)] {
+			let ready = CountLimitedPool::<()>::limit(Some(*limit), None, |_| 1, 0..*available);
+
+			assert_eq!(std::cmp::min(*available, *limit), ready.count());
+		}
+
+		let ready = CountLimitedPool::<()>::limit(None, None, |_| 1, 0..100);
+		assert_eq!(100, ready.count());
+	}
+
+	#[test]
+	fn ready_transactions_never_exceed_the_size_limit() {
+		let size = |size: &usize| *size;
+
+		let ready = CountLimitedPool::<()>::limit(None, Some(9), size, vec![4, 4, 1, 1]);
+		assert_eq!(vec![4, 4, 1], ready.collect::<Vec<_>>());
+
+		// The order is kept, the smaller transaction after the one that does not fit is skipped.
+		let ready = CountLimitedPool::<()>::limit(None, Some(10), size, vec![4, 8, 1]);
+		assert_eq!(vec![4], ready.collect::<Vec<_>>());
+
+		let ready = CountLimitedPool::<()>::limit(Some(1), Some(10), size, vec![4, 4]);
+		assert_eq!(vec![4], ready.collect::<Vec<_>>());
+	}
+}
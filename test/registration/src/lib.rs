@@ -0,0 +1,245 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Cumulus.
+
+// Cumulus is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Cumulus is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Cumulus.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Registration of a parachain on the Polkadot test relay chain.
+//!
+//! The parachain is registered with `registrar::register_para`, dispatched through
+//! `pallet_sudo::Call::sudo` by the sudo key of the relay chain.
+
+use codec::Encode;
+use polkadot_primitives::v0::{
+	Block as PBlock, BlockNumber as PBlockNumber, Hash as PHash, Id as ParaId, Info, Nonce,
+	Scheduling, Signature,
+};
+use polkadot_runtime_common::{parachains, registrar};
+use polkadot_test_runtime::{
+	Address, BlockHashCount, Call, Runtime, SignedExtra, SignedPayload, UncheckedExtrinsic,
+	VERSION,
+};
+use polkadot_test_service::PolkadotTestNode;
+use sp_blockchain::HeaderBackend;
+use sp_core::Pair;
+use sp_keyring::Sr25519Keyring;
+use sp_runtime::{
+	generic::{BlockId, Era},
+	traits::{BlakeTwo256, Hash as HashT},
+};
+use substrate_test_client::RpcHandlersExt;
+
+/// Everything needed to build the extrinsic that registers a parachain.
+#[derive(Clone, Debug)]
+pub struct RegisterParaParams {
+	/// The id to register the parachain with.
+	pub para_id: ParaId,
+	/// The validation function of the parachain.
+	pub validation_code: Vec<u8>,
+	/// The encoded genesis header of the parachain.
+	pub genesis_head: Vec<u8>,
+	/// The sudo key of the relay chain, signs the extrinsic.
+	pub sudo: Sr25519Keyring,
+	/// The nonce of the sudo account.
+	pub nonce: Nonce,
+	/// The genesis hash of the relay chain.
+	pub genesis_hash: PHash,
+	/// The hash of the relay chain block the mortality of the extrinsic starts at.
+	pub best_hash: PHash,
+	/// The number of the relay chain block the mortality of the extrinsic starts at.
+	pub best_number: PBlockNumber,
+}
+
+impl RegisterParaParams {
+	/// Create the params to register the parachain at the best block of `client`.
+	///
+	/// The extrinsic is signed by Alice, the sudo key of the test relay chain, with nonce `0`.
+	pub fn new<C: HeaderBackend<PBlock>>(
+		client: &C,
+		para_id: ParaId,
+		validation_code: Vec<u8>,
+		genesis_head: Vec<u8>,
+	) -> Result<Self, String> {
+		let info = client.info();
+		let genesis_hash = client
+			.hash(0)
+			.map_err(|e| format!("Failed to fetch the relay chain genesis hash: {:?}", e))?
+			.ok_or_else(|| "Relay chain genesis block not found".to_string())?;
+
+		Ok(Self {
+			para_id,
+			validation_code,
+			genesis_head,
+			sudo: Sr25519Keyring::Alice,
+			nonce: 0,
+			genesis_hash,
+			best_hash: info.best_hash,
+			best_number: info.best_number,
+		})
+	}
+}
+
+/// The period of the mortal era of the extrinsic.
+fn era_period() -> u64 {
+	BlockHashCount::get()
+		.checked_next_power_of_two()
+		.map(|c| c / 2)
+		.unwrap_or(2) as u64
+}
+
+/// Build the signed extrinsic that registers the parachain described by `params`.
+pub fn build_register_para_extrinsic(params: &RegisterParaParams) -> UncheckedExtrinsic {
+	let function = Call::Sudo(pallet_sudo::Call::sudo(Box::new(Call::Registrar(
+		registrar::Call::register_para(
+			params.para_id,
+			Info {
+				scheduling: Scheduling::Always,
+			},
+			params.validation_code.clone().into(),
+			params.genesis_head.clone().into(),
+		),
+	))));
+
+	// The order of the extensions and of their additional signed data needs to match
+	// `SignedExtra` of the relay chain runtime, otherwise the signature is invalid.
+	let extra: SignedExtra = (
+		frame_system::CheckSpecVersion::<Runtime>::new(),
+		frame_system::CheckTxVersion::<Runtime>::new(),
+		frame_system::CheckGenesis::<Runtime>::new(),
+		frame_system::CheckEra::<Runtime>::from(Era::mortal(
+			era_period(),
+			params.best_number.into(),
+		)),
+		frame_system::CheckNonce::<Runtime>::from(params.nonce),
+		frame_system::CheckWeight::<Runtime>::new(),
+		pallet_transaction_payment::ChargeTransactionPayment::<Runtime>::from(0),
+		registrar::LimitParathreadCommits::<Runtime>::new(),
+		parachains::ValidateDoubleVoteReports::<Runtime>::new(),
+	);
+	let raw_payload = SignedPayload::from_raw(
+		function.clone(),
+		extra.clone(),
+		(
+			VERSION.spec_version,
+			VERSION.transaction_version,
+			params.genesis_hash,
+			params.best_hash,
+			(),
+			(),
+			(),
+			(),
+			(),
+		),
+	);
+	let signature = raw_payload.using_encoded(|payload| params.sudo.pair().sign(payload));
+
+	UncheckedExtrinsic::new_signed(
+		function,
+		Address::Id(params.sudo.public().into()),
+		Signature::Sr25519(signature),
+		extra,
+	)
+}
+
+/// Register the parachain described by `params` on the relay chain of `node`.
+///
+/// Returns the hash of the submitted extrinsic. The parachain is registered once the extrinsic
+/// is included in a relay chain block.
+pub async fn submit_register_para<S, C>(
+	node: &PolkadotTestNode<S, C>,
+	params: RegisterParaParams,
+) -> Result<PHash, String>
+where
+	C: HeaderBackend<PBlock>,
+{
+	if node
+		.client
+		.header(BlockId::Hash(params.best_hash))
+		.map_err(|e| format!("{:?}", e))?
+		.is_none()
+	{
+		return Err(format!("Relay chain block `{:?}` not found", params.best_hash));
+	}
+
+	let extrinsic = build_register_para_extrinsic(&params);
+	let hash = BlakeTwo256::hash_of(&extrinsic);
+
+	node.rpc_handlers
+		.send_transaction(extrinsic.into())
+		.await
+		.map(|_| hash)
+		.map_err(|e| format!("Failed to register parachain {:?}: {:?}", params.para_id, e))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Decode;
+	use sp_core::hashing::blake2_256;
+	use sp_runtime::traits::Verify;
+
+	#[test]
+	fn register_para_extrinsic_is_signed_by_sudo() {
+		let params = RegisterParaParams {
+			para_id: 100.into(),
+			validation_code: vec![1; 1024],
+			genesis_head: vec![2; 32],
+			sudo: Sr25519Keyring::Alice,
+			nonce: 3,
+			genesis_hash: PHash::repeat_byte(4),
+			best_hash: PHash::repeat_byte(5),
+			best_number: 6,
+		};
+
+		let extrinsic = build_register_para_extrinsic(&params);
+		let extrinsic = UncheckedExtrinsic::decode(&mut &extrinsic.encode()[..]).unwrap();
+
+		match &extrinsic.function {
+			Call::Sudo(pallet_sudo::Call::sudo(call)) => match &**call {
+				Call::Registrar(registrar::Call::register_para(id, _, code, head)) => {
+					assert_eq!(params.para_id, *id);
+					assert_eq!(params.validation_code, code.0);
+					assert_eq!(params.genesis_head, head.0);
+				}
+				call => panic!("Unexpected call: {:?}", call),
+			},
+			call => panic!("Unexpected call: {:?}", call),
+		}
+
+		let (address, signature, extra) = extrinsic.signature.clone().unwrap();
+		assert_eq!(Address::Id(Sr25519Keyring::Alice.public().into()), address);
+
+		// Payloads longer than 256 bytes are signed by their hash.
+		let payload = (
+			&extrinsic.function,
+			extra,
+			(
+				VERSION.spec_version,
+				VERSION.transaction_version,
+				params.genesis_hash,
+				params.best_hash,
+				(),
+				(),
+				(),
+				(),
+				(),
+			),
+		)
+			.encode();
+		assert!(payload.len() > 256);
+		assert!(signature.verify(
+			&blake2_256(&payload)[..],
+			&Sr25519Keyring::Alice.to_account_id()
+		));
+	}
+}